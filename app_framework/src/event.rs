@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+
+use serde::{Serialize,Deserialize};
+
+/*
+  Platformが返すイベント. GLFWのWindowEventと同じ並びにして,GLFWの型は使わない.
+  GLFWからの変換はglfw_platform.rsでする. serdeに対応しているので入力の記録にもそのまま使う.
+  キーは割り当てのファイルと同じ名前("W","Space","LeftShift"など),マウスのボタンは0から始まる番号
+*/
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Action {
+    Press,
+    Release,
+    Repeat
+}
+
+/* 修飾キー. ビットはGLFWと同じ */
+#[derive(Debug,Copy,Clone,Default,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(transparent)]
+pub struct Modifiers(pub i32);
+
+impl Modifiers {
+    pub const SHIFT: Modifiers = Modifiers(0x1);
+    pub const CONTROL: Modifiers = Modifiers(0x2);
+    pub const ALT: Modifiers = Modifiers(0x4);
+    pub const SUPER: Modifiers = Modifiers(0x8);
+
+    pub fn contains(&self, other:Modifiers) -> bool {
+	self.0 & other.0 == other.0
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub enum Event {
+    Pos(i32, i32),
+    Size(i32, i32),
+    Close,
+    Refresh,
+    Focus(bool),
+    Iconify(bool),
+    FramebufferSize(i32, i32),
+    MouseButton(i32, Action, Modifiers),
+    CursorPos(f64, f64),
+    CursorEnter(bool),
+    Scroll(f64, f64),
+    /* キーの名前,スキャンコード,動作,修飾キー */
+    Key(String, i32, Action, Modifiers),
+    Char(char),
+    CharModifiers(char, Modifiers),
+    FileDrop(Vec<PathBuf>),
+    Maximize(bool),
+    ContentScale(f32, f32),
+    /*
+      Ctrl+Vで貼り付ける文字列. Platformがそのときのクリップボードを読んでKeyの後に入れる.
+      記録にも残るので,流すときにシステムのクリップボードを読まなくてよい
+    */
+    Paste(String)
+}
+
+impl Event {
+    /* 名前がkeyのキーを押した */
+    pub fn is_key_press(&self, key:&str) -> bool {
+	matches!(self, Event::Key(name, _, Action::Press, _) if name == key)
+    }
+}
@@ -0,0 +1,279 @@
+use std::time;
+use std::os::raw::c_void;
+use std::sync::mpsc::Receiver;
+
+use glfw::{Context,Key,WindowEvent};
+
+use graphic_math::input::{GamepadState,GamepadButton,GamepadAxis,GAMEPAD_BUTTONS,GAMEPAD_AXES};
+
+use crate::AppConfig;
+use crate::event::{Action,Event,Modifiers};
+use crate::platform::{Platform,CursorMode,PlatformError};
+
+/* GLFWのウィンドウ. 対話的に動かすときに使う */
+pub struct GlfwPlatform {
+    glfw: glfw::Glfw,
+    window: glfw::Window,
+    events: Receiver<(f64, glfw::WindowEvent)>
+}
+
+impl GlfwPlatform {
+    pub fn create(config:&AppConfig) -> Result<Self, PlatformError> {
+	let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).map_err(|e| PlatformError::Init(e.to_string()))?;
+	//3DのシーンもeguiもFRAMEBUFFER_SRGBでsRGBにして書く
+	glfw.window_hint(glfw::WindowHint::SRgbCapable(true));
+	if let Some(samples) = config.samples {
+	    glfw.window_hint(glfw::WindowHint::Samples(Some(samples)));
+	}
+
+	let (mut window, events) = glfw.create_window(config.width, config.height, &config.title, glfw::WindowMode::Windowed)
+	    .ok_or_else(|| PlatformError::Context("failed to create GLFW window".to_string()))?;
+	window.set_all_polling(true);
+	window.make_current();
+	let mut platform = GlfwPlatform { glfw, window, events };
+	platform.set_vsync(config.vsync);
+	Ok(platform)
+    }
+
+    /* GLFWにしかない機能を使うとき */
+    pub fn glfw_mut(&mut self) -> &mut glfw::Glfw {
+	&mut self.glfw
+    }
+
+    pub fn window_mut(&mut self) -> &mut glfw::Window {
+	&mut self.window
+    }
+}
+
+impl Platform for GlfwPlatform {
+    fn get_proc_address(&mut self, name:&str) -> *const c_void {
+	self.window.get_proc_address(name) as *const c_void
+    }
+
+    fn framebuffer_size(&self) -> (i32,i32) {
+	self.window.get_framebuffer_size()
+    }
+
+    fn content_scale(&self) -> f32 {
+	self.window.get_content_scale().0
+    }
+
+    fn time(&self) -> time::Duration {
+	time::Duration::from_secs_f64(self.glfw.get_time())
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+	self.glfw.poll_events();
+	let mut events = Vec::new();
+	for (_, event) in glfw::flush_messages(&self.events) {
+	    let event = from_glfw_event(&event);
+	    let paste = is_paste_key(&event);
+	    events.push(event);
+	    if paste {
+		events.push(Event::Paste(self.window.get_clipboard_string().unwrap_or_default()));
+	    }
+	}
+	events
+    }
+
+    fn swap_buffers(&mut self) {
+	self.window.swap_buffers();
+    }
+
+    fn should_close(&self) -> bool {
+	self.window.should_close()
+    }
+
+    fn set_should_close(&mut self, close:bool) {
+	self.window.set_should_close(close);
+    }
+
+    fn set_vsync(&mut self, vsync:bool) {
+	self.glfw.set_swap_interval(if vsync { glfw::SwapInterval::Sync(1) } else { glfw::SwapInterval::None });
+    }
+
+    fn cursor_pos(&self) -> (f64,f64) {
+	self.window.get_cursor_pos()
+    }
+
+    fn set_cursor_mode(&mut self, mode:CursorMode) {
+	self.window.set_cursor_mode(match mode {
+	    CursorMode::Normal => glfw::CursorMode::Normal,
+	    CursorMode::Hidden => glfw::CursorMode::Hidden,
+	    CursorMode::Disabled => glfw::CursorMode::Disabled
+	});
+    }
+
+    fn set_clipboard(&mut self, text:&str) {
+	self.window.set_clipboard_string(text);
+    }
+
+    /* 割り当て(gamecontrollerdb)の分かっている最初のジョイスティック */
+    fn gamepad(&mut self) -> Option<GamepadState> {
+	(0..=glfw::ffi::JOYSTICK_LAST).filter_map(glfw::JoystickId::from_i32)
+	    .map(|id| self.glfw.get_joystick(id))
+	    .filter(|joystick| joystick.is_present() && joystick.is_gamepad())
+	    .find_map(|joystick| joystick.get_gamepad_state())
+	    .map(|state| gamepad_state(&state))
+    }
+}
+
+/*
+  GLFWのイベントとEventの変換. キーの名前はglfw::KeyのDebug表示にする.
+  eguiへはglfw::WindowEventで渡すので,runは逆の変換も使う
+*/
+
+pub fn key_name(key:Key) -> String {
+    format!("{:?}", key)
+}
+
+/* key_nameの逆. 知らない名前はKey::Unknown */
+pub fn key_from_name(name:&str) -> Key {
+    KEYS.iter().copied().find(|key| key_name(*key) == name).unwrap_or(Key::Unknown)
+}
+
+const KEYS: [Key;121] = [
+    Key::Space, Key::Apostrophe, Key::Comma, Key::Minus, Key::Period, Key::Slash, Key::Num0,
+    Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8,
+    Key::Num9, Key::Semicolon, Key::Equal, Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G,
+    Key::H, Key::I, Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S,
+    Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z, Key::LeftBracket, Key::Backslash,
+    Key::RightBracket, Key::GraveAccent, Key::World1, Key::World2, Key::Escape, Key::Enter,
+    Key::Tab, Key::Backspace, Key::Insert, Key::Delete, Key::Right, Key::Left, Key::Down, Key::Up,
+    Key::PageUp, Key::PageDown, Key::Home, Key::End, Key::CapsLock, Key::ScrollLock, Key::NumLock,
+    Key::PrintScreen, Key::Pause, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7,
+    Key::F8, Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15, Key::F16,
+    Key::F17, Key::F18, Key::F19, Key::F20, Key::F21, Key::F22, Key::F23, Key::F24, Key::F25,
+    Key::Kp0, Key::Kp1, Key::Kp2, Key::Kp3, Key::Kp4, Key::Kp5, Key::Kp6, Key::Kp7, Key::Kp8,
+    Key::Kp9, Key::KpDecimal, Key::KpDivide, Key::KpMultiply, Key::KpSubtract, Key::KpAdd,
+    Key::KpEnter, Key::KpEqual, Key::LeftShift, Key::LeftControl, Key::LeftAlt, Key::LeftSuper,
+    Key::RightShift, Key::RightControl, Key::RightAlt, Key::RightSuper, Key::Menu, Key::Unknown
+];
+
+/* eguiと同じく,Ctrl+Vを押したときとリピートで貼り付ける */
+fn is_paste_key(event:&Event) -> bool {
+    matches!(event, Event::Key(key, _, Action::Press | Action::Repeat, modifiers) if key == "V" && modifiers.contains(Modifiers::CONTROL))
+}
+
+fn from_glfw_action(action:glfw::Action) -> Action {
+    match action {
+	glfw::Action::Press => Action::Press,
+	glfw::Action::Release => Action::Release,
+	glfw::Action::Repeat => Action::Repeat
+    }
+}
+
+fn to_glfw_action(action:Action) -> glfw::Action {
+    match action {
+	Action::Press => glfw::Action::Press,
+	Action::Release => glfw::Action::Release,
+	Action::Repeat => glfw::Action::Repeat
+    }
+}
+
+fn from_glfw_modifiers(modifiers:glfw::Modifiers) -> Modifiers {
+    Modifiers(modifiers.bits())
+}
+
+fn to_glfw_modifiers(modifiers:Modifiers) -> glfw::Modifiers {
+    glfw::Modifiers::from_bits_truncate(modifiers.0)
+}
+
+pub fn from_glfw_event(event:&WindowEvent) -> Event {
+    match event {
+	WindowEvent::Pos(x, y) => Event::Pos(*x, *y),
+	WindowEvent::Size(w, h) => Event::Size(*w, *h),
+	WindowEvent::Close => Event::Close,
+	WindowEvent::Refresh => Event::Refresh,
+	WindowEvent::Focus(focused) => Event::Focus(*focused),
+	WindowEvent::Iconify(iconified) => Event::Iconify(*iconified),
+	WindowEvent::FramebufferSize(w, h) => Event::FramebufferSize(*w, *h),
+	WindowEvent::MouseButton(button, action, modifiers) =>
+	    Event::MouseButton(*button as i32, from_glfw_action(*action), from_glfw_modifiers(*modifiers)),
+	WindowEvent::CursorPos(x, y) => Event::CursorPos(*x, *y),
+	WindowEvent::CursorEnter(entered) => Event::CursorEnter(*entered),
+	WindowEvent::Scroll(x, y) => Event::Scroll(*x, *y),
+	WindowEvent::Key(key, scancode, action, modifiers) =>
+	    Event::Key(key_name(*key), *scancode, from_glfw_action(*action), from_glfw_modifiers(*modifiers)),
+	WindowEvent::Char(c) => Event::Char(*c),
+	WindowEvent::CharModifiers(c, modifiers) => Event::CharModifiers(*c, from_glfw_modifiers(*modifiers)),
+	WindowEvent::FileDrop(paths) => Event::FileDrop(paths.clone()),
+	WindowEvent::Maximize(maximized) => Event::Maximize(*maximized),
+	WindowEvent::ContentScale(x, y) => Event::ContentScale(*x, *y)
+    }
+}
+
+/* GLFWにない番号のマウスのボタンとPasteはNone */
+pub fn to_glfw_event(event:&Event) -> Option<WindowEvent> {
+    let event = match event {
+	Event::Pos(x, y) => WindowEvent::Pos(*x, *y),
+	Event::Size(w, h) => WindowEvent::Size(*w, *h),
+	Event::Close => WindowEvent::Close,
+	Event::Refresh => WindowEvent::Refresh,
+	Event::Focus(focused) => WindowEvent::Focus(*focused),
+	Event::Iconify(iconified) => WindowEvent::Iconify(*iconified),
+	Event::FramebufferSize(w, h) => WindowEvent::FramebufferSize(*w, *h),
+	Event::MouseButton(button, action, modifiers) =>
+	    WindowEvent::MouseButton(glfw::MouseButton::from_i32(*button)?, to_glfw_action(*action), to_glfw_modifiers(*modifiers)),
+	Event::CursorPos(x, y) => WindowEvent::CursorPos(*x, *y),
+	Event::CursorEnter(entered) => WindowEvent::CursorEnter(*entered),
+	Event::Scroll(x, y) => WindowEvent::Scroll(*x, *y),
+	Event::Key(name, scancode, action, modifiers) =>
+	    WindowEvent::Key(key_from_name(name), *scancode, to_glfw_action(*action), to_glfw_modifiers(*modifiers)),
+	Event::Char(c) => WindowEvent::Char(*c),
+	Event::CharModifiers(c, modifiers) => WindowEvent::CharModifiers(*c, to_glfw_modifiers(*modifiers)),
+	Event::FileDrop(paths) => WindowEvent::FileDrop(paths.clone()),
+	Event::Maximize(maximized) => WindowEvent::Maximize(*maximized),
+	Event::ContentScale(x, y) => WindowEvent::ContentScale(*x, *y),
+	Event::Paste(_) => return None
+    };
+    Some(event)
+}
+
+fn gamepad_button(button:GamepadButton) -> glfw::GamepadButton {
+    match button {
+	GamepadButton::A => glfw::GamepadButton::ButtonA,
+	GamepadButton::B => glfw::GamepadButton::ButtonB,
+	GamepadButton::X => glfw::GamepadButton::ButtonX,
+	GamepadButton::Y => glfw::GamepadButton::ButtonY,
+	GamepadButton::LeftBumper => glfw::GamepadButton::ButtonLeftBumper,
+	GamepadButton::RightBumper => glfw::GamepadButton::ButtonRightBumper,
+	GamepadButton::Back => glfw::GamepadButton::ButtonBack,
+	GamepadButton::Start => glfw::GamepadButton::ButtonStart,
+	GamepadButton::Guide => glfw::GamepadButton::ButtonGuide,
+	GamepadButton::LeftThumb => glfw::GamepadButton::ButtonLeftThumb,
+	GamepadButton::RightThumb => glfw::GamepadButton::ButtonRightThumb,
+	GamepadButton::DpadUp => glfw::GamepadButton::ButtonDpadUp,
+	GamepadButton::DpadRight => glfw::GamepadButton::ButtonDpadRight,
+	GamepadButton::DpadDown => glfw::GamepadButton::ButtonDpadDown,
+	GamepadButton::DpadLeft => glfw::GamepadButton::ButtonDpadLeft
+    }
+}
+
+fn gamepad_axis(axis:GamepadAxis) -> glfw::GamepadAxis {
+    match axis {
+	GamepadAxis::LeftX => glfw::GamepadAxis::AxisLeftX,
+	GamepadAxis::LeftY => glfw::GamepadAxis::AxisLeftY,
+	GamepadAxis::RightX => glfw::GamepadAxis::AxisRightX,
+	GamepadAxis::RightY => glfw::GamepadAxis::AxisRightY,
+	GamepadAxis::LeftTrigger => glfw::GamepadAxis::AxisLeftTrigger,
+	GamepadAxis::RightTrigger => glfw::GamepadAxis::AxisRightTrigger
+    }
+}
+
+/* GLFWのトリガーは離して-1,引ききって1なので0から1にする */
+pub fn gamepad_state(state:&glfw::GamepadState) -> GamepadState {
+    let mut result = GamepadState::new();
+    for button in GAMEPAD_BUTTONS.iter() {
+	result = result.with_button(*button, state.get_button_state(gamepad_button(*button)) == glfw::Action::Press);
+    }
+    for axis in GAMEPAD_AXES.iter() {
+	let value = state.get_axis(gamepad_axis(*axis)) as f64;
+	let value = match axis {
+	    GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => 0.5*(value + 1.0),
+	    _ => value
+	};
+	result = result.with_axis(*axis, value);
+    }
+    result
+}
@@ -0,0 +1,252 @@
+use std::{ptr,time};
+use std::cell::Cell;
+use std::os::raw::c_void;
+
+use khronos_egl as egl;
+use glutils::golden::{RgbaImage,read_pixels};
+use graphic_math::input::GamepadState;
+
+use crate::AppConfig;
+use crate::event::Event;
+use crate::platform::{Platform,CursorMode,PlatformError};
+use crate::recording::Recording;
+
+/*
+  ウィンドウを出さずにEGLのpbufferに描く. ディスプレイのない環境でも動くように,
+  MesaのsurfacelessのEGLディスプレイがあればそれを使う.
+  時刻は実時間ではなくフレーム数から決め,イベントは決めておいたフレームで渡すので,
+  同じ入力からは毎回同じフレームになる. 自動テストで使う
+*/
+pub struct HeadlessPlatform {
+    display: egl::Display,
+    surface: egl::Surface,
+    context: egl::Context,
+    width: i32,
+    height: i32,
+    frame: u64,
+    frame_interval: time::Duration,
+    /* 記録を流すときの始まりの時刻と各フレームの時刻. 最初にtimeを呼んだときは始まりの時刻を返す */
+    replay_times: Option<(time::Duration, Vec<time::Duration>)>,
+    time_started: Cell<bool>,
+    content_scale: f32,
+    /* このフレーム数を描いたら閉じる */
+    frame_limit: Option<u64>,
+    /* フレーム番号とそのフレームで渡すイベント. フレーム順に並べる */
+    script: Vec<(u64, Event)>,
+    next_event: usize,
+    /* フレーム番号とそのフレームからのゲームパッドの状態 */
+    gamepad_script: Vec<(u64, Option<GamepadState>)>,
+    cursor: (f64,f64),
+    should_close: bool,
+    capture_frames: Vec<u64>,
+    captures: Vec<(u64, RgbaImage)>
+}
+
+/* EGL_MESA_platform_surfaceless */
+const PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+fn context_error(e:egl::Error) -> PlatformError {
+    PlatformError::Context(e.to_string())
+}
+
+impl HeadlessPlatform {
+    pub fn create(config:&AppConfig) -> Result<Self, PlatformError> {
+	let api = &egl::API;
+	let display = api.get_platform_display(PLATFORM_SURFACELESS_MESA, ptr::null_mut(), &[egl::ATTRIB_NONE]).ok()
+	    .or_else(|| api.get_display(egl::DEFAULT_DISPLAY))
+	    .ok_or_else(|| PlatformError::Init("no EGL display".to_string()))?;
+	api.initialize(display).map_err(|e| PlatformError::Init(e.to_string()))?;
+
+	let attributes = [
+	    egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+	    egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+	    egl::RED_SIZE, 8,
+	    egl::GREEN_SIZE, 8,
+	    egl::BLUE_SIZE, 8,
+	    egl::ALPHA_SIZE, 8,
+	    egl::DEPTH_SIZE, 24,
+	    egl::NONE
+	];
+	let egl_config = api.choose_first_config(display, &attributes).map_err(context_error)?
+	    .ok_or_else(|| PlatformError::Context("no EGL config for OpenGL pbuffer".to_string()))?;
+	let (width, height) = (config.width as i32, config.height as i32);
+	let surface = api.create_pbuffer_surface(display, egl_config, &[egl::WIDTH, width, egl::HEIGHT, height, egl::NONE])
+	    .map_err(context_error)?;
+	api.bind_api(egl::OPENGL_API).map_err(context_error)?;
+	//shaderは#version 300 esも使うので,版を指定せずに互換プロファイルのコンテキストにする
+	let context = api.create_context(display, egl_config, None, &[egl::NONE]).map_err(context_error)?;
+	api.make_current(display, Some(surface), Some(surface), Some(context)).map_err(context_error)?;
+
+	Ok(HeadlessPlatform {
+	    display,
+	    surface,
+	    context,
+	    width,
+	    height,
+	    frame: 0,
+	    frame_interval: config.fixed_step,
+	    replay_times: None,
+	    time_started: Cell::new(false),
+	    content_scale: 1.0,
+	    frame_limit: None,
+	    script: Vec::new(),
+	    next_event: 0,
+	    gamepad_script: Vec::new(),
+	    cursor: (0.0, 0.0),
+	    should_close: false,
+	    capture_frames: Vec::new(),
+	    captures: Vec::new()
+	})
+    }
+
+    /* 1フレームで進む時刻. 決めなければAppConfig::fixed_step */
+    pub fn with_frame_interval(mut self, interval:time::Duration) -> Self {
+	self.frame_interval = interval;
+	self
+    }
+
+    pub fn with_frame_limit(mut self, frames:u64) -> Self {
+	self.frame_limit = Some(frames);
+	self
+    }
+
+    /* (フレーム番号,イベント)の並び. 同じフレームのイベントは並びの順に渡す */
+    pub fn with_script(mut self, mut script:Vec<(u64, Event)>) -> Self {
+	script.sort_by_key(|(frame, _)| *frame);
+	self.script = script;
+	self.next_event = 0;
+	self
+    }
+
+    /* (フレーム番号,状態)の並び. 次の状態のフレームまで同じ状態が続く. 最初の状態までは外れている */
+    pub fn with_gamepad_script(mut self, mut script:Vec<(u64, Option<GamepadState>)>) -> Self {
+	script.sort_by_key(|(frame, _)| *frame);
+	self.gamepad_script = script;
+	self
+    }
+
+    /*
+      RecordingPlatformで記録した入力を,記録したときと同じフレームと時刻で流す.
+      最後のフレームを描いたら閉じる. 大きさはcreateに渡したAppConfigのまま
+    */
+    pub fn with_recording(self, recording:&Recording) -> Self {
+	let gamepad = recording.frames.iter().enumerate()
+	    .map(|(frame, recorded)| (frame as u64, recorded.gamepad)).collect();
+	let mut platform = self.with_script(recording.script())
+	    .with_gamepad_script(gamepad)
+	    .with_frame_limit(recording.frames.len() as u64);
+	platform.replay_times = Some((recording.start_time, recording.frames.iter().map(|recorded| recorded.time).collect()));
+	platform.content_scale = recording.content_scale;
+	platform
+    }
+
+    /* frame番目のフレームを描き終えたときに画面を読み出す */
+    pub fn capture_frame(&mut self, frame:u64) {
+	self.capture_frames.push(frame);
+    }
+
+    pub fn frame(&self) -> u64 {
+	self.frame
+    }
+
+    /* 読み出したフレーム番号と画像 */
+    pub fn captures(&self) -> &[(u64, RgbaImage)] {
+	&self.captures
+    }
+
+    pub fn take_captures(&mut self) -> Vec<(u64, RgbaImage)> {
+	std::mem::take(&mut self.captures)
+    }
+}
+
+impl Platform for HeadlessPlatform {
+    fn get_proc_address(&mut self, name:&str) -> *const c_void {
+	egl::API.get_proc_address(name).map_or(ptr::null(), |f| f as *const c_void)
+    }
+
+    fn framebuffer_size(&self) -> (i32,i32) {
+	(self.width, self.height)
+    }
+
+    fn content_scale(&self) -> f32 {
+	self.content_scale
+    }
+
+    fn time(&self) -> time::Duration {
+	match &self.replay_times {
+	    Some((start, times)) => {
+		if !self.time_started.replace(true) {
+		    *start
+		}
+		else {
+		    times.get(self.frame as usize).or_else(|| times.last()).copied().unwrap_or(*start)
+		}
+	    },
+	    None => self.frame_interval*(self.frame as u32)
+	}
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+	let mut events = Vec::new();
+	while let Some((frame, event)) = self.script.get(self.next_event) {
+	    if *frame > self.frame {
+		break;
+	    }
+	    match *event {
+		Event::CursorPos(x, y) => self.cursor = (x, y),
+		Event::Close => self.should_close = true,
+		_ => {}
+	    }
+	    events.push(event.clone());
+	    self.next_event += 1;
+	}
+	events
+    }
+
+    fn swap_buffers(&mut self) {
+	if self.capture_frames.contains(&self.frame) {
+	    self.captures.push((self.frame, read_pixels(self.width as u32, self.height as u32)));
+	}
+	//pbufferは読み出すだけなのでswapしなくてよい
+	self.frame += 1;
+	if self.frame_limit.is_some_and(|limit| self.frame >= limit) {
+	    self.should_close = true;
+	}
+    }
+
+    fn should_close(&self) -> bool {
+	self.should_close
+    }
+
+    fn set_should_close(&mut self, close:bool) {
+	self.should_close = close;
+    }
+
+    fn set_vsync(&mut self, _vsync:bool) {}
+
+    fn cursor_pos(&self) -> (f64,f64) {
+	self.cursor
+    }
+
+    fn set_cursor_mode(&mut self, _mode:CursorMode) {}
+
+    fn gamepad(&mut self) -> Option<GamepadState> {
+	self.gamepad_script.iter().take_while(|(frame, _)| *frame <= self.frame)
+	    .last().and_then(|(_, state)| *state)
+    }
+
+    fn is_realtime(&self) -> bool {
+	false
+    }
+}
+
+impl Drop for HeadlessPlatform {
+    fn drop(&mut self) {
+	let api = &egl::API;
+	//終了時の失敗はどうしようもないので無視する
+	let _ = api.make_current(self.display, None, None, None);
+	let _ = api.destroy_context(self.display, self.context);
+	let _ = api.destroy_surface(self.display, self.surface);
+	let _ = api.terminate(self.display);
+    }
+}
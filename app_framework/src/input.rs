@@ -0,0 +1,37 @@
+use graphic_math::camera::MouseButton;
+use graphic_math::input::{Button,Input};
+
+use crate::event::{Action,Event};
+
+/*
+  Platformのイベントをgraphic_math::inputの状態にする.
+  キーの名前は割り当てのファイルと同じく"W","Space","LeftShift"のように書く
+*/
+
+/* 0から始まるボタンの番号. 左,右,中 */
+pub fn mouse_button(button:i32) -> Option<MouseButton> {
+    match button {
+	0 => Some(MouseButton::Left),
+	1 => Some(MouseButton::Right),
+	2 => Some(MouseButton::Middle),
+	_ => None
+    }
+}
+
+pub fn apply_event(input:&mut Input, event:&Event) {
+    match event {
+	//キーリピートは押したままとして扱う
+	Event::Key(_, _, Action::Repeat, _) => {},
+	Event::Key(key, _, action, _) => input.state.button_event(Button::Key(key.clone()), *action == Action::Press),
+	Event::MouseButton(button, action, _) => {
+	    if let Some(button) = mouse_button(*button) {
+		input.state.button_event(Button::Mouse(button), *action == Action::Press);
+	    }
+	},
+	Event::CursorPos(x, y) => input.state.cursor_moved(*x, *y),
+	Event::Scroll(_, y) => input.state.scrolled(*y),
+	//別のウィンドウに移ると離したイベントが来ないことがある
+	Event::Focus(false) => input.state.release_all(),
+	_ => {}
+    }
+}
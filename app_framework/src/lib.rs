@@ -0,0 +1,511 @@
+use std::{thread,time};
+use std::path::PathBuf;
+
+use egui::{vec2, Pos2, Rect};
+use graphic_math::timestep::{FixedTimestep,FramePacer};
+use graphic_math::input::{Input,ActionMap};
+use glutils::hdr::default_framebuffer_is_srgb;
+#[cfg(feature = "headless")]
+use glutils::golden::RgbaImage;
+
+// 例で同じ版を使うように出しておく
+pub use egui;
+pub use glfw;
+
+pub mod event;
+pub mod platform;
+pub mod glfw_platform;
+pub mod input;
+pub mod recording;
+pub mod widgets;
+#[cfg(feature = "headless")]
+pub mod headless;
+
+pub use event::{Event,Action,Modifiers};
+pub use platform::{Platform,CursorMode,PlatformError};
+pub use glfw_platform::GlfwPlatform;
+pub use recording::{Recording,RecordingPlatform,RecordingError};
+#[cfg(feature = "headless")]
+pub use headless::HeadlessPlatform;
+
+/*
+  例で共通のメインループ. eguiの初期化,イベントの受け渡し,固定時間刻みのupdate,
+  描画とeguiの描画,swapまでをrunがして,例はAppを実装するだけにする.
+  ウィンドウやGLのコンテキストはPlatformが作るので,run_onにHeadlessPlatformを渡せばウィンドウなしで動く
+*/
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct AppConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    /* ウィンドウのframebufferのサンプル数(MSAA). Noneならドライバの既定. GlfwPlatformだけが使う */
+    pub samples: Option<u32>,
+    /* 垂直同期を待つ. 実行中はLoopControlで変えられる */
+    pub vsync: bool,
+    /* 垂直同期を待たないときのフレームレートの上限. Noneなら待たない */
+    pub max_fps: Option<f64>,
+    /* App::updateを呼ぶ間隔 */
+    pub fixed_step: time::Duration,
+    /* 1フレームで進める時間の上限. 重いフレームのあとにupdateが溜まりすぎないようにする */
+    pub max_frame_time: time::Duration,
+    pub close_on_escape: bool,
+    /* App::action_mapの割り当てを上書きするRONのファイル. なければ既定のまま */
+    pub bindings_file: Option<PathBuf>,
+    /* runで入力を記録して,閉じたときに書き出すファイル. replayで流せる */
+    pub record_file: Option<PathBuf>
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+	AppConfig {
+	    title: "app".to_string(),
+	    width: 800,
+	    height: 600,
+	    samples: None,
+	    vsync: true,
+	    max_fps: None,
+	    fixed_step: time::Duration::from_secs_f64(1.0/60.0),
+	    max_frame_time: time::Duration::from_millis(250),
+	    close_on_escape: true,
+	    bindings_file: None,
+	    record_file: None
+	}
+    }
+}
+
+/* App::initに渡す. GLのコンテキストはもう使える */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct InitContext {
+    pub width: i32,
+    pub height: i32,
+    /* falseならシーンをシェーダでsRGBにする. eguiはrunが合わせる */
+    pub srgb_framebuffer: bool
+}
+
+/* App::renderに渡すフレームの情報 */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct FrameContext {
+    /* framebufferの大きさ */
+    pub width: i32,
+    pub height: i32,
+    /* 最後のupdateから次のupdateまでの進み具合(0-1). 前の状態と今の状態の補間に使う */
+    pub alpha: f64,
+    /* 前のフレームからの実時間 */
+    pub frame_time: time::Duration,
+    /* updateで進めた時間の合計 */
+    pub time: time::Duration,
+    pub frame: u64
+}
+
+/* 実行中にメインループの設定を変える */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct LoopControl {
+    pub vsync: bool,
+    pub max_fps: Option<f64>,
+    pub cursor_mode: CursorMode,
+    should_close: bool
+}
+
+impl LoopControl {
+    pub fn close(&mut self) {
+	self.should_close = true;
+    }
+
+    pub fn should_close(&self) -> bool {
+	self.should_close
+    }
+}
+
+pub trait App {
+    /* ウィンドウとGLのコンテキストを作ったあとに1度呼ぶ */
+    fn init(context:&InitContext) -> Self where Self: Sized;
+
+    /* 使うアクションと既定の割り当て. initのあとに1度呼ぶ */
+    fn action_map(&self) -> ActionMap {
+	ActionMap::new()
+    }
+
+    /* AppConfig::fixed_stepごとに呼ぶ. dtはいつも同じ. 押したことや移動量はどれか1回のupdateにだけ入る */
+    fn update(&mut self, _dt:time::Duration, _input:&Input) {}
+
+    fn render(&mut self, context:&FrameContext);
+
+    /* eguiにも同じイベントを渡している */
+    fn on_event(&mut self, _event:&Event, _control:&mut LoopControl) {}
+
+    /* 毎フレームrenderの前に呼ぶ. eguiはシーンの上に描く */
+    fn ui(&mut self, _egui_ctx:&egui::CtxRef, _control:&mut LoopControl) {}
+}
+
+/* GLFWのウィンドウで,ウィンドウを閉じるまで戻らない */
+pub fn run<A: App>(config:AppConfig) {
+    let mut platform = GlfwPlatform::create(&config).unwrap_or_else(|e| panic!("{}", e));
+    match config.record_file.as_ref() {
+	Some(path) => {
+	    let mut recorder = RecordingPlatform::new(platform);
+	    run_on::<A, _>(&mut recorder, &config);
+	    if let Err(e) = recorder.into_recording().save_file(path) {
+		println!("{}", e);
+	    }
+	},
+	None => {
+	    run_on::<A, _>(&mut platform, &config);
+	}
+    }
+}
+
+/*
+  記録した入力をウィンドウなしで流して,capture_framesのフレームの画像と最後のAppを返す.
+  同じ記録からは毎回同じ画像になるので,golden imageと比べるテストに使う
+*/
+#[cfg(feature = "headless")]
+pub fn replay<A: App>(config:&AppConfig, recording:&Recording, capture_frames:&[u64])
+		      -> Result<(A, Vec<(u64, RgbaImage)>), PlatformError> {
+    let (width, height) = recording.framebuffer_size;
+    let config = AppConfig { width: width as u32, height: height as u32, record_file: None, ..config.clone() };
+    let mut platform = HeadlessPlatform::create(&config)?.with_recording(recording);
+    for frame in capture_frames {
+	platform.capture_frame(*frame);
+    }
+    let app = run_on::<A, _>(&mut platform, &config);
+    Ok((app, platform.take_captures()))
+}
+
+/* platformが閉じるまで回して,最後のAppを返す. テストで状態を確かめられる */
+pub fn run_on<A: App, P: Platform>(platform:&mut P, config:&AppConfig) -> A {
+    gl::load_with(|symbol| platform.get_proc_address(symbol));
+
+    let mut painter = egui_glfw_gl::Painter::create();
+    let mut egui_ctx = egui::CtxRef::default();
+
+    let (width, height) = platform.framebuffer_size();
+    let native_pixels_per_point = platform.content_scale();
+    let mut egui_input_state = egui_glfw_gl::EguiInputState::new(egui::RawInput {
+        screen_rect: Some(Rect::from_min_size(
+            Pos2::new(0f32, 0f32),
+            vec2(width as f32, height as f32) / native_pixels_per_point,
+        )),
+        pixels_per_point: Some(native_pixels_per_point),
+        ..Default::default()
+    });
+
+    //クリップボードはPlatformを通す. 貼り付けはEvent::Pasteで届くので記録にも残る
+    egui_input_state.clipboard = None;
+
+    let srgb_framebuffer = default_framebuffer_is_srgb();
+    //sRGBのframebufferが取れなかったときは,eguiもシェーダで変換する
+    painter.set_encode_srgb_in_shader(!srgb_framebuffer);
+    let mut app = A::init(&InitContext { width, height, srgb_framebuffer });
+    let mut input = Input::new(app.action_map());
+    if let Some(path) = config.bindings_file.as_ref().filter(|path| path.exists()) {
+	match ActionMap::load_file(path) {
+	    Ok(map) => input.map.merge(&map),
+	    Err(e) => println!("{}", e)
+	}
+    }
+
+    let mut control = LoopControl {
+	vsync: config.vsync,
+	max_fps: config.max_fps,
+	cursor_mode: CursorMode::Normal,
+	should_close: false
+    };
+    let (mut applied_vsync, mut applied_cursor_mode) = (control.vsync, control.cursor_mode);
+    let mut timestep = FixedTimestep::new(config.fixed_step).with_max_frame_time(config.max_frame_time);
+    let start_time = platform.time();
+    let mut previous_time = start_time;
+    let mut frame = 0;
+    while !platform.should_close() {
+	let frame_start = time::Instant::now();
+	let now = platform.time();
+	let frame_time = now - previous_time;
+	previous_time = now;
+
+	//入力欄に打っているキーで視点などが動かないようにする
+	input.state.set_ui_focus(egui_ctx.wants_keyboard_input(), egui_ctx.wants_pointer_input());
+	for event in platform.poll_events() {
+	    if config.close_on_escape && event.is_key_press("Escape") {
+		control.close();
+	    }
+	    input::apply_event(&mut input, &event);
+	    app.on_event(&event, &mut control);
+	    //eguiはGLFWのイベントを受け取る
+	    if let Event::Paste(text) = event {
+		egui_input_state.input.events.push(egui::Event::Text(text));
+	    }
+	    else if let Some(event) = glfw_platform::to_glfw_event(&event) {
+		egui_glfw_gl::handle_event(event, &mut egui_input_state);
+	    }
+	}
+	//ゲームパッドにはイベントがないので毎フレーム状態を読む
+	input.state.set_gamepad(platform.gamepad());
+
+	for _ in 0..timestep.advance(frame_time) {
+	    app.update(timestep.step(), &input);
+	    input.state.end_step();
+	}
+
+	let (width, height) = platform.framebuffer_size();
+	let native_pixels_per_point = platform.content_scale();
+	egui_input_state.input.time = Some((now - start_time).as_secs_f64());
+	egui_input_state.input.screen_rect = Some(Rect::from_min_size(
+            Pos2::new(0f32, 0f32),
+            vec2(width as f32, height as f32) / native_pixels_per_point,
+        ));
+	egui_input_state.input.pixels_per_point = Some(native_pixels_per_point);
+	egui_ctx.begin_frame(egui_input_state.input.take());
+	//takeで消えるが,次のフレームのイベントを変換するのに使う
+	egui_input_state.input.pixels_per_point = Some(native_pixels_per_point);
+	app.ui(&egui_ctx, &mut control);
+	let (egui_output, paint_cmds) = egui_ctx.end_frame();
+
+	app.render(&FrameContext {
+	    width,
+	    height,
+	    alpha: timestep.alpha(),
+	    frame_time,
+	    time: timestep.time(),
+	    frame
+	});
+
+	//Handle cut, copy text from egui
+        if !egui_output.copied_text.is_empty() {
+            platform.set_clipboard(&egui_output.copied_text);
+        }
+        let paint_jobs = egui_ctx.tessellate(paint_cmds);
+        painter.paint_jobs(
+            None,
+            paint_jobs,
+            &egui_ctx.texture(),
+	    width,
+	    height,
+            native_pixels_per_point,
+        );
+	//eguiが有効にしたブレンドを戻して,次のApp::renderはブレンドなしから始める
+	unsafe {
+	    gl::Disable(gl::BLEND);
+	}
+	platform.swap_buffers();
+	frame += 1;
+
+	if control.vsync != applied_vsync {
+	    platform.set_vsync(control.vsync);
+	    applied_vsync = control.vsync;
+	}
+	if control.cursor_mode != applied_cursor_mode {
+	    platform.set_cursor_mode(control.cursor_mode);
+	    applied_cursor_mode = control.cursor_mode;
+	}
+	if control.should_close() {
+	    platform.set_should_close(true);
+	}
+	//垂直同期を待つときはswap_buffersが間隔を決める
+	if platform.is_realtime() && !control.vsync {
+	    thread::sleep(FramePacer::new(control.max_fps).wait_time(frame_start.elapsed()));
+	}
+    }
+    app
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use graphic_math::input::{GamepadState,GamepadButton,GamepadAxis};
+    use linear_transform::vector::Vector3;
+    use recording::RecordedFrame;
+    use glfw_platform::{from_glfw_event,to_glfw_event,key_name,key_from_name};
+
+    fn key(name:&str, action:Action) -> Event {
+	Event::Key(name.to_string(), 0, action, Modifiers::default())
+    }
+
+    /* 時刻の間隔がそろっていない記録. 最後のフレームまでにSpaceを押して離す */
+    fn sample_recording() -> Recording {
+	let frame = |millis:u64, events:Vec<Event>| RecordedFrame {
+	    time: time::Duration::from_millis(millis),
+	    events,
+	    gamepad: None
+	};
+	let mut frames = vec![
+	    frame(16, vec![Event::CursorPos(10.5, 20.25), Event::MouseButton(0, Action::Press, Modifiers::default())]),
+	    frame(40, vec![Event::MouseButton(0, Action::Release, Modifiers::default())]),
+	    frame(41, vec![Event::Key("V".to_string(), 47, Action::Press, Modifiers::CONTROL), Event::Paste("pasted".to_string())]),
+	    frame(75, vec![key("Space", Action::Press), Event::Char('a'), Event::Scroll(0.0, -1.0)]),
+	    frame(90, vec![key("Space", Action::Release), Event::FileDrop(vec![PathBuf::from("a b.png")])]),
+	    frame(120, vec![])
+	];
+	frames[2].gamepad = Some(GamepadState::new().with_button(GamepadButton::A, true).with_axis(GamepadAxis::LeftX, -0.5));
+	Recording {
+	    framebuffer_size: (160, 120),
+	    content_scale: 1.0,
+	    start_time: time::Duration::from_millis(5),
+	    frames
+	}
+    }
+
+    #[test]
+    fn recording_round_trips_through_ron(){
+	let recording = sample_recording();
+	let text = recording.to_ron().unwrap();
+	assert_eq!(Recording::from_ron(&text).unwrap(), recording);
+	assert!(matches!(Recording::from_ron("(frames: 3)"), Err(RecordingError::Format(_))));
+
+	/* scriptは記録したフレームの番号を付けて同じ順に並べる */
+	let script = recording.script();
+	assert_eq!(script.len(), 10);
+	assert_eq!(script[0], (0, Event::CursorPos(10.5, 20.25)));
+	assert_eq!(script[4], (2, Event::Paste("pasted".to_string())));
+	assert_eq!(script.last().unwrap().0, 4);
+    }
+
+    #[test]
+    fn glfw_events_convert_both_ways(){
+	let modifiers = glfw::Modifiers::Shift | glfw::Modifiers::Control;
+	let events = vec![
+	    glfw::WindowEvent::Pos(1, 2),
+	    glfw::WindowEvent::Size(640, 480),
+	    glfw::WindowEvent::Close,
+	    glfw::WindowEvent::Focus(false),
+	    glfw::WindowEvent::FramebufferSize(1280, 960),
+	    glfw::WindowEvent::MouseButton(glfw::MouseButton::Button3, glfw::Action::Press, modifiers),
+	    glfw::WindowEvent::CursorPos(3.5, 4.0),
+	    glfw::WindowEvent::Scroll(0.0, 2.0),
+	    glfw::WindowEvent::Key(glfw::Key::LeftShift, 50, glfw::Action::Repeat, modifiers),
+	    glfw::WindowEvent::Char('x'),
+	    glfw::WindowEvent::CharModifiers('y', modifiers),
+	    glfw::WindowEvent::FileDrop(vec![PathBuf::from("scene.png")]),
+	    glfw::WindowEvent::ContentScale(2.0, 2.0)
+	];
+	for event in events.iter() {
+	    assert_eq!(to_glfw_event(&from_glfw_event(event)).as_ref(), Some(event));
+	}
+	assert_eq!(from_glfw_event(&events[5]), Event::MouseButton(2, Action::Press, Modifiers(0x3)));
+	assert_eq!(from_glfw_event(&events[8]), Event::Key("LeftShift".to_string(), 50, Action::Repeat, Modifiers(0x3)));
+
+	/* 割り当てのファイルに書く名前 */
+	assert_eq!(key_name(glfw::Key::Space), "Space");
+	assert_eq!(key_from_name("Kp5"), glfw::Key::Kp5);
+	assert_eq!(key_from_name("NoSuchKey"), glfw::Key::Unknown);
+	/* GLFWにないボタンと貼り付けはGLFWのイベントにならない */
+	assert_eq!(to_glfw_event(&Event::MouseButton(8, Action::Press, Modifiers::default())), None);
+	assert_eq!(to_glfw_event(&Event::Paste("text".to_string())), None);
+    }
+
+    #[test]
+    fn camera_entry_parses_the_fields(){
+	let camera = graphic_math::camera::Camera::new(Vector3(1.23456, 0.0, -2.0), Vector3(0.0, 0.5, 0.0), Vector3(0.0, 1.0, 0.0));
+	let mut entry = widgets::CameraEntry::new(&camera);
+	assert_eq!(entry.position, ["1.235".to_string(), "0".to_string(), "-2".to_string()]);
+	assert_eq!(entry.look_at(), Some((Vector3(1.235, 0.0, -2.0), Vector3(0.0, 0.5, 0.0), Vector3(0.0, 1.0, 0.0))));
+	entry.up[2] = "z".to_string();
+	assert_eq!(entry.look_at(), None);
+    }
+
+    /* Spaceを押すと消去する色が赤から緑になる */
+    #[cfg(feature = "headless")]
+    struct ClearApp {
+	color: [f32;3],
+	presses: u32
+    }
+
+    #[cfg(feature = "headless")]
+    impl App for ClearApp {
+	fn init(_context:&InitContext) -> Self {
+	    ClearApp { color: [1.0, 0.0, 0.0], presses: 0 }
+	}
+
+	fn on_event(&mut self, event:&Event, _control:&mut LoopControl) {
+	    if event.is_key_press("Space") {
+		self.color = [0.0, 1.0, 0.0];
+		self.presses += 1;
+	    }
+	}
+
+	fn render(&mut self, context:&FrameContext) {
+	    unsafe {
+		gl::Viewport(0, 0, context.width, context.height);
+		gl::ClearColor(self.color[0], self.color[1], self.color[2], 1.0);
+		gl::Clear(gl::COLOR_BUFFER_BIT);
+	    }
+	}
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn headless_app_receives_scripted_event(){
+	let config = AppConfig { width: 32, height: 24, ..AppConfig::default() };
+	let space = key("Space", Action::Press);
+	let mut platform = HeadlessPlatform::create(&config).unwrap_or_else(|e| panic!("{}", e))
+	    .with_script(vec![(2, space)])
+	    .with_frame_limit(4);
+	platform.capture_frame(1);
+	platform.capture_frame(3);
+	let app = run_on::<ClearApp, _>(&mut platform, &config);
+	assert_eq!(app.presses, 1);
+	assert_eq!(platform.frame(), 4);
+
+	let captures = platform.take_captures();
+	assert_eq!(captures.iter().map(|(frame, _)| *frame).collect::<Vec<_>>(), vec![1, 3]);
+	assert_eq!(&captures[0].1.pixels[0..4], &[255, 0, 0, 255]);
+	assert_eq!(&captures[1].1.pixels[0..4], &[0, 255, 0, 255]);
+    }
+
+    /* 入力欄に文字を打つ. 消去する色はupdateの回数とカーソルで変わる */
+    #[cfg(feature = "headless")]
+    struct TypingApp {
+	text: String,
+	updates: u32,
+	cursor: (f64,f64)
+    }
+
+    #[cfg(feature = "headless")]
+    impl App for TypingApp {
+	fn init(_context:&InitContext) -> Self {
+	    TypingApp { text: String::new(), updates: 0, cursor: (0.0, 0.0) }
+	}
+
+	fn update(&mut self, _dt:time::Duration, input:&Input) {
+	    self.updates += 1;
+	    if let Some(cursor) = input.state.cursor() {
+		self.cursor = cursor;
+	    }
+	}
+
+	fn ui(&mut self, egui_ctx:&egui::CtxRef, _control:&mut LoopControl) {
+	    egui::Window::new("typing").show(egui_ctx, |ui| {
+		ui.text_edit_singleline(&mut self.text).request_focus();
+		ui.label(format!("{} updates", self.updates));
+	    });
+	}
+
+	fn render(&mut self, context:&FrameContext) {
+	    unsafe {
+		gl::Viewport(0, 0, context.width, context.height);
+		gl::ClearColor((self.updates % 8) as f32/8.0, (self.cursor.0/160.0) as f32, 0.5, 1.0);
+		gl::Clear(gl::COLOR_BUFFER_BIT);
+	    }
+	}
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn replaying_a_recording_twice_gives_identical_frames(){
+	let recording = Recording::from_ron(&sample_recording().to_ron().unwrap()).unwrap();
+	let config = AppConfig { fixed_step: time::Duration::from_millis(10), ..AppConfig::default() };
+	let (app, first) = replay::<TypingApp>(&config, &recording, &[2, 5]).unwrap_or_else(|e| panic!("{}", e));
+	let (_, second) = replay::<TypingApp>(&config, &recording, &[2, 5]).unwrap_or_else(|e| panic!("{}", e));
+
+	/* 貼り付けは記録した文字列が入り,システムのクリップボードは読まない */
+	assert_eq!(app.text, "pasteda");
+	assert_eq!(app.cursor, (10.5, 20.25));
+	assert_eq!(first.len(), 2);
+	assert_eq!((first[0].1.width, first[0].1.height), (160, 120));
+	assert_ne!(first[0].1.pixels, first[1].1.pixels);
+	for ((frame_a, image_a), (frame_b, image_b)) in first.iter().zip(second.iter()) {
+	    assert_eq!(frame_a, frame_b);
+	    assert!(image_a.pixels == image_b.pixels, "frame {} differs between replays", frame_a);
+	}
+    }
+}
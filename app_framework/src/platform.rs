@@ -0,0 +1,81 @@
+use std::{fmt,time};
+use std::os::raw::c_void;
+
+use graphic_math::input::GamepadState;
+
+use crate::event::Event;
+
+/*
+  ウィンドウとGLのコンテキスト,イベントの取り出し方の違いをまとめる.
+  runはこのtraitだけを使うので,GLFWのウィンドウでもウィンドウのないコンテキストでも同じAppが動く.
+  イベントはEventにして返すので,AppはGLFWの型を使わなくてよい
+*/
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum CursorMode {
+    Normal,
+    /* ウィンドウの上では見えない */
+    Hidden,
+    /* 見えなくしてウィンドウから出ないようにする. 視点を回し続けるときに使う */
+    Disabled
+}
+
+#[derive(Debug)]
+pub enum PlatformError {
+    /* ライブラリの初期化に失敗した */
+    Init(String),
+    /* ウィンドウやGLのコンテキストを作れない */
+    Context(String)
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    PlatformError::Init(message) => write!(f, "failed to initialize platform. {}", message),
+	    PlatformError::Context(message) => write!(f, "failed to create GL context. {}", message)
+	}
+    }
+}
+
+impl std::error::Error for PlatformError {}
+
+pub trait Platform {
+    /* GLの関数のアドレス. runがgl::load_withに渡す */
+    fn get_proc_address(&mut self, name:&str) -> *const c_void;
+
+    fn framebuffer_size(&self) -> (i32,i32);
+
+    /* 画面の1pointの画素数. eguiの大きさを合わせる */
+    fn content_scale(&self) -> f32;
+
+    /* 始めてからの時刻. フレームの時間はこの差で決める */
+    fn time(&self) -> time::Duration;
+
+    /* 前に呼んでから届いたイベント */
+    fn poll_events(&mut self) -> Vec<Event>;
+
+    fn swap_buffers(&mut self);
+
+    fn should_close(&self) -> bool;
+
+    fn set_should_close(&mut self, close:bool);
+
+    fn set_vsync(&mut self, vsync:bool);
+
+    fn cursor_pos(&self) -> (f64,f64);
+
+    fn set_cursor_mode(&mut self, mode:CursorMode);
+
+    /* eguiでコピーした文字列. クリップボードのない環境では捨てる */
+    fn set_clipboard(&mut self, _text:&str) {}
+
+    /* 今のゲームパッドの状態. 遊びはInputStateで除くのでそのまま返す */
+    fn gamepad(&mut self) -> Option<GamepadState> {
+	None
+    }
+
+    /* 実時間で動くか. falseならフレームの間隔を合わせるために待たない */
+    fn is_realtime(&self) -> bool {
+	true
+    }
+}
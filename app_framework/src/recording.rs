@@ -0,0 +1,200 @@
+use std::{fmt,fs,io,time};
+use std::cell::Cell;
+use std::os::raw::c_void;
+use std::path::Path;
+
+use serde::{Serialize,Deserialize};
+use graphic_math::input::GamepadState;
+
+use crate::event::Event;
+use crate::platform::{Platform,CursorMode};
+
+/*
+  フレームごとのイベント,時刻,ゲームパッドの状態を記録してRONのファイルにする.
+  HeadlessPlatform::with_recordingで同じ順に流すと,eguiにもAppにも同じ入力が同じフレームで届き,
+  固定時間刻みのupdateの回数も同じになるので,UIや視点操作の不具合をウィンドウなしで再現できる
+*/
+
+/* 1フレームの入力. timeはそのフレームの始めにPlatform::timeが返した時刻 */
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct RecordedFrame {
+    pub time: time::Duration,
+    pub events: Vec<Event>,
+    pub gamepad: Option<GamepadState>
+}
+
+/*
+  start_timeはメインループの前に時刻を取ったときの値. 再生するときの画面の大きさは
+  framebuffer_sizeにするので,記録中にウィンドウの大きさを変えると同じ絵にはならない
+*/
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Recording {
+    pub framebuffer_size: (i32, i32),
+    pub content_scale: f32,
+    pub start_time: time::Duration,
+    pub frames: Vec<RecordedFrame>
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    /* RONとして読めない,または書けない */
+    Format(String)
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    RecordingError::Io(e) => write!(f, "failed to access input recording. {}", e),
+	    RecordingError::Format(message) => write!(f, "invalid input recording. {}", message)
+	}
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+impl From<io::Error> for RecordingError {
+    fn from(e:io::Error) -> Self {
+	RecordingError::Io(e)
+    }
+}
+
+impl Recording {
+    pub fn to_ron(&self) -> Result<String, RecordingError> {
+	ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+	    .map_err(|e| RecordingError::Format(e.to_string()))
+    }
+
+    pub fn from_ron(text:&str) -> Result<Self, RecordingError> {
+	ron::from_str(text).map_err(|e| RecordingError::Format(e.to_string()))
+    }
+
+    pub fn save_file<P: AsRef<Path>>(&self, path:P) -> Result<(), RecordingError> {
+	fs::write(path, self.to_ron()?)?;
+	Ok(())
+    }
+
+    pub fn load_file<P: AsRef<Path>>(path:P) -> Result<Self, RecordingError> {
+	Recording::from_ron(&fs::read_to_string(path)?)
+    }
+
+    /* フレーム番号を付けたイベント. HeadlessPlatform::with_scriptと同じ形 */
+    pub fn script(&self) -> Vec<(u64, Event)> {
+	self.frames.iter().enumerate().flat_map(|(frame, recorded)| {
+	    recorded.events.iter().map(move |event| (frame as u64, event.clone()))
+	}).collect()
+    }
+}
+
+/*
+  別のPlatformを包んで,runが使う入力を記録する. 描画などはそのまま渡す.
+  run_onは最初にtimeを呼んで始まりの時刻にし,毎フレームtime,poll_events,gamepadの順に呼ぶので,
+  最初に取った時刻と,poll_eventsの直前に取った時刻を記録する
+*/
+pub struct RecordingPlatform<P: Platform> {
+    inner: P,
+    framebuffer_size: (i32, i32),
+    content_scale: f32,
+    start_time: Cell<Option<time::Duration>>,
+    last_time: Cell<time::Duration>,
+    frames: Vec<RecordedFrame>
+}
+
+impl<P: Platform> RecordingPlatform<P> {
+    pub fn new(inner:P) -> Self {
+	RecordingPlatform {
+	    framebuffer_size: inner.framebuffer_size(),
+	    content_scale: inner.content_scale(),
+	    inner,
+	    start_time: Cell::new(None),
+	    last_time: Cell::new(time::Duration::ZERO),
+	    frames: Vec::new()
+	}
+    }
+
+    pub fn inner_mut(&mut self) -> &mut P {
+	&mut self.inner
+    }
+
+    pub fn into_recording(self) -> Recording {
+	Recording {
+	    framebuffer_size: self.framebuffer_size,
+	    content_scale: self.content_scale,
+	    start_time: self.start_time.get().unwrap_or(time::Duration::ZERO),
+	    frames: self.frames
+	}
+    }
+}
+
+impl<P: Platform> Platform for RecordingPlatform<P> {
+    fn get_proc_address(&mut self, name:&str) -> *const c_void {
+	self.inner.get_proc_address(name)
+    }
+
+    fn framebuffer_size(&self) -> (i32,i32) {
+	self.inner.framebuffer_size()
+    }
+
+    fn content_scale(&self) -> f32 {
+	self.inner.content_scale()
+    }
+
+    fn time(&self) -> time::Duration {
+	let now = self.inner.time();
+	if self.start_time.get().is_none() {
+	    self.start_time.set(Some(now));
+	}
+	self.last_time.set(now);
+	now
+    }
+
+    fn poll_events(&mut self) -> Vec<Event> {
+	let events = self.inner.poll_events();
+	self.frames.push(RecordedFrame {
+	    time: self.last_time.get(),
+	    events: events.clone(),
+	    gamepad: None
+	});
+	events
+    }
+
+    fn swap_buffers(&mut self) {
+	self.inner.swap_buffers();
+    }
+
+    fn should_close(&self) -> bool {
+	self.inner.should_close()
+    }
+
+    fn set_should_close(&mut self, close:bool) {
+	self.inner.set_should_close(close);
+    }
+
+    fn set_vsync(&mut self, vsync:bool) {
+	self.inner.set_vsync(vsync);
+    }
+
+    fn cursor_pos(&self) -> (f64,f64) {
+	self.inner.cursor_pos()
+    }
+
+    fn set_cursor_mode(&mut self, mode:CursorMode) {
+	self.inner.set_cursor_mode(mode);
+    }
+
+    fn set_clipboard(&mut self, text:&str) {
+	self.inner.set_clipboard(text);
+    }
+
+    fn gamepad(&mut self) -> Option<GamepadState> {
+	let state = self.inner.gamepad();
+	if let Some(frame) = self.frames.last_mut() {
+	    frame.gamepad = state;
+	}
+	state
+    }
+
+    fn is_realtime(&self) -> bool {
+	self.inner.is_realtime()
+    }
+}
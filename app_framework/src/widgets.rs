@@ -0,0 +1,106 @@
+use linear_transform::vector::Vector3;
+use graphic_math::camera::{Camera,ProjectionKind};
+
+/*
+  例で共通のeguiの部品. カメラの投影の設定と,視点を数値で入れる欄.
+*/
+
+/* 投影の種類と視野,クリップ面 */
+pub fn camera_ui(ui: &mut egui::Ui, camera: &mut Camera) {
+    ui.horizontal(|ui| {
+	ui.radio_value(&mut camera.projection, ProjectionKind::Perspective, "perspective");
+	ui.radio_value(&mut camera.projection, ProjectionKind::Orthographic, "orthographic");
+    });
+    match camera.projection {
+	ProjectionKind::Perspective => {
+	    ui.add(egui::Slider::new(&mut camera.fovy, 5.0..=120.0).text("fovy"));
+	},
+	ProjectionKind::Orthographic => {
+	    ui.add(egui::Slider::new(&mut camera.ortho_height, 0.1..=10.0).text("height"));
+	}
+    }
+    ui.horizontal(|ui| {
+	ui.label("near");
+	ui.add(egui::DragValue::new(&mut camera.near).speed(0.01).clamp_range(0.01..=100.0));
+	ui.label("far");
+	ui.add(egui::DragValue::new(&mut camera.far).speed(0.1).clamp_range(0.02..=1000.0));
+    });
+    //nearとfarが逆転すると何も見えなくなる
+    if camera.far <= camera.near {
+	camera.far = camera.near + 0.01;
+    }
+}
+
+/* CameraEntry::uiで押されたボタン */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum CameraCommand {
+    /* updateで入力欄の視点(eye,center,up)にする */
+    LookAt(Vector3, Vector3, Vector3),
+    /* resetで最初の視点に戻す */
+    Reset
+}
+
+/* 小数点以下3桁にした文字列 */
+fn fixed_strings(v:Vector3) -> [String;3] {
+    let fixed = |x:f64| ((x*1000.0).round()/1000.0).to_string();
+    [fixed(v.0), fixed(v.1), fixed(v.2)]
+}
+
+fn parse_vector(v:&[String;3]) -> Option<Vector3> {
+    match (v[0].parse::<f64>(), v[1].parse::<f64>(), v[2].parse::<f64>()) {
+	(Ok(x), Ok(y), Ok(z)) => Some(Vector3(x, y, z)),
+	_ => None
+    }
+}
+
+/* 視点の位置,注視点,上方向をx,y,zの入力欄で編集する. 入力中の文字列を持つ */
+#[derive(Debug,Clone,PartialEq)]
+pub struct CameraEntry {
+    pub position: [String;3],
+    pub center: [String;3],
+    pub up: [String;3]
+}
+
+impl CameraEntry {
+    pub fn new(camera:&Camera) -> Self {
+	CameraEntry {
+	    position: fixed_strings(camera.eye),
+	    center: fixed_strings(camera.center),
+	    up: fixed_strings(camera.up)
+	}
+    }
+
+    /* 入力欄の(eye,center,up). 数値でない欄があればNone */
+    pub fn look_at(&self) -> Option<(Vector3, Vector3, Vector3)> {
+	Some((parse_vector(&self.position)?, parse_vector(&self.center)?, parse_vector(&self.up)?))
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<CameraCommand> {
+	for (label, v) in [("position", &mut self.position), ("center", &mut self.center), ("up", &mut self.up)].iter_mut() {
+	    ui.horizontal(|ui| {
+		ui.set_max_width(250.0);
+		ui.label(*label);
+		for (axis, e) in ["x:", "y:", "z:"].iter().zip(v.iter_mut()) {
+		    ui.label(*axis);
+		    ui.add(egui::TextEdit::singleline(e).desired_width(30.0));
+		}
+	    });
+	}
+	let mut command = None;
+	ui.horizontal(|ui| {
+	    if ui.button("update").clicked() {
+		command = match self.look_at() {
+		    Some((eye, center, up)) => Some(CameraCommand::LookAt(eye, center, up)),
+		    None => {
+			println!("invalid input");
+			None
+		    }
+		};
+	    }
+	    if ui.button("reset").clicked() {
+		command = Some(CameraCommand::Reset);
+	    }
+	});
+	command
+    }
+}
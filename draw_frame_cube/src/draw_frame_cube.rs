@@ -3,7 +3,7 @@ use std::{ptr, str, mem};
 use gl;
 use gl::types::*;
 use linear_transform::{vector::*,matrix::{Matrix4x4}};
-use graphic_math::graphic_math;
+use graphic_math::camera::Camera;
 
 static VERTEX_SHADER_CODE: &'static str = include_str!("simple_viewport.vert");
 
@@ -99,15 +99,11 @@ fn link_program(vertex_shader: GLuint, fragment_shader: GLuint) -> GLuint {
 
 pub struct GlRender {
     shader_program: GLuint,
-    vertex_array_object: GLuint
+    vertex_array_object: GLuint,
+    camera: Camera
 }
 
-pub fn create_glrender<F>(loadfn:F) -> GlRender
-where
-    F:FnMut(&str) -> *const std::os::raw::c_void,
-{
-
-    gl::load_with(loadfn);
+pub fn create_glrender() -> GlRender {
 
     unsafe {
 	println!("Open GL version:{}",
@@ -176,24 +172,27 @@ where
 
     }
 
+    //視点と射影はCameraにまとめる. aspectはrenderでウィンドウに合わせる
+    let r:f64      = 7.0;
+    let theta:f64  = 45.0;
+    let phi:f64    = 45.0;
+    let eye    = Vector3(r*theta.sin()*phi.cos(),r*theta.sin()*phi.sin(),r*theta.cos());
+    let center = Vector3(0.0,0.0,0.0);
+    let up     = Vector3(0.0,1.0,0.0);
+    let camera = Camera::new(eye,center,up);
+
     GlRender {
 	shader_program: shader_program,
-	vertex_array_object: vao
+	vertex_array_object: vao,
+	camera
     }
 }
 
 impl GlRender {
-    pub fn render(&self) {
-	//射影変換行列を計算する.
-	let r:f64      = 7.0;
-	let theta:f64  = 45.0;
-	let phi:f64    = 45.0;
-	let eye    = Vector3(r*theta.sin()*phi.cos(),r*theta.sin()*phi.sin(),r*theta.cos());
-	let center = Vector3(0.0,0.0,0.0);
-	let up     = Vector3(0.0,1.0,0.0);
-	let lookat = graphic_math::look_at(eye,center,up);
-	let pers   = graphic_math::perspective(30.0, 1.0, 1.0, 11.0);
-	let mvp    = pers*lookat;
+    pub fn render(&mut self, width:i32, height:i32) {
+	//射影変換行列を計算する. aspectはウィンドウに合わせる
+	self.camera.resize(width, height);
+	let mvp    = self.camera.view_projection();
 	unsafe {
 	    let mvp_str = CString::new("mvp").unwrap_or_else(|_| panic!("failed to allocate string space"));
 	    let mvp_str_ptr = mvp_str.as_ptr();
@@ -1,38 +1,19 @@
 
 mod draw_frame_cube;
 
-use glfw::{Action, Context, Key};
+use app_framework::{App,AppConfig,InitContext,FrameContext};
 use draw_frame_cube::*;
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-
-    let (mut window, events) = glfw.create_window(800, 600, "Draw Frame Cube", glfw::WindowMode::Windowed)
-        .expect("Failed to create GLFW window.");
-
-    window.make_current();
-    window.set_key_polling(true);
-    window.set_framebuffer_size_polling(true);
-
-    let glrender = create_glrender(|s| window.get_proc_address(s) as *const _);
-
-    while !window.should_close() {
-	glfw.poll_events();
-        for (_, event) in glfw::flush_messages(&events) {
-            handle_window_event(&mut window, event);
-        }
+impl App for GlRender {
+    fn init(_context:&InitContext) -> Self {
+	create_glrender()
+    }
 
-	// rendering
-	glrender.render();
-	window.swap_buffers();
+    fn render(&mut self, context:&FrameContext) {
+	GlRender::render(self, context.width, context.height);
     }
 }
 
-fn handle_window_event(window: &mut glfw::Window, event: glfw::WindowEvent) {
-    match event {
-        glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-            window.set_should_close(true)
-        }
-        _ => {}
-    }
+fn main() {
+    app_framework::run::<GlRender>(AppConfig { title: "Draw Frame Cube".to_string(), ..AppConfig::default() });
 }
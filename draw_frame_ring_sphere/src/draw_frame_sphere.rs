@@ -4,7 +4,8 @@ use std::{f64::consts::PI};
 use gl;
 use gl::types::*;
 use linear_transform::{vector::*};
-use graphic_math::graphic_math;
+use graphic_math::camera::Camera;
+use glutils::msaa::{MsaaFramebuffer,clamp_samples,max_samples};
 
 static VERTEX_SHADER_CODE: &'static str = include_str!("simple_viewport.vert");
 
@@ -63,7 +64,13 @@ fn link_program(vertex_shader: GLuint, fragment_shader: GLuint) -> GLuint {
 
 pub struct GlRender {
     shader_program: GLuint,
-    vertex_array_object: Vec<GLuint>
+    vertex_array_object: Vec<GLuint>,
+    /* ウィンドウがマルチサンプルでないときに描くオフスクリーンのframebuffer */
+    msaa: MsaaFramebuffer,
+    samples: i32,
+    /* ウィンドウ作成時に指定したサンプル数. 0でなければ画面に直接描く */
+    window_samples: i32,
+    camera: Camera
 }
 
 fn sphere_vertices(radius:f64, slice:u32, stack:u32) -> (Vec<[f64;3]>,Vec<[u32;2]>) {
@@ -293,12 +300,8 @@ unsafe fn create_sphere_array_object(vao:GLuint) -> () {
     return;
 }
 
-pub fn create_glrender<F>(loadfn:F) -> GlRender
-where
-    F:FnMut(&str) -> *const std::os::raw::c_void,
-{
-
-    gl::load_with(loadfn);
+/* samplesはオフスクリーンのframebufferのサンプル数. 0ならマルチサンプルしない */
+pub fn create_glrender(samples:i32) -> GlRender {
 
     unsafe {
 	println!("Open GL version:{}",
@@ -311,11 +314,15 @@ where
     let fragment_shader = compile_shader(FRAGMENT_SHADER_CODE, gl::FRAGMENT_SHADER);
     let shader_program = link_program(vertex_shader, fragment_shader);
     let mut vao: [GLuint;2] = [0,0];
+    let mut window_samples:GLint = 0;
 
     unsafe {
 	gl::DeleteShader(fragment_shader);
 	gl::DeleteShader(vertex_shader);
 
+	//画面のframebufferのサンプル数
+	gl::GetIntegerv(gl::SAMPLES, &mut window_samples);
+
 	gl::GenVertexArrays(2, &mut vao[0]);
 
 	//座標軸をかく
@@ -326,25 +333,50 @@ where
 
     }
 
+    //視点と射影はCameraにまとめる. aspectはrenderでウィンドウに合わせる
+    let r:f64      = 4.0;
+    let theta:f64  = PI*60.0/180.0;
+    let phi:f64    = PI*45.0/180.0;
+    let eye    = Vector3(r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos());
+    let center = Vector3(0.0,0.0,0.0);
+    let up     = Vector3(0.0,0.0,1.0);
+    let camera = Camera::new(eye,center,up);
+
     GlRender {
 	shader_program: shader_program,
-	vertex_array_object: vec![vao[0],vao[1]]
+	vertex_array_object: vec![vao[0],vao[1]],
+	msaa: MsaaFramebuffer::create(1, 1, samples, gl::RGBA8),
+	samples: clamp_samples(samples, max_samples()),
+	window_samples,
+	camera
     }
 }
 
 impl GlRender {
-    pub fn render(&self, width:i32, height:i32) {
-	//射影変換行列を計算する.
-	let r:f64      = 4.0;
-	let theta:f64  = PI*60.0/180.0;
-	let phi:f64    = PI*45.0/180.0;
-	let eye    = Vector3(r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos());
-	let center = Vector3(0.0,0.0,0.0);
-	let up     = Vector3(0.0,0.0,1.0);
-	let lookat = graphic_math::look_at(eye,center,up);
-	let aspect = (width as f64)/ (height as f64);
-	let pers   = graphic_math::perspective(30.0, aspect, 1.0, 11.0);
-	let mvp    = pers*lookat;
+    /* オフスクリーンのサンプル数を変える. 上限に収めて,次のrenderで作りなおす */
+    pub fn set_samples(&mut self, samples:i32) {
+	self.samples = clamp_samples(samples, max_samples());
+    }
+
+    /* ウィンドウ自体のサンプル数. 0でなければset_samplesは効かない */
+    pub fn window_samples(&self) -> i32 {
+	self.window_samples
+    }
+
+    /* 実際に使っているサンプル数 */
+    pub fn samples(&self) -> i32 {
+	if self.window_samples > 0 { self.window_samples } else { self.samples }
+    }
+
+    pub fn render(&mut self, width:i32, height:i32) {
+	//ウィンドウがマルチサンプルでなければ,マルチサンプルのframebufferに描いてから画面に解決する
+	if self.window_samples == 0 {
+	    self.msaa.update(width, height, self.samples);
+	    self.msaa.bind();
+	}
+	//射影変換行列を計算する. aspectはウィンドウに合わせる
+	self.camera.resize(width, height);
+	let mvp    = self.camera.view_projection();
 	unsafe {
 	    let mvp_str = CString::new("mvp").unwrap_or_else(|_| panic!("failed to allocate string space"));
 	    let mvp_str_ptr = mvp_str.as_ptr();
@@ -363,6 +395,9 @@ impl GlRender {
 	    gl::DrawElements(gl::LINES, 48*7, gl::UNSIGNED_INT, ptr::null());
 	    gl::BindVertexArray(0);
 
+	    if self.window_samples == 0 {
+		self.msaa.resolve_to(0);
+	    }
 	    gl::Flush();
 	}
     }
@@ -1,39 +1,46 @@
 
 mod draw_frame_sphere;
 
-use glfw::{Action, Context, Key};
+use app_framework::{App,AppConfig,InitContext,FrameContext,LoopControl,Event};
 use draw_frame_sphere::*;
+use glutils::msaa::{next_sample_count,max_samples};
+
+/* --samples N オフスクリーンのサンプル数, --window-samples N ウィンドウ自体のサンプル数 */
+fn sample_option(name:&str, default:i32) -> i32 {
+    let args:Vec<String> = std::env::args().collect();
+    args.windows(2).find(|pair| pair[0] == name)
+	.and_then(|pair| pair[1].parse::<i32>().ok())
+	.unwrap_or(default)
+}
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
-
-    let (mut window, events) = glfw.create_window(800, 600, "Draw Frame Sphere", glfw::WindowMode::Windowed)
-        .expect("Failed to create GLFW window.");
-
-    window.make_current();
-    window.set_key_polling(true);
-    window.set_framebuffer_size_polling(true);
-
-    let glrender = create_glrender(|s| window.get_proc_address(s) as *const _);
+impl App for GlRender {
+    fn init(_context:&InitContext) -> Self {
+	create_glrender(sample_option("--samples", 4))
+    }
 
-    while !window.should_close() {
-	glfw.poll_events();
-        for (_, event) in glfw::flush_messages(&events) {
-            handle_window_event(&mut window, event);
-        }
+    //Mキーでサンプル数を切り替える. --window-samplesのときはウィンドウのサンプル数で固定
+    fn on_event(&mut self, event:&Event, _control:&mut LoopControl) {
+	if !event.is_key_press("M") {
+	    return;
+	}
+	if self.window_samples() > 0 {
+	    println!("MSAA samples: {} (fixed by --window-samples)", self.window_samples());
+	    return;
+	}
+	self.set_samples(next_sample_count(self.samples(), max_samples()));
+	println!("MSAA samples: {}", self.samples());
+    }
 
-	// rendering
-	let (width,height) = window.get_size();
-	glrender.render(width,height);
-	window.swap_buffers();
+    fn render(&mut self, context:&FrameContext) {
+	GlRender::render(self, context.width, context.height);
     }
 }
 
-fn handle_window_event(window: &mut glfw::Window, event: glfw::WindowEvent) {
-    match event {
-        glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-            window.set_should_close(true)
-        },
-        _ => {}
-    }
+fn main() {
+    let window_samples = sample_option("--window-samples", 0);
+    app_framework::run::<GlRender>(AppConfig {
+	title: "Draw Frame Sphere".to_string(),
+	samples: if window_samples > 0 { Some(window_samples as u32) } else { None },
+	..AppConfig::default()
+    });
 }
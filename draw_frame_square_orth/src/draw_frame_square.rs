@@ -79,12 +79,7 @@ pub struct GlRender {
     vertex_array_object: GLuint
 }
 
-pub fn create_glrender<F>(loadfn:F) -> GlRender
-where
-    F:FnMut(&str) -> *const std::os::raw::c_void,
-{
-
-    gl::load_with(loadfn);
+pub fn create_glrender() -> GlRender {
 
     unsafe {
 	println!("Open GL version:{}",
@@ -5,6 +5,7 @@ use gl;
 use gl::types::*;
 use linear_transform::{vector::*,matrix::Matrix4x4};
 use graphic_math::graphic_math;
+use ::graphic_math::camera::Camera;
 
 static VERTEX_SHADER_CODE: &'static str = include_str!("simple_viewport.vert");
 
@@ -71,7 +72,8 @@ struct VertexArrayObjectContext {
 
 pub struct GlRender {
     shader_programs: Vec<GLuint>,
-    vertex_array_object_contexts: Vec<VertexArrayObjectContext>
+    vertex_array_object_contexts: Vec<VertexArrayObjectContext>,
+    camera: Camera
 }
 
 fn sphere_vertices(radius:f64, slice:u32, stack:u32) -> (Vec<[f64;3]>,Vec<[f64;3]>,Vec<[u32;3]>) {
@@ -359,16 +361,11 @@ unsafe fn create_sphere_array_object(vao:GLuint) ->  VertexArrayObjectContext {
     }
 }
 
-pub fn create_glrender<F>(loadfn:F) -> GlRender
-where
-    F:FnMut(&str) -> *const std::os::raw::c_void,
-{
+pub fn create_glrender() -> GlRender {
 
     let mut shader_programs:Vec<GLuint> = Vec::new();
     let mut ctxs : Vec<VertexArrayObjectContext> = Vec::new();
 
-    gl::load_with(loadfn);
-
     unsafe {
 	println!("Open GL version:{}",
 		 CStr::from_ptr(gl::GetString(gl::VERSION) as *const i8).to_string_lossy().into_owned());
@@ -412,25 +409,29 @@ where
 	}
     }
 
+    //視点と射影はCameraにまとめる. aspectはrenderでウィンドウに合わせる
+    let r:f64      = 4.0;
+    let theta:f64  = PI*60.0/180.0;
+    let phi:f64    = PI*45.0/180.0;
+    let eye    = Vector3(r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos());
+    let center = Vector3(0.0,0.0,0.0);
+    let up     = Vector3(0.0,0.0,1.0);
+    let camera = Camera::new(eye,center,up);
+
     GlRender {
 	shader_programs: shader_programs,
-	vertex_array_object_contexts: ctxs
+	vertex_array_object_contexts: ctxs,
+	camera
     }
 }
 
 impl GlRender {
-    pub fn render(&self, width:i32, height:i32) {
-	//射影変換行列を計算する.
-	let r:f64      = 4.0;
-	let theta:f64  = PI*60.0/180.0;
-	let phi:f64    = PI*45.0/180.0;
-	let eye    = Vector3(r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos());
-	let center = Vector3(0.0,0.0,0.0);
-	let up     = Vector3(0.0,0.0,1.0);
-	let lookat = graphic_math::look_at(eye,center,up);
-	let aspect = (width as f64)/ (height as f64);
-	let pers   = graphic_math::perspective(30.0, aspect, 1.0, 11.0);
-	let mvp    = pers*lookat;
+    pub fn render(&mut self, width:i32, height:i32) {
+	//射影変換行列を計算する. aspectはウィンドウに合わせる
+	self.camera.resize(width, height);
+	let lookat = self.camera.view();
+	let pers   = self.camera.projection();
+	let mvp    = self.camera.view_projection();
 	unsafe {
 
 	    gl::ClearColor(0.3, 0.3, 0.3, 1.0);
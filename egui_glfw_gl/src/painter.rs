@@ -67,6 +67,7 @@ const VS_SRC: &str = r#"
 const FS_SRC: &str = r#"
     #version 150
     uniform sampler2D u_sampler;
+    uniform bool u_encode_srgb;
     in vec4 v_rgba;
     in vec2 v_tc;
     out vec4 f_color;
@@ -98,6 +99,11 @@ const FS_SRC: &str = r#"
         // Need to convert from SRGBA to linear.
         vec4 texture_rgba = linear_from_srgba(texture(u_sampler, v_tc) * 255.0);
         f_color = v_rgba * texture_rgba;
+        // Without FRAMEBUFFER_SRGB the framebuffer stores what we write,
+        // so encode here to match the sRGB output of the 3D scene.
+        if (u_encode_srgb) {
+            f_color = srgba_from_linear(f_color) / 255.0;
+        }
     }
 "#;
 
@@ -113,6 +119,7 @@ pub struct Painter {
     vert_shader: GLuint,
     frag_shader: GLuint,
     user_textures: Vec<UserTexture>,
+    encode_srgb_in_shader: bool,
 }
 
 pub fn compile_shader(src: &str, ty: GLenum) -> GLuint {
@@ -184,9 +191,16 @@ impl Painter {
     pub fn new(
         window: &mut glfw::Window
     ) -> Painter {
+        gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+        Painter::create()
+    }
+
+    /// Creates the painter on the current context, for windows that do not
+    /// come from glfw (e.g. a headless EGL context). The GL functions must
+    /// already be loaded with `gl::load_with`.
+    pub fn create() -> Painter {
         unsafe {
             let mut egui_texture = 0;
-            gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
             gl::GenTextures(1, &mut egui_texture);
             gl::BindTexture(gl::TEXTURE_2D, egui_texture);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
@@ -222,10 +236,19 @@ impl Painter {
                 frag_shader,
                 egui_texture_version: None,
                 user_textures: Default::default(),
+                encode_srgb_in_shader: false,
             }
         }
     }
 
+    /// By default the painter relies on `FRAMEBUFFER_SRGB` to encode its
+    /// linear output. Set this when the window framebuffer is not sRGB
+    /// capable, so the shader writes sRGB values itself (the same choice as
+    /// `glutils::hdr::OutputEncoding::ShaderSrgb` for the 3D scene).
+    pub fn set_encode_srgb_in_shader(&mut self, enabled: bool) {
+        self.encode_srgb_in_shader = enabled;
+    }
+
     pub fn new_user_texture(
         &mut self,
         size: (usize, usize),
@@ -414,7 +437,9 @@ impl Painter {
             //Let OpenGL know we are dealing with SRGB colors so that it
             //can do the blending correctly. Not setting the framebuffer
             //leads to darkened, oversaturated colors.
-            gl::Enable(gl::FRAMEBUFFER_SRGB);
+            if !self.encode_srgb_in_shader {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
 
             gl::Enable(gl::SCISSOR_TEST);
             gl::Enable(gl::BLEND);
@@ -436,6 +461,9 @@ impl Painter {
             let u_sampler_ptr = u_sampler.as_ptr();
             let u_sampler_loc = gl::GetUniformLocation(self.program, u_sampler_ptr);
             gl::Uniform1i(u_sampler_loc, 0);
+            let u_encode_srgb = CString::new("u_encode_srgb").unwrap();
+            let u_encode_srgb_loc = gl::GetUniformLocation(self.program, u_encode_srgb.as_ptr());
+            gl::Uniform1i(u_encode_srgb_loc, self.encode_srgb_in_shader as i32);
             gl::Viewport(0, 0, canvas_width as i32, canvas_height as i32);
 
             for ClippedMesh(clip_rect, mesh) in meshes {
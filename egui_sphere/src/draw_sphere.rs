@@ -6,6 +6,8 @@ use gl;
 use gl::types::*;
 use linear_transform::{vector::*,matrix::Matrix4x4};
 use graphic_math::graphic_math;
+use ::graphic_math::camera::Camera;
+use glutils::hdr::{HdrTarget,ToneMapPass,ToneMapSettings};
 
 static VERTEX_SHADER_CODE: &'static str = include_str!("simple_viewport.vert");
 
@@ -26,7 +28,10 @@ struct RenderSet {
 
 pub struct DrawSphere {
     axis_set: RenderSet,
-    sphere_set: RenderSet
+    sphere_set: RenderSet,
+    hdr_target: HdrTarget,
+    tone_map: ToneMapPass,
+    tone_map_settings: ToneMapSettings
 }
 
 fn compile_shader(shader_code: &str, shader_type: GLenum) -> GLuint {
@@ -405,30 +410,32 @@ impl DrawSphere {
 
 	DrawSphere {
 	    axis_set: create_coordinate_axes_array(vao[0]),
-	    sphere_set: create_sphere_array_object(vao[1])
+	    sphere_set: create_sphere_array_object(vao[1]),
+	    //大きさは最初のrenderで合わせる
+	    hdr_target: HdrTarget::create(1, 1),
+	    tone_map: ToneMapPass::create(),
+	    tone_map_settings: ToneMapSettings::default()
 	}
     }
 
-    pub fn render(&self, width:i32, height:i32, camera_param:((f64,f64,f64),(f64,f64,f64),(f64,f64,f64))) -> () {
+    pub fn tone_map_settings_mut(&mut self) -> &mut ToneMapSettings {
+	&mut self.tone_map_settings
+    }
+
+    /* cameraのaspectは呼ぶ側でresizeしておく */
+    pub fn render(&mut self, width:i32, height:i32, camera:&Camera) -> () {
 	//射影変換行列を計算する.
-	let r:f64      = 4.0;
-	let theta:f64  = PI*60.0/180.0;
-	let phi:f64    = PI*45.0/180.0;
-	//let eye    = Vector3(r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos());
-	//let center = Vector3(0.0,0.0,0.0);
-	//let up     = Vector3(0.0,0.0,1.0);
-	let eye    = Vector3::make_from_tuple(camera_param.0);
-	let center = Vector3::make_from_tuple(camera_param.1);
-	let up     = Vector3::make_from_tuple(camera_param.2);
-	let lookat = graphic_math::look_at(eye,center,up);
-	let aspect = (width as f64)/ (height as f64);
-	let pers   = graphic_math::perspective(30.0, aspect, 1.0, 11.0);
+	let lookat = camera.view();
+	let pers   = camera.projection();
 	let mvp    = pers*lookat;
+	//シーンはリニアなままHDRのframebufferに描き,最後にトーンマッピングして画面に出す.
+	//eguiがFRAMEBUFFER_SRGBを使うので,画面に直接描くと色空間がずれる
+	self.hdr_target.resize(width, height);
+	self.hdr_target.bind();
 	unsafe {
 
 	    gl::ClearColor(0.3, 0.3, 0.3, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT);
-	    gl::Viewport(0, 0, width, height);
 
 	    gl::UseProgram(self.axis_set.shader);
 	    {
@@ -465,6 +472,12 @@ impl DrawSphere {
 		gl::BindVertexArray(0);
 	    }
 	    gl::Disable(gl::DEPTH_TEST);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	    gl::Viewport(0, 0, width, height);
+	}
+	self.tone_map.render(self.hdr_target.color_texture(), &self.tone_map_settings);
+
+	unsafe {
 	    gl::Flush();
 	}
 
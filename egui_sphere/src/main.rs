@@ -1,208 +1,249 @@
-
 mod draw_sphere;
 
-use std::{f64::consts::PI};
 use std::time;
 
-use glfw::{Context};
-use egui_glfw_gl::egui::{vec2, Color32, Image, Pos2, Rect};
+use app_framework::{App,AppConfig,InitContext,FrameContext,LoopControl};
+use app_framework::widgets::{CameraEntry,CameraCommand,camera_ui};
+use linear_transform::vector::*;
+use graphic_math::camera::{Camera,CameraController,OrbitController,ArcballController,FlyController,PanZoomController};
+use graphic_math::camera_bookmark::{CameraBookmarks,CameraTransition};
+use graphic_math::camera_path::{CameraPath,CameraKeyframe,CameraPathPlayer,PathInterpolation,PathOrientation};
+use graphic_math::input::{Input,ActionMap,drive_camera};
+use glutils::hdr::OutputEncoding;
 
 use draw_sphere::DrawSphere;
 
-macro_rules! real_fixed {
-    ($x:expr,$decimal:expr) => (($x * 10f64.powf($decimal as f64)).round()/(10f64.powf($decimal as f64)))
-}
+/* ブックマークは起動したディレクトリに保存して,次に起動したときに読み込む */
+const BOOKMARK_FILE: &str = "camera_bookmarks.ron";
+/* ブックマークへ移るのにかける秒数 */
+const BOOKMARK_TRANSITION_SECONDS: f64 = 1.0;
 
-#[derive(Clone,Copy,Debug)]
-struct CameraParamInput {
-    position: (f64, f64, f64),
-    center: (f64, f64, f64),
-    up: (f64, f64, f64),
+/* カメラパスに足すキーフレームの間隔(秒) */
+const PATH_KEY_INTERVAL: f64 = 2.0;
+
+fn save_bookmarks(bookmarks:&CameraBookmarks) {
+    if let Err(e) = bookmarks.save_file(BOOKMARK_FILE) {
+	println!("{}", e);
+    }
 }
 
-struct AppControlContext {
-    camera_parameter : CameraParamInput
+/*
+  今の視点に名前をつけて保存し,一覧から選んだ視点へ移る. 入力欄の名前はsaveとrenameに使う.
+  変更はその場でファイルに書く. goが押されたブックマークの位置を返す
+*/
+fn bookmark_ui(ui: &mut egui::Ui, bookmarks: &mut CameraBookmarks, name: &mut String, camera: &Camera) -> Option<usize> {
+    let mut recall = None;
+    let mut rename = None;
+    let mut remove = None;
+    let mut changed = false;
+    ui.label("bookmarks");
+    ui.horizontal(|ui| {
+	ui.add(egui::TextEdit::singleline(name).desired_width(120.0));
+	if ui.button("save").clicked() {
+	    let bookmark_name = if name.trim().is_empty() { bookmarks.unused_name() } else { name.trim().to_owned() };
+	    bookmarks.save(&bookmark_name, *camera);
+	    name.clear();
+	    changed = true;
+	}
+    });
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+	ui.horizontal(|ui| {
+	    if ui.button("go").clicked() {
+		recall = Some(index);
+	    }
+	    if ui.button("rename").clicked() {
+		rename = Some(index);
+	    }
+	    if ui.button("x").clicked() {
+		remove = Some(index);
+	    }
+	    ui.label(bookmark.name.as_str());
+	});
+    }
+    if let Some(index) = rename {
+	if !name.trim().is_empty() && bookmarks.rename(index, name.trim()) {
+	    name.clear();
+	    changed = true;
+	}
+    }
+    if let Some(index) = remove {
+	bookmarks.remove(index);
+	changed = true;
+    }
+    if changed {
+	save_bookmarks(bookmarks);
+    }
+    recall
 }
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap_or_else(|e| panic!("failed to init GLFW. {}",e));
-
-    let (mut window, events) = glfw.create_window(800, 600, "Draw Frame Sphere", glfw::WindowMode::Windowed)
-        .unwrap_or_else(|| panic!("Failed to create GLFW window."));
-
-    window.set_all_polling(true);
-    window.set_mouse_button_polling(true);
-    window.make_current();
-    glfw.set_swap_interval(glfw::SwapInterval::Sync(1));
-
-    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
-
-    let mut painter = egui_glfw_gl::Painter::new(&mut window);
-    let mut egui_ctx = egui::CtxRef::default();
-
-    let (width, height) = window.get_framebuffer_size();
-    let native_pixels_per_point = window.get_content_scale().0;
-    let mut egui_input_state = egui_glfw_gl::EguiInputState::new(egui::RawInput {
-        screen_rect: Some(Rect::from_min_size(
-            Pos2::new(0f32, 0f32),
-            vec2(width as f32, height as f32) / native_pixels_per_point,
-        )),
-        pixels_per_point: Some(native_pixels_per_point),
-        ..Default::default()
+/* 今の視点をキーフレームとして後ろに足していき,通して再生する */
+fn camera_path_ui(ui: &mut egui::Ui, path: &mut CameraPath, player: &mut CameraPathPlayer, camera: &Camera) {
+    ui.label("camera path");
+    ui.horizontal(|ui| {
+	ui.radio_value(&mut path.interpolation, PathInterpolation::Linear, "linear");
+	ui.radio_value(&mut path.interpolation, PathInterpolation::CatmullRom, "catmull-rom");
+	ui.radio_value(&mut path.interpolation, PathInterpolation::Bezier, "bezier");
     });
-    let start_time = time::Instant::now();
-    //let mut value_str: String = "0.0".to_owned();
-
-    let r:f64 = 4.0;
-    let theta:f64 = PI*60.0/180.0;
-    let phi:f64 = PI*45.0/180.0;
-    let init_camera_param : CameraParamInput = CameraParamInput {
-	position: (r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos()),
-	center: (0.0, 0.0, 0.0),
-	up: (0.0, 0.0, 1.0),
-    };
-
-    let mut control_context = AppControlContext {
-	camera_parameter: init_camera_param
-    };
-
-    let camera_position_fixed = (real_fixed!(control_context.camera_parameter.position.0, 3),
-				 real_fixed!(control_context.camera_parameter.position.1, 3),
-				 real_fixed!(control_context.camera_parameter.position.2, 3));
-    let camera_center_fixed = (real_fixed!(control_context.camera_parameter.center.0, 3),
-				 real_fixed!(control_context.camera_parameter.center.1, 3),
-			       real_fixed!(control_context.camera_parameter.center.2, 3));
-    let camera_up_fixed = (real_fixed!(control_context.camera_parameter.up.0, 3),
-			       real_fixed!(control_context.camera_parameter.up.1, 3),
-			       real_fixed!(control_context.camera_parameter.up.2, 3));
-    let mut camera_position = (camera_position_fixed.0.to_string().to_owned(),
-			       camera_position_fixed.1.to_string().to_owned(),
-			       camera_position_fixed.2.to_string().to_owned());
-    let mut camera_center   = (camera_center_fixed.0.to_string().to_owned(),
-			       camera_center_fixed.1.to_string().to_owned(),
-			       camera_center_fixed.2.to_string().to_owned());
-    let mut camera_up       = (camera_up_fixed.0.to_string().to_owned(),
-			       camera_up_fixed.1.to_string().to_owned(),
-			       camera_up_fixed.2.to_string().to_owned());
-
-    let draw_sphere = DrawSphere::create();
-
-    println!("{:?}",camera_position);
-    while !window.should_close() {
-	let mut camera_update = false;
-	let mut camera_reset = false;
-	let (width,height) = window.get_size();
-	let native_pixels_per_point = window.get_content_scale().0;
-
-	egui_input_state.input.time = Some(start_time.elapsed().as_secs_f64());
-	egui_ctx.begin_frame(egui_input_state.input.take());
-
-	egui_input_state.input.screen_rect = Some(Rect::from_min_size(
-            Pos2::new(0f32, 0f32),
-            vec2(width as f32, height as f32) / native_pixels_per_point,
-        ));
-	egui_input_state.input.pixels_per_point = Some(native_pixels_per_point);
-
-	egui::Window::new("Control").show(&egui_ctx, |ui| {
+    ui.horizontal(|ui| {
+	ui.radio_value(&mut path.orientation, PathOrientation::LookAt, "look at");
+	ui.radio_value(&mut path.orientation, PathOrientation::Slerp, "slerp");
+    });
+    ui.horizontal(|ui| {
+	if ui.button("add key").clicked() {
+	    let time = if path.is_empty() { 0.0 } else { path.end_time() + PATH_KEY_INTERVAL };
+	    path.add(CameraKeyframe::new(time, *camera));
+	}
+	if ui.button("clear").clicked() {
+	    path.clear();
+	    player.stop();
+	}
+	if player.playing {
+	    if ui.button("stop").clicked() {
+		player.stop();
+	    }
+	}
+	else if ui.button("play").clicked() && path.len() >= 2 {
+	    player.play(path);
+	}
+	ui.checkbox(&mut player.looping, "loop");
+    });
+    ui.label(format!("{} keys, {:.1}/{:.1} s", path.len(), player.time - path.start_time(), path.duration()));
+}
+
+/* 選べる視点の動かし方. どれも同じ視点から始める */
+fn create_camera_controllers(camera:&Camera) -> Vec<Box<dyn CameraController>> {
+    let (eye, center, up) = (camera.eye, camera.center, camera.up);
+    let mut controllers:Vec<Box<dyn CameraController>> = vec![
+	Box::new(OrbitController::new(center, 1.0, 90.0, 0.0)),
+	Box::new(ArcballController::new(eye, center, up)),
+	Box::new(FlyController::new(eye, 0.0, 0.0)),
+	Box::new(PanZoomController::new(eye, center, up))
+    ];
+    for controller in controllers.iter_mut() {
+	controller.set_look_at(eye, center, up);
+    }
+    controllers
+}
+
+struct SphereApp {
+    draw_sphere: DrawSphere,
+    camera: Camera,
+    init_camera: Camera,
+    camera_entry: CameraEntry,
+    /* マウスで視点を動かす. 入力欄の値はupdateで今のコントローラに設定する */
+    camera_controllers: Vec<Box<dyn CameraController>>,
+    camera_controller_index: usize,
+    bookmarks: CameraBookmarks,
+    bookmark_name: String,
+    camera_transition: Option<CameraTransition>,
+    camera_path: CameraPath,
+    camera_path_player: CameraPathPlayer
+}
+
+impl App for SphereApp {
+    fn init(context:&InitContext) -> Self {
+	let init_camera = Camera::from_spherical(Vector3(0.0, 0.0, 0.0), 4.0, 60.0, 45.0);
+	let mut draw_sphere = DrawSphere::create();
+	//sRGBのframebufferが取れなかったときは,シーンもシェーダで変換する
+	if !context.srgb_framebuffer {
+	    draw_sphere.tone_map_settings_mut().output = OutputEncoding::ShaderSrgb;
+	}
+	let bookmarks = CameraBookmarks::load_file(BOOKMARK_FILE).unwrap_or_else(|e| {
+	    println!("{}", e);
+	    CameraBookmarks::new()
+	});
+	SphereApp {
+	    draw_sphere,
+	    camera: init_camera,
+	    init_camera,
+	    camera_entry: CameraEntry::new(&init_camera),
+	    camera_controllers: create_camera_controllers(&init_camera),
+	    camera_controller_index: 0,
+	    bookmarks,
+	    bookmark_name: String::new(),
+	    camera_transition: None,
+	    camera_path: CameraPath::new(PathInterpolation::CatmullRom, PathOrientation::LookAt),
+	    camera_path_player: CameraPathPlayer::new()
+	}
+    }
+
+    /* ドラッグで回す(右ボタンで平行移動),スクロールで寄る,flyはWASD+QE */
+    fn action_map(&self) -> ActionMap {
+	ActionMap::camera_defaults()
+    }
+
+    fn update(&mut self, dt:time::Duration, input:&Input) {
+	let dt = dt.as_secs_f64();
+	let camera_controller = &mut self.camera_controllers[self.camera_controller_index];
+	drive_camera(input, camera_controller.as_mut());
+	//ブックマークへ移っている間はマウスより優先して視点と投影を決める
+	if let Some(transition) = self.camera_transition.as_mut() {
+	    let camera = transition.update(dt);
+	    camera_controller.set_look_at(camera.eye, camera.center, camera.up);
+	    self.camera = Camera { aspect: self.camera.aspect, ..camera };
+	    if transition.is_finished() {
+		self.camera_transition = None;
+	    }
+	}
+	//再生中のカメラパスはブックマークより優先する
+	if let Some(camera) = self.camera_path_player.update(&self.camera_path, dt) {
+	    camera_controller.set_look_at(camera.eye, camera.center, camera.up);
+	    self.camera = Camera { aspect: self.camera.aspect, ..camera };
+	}
+	camera_controller.update(dt);
+	self.camera.follow(camera_controller.as_ref());
+    }
+
+    fn render(&mut self, context:&FrameContext) {
+	self.camera_controllers[self.camera_controller_index].set_viewport(context.width as f64, context.height as f64);
+	self.camera.resize(context.width, context.height);
+	self.draw_sphere.render(context.width, context.height, &self.camera);
+    }
+
+    fn ui(&mut self, egui_ctx:&egui::CtxRef, _control:&mut LoopControl) {
+	let mut camera_command = None;
+	let mut bookmark_recall = None;
+	let camera = &mut self.camera;
+	let camera_entry = &mut self.camera_entry;
+	let camera_controllers = &self.camera_controllers;
+	let camera_controller_index = &mut self.camera_controller_index;
+	let (bookmarks, bookmark_name) = (&mut self.bookmarks, &mut self.bookmark_name);
+	let (camera_path, camera_path_player) = (&mut self.camera_path, &mut self.camera_path_player);
+	egui::Window::new("Control").show(egui_ctx, |ui| {
 	    ui.set_max_width(280.0);
 	    ui.group(|ui| {
 		ui.label("camera");
+		camera_command = camera_entry.ui(ui);
 		ui.horizontal(|ui| {
-		    ui.set_max_width(250.0);
-		    ui.label("position");
-		    ui.label("x:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_position.0).desired_width(30.0));
-		    ui.label("y:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_position.1).desired_width(30.0));
-		    ui.label("z:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_position.2).desired_width(30.0));
-		});
-		ui.horizontal(|ui| {
-		    ui.set_max_width(250.0);
-		    ui.label("center");
-		    ui.label("x:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_center.0).desired_width(30.0));
-		    ui.label("y:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_center.1).desired_width(30.0));
-		    ui.label("z:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_center.2).desired_width(30.0));
-		});
-		ui.horizontal(|ui| {
-		    ui.set_max_width(250.0);
-		    ui.label("up");
-		    ui.label("x:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_up.0).desired_width(30.0));
-		    ui.label("y:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_up.1).desired_width(30.0));
-		    ui.label("z:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_up.2).desired_width(30.0));
-		});
-		ui.horizontal(|ui| {
-		    if ui.button("update").clicked() {
-			camera_update = true;
-		    }
-		    if ui.button("reset").clicked() {
-			camera_reset = true;
+		    for (index, controller) in camera_controllers.iter().enumerate() {
+			ui.radio_value(camera_controller_index, index, controller.name());
 		    }
 		});
+		ui.label("drag: rotate/pan(right button), scroll: zoom, fly: WASD+QE");
+		camera_ui(ui, camera);
+	    });
+	    ui.group(|ui| {
+		bookmark_recall = bookmark_ui(ui, bookmarks, bookmark_name, camera);
+	    });
+	    ui.group(|ui| {
+		camera_path_ui(ui, camera_path, camera_path_player, camera);
 	    });
 	});
 
-	let (egui_output, paint_cmds) = egui_ctx.end_frame();
-	if camera_reset {
-	    control_context.camera_parameter = init_camera_param;
+	if camera_command == Some(CameraCommand::Reset) {
+	    self.camera = self.init_camera;
+	    self.camera_controllers = create_camera_controllers(&self.init_camera);
+	    self.camera_transition = None;
 	}
-	else if camera_update {
-	    let position = (camera_position.0.parse::<f64>(),
-			    camera_position.1.parse::<f64>(),
-			    camera_position.2.parse::<f64>());
-	    let center = (camera_center.0.parse::<f64>(),
-			  camera_center.1.parse::<f64>(),
-			  camera_center.2.parse::<f64>());
-	    let up = (camera_up.0.parse::<f64>(),
-		      camera_up.1.parse::<f64>(),
-		      camera_up.2.parse::<f64>());
-	    match (position,center,up) {
-		((Ok(px),Ok(py),Ok(pz)),(Ok(cx),Ok(cy),Ok(cz)),(Ok(ux),Ok(uy),Ok(uz))) => {
-		    control_context.camera_parameter.position = (px,py,pz);
-		    control_context.camera_parameter.center = (cx,cy,cz);
-		    control_context.camera_parameter.up = (ux,uy,uz);
-		},
-		_ => {
-		    println!("invalid input");
-		    ()
-		}
-	    }
+	else if let Some(bookmark) = bookmark_recall.and_then(|index| self.bookmarks.get(index)) {
+	    self.camera_transition = Some(CameraTransition::new(self.camera, bookmark.camera, BOOKMARK_TRANSITION_SECONDS));
+	}
+	else if let Some(CameraCommand::LookAt(eye, center, up)) = camera_command {
+	    self.camera_controllers[self.camera_controller_index].set_look_at(eye, center, up);
 	}
-
-	draw_sphere.render(width,height,
-			   (control_context.camera_parameter.position,
-			    control_context.camera_parameter.center,
-			    control_context.camera_parameter.up));
-
-	//Handle cut, copy text from egui
-        if !egui_output.copied_text.is_empty() {
-            egui_glfw_gl::copy_to_clipboard(&mut egui_input_state, egui_output.copied_text);
-        }
-
-        let paint_jobs = egui_ctx.tessellate(paint_cmds);
-        painter.paint_jobs(
-            None,
-            paint_jobs,
-            &egui_ctx.texture(),
-	    width,
-	    height,
-            native_pixels_per_point,
-        );
-
-	for (_, event) in glfw::flush_messages(&events) {
-            match event {
-                glfw::WindowEvent::Close => window.set_should_close(true),
-                _ => { egui_glfw_gl::handle_event(event, &mut egui_input_state); }
-            }
-        }
-
-	window.swap_buffers();
-        glfw.poll_events();
     }
 }
+
+fn main() {
+    app_framework::run::<SphereApp>(AppConfig { title: "Draw Frame Sphere".to_string(), ..AppConfig::default() });
+}
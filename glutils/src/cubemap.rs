@@ -0,0 +1,428 @@
+use std::f64::consts::PI;
+use std::path::Path;
+use std::ptr;
+
+use gl::types::*;
+use linear_transform::matrix::*;
+
+use crate::renderer::UniformValue;
+use crate::texture::{TextureData,TextureError,ColorSpace,Sampler,TextureBinding,mip_level_count};
+use crate::{compile_shader,link_program};
+
+static SKYBOX_VERTEX_SHADER_CODE: &str = include_str!("shaders/skybox.vert");
+
+static SKYBOX_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/skybox.frag");
+
+/* 環境マップを結びつけるテクスチャユニット. see shaders/environment.glsl */
+pub const ENVIRONMENT_TEXTURE_UNIT: GLuint = 9;
+
+/* GL_TEXTURE_CUBE_MAP_POSITIVE_Xから順に並べた面 */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum CubeFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5
+}
+
+pub const CUBE_FACES: [CubeFace;6] = [
+    CubeFace::PositiveX, CubeFace::NegativeX,
+    CubeFace::PositiveY, CubeFace::NegativeY,
+    CubeFace::PositiveZ, CubeFace::NegativeZ
+];
+
+impl CubeFace {
+    pub fn gl_target(&self) -> GLenum {
+	gl::TEXTURE_CUBE_MAP_POSITIVE_X + (*self as GLenum)
+    }
+
+    /*
+      面の上の点(s,t)が指す方向(正規化しない). s,tは0..1でGLの仕様の表と同じ向き.
+      tは面の画像の最初の行が0
+    */
+    pub fn direction(&self, s:f64, t:f64) -> [f64;3] {
+	let sc = 2.0*s - 1.0;
+	let tc = 2.0*t - 1.0;
+	match self {
+	    CubeFace::PositiveX => [ 1.0, -tc, -sc ],
+	    CubeFace::NegativeX => [ -1.0, -tc, sc ],
+	    CubeFace::PositiveY => [ sc, 1.0, tc ],
+	    CubeFace::NegativeY => [ sc, -1.0, -tc ],
+	    CubeFace::PositiveZ => [ sc, -tc, 1.0 ],
+	    CubeFace::NegativeZ => [ -sc, -tc, -1.0 ]
+	}
+    }
+}
+
+/* directionがキューブマップのどの面のどこに当たるか. directionの逆 */
+pub fn direction_to_face(direction:[f64;3]) -> (CubeFace, f64, f64) {
+    let [x, y, z] = direction;
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+	if x > 0.0 { (CubeFace::PositiveX, -z, -y, ax) } else { (CubeFace::NegativeX, z, -y, ax) }
+    }
+    else if ay >= az {
+	if y > 0.0 { (CubeFace::PositiveY, x, z, ay) } else { (CubeFace::NegativeY, x, -z, ay) }
+    }
+    else if z > 0.0 { (CubeFace::PositiveZ, x, -y, az) } else { (CubeFace::NegativeZ, -x, -y, az) };
+    (face, 0.5*(sc/ma + 1.0), 0.5*(tc/ma + 1.0))
+}
+
+/*
+  パノラマ画像(正距円筒図法)の座標. exampleに合わせて+zを上にする.
+  uは+x方向が0.5で反時計回りに増え,vは天頂が0(画像の最初の行)
+*/
+pub fn direction_to_equirect(direction:[f64;3]) -> (f64, f64) {
+    let [x, y, z] = direction;
+    let length = (x*x + y*y + z*z).sqrt().max(1.0e-12);
+    let u = 0.5 + y.atan2(x)/(2.0*PI);
+    let v = (z/length).clamp(-1.0, 1.0).acos()/PI;
+    (u, v)
+}
+
+/* 6面の画像. facesはCUBE_FACESの順で,各面は最初の行がt=0のRGBA8 */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct CubeMapData {
+    pub size: u32,
+    pub faces: Vec<Vec<u8>>
+}
+
+fn sample_bilinear(image:&TextureData, u:f64, v:f64) -> [u8;4] {
+    let (w, h) = (image.width as i64, image.height as i64);
+    /* uは一周するのでwrap,vは端で止める */
+    let x = u*(w as f64) - 0.5;
+    let y = (v*(h as f64) - 0.5).clamp(0.0, (h - 1) as f64);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let texel = |xi:i64, yi:i64| -> [f64;4] {
+	let xi = xi.rem_euclid(w);
+	let yi = yi.clamp(0, h - 1);
+	let i = ((yi*w + xi)*4) as usize;
+	[image.pixels[i] as f64, image.pixels[i+1] as f64, image.pixels[i+2] as f64, image.pixels[i+3] as f64]
+    };
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let (a, b, c, d) = (texel(x0, y0), texel(x0+1, y0), texel(x0, y0+1), texel(x0+1, y0+1));
+    let mut out = [0u8;4];
+    for k in 0..4 {
+	let top = a[k]*(1.0 - fx) + b[k]*fx;
+	let bottom = c[k]*(1.0 - fx) + d[k]*fx;
+	out[k] = (top*(1.0 - fy) + bottom*fy).round().clamp(0.0, 255.0) as u8;
+    }
+    out
+}
+
+impl CubeMapData {
+    /* 6枚の同じ大きさの正方形の画像から作る */
+    pub fn from_faces(faces:Vec<TextureData>) -> Result<Self, TextureError> {
+	if faces.len() != 6 {
+	    return Err(TextureError::Decode(format!("cube map needs 6 faces, but got {}", faces.len())));
+	}
+	let size = faces[0].width;
+	if let Some(face) = faces.iter().find(|f| f.width != size || f.height != size) {
+	    return Err(TextureError::InvalidSize { width: face.width, height: face.height, length: face.pixels.len() });
+	}
+	Ok(CubeMapData { size, faces: faces.into_iter().map(|f| f.pixels).collect() })
+    }
+
+    /* パノラマ画像をsize*sizeの6面に変換する */
+    pub fn from_equirect(panorama:&TextureData, size:u32) -> Self {
+	let faces = CUBE_FACES.iter().map(|face| {
+	    let mut pixels = Vec::with_capacity((size*size*4) as usize);
+	    for y in 0..size {
+		for x in 0..size {
+		    let s = (x as f64 + 0.5)/(size as f64);
+		    let t = (y as f64 + 0.5)/(size as f64);
+		    let (u, v) = direction_to_equirect(face.direction(s, t));
+		    pixels.extend_from_slice(&sample_bilinear(panorama, u, v));
+		}
+	    }
+	    pixels
+	}).collect();
+	CubeMapData { size, faces }
+    }
+
+    /* 上下で色が変わる空. 画像が無いときの代わりに使う. +zが上 */
+    pub fn gradient(size:u32, zenith:[u8;4], horizon:[u8;4], ground:[u8;4]) -> Self {
+	let mix = |a:[u8;4], b:[u8;4], t:f64| -> [u8;4] {
+	    let mut c = [0u8;4];
+	    for k in 0..4 {
+		c[k] = ((a[k] as f64)*(1.0 - t) + (b[k] as f64)*t).round() as u8;
+	    }
+	    c
+	};
+	let faces = CUBE_FACES.iter().map(|face| {
+	    let mut pixels = Vec::with_capacity((size*size*4) as usize);
+	    for y in 0..size {
+		for x in 0..size {
+		    let d = face.direction((x as f64 + 0.5)/(size as f64), (y as f64 + 0.5)/(size as f64));
+		    let elevation = d[2]/(d[0]*d[0] + d[1]*d[1] + d[2]*d[2]).sqrt();
+		    let color = if elevation >= 0.0 { mix(horizon, zenith, elevation) } else { mix(horizon, ground, (-4.0*elevation).min(1.0)) };
+		    pixels.extend_from_slice(&color);
+		}
+	    }
+	    pixels
+	}).collect();
+	CubeMapData { size, faces }
+    }
+}
+
+/* キューブマップテクスチャ. GLコンテキスト作成後に作る */
+pub struct CubeMap {
+    id: GLuint,
+    size: u32,
+    mip_levels: u32
+}
+
+impl CubeMap {
+
+    pub fn from_data(data:&CubeMapData, color_space:ColorSpace, mipmaps:bool) -> Self {
+	let mip_levels = if mipmaps { mip_level_count(data.size, data.size) } else { 1 };
+	let internal_format = match color_space {
+	    ColorSpace::Srgb => gl::SRGB8_ALPHA8,
+	    ColorSpace::Linear => gl::RGBA8
+	};
+	let mut id = 0;
+	unsafe {
+	    //面の境目で補間が切れないようにする. ES3.0では常に有効
+	    gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+	    gl::GenTextures(1, &mut id);
+	    gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+	    gl::TexStorage2D(gl::TEXTURE_CUBE_MAP, mip_levels as GLsizei, internal_format,
+			     data.size as GLsizei, data.size as GLsizei);
+	    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+	    for (face, pixels) in CUBE_FACES.iter().zip(data.faces.iter()) {
+		gl::TexSubImage2D(face.gl_target(), 0, 0, 0, data.size as GLsizei, data.size as GLsizei,
+				  gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const _);
+	    }
+	    if mipmaps {
+		gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+	    }
+	    let min_filter = if mipmaps { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
+	    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+	    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+	    gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+	}
+	CubeMap { id, size: data.size, mip_levels }
+    }
+
+    /* +x,-x,+y,-y,+z,-zの順の6枚の画像 */
+    pub fn load_faces<P: AsRef<Path>>(paths:&[P;6], color_space:ColorSpace) -> Result<Self, TextureError> {
+	let faces = paths.iter().map(TextureData::load).collect::<Result<Vec<_>,_>>()?;
+	Ok(CubeMap::from_data(&CubeMapData::from_faces(faces)?, color_space, true))
+    }
+
+    /* パノラマ画像を読み込んで一辺sizeのキューブマップにする */
+    pub fn load_equirect<P: AsRef<Path>>(path:P, size:u32, color_space:ColorSpace) -> Result<Self, TextureError> {
+	let panorama = TextureData::load(path)?;
+	Ok(CubeMap::from_data(&CubeMapData::from_equirect(&panorama, size), color_space, true))
+    }
+
+    /* 描画で作ったキューブマップを包む(IBLの前計算など). 削除はCubeMapが行う */
+    pub fn from_raw(id:GLuint, size:u32, mip_levels:u32) -> Self {
+	CubeMap { id, size, mip_levels }
+    }
+
+    pub fn id(&self) -> GLuint {
+	self.id
+    }
+
+    pub fn size(&self) -> u32 {
+	self.size
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+	self.mip_levels
+    }
+
+    pub fn bind(&self, unit:GLuint) {
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE0 + unit);
+	    gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	}
+    }
+
+    pub fn binding(&self, unit:GLuint, sampler:Option<&Sampler>) -> TextureBinding {
+	TextureBinding {
+	    unit,
+	    target: gl::TEXTURE_CUBE_MAP,
+	    texture: self.id,
+	    sampler: sampler.map(|s| s.id()).unwrap_or(0)
+	}
+    }
+}
+
+impl Drop for CubeMap {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteTextures(1, &self.id);
+	}
+    }
+}
+
+/*
+  shaders/environment.glslのuniform. 環境マップはENVIRONMENT_TEXTURE_UNITに結びつけておくこと.
+  enabledがfalseなら反射しない
+*/
+pub fn environment_uniforms(enabled:bool, intensity:f32) -> Vec<(String, UniformValue)> {
+    vec![
+	("environmentMap".to_string(), UniformValue::Int(ENVIRONMENT_TEXTURE_UNIT as GLint)),
+	("hasEnvironmentMap".to_string(), UniformValue::Int(if enabled { 1 } else { 0 })),
+	("environmentIntensity".to_string(), UniformValue::Float(intensity))
+    ]
+}
+
+/* ビュー行列から平行移動を除く. スカイボックスはカメラが動いても遠くにあるように見せる */
+pub fn rotation_only(view:&Matrix4x4) -> Matrix4x4 {
+    let mut m = *view;
+    for i in 0..3 {
+	m.v[i][3] = 0.0;
+	m.v[3][i] = 0.0;
+    }
+    m.v[3][3] = 1.0;
+    m
+}
+
+static SKYBOX_VERTEX_DATA: [[GLfloat;3];8] = [
+    [ -1.0, -1.0, -1.0 ],
+    [  1.0, -1.0, -1.0 ],
+    [  1.0,  1.0, -1.0 ],
+    [ -1.0,  1.0, -1.0 ],
+    [ -1.0, -1.0,  1.0 ],
+    [  1.0, -1.0,  1.0 ],
+    [  1.0,  1.0,  1.0 ],
+    [ -1.0,  1.0,  1.0 ],
+];
+
+/* 内側から見るので,表裏は気にしない(カリングしない) */
+static SKYBOX_INDEX_DATA: [[GLuint;3];12] = [
+    [ 0, 1, 2 ], [ 0, 2, 3 ],
+    [ 4, 6, 5 ], [ 4, 7, 6 ],
+    [ 0, 4, 5 ], [ 0, 5, 1 ],
+    [ 1, 5, 6 ], [ 1, 6, 2 ],
+    [ 2, 6, 7 ], [ 2, 7, 3 ],
+    [ 3, 7, 4 ], [ 3, 4, 0 ],
+];
+
+/* 原点を囲む一辺2の立方体. スカイボックスとキューブマップの各面への描画で使う */
+pub(crate) struct UnitCube {
+    vao: GLuint,
+    vbos: [GLuint;2]
+}
+
+impl UnitCube {
+    pub(crate) fn create() -> Self {
+	let mut vao = 0;
+	let mut vbos : [GLuint;2] = [0,0];
+	unsafe {
+	    gl::GenVertexArrays(1, &mut vao);
+	    gl::BindVertexArray(vao);
+	    gl::GenBuffers(2, &mut vbos[0]);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, vbos[0]);
+	    gl::BufferData(gl::ARRAY_BUFFER, std::mem::size_of_val(&SKYBOX_VERTEX_DATA) as GLsizeiptr,
+			   SKYBOX_VERTEX_DATA.as_ptr() as *const _, gl::STATIC_DRAW);
+	    gl::EnableVertexAttribArray(0);
+	    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+	    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vbos[1]);
+	    gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, std::mem::size_of_val(&SKYBOX_INDEX_DATA) as GLsizeiptr,
+			   SKYBOX_INDEX_DATA.as_ptr() as *const _, gl::STATIC_DRAW);
+	    gl::BindVertexArray(0);
+	    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+	}
+	UnitCube { vao, vbos }
+    }
+
+    /* programなどの設定は呼ぶ側で行う */
+    pub(crate) fn draw(&self) {
+	unsafe {
+	    gl::BindVertexArray(self.vao);
+	    gl::DrawElements(gl::TRIANGLES, (SKYBOX_INDEX_DATA.len()*3) as GLsizei, gl::UNSIGNED_INT, ptr::null());
+	    gl::BindVertexArray(0);
+	}
+    }
+}
+
+impl Drop for UnitCube {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteBuffers(2, &self.vbos[0]);
+	    gl::DeleteVertexArrays(1, &self.vao);
+	}
+    }
+}
+
+/*
+  背景にキューブマップを描く. 深度は最も奥(1.0)になるので,不透明な物体を描いた後に描くと
+  物体に隠れた部分のフラグメントシェーダが走らない.
+*/
+pub struct Skybox {
+    program: GLuint,
+    cube: UnitCube,
+    view_location: GLint,
+    projection_location: GLint,
+    environment_location: GLint,
+    intensity_location: GLint
+}
+
+impl Skybox {
+
+    pub fn create() -> Self {
+	let vertex_shader = compile_shader(SKYBOX_VERTEX_SHADER_CODE, gl::VERTEX_SHADER);
+	let fragment_shader = compile_shader(SKYBOX_FRAGMENT_SHADER_CODE, gl::FRAGMENT_SHADER);
+	let program = link_program(vertex_shader, fragment_shader);
+	let location = |name:&str| unsafe {
+	    let c_str = std::ffi::CString::new(name).unwrap();
+	    gl::GetUniformLocation(program, c_str.as_ptr())
+	};
+	unsafe {
+	    gl::DeleteShader(fragment_shader);
+	    gl::DeleteShader(vertex_shader);
+	}
+	Skybox {
+	    program,
+	    cube: UnitCube::create(),
+	    view_location: location("view"),
+	    projection_location: location("projection"),
+	    environment_location: location("environmentMap"),
+	    intensity_location: location("intensity")
+	}
+    }
+
+    /* viewは平行移動を含んだままでよい. 深度テストはLEQUALで行い,終わったら切る */
+    pub fn render(&self, view:&Matrix4x4, projection:&Matrix4x4, environment:&CubeMap, intensity:f32) {
+	let view = rotation_only(view);
+	environment.bind(ENVIRONMENT_TEXTURE_UNIT);
+	unsafe {
+	    gl::UseProgram(self.program);
+	    gl::UniformMatrix4fv(self.view_location, 1, gl::TRUE, view.serialize_f32().as_ptr());
+	    gl::UniformMatrix4fv(self.projection_location, 1, gl::TRUE, projection.serialize_f32().as_ptr());
+	    gl::Uniform1i(self.environment_location, ENVIRONMENT_TEXTURE_UNIT as GLint);
+	    gl::Uniform1f(self.intensity_location, intensity);
+
+	    gl::Enable(gl::DEPTH_TEST);
+	    gl::DepthFunc(gl::LEQUAL);
+	    gl::DepthMask(gl::FALSE);
+	    gl::Disable(gl::CULL_FACE);
+	}
+	self.cube.draw();
+	unsafe {
+	    gl::DepthMask(gl::TRUE);
+	    gl::DepthFunc(gl::LESS);
+	    gl::Disable(gl::DEPTH_TEST);
+	}
+    }
+}
+
+impl Drop for Skybox {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteProgram(self.program);
+	}
+    }
+}
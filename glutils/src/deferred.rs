@@ -0,0 +1,435 @@
+use gl::types::*;
+use linear_transform::matrix::*;
+
+use crate::renderer::{Renderer,DrawItem,Material,UniformValue};
+use crate::material::{ShadingModel,BASE_COLOR_TEXTURE_UNIT};
+use crate::texture::{Texture2D,Sampler};
+use crate::light::{Light,LightKind};
+use crate::cubemap::UnitCube;
+use crate::build_program;
+
+/*
+  遅延シェーディング. 不透明な三角形をまずGバッファ(色,法線,材質,深度)に描き,
+  その後で光源ごとに照らされる範囲だけを加算で描く. 光源の数が増えても物体を描きなおさない.
+  点光源とスポットライトは届く範囲を囲む立方体(ライトボリューム)を描き,
+  平行光源と範囲が決まらない光源は画面全体を描く.
+  照明は常にPBRのBRDFで計算するので,他のシェーディングモデルはdeferred_uniformsで近いパラメータに直す.
+  線や半透明の物体はGバッファに描けないので,呼ぶ側が後から前方描画する.
+*/
+
+static MATERIAL_VERTEX_SHADER_CODE: &str = include_str!("shaders/material.vert");
+
+static FULLSCREEN_VERTEX_SHADER_CODE: &str = include_str!("shaders/fullscreen.vert");
+
+static LIGHT_VOLUME_VERTEX_SHADER_CODE: &str = include_str!("shaders/light_volume.vert");
+
+static GEOMETRY_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/deferred_geometry.frag");
+
+static AMBIENT_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/deferred_ambient.frag");
+
+static LIGHT_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/deferred_light.frag");
+
+static DEBUG_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/deferred_debug.frag");
+
+/* 照明のパスでGバッファを結びつけるテクスチャユニット. 影やIBLのユニットとはぶつからない */
+const GBUFFER_TEXTURE_UNITS: [(&str, GLuint);4] = [("gAlbedo", 0), ("gNormal", 1), ("gMaterial", 2), ("gDepth", 3)];
+
+/* 画面に出すもの. Lit以外はGバッファの中身をそのまま見る */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum GBufferView {
+    Lit = 0,
+    Albedo = 1,
+    Normal = 2,
+    Material = 3,
+    Depth = 4
+}
+
+pub const GBUFFER_VIEWS: [GBufferView;5] = [GBufferView::Lit, GBufferView::Albedo, GBufferView::Normal,
+					      GBufferView::Material, GBufferView::Depth];
+
+impl GBufferView {
+    pub fn name(&self) -> &'static str {
+	match self {
+	    GBufferView::Lit => "lit",
+	    GBufferView::Albedo => "albedo",
+	    GBufferView::Normal => "normal",
+	    GBufferView::Material => "metallic/roughness/ao",
+	    GBufferView::Depth => "depth"
+	}
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct DeferredSettings {
+    /* falseなら前方描画. 切り替えは呼ぶ側で行う */
+    pub enabled: bool,
+    pub debug_view: GBufferView,
+    /* 光の強さ(radianceの最大の成分)がこれを下回る距離でライトボリュームを打ち切る */
+    pub light_cutoff: f32,
+    /* IBLを使わないときの環境光. アルベドに掛ける */
+    pub ambient: [f32;3]
+}
+
+impl Default for DeferredSettings {
+    fn default() -> Self {
+	DeferredSettings {
+	    enabled: false,
+	    debug_view: GBufferView::Lit,
+	    light_cutoff: 0.01,
+	    ambient: [0.03, 0.03, 0.03]
+	}
+    }
+}
+
+/*
+  shaders/deferred_geometry.fragのuniform. PBR以外は次のように直す.
+  Unlit: 照明しない. Lambert: 金属でなく最も粗い. Phong,Blinn-Phong: shininessから粗さを決める
+*/
+pub fn deferred_uniforms(model:&ShadingModel) -> Vec<(String, UniformValue)> {
+    let entry = |name:&str, value:UniformValue| (name.to_string(), value);
+    let (base_color, metallic, roughness, ambient_occlusion, unlit) = match model {
+	ShadingModel::Unlit(p) => ([p.base_color[0], p.base_color[1], p.base_color[2]], 0.0, 1.0, 1.0, 1),
+	ShadingModel::Lambert(p) => (p.diffuse, 0.0, 1.0, 1.0, 0),
+	ShadingModel::Phong(p) | ShadingModel::BlinnPhong(p) =>
+	    (p.diffuse, 0.0, (2.0/(p.shininess + 2.0)).sqrt().clamp(0.0, 1.0), 1.0, 0),
+	ShadingModel::Pbr(p) => (p.base_color, p.metallic, p.roughness, p.ambient_occlusion, 0)
+    };
+    vec![
+	entry("baseColor", UniformValue::Vec3(base_color)),
+	entry("metallic", UniformValue::Float(metallic)),
+	entry("roughness", UniformValue::Float(roughness)),
+	entry("ambientOcclusion", UniformValue::Float(ambient_occlusion)),
+	entry("unlit", UniformValue::Int(unlit))
+    ]
+}
+
+/* Gバッファに描くものか. 不透明な三角形だけ */
+pub fn is_deferred(item:&DrawItem) -> bool {
+    item.mesh.draw_mode == gl::TRIANGLES && !item.state.is_transparent()
+}
+
+/*
+  点光源とスポットライトの光が届く距離. radianceの最大の成分がcutoffまで減衰する距離で,
+  平行光源と減衰しない光源はNone(画面全体)
+*/
+pub fn light_range(light:&Light, cutoff:f32) -> Option<f32> {
+    let attenuation = match light.kind {
+	LightKind::Directional { .. } => return None,
+	LightKind::Point { attenuation, .. } | LightKind::Spot { attenuation, .. } => attenuation
+    };
+    let radiance = light.color.iter().fold(0.0f32, |m, c| m.max(*c))*light.intensity;
+    /* constant + linear*d + quadratic*d^2 = radiance/cutoff を解く */
+    let target = radiance/cutoff.max(1.0e-6);
+    if attenuation.constant >= target {
+	return Some(0.0);
+    }
+    if attenuation.quadratic > 0.0 {
+	let (a, b, c) = (attenuation.quadratic, attenuation.linear, attenuation.constant - target);
+	Some((-b + (b*b - 4.0*a*c).sqrt())/(2.0*a))
+    }
+    else if attenuation.linear > 0.0 {
+	Some((target - attenuation.constant)/attenuation.linear)
+    }
+    else {
+	None
+    }
+}
+
+/* 色,法線,材質の3枚の色と深度テクスチャ */
+struct DeferredGBuffer {
+    framebuffer: GLuint,
+    /* albedo, normal, material, depth の順 */
+    textures: [GLuint;4],
+    width: i32,
+    height: i32
+}
+
+impl DeferredGBuffer {
+    fn create(width:i32, height:i32) -> Self {
+	let formats = [gl::RGBA8, gl::RGBA16F, gl::RGBA8, gl::DEPTH_COMPONENT24];
+	let mut framebuffer = 0;
+	let mut textures:[GLuint;4] = [0;4];
+	unsafe {
+	    let mut previous_framebuffer:GLint = 0;
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	    gl::GenTextures(4, &mut textures[0]);
+	    for (texture, format) in textures.iter().zip(formats.iter()) {
+		gl::BindTexture(gl::TEXTURE_2D, *texture);
+		gl::TexStorage2D(gl::TEXTURE_2D, 1, *format, width, height);
+		//画素ごとに読むので補間しない
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	    }
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    let attachments = [gl::COLOR_ATTACHMENT0, gl::COLOR_ATTACHMENT1, gl::COLOR_ATTACHMENT2];
+	    for (attachment, texture) in attachments.iter().zip(textures.iter()) {
+		gl::FramebufferTexture2D(gl::FRAMEBUFFER, *attachment, gl::TEXTURE_2D, *texture, 0);
+	    }
+	    gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, textures[3], 0);
+	    gl::DrawBuffers(attachments.len() as GLsizei, attachments.as_ptr());
+	    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+		println!("deferred G-buffer framebuffer is incomplete");
+	    }
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+	}
+	DeferredGBuffer { framebuffer, textures, width, height }
+    }
+}
+
+impl Drop for DeferredGBuffer {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteFramebuffers(1, &self.framebuffer);
+	    gl::DeleteTextures(4, &self.textures[0]);
+	}
+    }
+}
+
+/* Gバッファと照明のパス. GLコンテキスト作成後に作る */
+pub struct DeferredRenderer {
+    geometry_program: GLuint,
+    ambient_program: GLuint,
+    /* 同じフラグメントシェーダで,画面全体に描くものとライトボリュームを描くもの */
+    fullscreen_light_program: GLuint,
+    volume_light_program: GLuint,
+    debug_program: GLuint,
+    vao: GLuint,
+    cube: UnitCube,
+    renderer: Renderer,
+    frame_uniforms: Vec<(String, UniformValue)>,
+    gbuffer: Option<DeferredGBuffer>
+}
+
+impl DeferredRenderer {
+
+    pub fn create() -> Self {
+	let mut vao = 0;
+	unsafe {
+	    gl::GenVertexArrays(1, &mut vao);
+	}
+	DeferredRenderer {
+	    geometry_program: build_program(MATERIAL_VERTEX_SHADER_CODE, GEOMETRY_FRAGMENT_SHADER_CODE),
+	    ambient_program: build_program(FULLSCREEN_VERTEX_SHADER_CODE, AMBIENT_FRAGMENT_SHADER_CODE),
+	    fullscreen_light_program: build_program(FULLSCREEN_VERTEX_SHADER_CODE, LIGHT_FRAGMENT_SHADER_CODE),
+	    volume_light_program: build_program(LIGHT_VOLUME_VERTEX_SHADER_CODE, LIGHT_FRAGMENT_SHADER_CODE),
+	    debug_program: build_program(FULLSCREEN_VERTEX_SHADER_CODE, DEBUG_FRAGMENT_SHADER_CODE),
+	    vao,
+	    cube: UnitCube::create(),
+	    renderer: Renderer::new(),
+	    frame_uniforms: Vec::new(),
+	    gbuffer: None
+	}
+    }
+
+    /* Gバッファに描くMaterial. MaterialPrograms::materialの代わりに使う */
+    pub fn material(&self, model:&ShadingModel) -> Material {
+	Material {
+	    shader: self.geometry_program,
+	    uniforms: deferred_uniforms(model),
+	    textures: Vec::new()
+	}.with_uniform("hasBaseColorMap", UniformValue::Int(0))
+    }
+
+    pub fn textured_material(&self, model:&ShadingModel, base_color_map:&Texture2D, sampler:Option<&Sampler>) -> Material {
+	self.material(model)
+	    .with_uniform("hasBaseColorMap", UniformValue::Int(1))
+	    .with_texture("baseColorMap", base_color_map.binding(BASE_COLOR_TEXTURE_UNIT, sampler))
+    }
+
+    /*
+      照明のパスに設定するuniform(影,IBL,SSAOなど). テクスチャは呼ぶ側で結びつけておく.
+      shadowLightは光源ごとに設定しなおす
+    */
+    pub fn set_frame_uniform(&mut self, name:&str, value:UniformValue) {
+	match self.frame_uniforms.iter_mut().find(|(n,_)| n == name) {
+	    Some(entry) => entry.1 = value,
+	    None => self.frame_uniforms.push((name.to_string(), value))
+	}
+    }
+
+    fn set_uniforms(&mut self, program:GLuint, uniforms:&[(String, UniformValue)]) {
+	for (name, value) in uniforms {
+	    self.renderer.set_uniform(program, name, value);
+	}
+    }
+
+    fn draw_fullscreen(&self) {
+	unsafe {
+	    gl::BindVertexArray(self.vao);
+	    gl::DrawArrays(gl::TRIANGLES, 0, 3);
+	    gl::BindVertexArray(0);
+	}
+    }
+
+    /*
+      itemsのうちis_deferredなものをGバッファに描き,lightsで照らして呼んだときのframebufferに描く.
+      大きさは今のviewportに合わせる. projectionは左右上下対称な透視投影であること.
+      描き終わるとGバッファの深度を呼んだときのframebufferに写すので,
+      そのframebufferの深度もDEPTH_COMPONENT24なら,残りの物体をそのまま前方描画で重ねられる
+    */
+    pub fn render(&mut self, view:&Matrix4x4, projection:&Matrix4x4, items:&[DrawItem],
+		  lights:&[Light], settings:&DeferredSettings) {
+	let mut output_framebuffer:GLint = 0;
+	let mut viewport:[GLint;4] = [0;4];
+	unsafe {
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut output_framebuffer);
+	    gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+	}
+	let (width, height) = (viewport[2].max(1), viewport[3].max(1));
+	if self.gbuffer.as_ref().map(|g| (g.width, g.height)) != Some((width, height)) {
+	    self.gbuffer = Some(DeferredGBuffer::create(width, height));
+	}
+	let (gbuffer_framebuffer, gbuffer_textures) = match &self.gbuffer {
+	    Some(gbuffer) => (gbuffer.framebuffer, gbuffer.textures),
+	    None => return
+	};
+
+	/* Gバッファに描く */
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, gbuffer_framebuffer);
+	    gl::Viewport(0, 0, width, height);
+	    gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+	    gl::DepthMask(gl::TRUE);
+	    gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT);
+	}
+	self.renderer.begin_frame(*view, *projection);
+	for item in items.iter().filter(|item| is_deferred(item)) {
+	    self.renderer.submit(item.clone());
+	}
+	self.renderer.end_frame();
+
+	/* Gバッファを読んで呼んだときのframebufferに描く */
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, output_framebuffer as GLuint);
+	    gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+	    gl::Disable(gl::DEPTH_TEST);
+	    gl::DepthMask(gl::FALSE);
+	    gl::Disable(gl::BLEND);
+	    gl::Disable(gl::CULL_FACE);
+	    for ((_, unit), texture) in GBUFFER_TEXTURE_UNITS.iter().zip(gbuffer_textures.iter()) {
+		gl::ActiveTexture(gl::TEXTURE0 + unit);
+		gl::BindTexture(gl::TEXTURE_2D, *texture);
+	    }
+	    gl::ActiveTexture(gl::TEXTURE0);
+	}
+	let mut common = vec![
+	    ("view".to_string(), UniformValue::Mat4(view.serialize_f32())),
+	    ("projection".to_string(), UniformValue::Mat4(projection.serialize_f32())),
+	    ("screenSize".to_string(), UniformValue::Vec2([width as f32, height as f32]))
+	];
+	for (name, unit) in GBUFFER_TEXTURE_UNITS.iter() {
+	    common.push((name.to_string(), UniformValue::Int(*unit as GLint)));
+	}
+
+	if settings.debug_view != GBufferView::Lit {
+	    let program = self.debug_program;
+	    self.set_uniforms(program, &common);
+	    self.renderer.set_uniform(program, "attachment", &UniformValue::Int(settings.debug_view as GLint));
+	    unsafe {
+		gl::UseProgram(program);
+	    }
+	    self.draw_fullscreen();
+	}
+	else {
+	    self.render_lighting(view, projection, &common, lights, settings);
+	}
+
+	unsafe {
+	    gl::UseProgram(0);
+	    for (_, unit) in GBUFFER_TEXTURE_UNITS.iter() {
+		gl::ActiveTexture(gl::TEXTURE0 + unit);
+		gl::BindTexture(gl::TEXTURE_2D, 0);
+	    }
+	    gl::ActiveTexture(gl::TEXTURE0);
+	    gl::DepthMask(gl::TRUE);
+	    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, gbuffer_framebuffer);
+	    gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, output_framebuffer as GLuint);
+	    gl::BlitFramebuffer(0, 0, width, height, 0, 0, width, height, gl::DEPTH_BUFFER_BIT, gl::NEAREST);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, output_framebuffer as GLuint);
+	}
+    }
+
+    /* 環境光を上書きで描いてから,光源ごとに加算する */
+    fn render_lighting(&mut self, view:&Matrix4x4, projection:&Matrix4x4, common:&[(String, UniformValue)],
+		       lights:&[Light], settings:&DeferredSettings) {
+	let frame_uniforms = self.frame_uniforms.clone();
+	let program = self.ambient_program;
+	self.set_uniforms(program, common);
+	self.set_uniforms(program, &frame_uniforms);
+	self.renderer.set_uniform(program, "ambientColor", &UniformValue::Vec3(settings.ambient));
+	unsafe {
+	    gl::UseProgram(program);
+	}
+	self.draw_fullscreen();
+
+	for program in [self.fullscreen_light_program, self.volume_light_program].iter() {
+	    self.set_uniforms(*program, common);
+	    self.set_uniforms(*program, &frame_uniforms);
+	}
+	unsafe {
+	    gl::Enable(gl::BLEND);
+	    gl::BlendFunc(gl::ONE, gl::ONE);
+	}
+	/* 影を落とす光源はLightSet::shadow_casterと同じく,有効な光源の中で最初のもの */
+	let enabled:Vec<&Light> = lights.iter().filter(|l| l.enabled).collect();
+	let shadow_caster = enabled.iter().position(|l| l.cast_shadow && !matches!(l.kind, LightKind::Point { .. }));
+	for (index, light) in enabled.iter().enumerate() {
+	    let range = light_range(light, settings.light_cutoff);
+	    if range == Some(0.0) {
+		continue;
+	    }
+	    let program = if range.is_some() { self.volume_light_program } else { self.fullscreen_light_program };
+	    self.set_uniforms(program, &light.uniforms(0));
+	    self.renderer.set_uniform(program, "lightCount", &UniformValue::Int(1));
+	    self.renderer.set_uniform(program, "shadowLight", &UniformValue::Int(if shadow_caster == Some(index) { 0 } else { -1 }));
+	    unsafe {
+		gl::UseProgram(program);
+	    }
+	    match (range, light.kind) {
+		(Some(range), LightKind::Point { position, .. }) | (Some(range), LightKind::Spot { position, .. }) => {
+		    /*
+		      UnitCubeの面は内側を向いているので,表面だけを描くと奥側の面だけになる.
+		      カメラが立方体の中に入っても各画素を一度だけ描く
+		    */
+		    let mut model = Matrix4x4::identity();
+		    for (i, p) in position.iter().enumerate() {
+			model.v[i][i] = range as f64;
+			model.v[i][3] = *p as f64;
+		    }
+		    let mvp = *projection**view*model;
+		    self.renderer.set_uniform(program, "mvp", &UniformValue::Mat4(mvp.serialize_f32()));
+		    unsafe {
+			gl::Enable(gl::CULL_FACE);
+			gl::CullFace(gl::BACK);
+		    }
+		    self.cube.draw();
+		    unsafe {
+			gl::Disable(gl::CULL_FACE);
+		    }
+		},
+		_ => self.draw_fullscreen()
+	    }
+	}
+	unsafe {
+	    gl::Disable(gl::BLEND);
+	}
+    }
+}
+
+impl Drop for DeferredRenderer {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteVertexArrays(1, &self.vao);
+	    gl::DeleteProgram(self.geometry_program);
+	    gl::DeleteProgram(self.ambient_program);
+	    gl::DeleteProgram(self.fullscreen_light_program);
+	    gl::DeleteProgram(self.volume_light_program);
+	    gl::DeleteProgram(self.debug_program);
+	}
+    }
+}
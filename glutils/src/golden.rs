@@ -0,0 +1,62 @@
+use gl::types::*;
+
+/*
+  ゴールデンイメージとの比較. 描画結果をRGBA8で読み出して,保存しておいた画像と比べる.
+  GPUやドライバで結果が少し変わるので,チャンネルごとの差がtolerance以下なら一致とみなす.
+*/
+
+/* 読み出した画像. pixelsは上の行から並べたRGBA8 */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct ImageDiff {
+    /* 一致しなかった画素の数 */
+    pub mismatched_pixels: usize,
+    /* チャンネルごとの差の最大値 */
+    pub max_difference: u8
+}
+
+impl ImageDiff {
+    /* 全画素のうちmismatched_pixelsの割合がmax_ratio以下か */
+    pub fn within(&self, pixel_count:usize, max_ratio:f64) -> bool {
+	(self.mismatched_pixels as f64) <= (pixel_count as f64)*max_ratio
+    }
+}
+
+/* 大きさが違う場合はNone */
+pub fn compare_rgba(expected:&RgbaImage, actual:&RgbaImage, tolerance:u8) -> Option<ImageDiff> {
+    if expected.width != actual.width || expected.height != actual.height ||
+	expected.pixels.len() != actual.pixels.len() {
+	return None;
+    }
+    let mut diff = ImageDiff { mismatched_pixels: 0, max_difference: 0 };
+    for (e, a) in expected.pixels.chunks(4).zip(actual.pixels.chunks(4)) {
+	let d = e.iter().zip(a.iter()).map(|(x, y)| (*x as i16 - *y as i16).unsigned_abs() as u8).max().unwrap_or(0);
+	diff.max_difference = diff.max_difference.max(d);
+	if d > tolerance {
+	    diff.mismatched_pixels += 1;
+	}
+    }
+    Some(diff)
+}
+
+/* 今のframebufferの(0,0)からwidth*heightを読み出す. GLは下の行からなので上下を入れ替える */
+pub fn read_pixels(width:u32, height:u32) -> RgbaImage {
+    let stride = (width*4) as usize;
+    let mut buffer = vec![0u8; stride*(height as usize)];
+    unsafe {
+	gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+	gl::ReadPixels(0, 0, width as GLsizei, height as GLsizei, gl::RGBA, gl::UNSIGNED_BYTE,
+		       buffer.as_mut_ptr() as *mut std::ffi::c_void);
+    }
+    let mut pixels = Vec::with_capacity(buffer.len());
+    for row in buffer.chunks(stride).rev() {
+	pixels.extend_from_slice(row);
+    }
+    RgbaImage { width, height, pixels }
+}
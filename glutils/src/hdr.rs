@@ -0,0 +1,266 @@
+use gl::types::*;
+
+use crate::{build_program,uniform_location};
+
+/*
+  HDRの描画先とトーンマッピング. シーンはリニアな値のままRGBA16Fに描き,
+  最後にToneMapPassで露出をかけて0..1に収め,sRGBにして画面に出す.
+  egui_glfw_gl::PainterもFRAMEBUFFER_SRGBで書くので,同じOutputEncodingにそろえると色空間が一致する.
+*/
+
+static FULLSCREEN_VERTEX_SHADER_CODE: &str = include_str!("shaders/fullscreen.vert");
+
+static TONEMAP_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/tonemap.frag");
+
+/* shaders/tonemap.fragのtoneMapperと同じ順 */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum ToneMapper {
+    /* 1.0を超えた分は切り捨てる */
+    Clamp = 0,
+    Reinhard = 1,
+    AcesFilmic = 2
+}
+
+pub const TONE_MAPPERS: [ToneMapper;3] = [ToneMapper::Clamp, ToneMapper::Reinhard, ToneMapper::AcesFilmic];
+
+impl ToneMapper {
+    pub fn name(&self) -> &'static str {
+	match self {
+	    ToneMapper::Clamp => "clamp",
+	    ToneMapper::Reinhard => "Reinhard",
+	    ToneMapper::AcesFilmic => "ACES filmic"
+	}
+    }
+
+    /* シェーダと同じ計算. 露出をかけた後のリニアな値を0..1にする */
+    pub fn apply(&self, x:f32) -> f32 {
+	let y = match self {
+	    ToneMapper::Clamp => x,
+	    ToneMapper::Reinhard => x/(1.0 + x),
+	    ToneMapper::AcesFilmic => (x*(2.51*x + 0.03))/(x*(2.43*x + 0.59) + 0.14)
+	};
+	y.clamp(0.0, 1.0)
+    }
+}
+
+/* 画面に出すときのsRGBへの変換をどこで行うか */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum OutputEncoding {
+    /* FRAMEBUFFER_SRGBを有効にしてGLに変換させる. 画面のframebufferがsRGB対応のとき */
+    FramebufferSrgb,
+    /* シェーダで変換する. framebufferがsRGBに対応していないとき */
+    ShaderSrgb,
+    /* 変換しない. 結果をさらに別のパスで加工するとき */
+    Linear
+}
+
+/* 画面のframebufferがsRGBで書けるか. glfwならWindowHint::SRgbCapable(true)で作る */
+pub fn default_framebuffer_is_srgb() -> bool {
+    let mut encoding:GLint = 0;
+    unsafe {
+	let mut previous_framebuffer:GLint = 0;
+	gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	gl::GetFramebufferAttachmentParameteriv(gl::FRAMEBUFFER, gl::BACK_LEFT,
+						gl::FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING, &mut encoding);
+	//コンテキストによってはBACK_LEFTを受け付けない
+	while gl::GetError() != gl::NO_ERROR {}
+	gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+    }
+    encoding as GLenum == gl::SRGB
+}
+
+/* リニアな値をsRGBの0..1にする. シェーダのsrgbFromLinearと同じ */
+pub fn srgb_from_linear(x:f32) -> f32 {
+    if x < 0.0031308 {
+	12.92*x
+    }
+    else {
+	1.055*x.powf(1.0/2.4) - 0.055
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct ToneMapSettings {
+    pub tone_mapper: ToneMapper,
+    pub exposure: f32,
+    pub output: OutputEncoding
+}
+
+impl Default for ToneMapSettings {
+    fn default() -> Self {
+	ToneMapSettings {
+	    tone_mapper: ToneMapper::AcesFilmic,
+	    exposure: 1.0,
+	    output: OutputEncoding::FramebufferSrgb
+	}
+    }
+}
+
+impl ToneMapSettings {
+    /* シーンのリニアな色が画面でどの値になるか. outputがFramebufferSrgbならGLが変換した後の値 */
+    pub fn apply(&self, color:[f32;3]) -> [f32;3] {
+	let mut out = [0.0;3];
+	for (o, c) in out.iter_mut().zip(color.iter()) {
+	    let mapped = self.tone_mapper.apply(self.exposure*c);
+	    *o = match self.output {
+		OutputEncoding::Linear => mapped,
+		_ => srgb_from_linear(mapped)
+	    };
+	}
+	out
+    }
+}
+
+/* シーンを描くRGBA16Fの色と24bitの深度. GLコンテキスト作成後に作る */
+pub struct HdrTarget {
+    framebuffer: GLuint,
+    color_texture: GLuint,
+    depth_renderbuffer: GLuint,
+    width: i32,
+    height: i32
+}
+
+impl HdrTarget {
+
+    pub fn create(width:i32, height:i32) -> Self {
+	let (width, height) = (width.max(1), height.max(1));
+	let mut framebuffer = 0;
+	let mut color_texture = 0;
+	let mut depth_renderbuffer = 0;
+	unsafe {
+	    gl::GenTextures(1, &mut color_texture);
+	    gl::BindTexture(gl::TEXTURE_2D, color_texture);
+	    gl::TexStorage2D(gl::TEXTURE_2D, 1, gl::RGBA16F, width, height);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+
+	    gl::GenRenderbuffers(1, &mut depth_renderbuffer);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, depth_renderbuffer);
+	    gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT24, width, height);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+	    let mut previous_framebuffer:GLint = 0;
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, color_texture, 0);
+	    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, depth_renderbuffer);
+	    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+		println!("HDR framebuffer is incomplete");
+	    }
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+	}
+	HdrTarget { framebuffer, color_texture, depth_renderbuffer, width, height }
+    }
+
+    /* ウィンドウの大きさが変わったら作りなおす */
+    pub fn resize(&mut self, width:i32, height:i32) {
+	if self.width != width.max(1) || self.height != height.max(1) {
+	    *self = HdrTarget::create(width, height);
+	}
+    }
+
+    pub fn width(&self) -> i32 {
+	self.width
+    }
+
+    pub fn height(&self) -> i32 {
+	self.height
+    }
+
+    pub fn framebuffer(&self) -> GLuint {
+	self.framebuffer
+    }
+
+    pub fn color_texture(&self) -> GLuint {
+	self.color_texture
+    }
+
+    /* 描画先にしてviewportを全体に合わせる */
+    pub fn bind(&self) {
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+	    gl::Viewport(0, 0, self.width, self.height);
+	}
+    }
+}
+
+impl Drop for HdrTarget {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteFramebuffers(1, &self.framebuffer);
+	    gl::DeleteRenderbuffers(1, &self.depth_renderbuffer);
+	    gl::DeleteTextures(1, &self.color_texture);
+	}
+    }
+}
+
+/* HDRのテクスチャを今のframebufferに全画面で描くパス. GLコンテキスト作成後に作る */
+pub struct ToneMapPass {
+    program: GLuint,
+    vao: GLuint,
+    hdr_color_location: GLint,
+    exposure_location: GLint,
+    tone_mapper_location: GLint,
+    encode_srgb_location: GLint
+}
+
+impl ToneMapPass {
+
+    pub fn create() -> Self {
+	let program = build_program(FULLSCREEN_VERTEX_SHADER_CODE, TONEMAP_FRAGMENT_SHADER_CODE);
+	let mut vao = 0;
+	unsafe {
+	    gl::GenVertexArrays(1, &mut vao);
+	}
+	ToneMapPass {
+	    program,
+	    vao,
+	    hdr_color_location: uniform_location(program, "hdrColor"),
+	    exposure_location: uniform_location(program, "exposure"),
+	    tone_mapper_location: uniform_location(program, "toneMapper"),
+	    encode_srgb_location: uniform_location(program, "encodeSrgb")
+	}
+    }
+
+    /*
+      sourceの色テクスチャをトーンマッピングして今のframebufferに描く. viewportは呼ぶ側で設定する.
+      深度テストとブレンドは切る. FRAMEBUFFER_SRGBは描き終わったら切る
+    */
+    pub fn render(&self, source_texture:GLuint, settings:&ToneMapSettings) {
+	unsafe {
+	    gl::Disable(gl::DEPTH_TEST);
+	    gl::Disable(gl::BLEND);
+	    gl::Disable(gl::CULL_FACE);
+	    if settings.output == OutputEncoding::FramebufferSrgb {
+		gl::Enable(gl::FRAMEBUFFER_SRGB);
+	    }
+	    gl::UseProgram(self.program);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	    gl::BindTexture(gl::TEXTURE_2D, source_texture);
+	    gl::Uniform1i(self.hdr_color_location, 0);
+	    gl::Uniform1f(self.exposure_location, settings.exposure);
+	    gl::Uniform1i(self.tone_mapper_location, settings.tone_mapper as GLint);
+	    gl::Uniform1i(self.encode_srgb_location, if settings.output == OutputEncoding::ShaderSrgb { 1 } else { 0 });
+	    gl::BindVertexArray(self.vao);
+	    gl::DrawArrays(gl::TRIANGLES, 0, 3);
+	    gl::BindVertexArray(0);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	    gl::UseProgram(0);
+	    gl::Disable(gl::FRAMEBUFFER_SRGB);
+	}
+    }
+}
+
+impl Drop for ToneMapPass {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteVertexArrays(1, &self.vao);
+	    gl::DeleteProgram(self.program);
+	}
+    }
+}
@@ -0,0 +1,528 @@
+use std::fs;
+use std::io;
+use std::time;
+use std::path::Path;
+
+use gl::types::*;
+use linear_transform::matrix::*;
+use graphic_math::graphic_math::perspective;
+
+use crate::renderer::UniformValue;
+use crate::texture::{TextureError,ColorSpace,Texture2D};
+use crate::cubemap::{CubeFace,CubeMap,UnitCube,CUBE_FACES,ENVIRONMENT_TEXTURE_UNIT};
+use crate::{build_program,uniform_location};
+
+/*
+  image based lighting. 環境マップから次の3つを前もって計算しておき,PBRの環境光に使う.
+  - irradiance: 法線の周りの半球の放射照度(拡散反射)
+  - prefiltered: 粗さごとにGGXでぼかした環境マップ. ミップレベルが粗さに対応する(鏡面反射)
+  - brdf_lut: split sum近似のBRDFの積分. x: NdotV, y: 粗さ
+*/
+
+static CAPTURE_VERTEX_SHADER_CODE: &str = include_str!("shaders/skybox.vert");
+
+static IRRADIANCE_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/irradiance.frag");
+
+static PREFILTER_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/prefilter.frag");
+
+static FULLSCREEN_VERTEX_SHADER_CODE: &str = include_str!("shaders/fullscreen.vert");
+
+static BRDF_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/brdf.frag");
+
+/* shaders/ibl.glslのテクスチャを結びつけるユニット. 環境マップの後ろを使う */
+pub const IRRADIANCE_TEXTURE_UNIT: GLuint = 10;
+pub const PREFILTERED_TEXTURE_UNIT: GLuint = 11;
+pub const BRDF_LUT_TEXTURE_UNIT: GLuint = 12;
+
+/* キャッシュファイルの名前. IblMaps::saveで指定したディレクトリに置く */
+pub const IRRADIANCE_FILE_NAME: &str = "irradiance.ibl";
+pub const PREFILTERED_FILE_NAME: &str = "prefiltered.ibl";
+pub const BRDF_LUT_FILE_NAME: &str = "brdf_lut.ibl";
+
+/* 前計算するテクスチャの大きさとサンプル数 */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct IblSettings {
+    pub irradiance_size: u32,
+    pub prefiltered_size: u32,
+    /* prefilteredのミップレベル数. 最後のレベルが粗さ1.0 */
+    pub prefiltered_levels: u32,
+    pub brdf_lut_size: u32,
+    /* prefilteredとbrdf_lutの重点サンプリングの数 */
+    pub sample_count: u32
+}
+
+impl Default for IblSettings {
+    fn default() -> Self {
+	IblSettings {
+	    irradiance_size: 32,
+	    prefiltered_size: 128,
+	    prefiltered_levels: 5,
+	    brdf_lut_size: 256,
+	    sample_count: 1024
+	}
+    }
+}
+
+impl IblSettings {
+    /* prefilteredのlevel番目のミップレベルに対応する粗さ */
+    pub fn prefiltered_roughness(&self, level:u32) -> f32 {
+	if self.prefiltered_levels <= 1 {
+	    0.0
+	}
+	else {
+	    (level as f32)/((self.prefiltered_levels - 1) as f32)
+	}
+    }
+}
+
+/*
+  faceの面に描くためのビュー行列. 90度の透視投影と組み合わせると,
+  face.direction(s,t)が正規化デバイス座標の(2s-1,2t-1)に写る.
+  framebufferの0行目はテクスチャの0行目なので,描いた結果はCubeMapDataと同じ向きになる.
+*/
+pub fn capture_view(face:CubeFace) -> Matrix4x4 {
+    let forward = face.direction(0.5, 0.5);
+    let s = face.direction(1.0, 0.5);
+    let t = face.direction(0.5, 1.0);
+    let right = [s[0] - forward[0], s[1] - forward[1], s[2] - forward[2]];
+    let up = [t[0] - forward[0], t[1] - forward[1], t[2] - forward[2]];
+    Matrix4x4 {
+	v: [
+	    [ right[0], right[1], right[2], 0.0 ],
+	    [ up[0], up[1], up[2], 0.0 ],
+	    [ -forward[0], -forward[1], -forward[2], 0.0 ],
+	    [ 0.0, 0.0, 0.0, 1.0 ]
+	]
+    }
+}
+
+pub fn capture_projection() -> Matrix4x4 {
+    perspective(90.0, 1.0, 0.1, 10.0)
+}
+
+/* widthとheightのテクスチャのlevel番目のミップレベルの大きさ */
+pub fn level_size(width:u32, height:u32, level:u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+const CACHE_MAGIC: [u8;8] = *b"GLUTIBL\x02";
+const CACHE_HEADER_SIZE: usize = 8 + 4*5 + 8*2;
+
+/*
+  キャッシュの元にした画像のファイルの大きさと更新時刻(UNIX時刻からのナノ秒).
+  同じパスの画像を差し替えたときに古いキャッシュを使わないよう,キャッシュファイルのヘッダに入れて比べる
+*/
+#[derive(Debug,Copy,Clone,Default,PartialEq,Eq)]
+pub struct SourceStamp {
+    pub size: u64,
+    pub modified: u64
+}
+
+impl SourceStamp {
+    pub fn of_file<P: AsRef<Path>>(path:P) -> io::Result<Self> {
+	let metadata = fs::metadata(path)?;
+	let modified = metadata.modified()?.duration_since(time::UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+	Ok(SourceStamp { size: metadata.len(), modified })
+    }
+}
+
+/*
+  前計算の結果を保存する浮動小数点テクスチャ. KTXやDDSと同じく,ヘッダの後にミップレベルごと,面ごとの画素を並べる.
+  ヘッダ: magic(8バイト), cube, width, height, channels, levelsをu32で, sourceのsize, modifiedをu64で.
+  数値はすべてリトルエンディアン.
+  levels[i]はi番目のミップレベルの全部の面(キューブならCUBE_FACESの順)をつなげたf32の画素
+*/
+#[derive(Debug,Clone,PartialEq)]
+pub struct FloatTextureFile {
+    pub cube: bool,
+    pub width: u32,
+    pub height: u32,
+    pub channels: u32,
+    pub source: SourceStamp,
+    pub levels: Vec<Vec<f32>>
+}
+
+impl FloatTextureFile {
+
+    pub fn face_count(&self) -> u32 {
+	if self.cube { 6 } else { 1 }
+    }
+
+    /* level番目のミップレベルの画素数*チャンネル数. ヘッダの値は壊れていることがあるので,あふれたらNone */
+    pub fn level_length(&self, level:u32) -> Option<usize> {
+	let (w, h) = level_size(self.width, self.height, level);
+	[h, self.channels, self.face_count()].iter().try_fold(w as usize, |length, &n| length.checked_mul(n as usize))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+	let mut bytes = Vec::with_capacity(CACHE_HEADER_SIZE + 4*self.levels.iter().map(|l| l.len()).sum::<usize>());
+	bytes.extend_from_slice(&CACHE_MAGIC);
+	for value in [self.cube as u32, self.width, self.height, self.channels, self.levels.len() as u32].iter() {
+	    bytes.extend_from_slice(&value.to_le_bytes());
+	}
+	bytes.extend_from_slice(&self.source.size.to_le_bytes());
+	bytes.extend_from_slice(&self.source.modified.to_le_bytes());
+	for level in self.levels.iter() {
+	    for value in level.iter() {
+		bytes.extend_from_slice(&value.to_le_bytes());
+	    }
+	}
+	bytes
+    }
+
+    pub fn decode(bytes:&[u8]) -> Result<Self, TextureError> {
+	if bytes.len() < CACHE_HEADER_SIZE || bytes[0..8] != CACHE_MAGIC {
+	    return Err(TextureError::Decode("not an IBL cache file".to_string()));
+	}
+	let header = |i:usize| {
+	    let offset = 8 + 4*i;
+	    u32::from_le_bytes([bytes[offset], bytes[offset+1], bytes[offset+2], bytes[offset+3]])
+	};
+	let header64 = |offset:usize| {
+	    let mut value = [0u8;8];
+	    value.copy_from_slice(&bytes[offset..offset+8]);
+	    u64::from_le_bytes(value)
+	};
+	let mut file = FloatTextureFile {
+	    cube: header(0) != 0,
+	    width: header(1),
+	    height: header(2),
+	    channels: header(3),
+	    source: SourceStamp { size: header64(8 + 4*5), modified: header64(8 + 4*5 + 8) },
+	    levels: Vec::new()
+	};
+	let level_count = header(4);
+	if file.width == 0 || file.height == 0 || !(1..=4).contains(&file.channels) || level_count == 0 || level_count > 32 {
+	    return Err(TextureError::Decode(format!("invalid IBL cache header {}x{} channels {} levels {}",
+						    file.width, file.height, file.channels, level_count)));
+	}
+	let mut offset = CACHE_HEADER_SIZE;
+	for level in 0..level_count {
+	    let end = file.level_length(level).and_then(|length| length.checked_mul(4)).and_then(|length| length.checked_add(offset))
+		.ok_or_else(|| TextureError::Decode(format!("IBL cache level {} is too large", level)))?;
+	    if end > bytes.len() {
+		return Err(TextureError::Decode(format!("IBL cache is truncated at level {}", level)));
+	    }
+	    file.levels.push(bytes[offset..end].chunks(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect());
+	    offset = end;
+	}
+	Ok(file)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path:P) -> io::Result<()> {
+	fs::write(path, self.encode())
+    }
+
+    pub fn load<P: AsRef<Path>>(path:P) -> Result<Self, TextureError> {
+	let bytes = fs::read(path.as_ref()).map_err(|e| TextureError::Decode(format!("{}. {}", path.as_ref().display(), e)))?;
+	FloatTextureFile::decode(&bytes)
+    }
+}
+
+/* チャンネル数ごとの半精度浮動小数点の形式 */
+fn float_formats(channels:u32) -> (GLenum, GLenum) {
+    match channels {
+	1 => (gl::R16F, gl::RED),
+	2 => (gl::RG16F, gl::RG),
+	3 => (gl::RGB16F, gl::RGB),
+	_ => (gl::RGBA16F, gl::RGBA)
+    }
+}
+
+/* 中身の無い浮動小数点テクスチャを作る. 補間は線形,端はCLAMP_TO_EDGE */
+fn create_float_texture(cube:bool, width:u32, height:u32, channels:u32, levels:u32) -> GLuint {
+    let target = if cube { gl::TEXTURE_CUBE_MAP } else { gl::TEXTURE_2D };
+    let (internal_format, _) = float_formats(channels);
+    let min_filter = if levels > 1 { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
+    let mut id = 0;
+    unsafe {
+	gl::GenTextures(1, &mut id);
+	gl::BindTexture(target, id);
+	gl::TexStorage2D(target, levels as GLsizei, internal_format, width as GLsizei, height as GLsizei);
+	gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+	gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+	gl::TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+	gl::TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	gl::TexParameteri(target, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+	gl::BindTexture(target, 0);
+    }
+    id
+}
+
+fn face_targets(cube:bool) -> Vec<GLenum> {
+    if cube {
+	CUBE_FACES.iter().map(|f| f.gl_target()).collect()
+    }
+    else {
+	vec![gl::TEXTURE_2D]
+    }
+}
+
+/* キャッシュから読んだ画素をテクスチャに送る */
+fn upload_float_texture(file:&FloatTextureFile) -> GLuint {
+    let id = create_float_texture(file.cube, file.width, file.height, file.channels, file.levels.len() as u32);
+    let (_, format) = float_formats(file.channels);
+    let target = if file.cube { gl::TEXTURE_CUBE_MAP } else { gl::TEXTURE_2D };
+    unsafe {
+	gl::BindTexture(target, id);
+	gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+	for (level, pixels) in file.levels.iter().enumerate() {
+	    let (w, h) = level_size(file.width, file.height, level as u32);
+	    let face_length = (w*h*file.channels) as usize;
+	    for (face_target, face) in face_targets(file.cube).into_iter().zip(pixels.chunks(face_length)) {
+		gl::TexSubImage2D(face_target, level as GLint, 0, 0, w as GLsizei, h as GLsizei,
+				  format, gl::FLOAT, face.as_ptr() as *const _);
+	    }
+	}
+	gl::BindTexture(target, 0);
+    }
+    id
+}
+
+/* テクスチャをframebufferに付けて読み出す. 読み出しはどの形式でもRGBAのfloatで行い,channelsだけ残す */
+fn read_float_texture(texture:GLuint, cube:bool, width:u32, height:u32, channels:u32, levels:u32, source:SourceStamp) -> FloatTextureFile {
+    let mut file = FloatTextureFile { cube, width, height, channels, source, levels: Vec::new() };
+    let mut framebuffer = 0;
+    let mut previous_framebuffer:GLint = 0;
+    unsafe {
+	gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	gl::GenFramebuffers(1, &mut framebuffer);
+	gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+	for level in 0..levels {
+	    let (w, h) = level_size(width, height, level);
+	    let mut pixels = Vec::with_capacity(file.level_length(level).unwrap_or(0));
+	    let mut buffer = vec![0.0f32; (w*h*4) as usize];
+	    for face_target in face_targets(cube) {
+		gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, face_target, texture, level as GLint);
+		gl::ReadPixels(0, 0, w as GLsizei, h as GLsizei, gl::RGBA, gl::FLOAT, buffer.as_mut_ptr() as *mut _);
+		for texel in buffer.chunks(4) {
+		    pixels.extend_from_slice(&texel[..channels as usize]);
+		}
+	    }
+	    file.levels.push(pixels);
+	}
+	gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+	gl::DeleteFramebuffers(1, &framebuffer);
+    }
+    file
+}
+
+/* programで6面を描く. programは使用中で,view以外のuniformは設定済みであること */
+fn render_cube_faces(program:GLuint, cube:&UnitCube, texture:GLuint, size:u32, level:u32) {
+    let view_location = uniform_location(program, "view");
+    let (w, _) = level_size(size, size, level);
+    for face in CUBE_FACES.iter() {
+	unsafe {
+	    gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, face.gl_target(), texture, level as GLint);
+	    gl::Viewport(0, 0, w as GLsizei, w as GLsizei);
+	    gl::UniformMatrix4fv(view_location, 1, gl::TRUE, capture_view(*face).serialize_f32().as_ptr());
+	    gl::Clear(gl::COLOR_BUFFER_BIT);
+	}
+	cube.draw();
+    }
+}
+
+/* bakeの間に切り替えて,終わったら元に戻すcapability */
+const BAKE_CAPABILITIES: [GLenum;4] = [gl::DEPTH_TEST, gl::CULL_FACE, gl::BLEND, gl::TEXTURE_CUBE_MAP_SEAMLESS];
+
+/* 前計算したIBLのテクスチャ. GLコンテキスト作成後に作る */
+pub struct IblMaps {
+    irradiance: CubeMap,
+    prefiltered: CubeMap,
+    brdf_lut: Texture2D
+}
+
+impl IblMaps {
+
+    /*
+      environmentから3つのテクスチャを描画で作る. environmentはミップマップ付きであること.
+      終わったらframebuffer,viewport,program,クリア色と,切り替えたcapabilityの有効,無効を元に戻す.
+      ENVIRONMENT_TEXTURE_UNITにはenvironmentが結びついたままになる
+    */
+    pub fn bake(environment:&CubeMap, settings:&IblSettings) -> Self {
+	let irradiance = create_float_texture(true, settings.irradiance_size, settings.irradiance_size, 4, 1);
+	let prefiltered = create_float_texture(true, settings.prefiltered_size, settings.prefiltered_size, 4, settings.prefiltered_levels);
+	let brdf_lut = create_float_texture(false, settings.brdf_lut_size, settings.brdf_lut_size, 2, 1);
+
+	let cube = UnitCube::create();
+	let projection = capture_projection().serialize_f32();
+	let mut framebuffer = 0;
+	let mut empty_vao = 0;
+	let mut previous_framebuffer:GLint = 0;
+	let mut previous_viewport:[GLint;4] = [0;4];
+	let mut previous_program:GLint = 0;
+	let mut previous_clear_color:[GLfloat;4] = [0.0;4];
+	let mut previous_capabilities = [false;4];
+	unsafe {
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	    gl::GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr());
+	    gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut previous_program);
+	    gl::GetFloatv(gl::COLOR_CLEAR_VALUE, previous_clear_color.as_mut_ptr());
+	    for (capability, enabled) in BAKE_CAPABILITIES.iter().zip(previous_capabilities.iter_mut()) {
+		*enabled = gl::IsEnabled(*capability) == gl::TRUE;
+	    }
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    gl::Disable(gl::DEPTH_TEST);
+	    gl::Disable(gl::CULL_FACE);
+	    gl::Disable(gl::BLEND);
+	    gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+	    gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+	}
+	environment.bind(ENVIRONMENT_TEXTURE_UNIT);
+
+	/* 拡散反射 */
+	let program = build_program(CAPTURE_VERTEX_SHADER_CODE, IRRADIANCE_FRAGMENT_SHADER_CODE);
+	unsafe {
+	    gl::UseProgram(program);
+	    gl::UniformMatrix4fv(uniform_location(program, "projection"), 1, gl::TRUE, projection.as_ptr());
+	    gl::Uniform1i(uniform_location(program, "environmentMap"), ENVIRONMENT_TEXTURE_UNIT as GLint);
+	}
+	render_cube_faces(program, &cube, irradiance, settings.irradiance_size, 0);
+	unsafe {
+	    gl::DeleteProgram(program);
+	}
+
+	/* 鏡面反射. ミップレベルごとに粗さを変える */
+	let program = build_program(CAPTURE_VERTEX_SHADER_CODE, PREFILTER_FRAGMENT_SHADER_CODE);
+	unsafe {
+	    gl::UseProgram(program);
+	    gl::UniformMatrix4fv(uniform_location(program, "projection"), 1, gl::TRUE, projection.as_ptr());
+	    gl::Uniform1i(uniform_location(program, "environmentMap"), ENVIRONMENT_TEXTURE_UNIT as GLint);
+	    gl::Uniform1f(uniform_location(program, "environmentSize"), environment.size() as GLfloat);
+	    gl::Uniform1i(uniform_location(program, "sampleCount"), settings.sample_count as GLint);
+	}
+	let roughness_location = uniform_location(program, "roughness");
+	for level in 0..settings.prefiltered_levels {
+	    unsafe {
+		gl::Uniform1f(roughness_location, settings.prefiltered_roughness(level));
+	    }
+	    render_cube_faces(program, &cube, prefiltered, settings.prefiltered_size, level);
+	}
+	unsafe {
+	    gl::DeleteProgram(program);
+	}
+
+	/* BRDFのテーブル. 頂点はシェーダで作るので空のVAOで描く */
+	let program = build_program(FULLSCREEN_VERTEX_SHADER_CODE, BRDF_FRAGMENT_SHADER_CODE);
+	unsafe {
+	    gl::UseProgram(program);
+	    gl::Uniform1i(uniform_location(program, "sampleCount"), settings.sample_count as GLint);
+	    gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, brdf_lut, 0);
+	    gl::Viewport(0, 0, settings.brdf_lut_size as GLsizei, settings.brdf_lut_size as GLsizei);
+	    gl::Clear(gl::COLOR_BUFFER_BIT);
+	    gl::GenVertexArrays(1, &mut empty_vao);
+	    gl::BindVertexArray(empty_vao);
+	    gl::DrawArrays(gl::TRIANGLES, 0, 3);
+	    gl::BindVertexArray(0);
+	    gl::DeleteVertexArrays(1, &empty_vao);
+	    gl::DeleteProgram(program);
+
+	    gl::UseProgram(previous_program as GLuint);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+	    gl::DeleteFramebuffers(1, &framebuffer);
+	    gl::Viewport(previous_viewport[0], previous_viewport[1], previous_viewport[2], previous_viewport[3]);
+	    gl::ClearColor(previous_clear_color[0], previous_clear_color[1], previous_clear_color[2], previous_clear_color[3]);
+	    for (capability, enabled) in BAKE_CAPABILITIES.iter().zip(previous_capabilities.iter()) {
+		if *enabled { gl::Enable(*capability) } else { gl::Disable(*capability) }
+	    }
+	}
+
+	IblMaps {
+	    irradiance: CubeMap::from_raw(irradiance, settings.irradiance_size, 1),
+	    prefiltered: CubeMap::from_raw(prefiltered, settings.prefiltered_size, settings.prefiltered_levels),
+	    brdf_lut: Texture2D::from_raw(brdf_lut, settings.brdf_lut_size, settings.brdf_lut_size, ColorSpace::Linear, 1)
+	}
+    }
+
+    pub fn irradiance(&self) -> &CubeMap {
+	&self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &CubeMap {
+	&self.prefiltered
+    }
+
+    pub fn brdf_lut(&self) -> &Texture2D {
+	&self.brdf_lut
+    }
+
+    /*
+      3つのテクスチャをdirectoryに保存する. 環境マップごとに別のディレクトリにすること.
+      sourceは元の画像のSourceStamp
+    */
+    pub fn save<P: AsRef<Path>>(&self, directory:P, source:SourceStamp) -> io::Result<()> {
+	let directory = directory.as_ref();
+	fs::create_dir_all(directory)?;
+	let irradiance = read_float_texture(self.irradiance.id(), true, self.irradiance.size(), self.irradiance.size(), 4, 1, source);
+	irradiance.save(directory.join(IRRADIANCE_FILE_NAME))?;
+	let prefiltered = read_float_texture(self.prefiltered.id(), true, self.prefiltered.size(), self.prefiltered.size(),
+					     4, self.prefiltered.mip_levels(), source);
+	prefiltered.save(directory.join(PREFILTERED_FILE_NAME))?;
+	let brdf_lut = read_float_texture(self.brdf_lut.id(), false, self.brdf_lut.width(), self.brdf_lut.height(), 2, 1, source);
+	brdf_lut.save(directory.join(BRDF_LUT_FILE_NAME))
+    }
+
+    /* saveで保存したものを読む. 大きさやミップレベル数がsettingsと,または元の画像がsourceと違えばエラー */
+    pub fn load<P: AsRef<Path>>(directory:P, settings:&IblSettings, source:SourceStamp) -> Result<Self, TextureError> {
+	let directory = directory.as_ref();
+	let irradiance = FloatTextureFile::load(directory.join(IRRADIANCE_FILE_NAME))?;
+	let prefiltered = FloatTextureFile::load(directory.join(PREFILTERED_FILE_NAME))?;
+	let brdf_lut = FloatTextureFile::load(directory.join(BRDF_LUT_FILE_NAME))?;
+	let matches = |file:&FloatTextureFile, cube:bool, size:u32, levels:u32| {
+	    file.cube == cube && file.width == size && file.height == size && file.levels.len() as u32 == levels
+	};
+	if !matches(&irradiance, true, settings.irradiance_size, 1) ||
+	    !matches(&prefiltered, true, settings.prefiltered_size, settings.prefiltered_levels) ||
+	    !matches(&brdf_lut, false, settings.brdf_lut_size, 1) {
+		return Err(TextureError::Decode(format!("IBL cache in {} was made with other settings", directory.display())));
+	    }
+	if [&irradiance, &prefiltered, &brdf_lut].iter().any(|file| file.source != source) {
+	    return Err(TextureError::Decode(format!("IBL cache in {} was made from another image", directory.display())));
+	}
+	Ok(IblMaps {
+	    irradiance: CubeMap::from_raw(upload_float_texture(&irradiance), irradiance.width, 1),
+	    prefiltered: CubeMap::from_raw(upload_float_texture(&prefiltered), prefiltered.width, prefiltered.levels.len() as u32),
+	    brdf_lut: Texture2D::from_raw(upload_float_texture(&brdf_lut), brdf_lut.width, brdf_lut.height, ColorSpace::Linear, 1)
+	})
+    }
+
+    /*
+      キャッシュがあれば読み,無いか古ければ計算して保存する. 保存に失敗しても計算結果は返す.
+      sourceはenvironmentを読んだ画像のSourceStamp
+    */
+    pub fn load_or_bake<P: AsRef<Path>>(directory:P, environment:&CubeMap, settings:&IblSettings, source:SourceStamp) -> Self {
+	if let Ok(maps) = IblMaps::load(directory.as_ref(), settings, source) {
+	    return maps;
+	}
+	let maps = IblMaps::bake(environment, settings);
+	if let Err(e) = maps.save(directory.as_ref(), source) {
+	    println!("failed to save IBL cache to {}. {}", directory.as_ref().display(), e);
+	}
+	maps
+    }
+
+    /* IRRADIANCE_TEXTURE_UNITなどに結びつける */
+    pub fn bind(&self) {
+	self.irradiance.bind(IRRADIANCE_TEXTURE_UNIT);
+	self.prefiltered.bind(PREFILTERED_TEXTURE_UNIT);
+	self.brdf_lut.bind(BRDF_LUT_TEXTURE_UNIT);
+    }
+}
+
+/*
+  shaders/ibl.glslのuniform. mapsがNoneならIBLを使わずに環境光とenvironment.glslの反射になる.
+  programは共有されるので,使わない場合もフレームごとに設定すること
+*/
+pub fn ibl_uniforms(maps:Option<&IblMaps>, intensity:f32) -> Vec<(String, UniformValue)> {
+    let max_lod = maps.map(|m| m.prefiltered.mip_levels().max(1) - 1).unwrap_or(0);
+    vec![
+	("irradianceMap".to_string(), UniformValue::Int(IRRADIANCE_TEXTURE_UNIT as GLint)),
+	("prefilteredMap".to_string(), UniformValue::Int(PREFILTERED_TEXTURE_UNIT as GLint)),
+	("brdfLut".to_string(), UniformValue::Int(BRDF_LUT_TEXTURE_UNIT as GLint)),
+	("hasIbl".to_string(), UniformValue::Int(if maps.is_some() { 1 } else { 0 })),
+	("iblIntensity".to_string(), UniformValue::Float(intensity)),
+	("prefilteredMaxLod".to_string(), UniformValue::Float(max_lod as f32))
+    ]
+}
@@ -0,0 +1,166 @@
+use std::mem;
+use std::os::raw::c_void;
+
+use gl::types::*;
+use linear_transform::matrix::*;
+
+use crate::renderer::{VertexArrayObjectContext,DrawItem,Material,RenderState};
+
+/*
+  同じメッシュを変換行列と色だけ変えてたくさん描く.
+  インスタンスごとの値はVBOに詰めてglVertexAttribDivisorで1インスタンスに1つ進める.
+  material.vert,depth.vertがinstanceModel(location 4-7),instanceColor(location 8)で受け取る
+*/
+
+/* mat4は1列ずつ4つのlocationを使う */
+pub const INSTANCE_MODEL_LOCATION: GLuint = 4;
+pub const INSTANCE_COLOR_LOCATION: GLuint = 8;
+/* 1インスタンスのfloatの数. 行列16+色4 */
+pub const INSTANCE_STRIDE: usize = 20;
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Instance {
+    pub transform: Matrix4x4,
+    /* 頂点色に掛ける */
+    pub color: [GLfloat;4]
+}
+
+impl Instance {
+    pub fn new(transform:Matrix4x4) -> Self {
+	Instance { transform, color: [1.0, 1.0, 1.0, 1.0] }
+    }
+}
+
+/* VBOに詰める並び. 行列はGLの属性が列ごとなので列優先にする */
+pub fn pack_instances(instances:&[Instance]) -> Vec<GLfloat> {
+    let mut data = Vec::with_capacity(instances.len()*INSTANCE_STRIDE);
+    for instance in instances {
+	for column in 0..4 {
+	    for row in 0..4 {
+		data.push(instance.transform[row][column] as GLfloat);
+	    }
+	}
+	data.extend_from_slice(&instance.color);
+    }
+    data
+}
+
+/* インスタンスごとの属性を持つVBO */
+pub struct InstanceBuffer {
+    vbo: GLuint,
+    /* 確保済みのインスタンス数. 超えたら確保しなおす */
+    capacity: usize,
+    count: usize
+}
+
+impl InstanceBuffer {
+
+    pub fn create() -> Self {
+	let mut vbo = 0;
+	unsafe {
+	    gl::GenBuffers(1, &mut vbo);
+	}
+	InstanceBuffer { vbo, capacity: 0, count: 0 }
+    }
+
+    /* vaoのlocation 4-8にこのVBOを繋ぐ */
+    pub fn attach(&self, vao:GLuint) {
+	let stride = (INSTANCE_STRIDE*mem::size_of::<GLfloat>()) as GLsizei;
+	unsafe {
+	    gl::BindVertexArray(vao);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+	    for column in 0..4 {
+		let location = INSTANCE_MODEL_LOCATION + column;
+		gl::EnableVertexAttribArray(location);
+		gl::VertexAttribPointer(location, 4, gl::FLOAT, gl::FALSE, stride,
+					(column as usize*4*mem::size_of::<GLfloat>()) as *const c_void);
+		gl::VertexAttribDivisor(location, 1);
+	    }
+	    gl::EnableVertexAttribArray(INSTANCE_COLOR_LOCATION);
+	    gl::VertexAttribPointer(INSTANCE_COLOR_LOCATION, 4, gl::FLOAT, gl::FALSE, stride,
+				    (16*mem::size_of::<GLfloat>()) as *const c_void);
+	    gl::VertexAttribDivisor(INSTANCE_COLOR_LOCATION, 1);
+	    gl::BindVertexArray(0);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+	}
+    }
+
+    /* 毎フレーム呼んでよい. 足りないときだけ確保しなおし,それ以外はBufferSubDataで書き換える */
+    pub fn update(&mut self, instances:&[Instance]) {
+	let data = pack_instances(instances);
+	let size = (data.len()*mem::size_of::<GLfloat>()) as GLsizeiptr;
+	unsafe {
+	    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+	    if instances.len() > self.capacity {
+		gl::BufferData(gl::ARRAY_BUFFER, size, data.as_ptr() as *const c_void, gl::DYNAMIC_DRAW);
+		self.capacity = instances.len();
+	    }
+	    else if !data.is_empty() {
+		gl::BufferSubData(gl::ARRAY_BUFFER, 0, size, data.as_ptr() as *const c_void);
+	    }
+	    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+	}
+	self.count = instances.len();
+    }
+
+    pub fn count(&self) -> usize {
+	self.count
+    }
+}
+
+impl Drop for InstanceBuffer {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteBuffers(1, &self.vbo);
+	}
+    }
+}
+
+/*
+  メッシュとインスタンスの組. 1回のglDrawElementsInstancedで全部描く.
+  メッシュのVAOにインスタンスの属性を繋ぐ. 同じVAOを普通のDrawItemで描いてもシェーダはinstanced=0なので属性を読まない
+*/
+pub struct InstancedMesh {
+    mesh: VertexArrayObjectContext,
+    buffer: InstanceBuffer
+}
+
+impl InstancedMesh {
+
+    pub fn create(mesh:VertexArrayObjectContext) -> Self {
+	let buffer = InstanceBuffer::create();
+	buffer.attach(mesh.vao);
+	InstancedMesh { mesh: mesh.with_instances(0), buffer }
+    }
+
+    pub fn set_instances(&mut self, instances:&[Instance]) {
+	self.buffer.update(instances);
+	self.mesh.instance_count = self.buffer.count() as GLsizei;
+    }
+
+    /* 全部同じ色で描く */
+    pub fn set_transforms(&mut self, transforms:&[Matrix4x4], color:[GLfloat;4]) {
+	let instances:Vec<Instance> = transforms.iter().map(|&transform| Instance { transform, color }).collect();
+	self.set_instances(&instances);
+    }
+
+    pub fn instance_count(&self) -> usize {
+	self.buffer.count()
+    }
+
+    /* instance_countを設定したVAOの情報. DrawItemのmeshに使う */
+    pub fn mesh(&self) -> VertexArrayObjectContext {
+	self.mesh
+    }
+
+    /*
+      インスタンス全体を1つのDrawItemにする. transformは全インスタンスに共通で掛かる.
+      インスタンスが0個なら描くものがないのでNone
+    */
+    pub fn draw_item(&self, material:Material, transform:Matrix4x4, state:RenderState) -> Option<DrawItem> {
+	if self.mesh.instance_count == 0 {
+	    return None;
+	}
+	Some(DrawItem { mesh: self.mesh, material, transform, state })
+    }
+}
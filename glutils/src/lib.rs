@@ -1,9 +1,24 @@
 use std::ffi::{CString};
-use std::{ptr, str, mem};
+use std::{ptr, str};
 
-use gl;
 use gl::types::*;
 
+pub mod renderer;
+pub mod material;
+pub mod light;
+pub mod shadow;
+pub mod golden;
+pub mod texture;
+pub mod cubemap;
+pub mod ibl;
+pub mod hdr;
+pub mod postprocess;
+pub mod msaa;
+pub mod ssao;
+pub mod deferred;
+pub mod instancing;
+pub mod skinning;
+
 #[allow(dead_code)]
 pub fn compile_shader(shader_code: &str, shader_type: GLenum) -> GLuint {
     let shader;
@@ -22,10 +37,10 @@ pub fn compile_shader(shader_code: &str, shader_type: GLenum) -> GLuint {
         if status != (gl::TRUE as GLint) {
             let mut len: GLint = 0;
             gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len - 1) as usize); // -1 removes null terminator
+            let mut buf: Vec<u8> = vec![0; len as usize];
             gl::GetShaderInfoLog(shader, len, ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
-            println!("{}", str::from_utf8(mem::transmute(buf.as_slice())).ok().expect("Failed to transmute shader error string!"));
+            buf.truncate((len - 1) as usize); // -1 removes null terminator
+            println!("{}", str::from_utf8(&buf).expect("Failed to convert shader error string!"));
         }
     }
     shader
@@ -48,11 +63,533 @@ pub fn link_program(vertex_shader: GLuint, fragment_shader: GLuint) -> GLuint {
         if status != (gl::TRUE as GLint) {
             let mut len: GLint = 0;
             gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
-            let mut buf = Vec::with_capacity(len as usize);
-            buf.set_len((len - 1) as usize); // -1 removes null terminator
+            let mut buf: Vec<u8> = vec![0; len as usize];
             gl::GetProgramInfoLog(program, len, ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
-            println!("{}", str::from_utf8(mem::transmute(buf.as_slice())).ok().expect("Failed to transmute program error string!"));
+            buf.truncate((len - 1) as usize); // -1 removes null terminator
+            println!("{}", str::from_utf8(&buf).expect("Failed to convert program error string!"));
         }
     }
     program
 }
+
+/*
+  #includeを展開してからコンパイルする. マテリアルやオフスクリーンのパスなどで使う.
+  #includeで入るsamplerのユニットと,影などを使わない既定の値もここで設定する
+*/
+pub(crate) fn build_program(vertex_shader_code:&str, fragment_shader_code:&str) -> GLuint {
+    let vertex_shader = compile_shader(vertex_shader_code, gl::VERTEX_SHADER);
+    let fragment_shader = compile_shader(&material::expand_includes(fragment_shader_code), gl::FRAGMENT_SHADER);
+    let program = link_program(vertex_shader, fragment_shader);
+    unsafe {
+        gl::DeleteShader(fragment_shader);
+        gl::DeleteShader(vertex_shader);
+    }
+    material::set_default_uniforms(program);
+    program
+}
+
+pub(crate) fn uniform_location(program:GLuint, name:&str) -> GLint {
+    let c_str = CString::new(name).unwrap();
+    unsafe { gl::GetUniformLocation(program, c_str.as_ptr()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::renderer::*;
+    use super::material::*;
+    use super::light::*;
+    use super::shadow::*;
+    use super::golden::*;
+    use super::texture::*;
+    use super::cubemap::*;
+    use super::ibl::*;
+    use super::hdr::*;
+    use super::postprocess::*;
+    use super::msaa::*;
+    use super::ssao::*;
+    use super::deferred::*;
+    use super::instancing::*;
+    use super::skinning::*;
+    use graphic_math::skeleton::SkinWeights;
+    use linear_transform::{vector::*,matrix::*};
+
+    fn translate(z:f64) -> Matrix4x4 {
+	let mut m = Matrix4x4::identity();
+	m.v[2][3] = z;
+	m
+    }
+
+    fn item(shader:u32, vao:u32, z:f64, blend:BlendMode) -> DrawItem {
+	DrawItem {
+	    mesh: VertexArrayObjectContext::new(vao, gl::TRIANGLES, 6),
+	    material: Material::new(shader),
+	    transform: translate(z),
+	    state: RenderState { blend, ..RenderState::default() }
+	}
+    }
+
+    #[test]
+    fn view_depth_is_distance_along_minus_z(){
+	assert_eq!(view_depth(&Matrix4x4::identity(), &translate(-3.0)), 3.0);
+    }
+
+    #[test]
+    fn opaque_front_to_back_then_transparent_back_to_front(){
+	let items = vec![
+	    item(1, 1, -5.0, BlendMode::Alpha),
+	    item(1, 1, -9.0, BlendMode::Opaque),
+	    item(1, 1, -2.0, BlendMode::Opaque),
+	    item(1, 1, -8.0, BlendMode::Alpha),
+	];
+	assert_eq!(sort_draw_items(&items, &Matrix4x4::identity()), vec![2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn opaque_same_depth_bucket_sorted_by_program_and_vao(){
+	let items = vec![
+	    item(2, 1, -3.1, BlendMode::Opaque),
+	    item(1, 2, -3.2, BlendMode::Opaque),
+	    item(1, 1, -3.3, BlendMode::Opaque),
+	];
+	assert_eq!(sort_draw_items(&items, &Matrix4x4::identity()), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn triangle_count(){
+	let strip = VertexArrayObjectContext::new(1, gl::TRIANGLE_STRIP, 6);
+	let lines = VertexArrayObjectContext::new(1, gl::LINES, 6);
+	assert_eq!(item(1, 1, 0.0, BlendMode::Opaque).mesh.triangle_count(), 2);
+	assert_eq!(strip.triangle_count(), 4);
+	assert_eq!(lines.triangle_count(), 0);
+    }
+
+    #[test]
+    fn material_set_uniform_overwrites(){
+	let mut m = Material::new(1).with_uniform("alpha", UniformValue::Float(0.5));
+	m.set_uniform("alpha", UniformValue::Float(1.0));
+	assert_eq!(m.uniforms, vec![("alpha".to_string(), UniformValue::Float(1.0))]);
+    }
+
+    #[test]
+    fn uniform_value_cache_skips_unchanged_values(){
+	let mut cache = super::renderer::UniformValueCache::default();
+	assert!(cache.update(1, "alpha", &UniformValue::Float(0.5)));
+	assert!(!cache.update(1, "alpha", &UniformValue::Float(0.5)));
+	assert!(cache.update(2, "alpha", &UniformValue::Float(0.5)));
+	assert!(cache.update(1, "alpha", &UniformValue::Float(1.0)));
+	cache.forget(1);
+	assert!(cache.update(1, "alpha", &UniformValue::Float(1.0)));
+	assert!(!cache.update(2, "alpha", &UniformValue::Float(0.5)));
+	cache.clear();
+	assert!(cache.update(2, "alpha", &UniformValue::Float(0.5)));
+    }
+
+    #[test]
+    fn shading_model_uniforms(){
+	let phong = ShadingModel::Phong(PhongParameter::default());
+	let blinn = ShadingModel::BlinnPhong(PhongParameter::default());
+	let find = |m:&ShadingModel, name:&str| m.uniforms().into_iter().find(|(n,_)| n == name).map(|(_,v)| v);
+	assert_eq!(find(&phong, "blinn"), Some(UniformValue::Int(0)));
+	assert_eq!(find(&blinn, "blinn"), Some(UniformValue::Int(1)));
+	assert_eq!(find(&ShadingModel::Pbr(PbrParameter::default()), "roughness"), Some(UniformValue::Float(0.5)));
+	assert!(!phong.same_model(&blinn));
+	assert!(ShadingModel::defaults().iter().all(|m| m.same_model(m)));
+    }
+
+    #[test]
+    fn expand_lights_include(){
+	let code = expand_includes("#version 300 es\n#include lights.glsl\nvoid main(void){}\n");
+	assert!(code.starts_with("#version 300 es\n"));
+	assert!(code.contains("uniform Light lights[MAX_LIGHTS];"));
+	assert!(!code.contains("#include"));
+	assert!(code.contains(&format!("#define MAX_LIGHTS {}", MAX_LIGHTS)));
+
+	let lit = expand_includes("#include lights.glsl\n#include shadow.glsl\n");
+	assert!(lit.contains(&format!("#define MAX_CASCADES {}", MAX_CASCADES)));
+	assert!(lit.contains("float shadowFactor("));
+    }
+
+    #[test]
+    fn light_set_uploads_enabled_lights_only(){
+	let mut lights = LightSet::new();
+	lights.add(Light::directional([0.0, 0.0, -1.0], [1.0, 1.0, 1.0], 1.0));
+	let point = lights.add(Light::point([1.0, 0.0, 0.0], [1.0, 0.5, 0.0], 2.0)).unwrap();
+	lights.add(Light::spot([0.0, 0.0, 2.0], [0.0, 0.0, -1.0], 15.0, 25.0, [1.0, 1.0, 1.0], 1.0));
+	lights.get_mut(point).unwrap().enabled = false;
+
+	let uniforms = lights.uniforms();
+	let find = |name:&str| uniforms.iter().find(|(n,_)| n == name).map(|(_,v)| v.clone());
+	assert_eq!(find("lightCount"), Some(UniformValue::Int(2)));
+	assert_eq!(find("lights[0].lightType"), Some(UniformValue::Int(0)));
+	assert_eq!(find("lights[1].lightType"), Some(UniformValue::Int(2)));
+	assert_eq!(find("lights[2].lightType"), None);
+    }
+
+    #[test]
+    fn light_set_is_bounded(){
+	let mut lights = LightSet::new();
+	for _ in 0..MAX_LIGHTS {
+	    assert!(lights.add(Light::point([0.0; 3], [1.0; 3], 1.0)).is_some());
+	}
+	assert!(lights.add(Light::point([0.0; 3], [1.0; 3], 1.0)).is_none());
+    }
+
+    #[test]
+    fn attenuation_and_spot_cone(){
+	let a = Attenuation { constant: 1.0, linear: 0.5, quadratic: 0.25 };
+	assert_eq!(a.factor(0.0), 1.0);
+	assert_eq!(a.factor(2.0), 1.0/3.0);
+
+	let (inner, outer) = (15.0f32.to_radians().cos(), 25.0f32.to_radians().cos());
+	assert_eq!(Light::spot_factor(inner, outer, 1.0), 1.0);
+	assert_eq!(Light::spot_factor(inner, outer, 30.0f32.to_radians().cos()), 0.0);
+	let middle = Light::spot_factor(inner, outer, 20.0f32.to_radians().cos());
+	assert!(middle > 0.0 && middle < 1.0);
+    }
+
+    #[test]
+    fn shadow_caster_index_skips_disabled_and_point_lights(){
+	let mut lights = LightSet::new();
+	let disabled = lights.add(Light::directional([0.0, 0.0, -1.0], [1.0; 3], 1.0)).unwrap();
+	let point = lights.add(Light::point([0.0; 3], [1.0; 3], 1.0)).unwrap();
+	let spot = lights.add(Light::spot([0.0, 0.0, 2.0], [0.0, 0.0, -1.0], 15.0, 25.0, [1.0; 3], 1.0)).unwrap();
+	assert!(lights.shadow_caster().is_none());
+
+	lights.get_mut(disabled).unwrap().enabled = false;
+	lights.get_mut(point).unwrap().cast_shadow = true;
+	lights.get_mut(spot).unwrap().cast_shadow = true;
+	let (index, light) = lights.shadow_caster().unwrap();
+	assert_eq!(index, 1);
+	assert_eq!(light.type_name(), "spot");
+    }
+
+    #[test]
+    fn shadow_cascades_and_uniforms(){
+	let settings = ShadowSettings { cascade_count: 3, ..ShadowSettings::default() };
+	let frustum = ViewFrustum {
+	    eye: Vector3(3.0, 3.0, 3.0), center: Vector3(0.0, 0.0, 0.0), up: Vector3(0.0, 0.0, 1.0),
+	    fovy: 30.0, aspect: 1.0, near: 1.0, far: 11.0
+	};
+	let sun = Light::directional([-1.0, -0.5, -0.25], [1.0; 3], 1.0);
+	let cascades = settings.cascades(&sun, &frustum);
+	assert_eq!(cascades.len(), 3);
+	assert!(cascades.windows(2).all(|c| c[0].far < c[1].far));
+	assert_eq!(cascades[2].far, 11.0);
+
+	let spot = Light::spot([0.0, 0.0, 2.0], [0.0, 0.0, -1.0], 20.0, 30.0, [1.0; 3], 1.0);
+	assert_eq!(settings.cascades(&spot, &frustum).len(), 1);
+	assert!(settings.cascades(&Light::point([0.0; 3], [1.0; 3], 1.0), &frustum).is_empty());
+
+	let uniforms = settings.uniforms(Some(0), &cascades);
+	let find = |name:&str| uniforms.iter().find(|(n,_)| n == name).map(|(_,v)| v.clone());
+	assert_eq!(find("cascadeCount"), Some(UniformValue::Int(3)));
+	assert_eq!(find("shadowLight"), Some(UniformValue::Int(0)));
+	assert!(find("lightSpaceMatrices[2]").is_some());
+	let none = settings.uniforms(None, &cascades);
+	assert!(none.contains(&("cascadeCount".to_string(), UniformValue::Int(0))));
+	assert!(none.contains(&("shadowLight".to_string(), UniformValue::Int(-1))));
+    }
+
+    #[test]
+    fn golden_image_comparison(){
+	let expected = RgbaImage { width: 2, height: 1, pixels: vec![10, 10, 10, 255, 200, 0, 0, 255] };
+	let close = RgbaImage { width: 2, height: 1, pixels: vec![12, 9, 10, 255, 200, 0, 0, 255] };
+	let far = RgbaImage { width: 2, height: 1, pixels: vec![10, 10, 10, 255, 100, 0, 0, 255] };
+	assert_eq!(compare_rgba(&expected, &close, 2), Some(ImageDiff { mismatched_pixels: 0, max_difference: 2 }));
+	let diff = compare_rgba(&expected, &far, 2).unwrap();
+	assert_eq!(diff, ImageDiff { mismatched_pixels: 1, max_difference: 100 });
+	assert!(diff.within(2, 0.5) && !diff.within(2, 0.1));
+	assert_eq!(compare_rgba(&expected, &RgbaImage { width: 1, height: 2, pixels: far.pixels.clone() }, 2), None);
+    }
+
+    #[test]
+    fn texture_data_validation_and_decoding(){
+	assert!(TextureData::from_rgba(2, 2, vec![0; 16]).is_ok());
+	assert!(matches!(TextureData::from_rgba(2, 2, vec![0; 15]), Err(TextureError::InvalidSize { .. })));
+	assert!(matches!(TextureData::from_memory(&[0, 1, 2, 3]), Err(TextureError::Decode(_))));
+
+	let checker = TextureData::checkerboard(4, 2, [255, 0, 0, 255], [0, 0, 255, 255]);
+	let mut png = Vec::new();
+	image::png::PngEncoder::new(&mut png)
+	    .encode(&checker.pixels, 4, 4, image::ColorType::Rgba8).unwrap();
+	let decoded = TextureData::from_memory(&png).unwrap();
+	assert_eq!(decoded, checker);
+	assert_eq!(&decoded.pixels[0..4], &[255, 0, 0, 255]);
+	assert_eq!(&decoded.pixels[8..12], &[0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn mip_levels_and_filters(){
+	assert_eq!(mip_level_count(1, 1), 1);
+	assert_eq!(mip_level_count(256, 256), 9);
+	assert_eq!(mip_level_count(300, 20), 9);
+	let description = SamplerDescription::default();
+	assert_eq!(description.min_filter_enum(), gl::LINEAR_MIPMAP_LINEAR);
+	let nearest = SamplerDescription { min_filter: Filter::Nearest, mipmap: None, ..description };
+	assert_eq!(nearest.min_filter_enum(), gl::NEAREST);
+    }
+
+    #[test]
+    fn material_texture_binding_sets_sampler_unit(){
+	let binding = TextureBinding { unit: 2, target: gl::TEXTURE_2D, texture: 7, sampler: 0 };
+	let mut m = Material::new(1).with_texture("baseColorMap", binding);
+	m.set_texture("baseColorMap", TextureBinding { texture: 8, ..binding });
+	assert_eq!(m.textures, vec![TextureBinding { texture: 8, ..binding }]);
+	assert_eq!(m.uniforms, vec![("baseColorMap".to_string(), UniformValue::Int(2))]);
+    }
+
+    #[test]
+    fn cube_face_directions_round_trip(){
+	assert_eq!(CubeFace::PositiveX.direction(0.5, 0.5), [1.0, 0.0, 0.0]);
+	assert_eq!(CubeFace::NegativeZ.direction(0.5, 0.5), [0.0, 0.0, -1.0]);
+	for face in CUBE_FACES.iter() {
+	    for &(s, t) in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)].iter() {
+		let (f, s2, t2) = direction_to_face(face.direction(s, t));
+		assert_eq!(f, *face);
+		assert!((s-s2).abs() < 1.0e-9 && (t-t2).abs() < 1.0e-9);
+	    }
+	}
+    }
+
+    #[test]
+    fn equirect_to_cube_keeps_up_as_plus_z(){
+	/* 上半分が赤,下半分が青のパノラマ */
+	let (w, h) = (16, 8);
+	let mut pixels = Vec::new();
+	for y in 0..h {
+	    for _ in 0..w {
+		pixels.extend_from_slice(if y < h/2 { &[255, 0, 0, 255] } else { &[0, 0, 255, 255] });
+	    }
+	}
+	let panorama = TextureData::from_rgba(w, h, pixels).unwrap();
+	let cube = CubeMapData::from_equirect(&panorama, 4);
+	assert_eq!(cube.faces.len(), 6);
+	assert!(cube.faces[CubeFace::PositiveZ as usize].chunks(4).all(|p| p == [255, 0, 0, 255]));
+	assert!(cube.faces[CubeFace::NegativeZ as usize].chunks(4).all(|p| p == [0, 0, 255, 255]));
+
+	let (u, v) = direction_to_equirect([1.0, 0.0, 0.0]);
+	assert!((u-0.5).abs() < 1.0e-9 && (v-0.5).abs() < 1.0e-9);
+	assert!(CubeMapData::from_faces(vec![panorama.clone()]).is_err());
+    }
+
+    #[test]
+    fn skybox_view_has_no_translation(){
+	let mut view = Matrix4x4::identity();
+	view.v[0][1] = 0.5;
+	view.v[0][3] = 3.0;
+	view.v[2][3] = -2.0;
+	let r = rotation_only(&view);
+	assert_eq!(r.v[0][3], 0.0);
+	assert_eq!(r.v[2][3], 0.0);
+	assert_eq!(r.v[0][1], 0.5);
+	assert_eq!(environment_uniforms(false, 1.0)[1], ("hasEnvironmentMap".to_string(), UniformValue::Int(0)));
+    }
+
+    #[test]
+    fn capture_view_maps_face_texels_to_viewport(){
+	let projection = capture_projection();
+	for face in CUBE_FACES.iter() {
+	    for &(s, t) in [(0.5, 0.5), (0.1, 0.8), (0.9, 0.25)].iter() {
+		let d = face.direction(s, t);
+		let p = projection*(capture_view(*face)*Vector4(d[0], d[1], d[2], 1.0));
+		assert!((p.0/p.3 - (2.0*s - 1.0)).abs() < 1.0e-9, "{:?} {} {}", face, s, t);
+		assert!((p.1/p.3 - (2.0*t - 1.0)).abs() < 1.0e-9, "{:?} {} {}", face, s, t);
+	    }
+	}
+	let settings = IblSettings::default();
+	assert_eq!(settings.prefiltered_roughness(0), 0.0);
+	assert_eq!(settings.prefiltered_roughness(settings.prefiltered_levels - 1), 1.0);
+	assert_eq!(level_size(128, 128, 4), (8, 8));
+	assert_eq!(ibl_uniforms(None, 1.0)[3], ("hasIbl".to_string(), UniformValue::Int(0)));
+    }
+
+    #[test]
+    fn tone_mappers_and_srgb_output(){
+	for mapper in TONE_MAPPERS.iter() {
+	    assert_eq!(mapper.apply(0.0), 0.0);
+	    assert!(mapper.apply(0.5) <= mapper.apply(2.0));
+	    assert!(mapper.apply(100.0) <= 1.0);
+	}
+	assert_eq!(ToneMapper::Reinhard.apply(1.0), 0.5);
+	assert_eq!(ToneMapper::Clamp.apply(3.0), 1.0);
+	/* リニアな0.5はsRGBで約0.735 */
+	assert!((srgb_from_linear(0.5) - 0.7354).abs() < 1.0e-3);
+	assert!((srgb_from_linear(1.0) - 1.0).abs() < 1.0e-6);
+	let settings = ToneMapSettings { tone_mapper: ToneMapper::Clamp, exposure: 2.0, output: OutputEncoding::Linear };
+	assert_eq!(settings.apply([0.25, 1.0, 0.0]), [0.5, 1.0, 0.0]);
+	let srgb = ToneMapSettings { output: OutputEncoding::ShaderSrgb, ..settings };
+	assert_eq!(srgb.apply([0.25, 0.0, 0.0])[0], srgb_from_linear(0.5));
+    }
+
+    #[test]
+    fn post_process_passes_and_bloom_levels(){
+	let mut settings = PostProcessSettings::default();
+	settings.color_grading.enabled = true;
+	settings.vignette.enabled = true;
+	assert_eq!(settings.ldr_passes(), vec![LdrPass::ColorGrading, LdrPass::Vignette, LdrPass::Fxaa]);
+	settings.fxaa.enabled = false;
+	settings.color_grading.enabled = false;
+	assert_eq!(settings.ldr_passes(), vec![LdrPass::Vignette]);
+
+	assert_eq!(bloom_level_sizes(800, 600, 3), vec![(400, 300), (200, 150), (100, 75)]);
+	/* 1画素になったらそれ以上縮めない */
+	assert_eq!(bloom_level_sizes(4, 2, 5), vec![(2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn color_lut_from_strip(){
+	let identity = ColorLut::identity(4);
+	assert_eq!(identity.pixels.len(), 4*4*4*4);
+	/* r=3,g=0,b=0は赤 */
+	assert_eq!(&identity.pixels[3*4..3*4 + 4], &[255, 0, 0, 255]);
+	/* 同じ表を16x4の横長の画像にして読みなおす */
+	let mut strip = vec![0u8; 16*4*4];
+	for b in 0..4 {
+	    for g in 0..4 {
+		for r in 0..4 {
+		    let src = ((b*16 + g*4 + r)*4) as usize;
+		    let dst = ((g*16 + b*4 + r)*4) as usize;
+		    strip[dst..dst + 4].copy_from_slice(&identity.pixels[src..src + 4]);
+		}
+	    }
+	}
+	let image = TextureData::from_rgba(16, 4, strip).unwrap();
+	assert_eq!(ColorLut::from_strip(&image).unwrap(), identity);
+	assert!(ColorLut::from_strip(&TextureData::from_rgba(4, 4, vec![0; 64]).unwrap()).is_err());
+    }
+
+    #[test]
+    fn msaa_sample_count_is_clamped(){
+	assert_eq!(clamp_samples(0, 8), 0);
+	assert_eq!(clamp_samples(1, 8), 0);
+	assert_eq!(clamp_samples(4, 8), 4);
+	assert_eq!(clamp_samples(6, 8), 4);
+	assert_eq!(clamp_samples(16, 8), 8);
+	/* マルチサンプルに対応していなければ常に0 */
+	assert_eq!(clamp_samples(4, 0), 0);
+	assert!(!RenderState::default().alpha_to_coverage);
+    }
+
+    #[test]
+    fn msaa_sample_count_cycles_up_to_the_limit(){
+	let cycle = |max:i32| {
+	    let mut samples = vec![0];
+	    loop {
+		let next = next_sample_count(*samples.last().unwrap(), max);
+		if next == 0 { break samples; }
+		samples.push(next);
+	    }
+	};
+	assert_eq!(cycle(16), vec![0, 2, 4, 8, 16]);
+	assert_eq!(cycle(8), vec![0, 2, 4, 8]);
+	assert_eq!(cycle(0), vec![0]);
+	/* 候補にない数からは0に戻る */
+	assert_eq!(next_sample_count(6, 16), 0);
+    }
+
+    #[test]
+    fn ssao_kernel_stays_in_the_hemisphere(){
+	let kernel = ssao_kernel(32);
+	assert_eq!(kernel.len(), 32);
+	for k in kernel.iter() {
+	    assert!(k[2] >= 0.0);
+	    assert!(k[0]*k[0] + k[1]*k[1] + k[2]*k[2] <= 1.0 + 1.0e-5);
+	}
+	assert_eq!(kernel, ssao_kernel(32));
+	assert_eq!(ssao_kernel(1000).len(), MAX_SSAO_KERNEL_SIZE);
+	assert_eq!(ssao_noise().len(), (SSAO_NOISE_SIZE*SSAO_NOISE_SIZE) as usize);
+	let lit = expand_includes("#include ssao.glsl\n");
+	assert!(lit.contains("screenSpaceOcclusion"));
+    }
+
+    #[test]
+    fn deferred_light_range_and_parameters(){
+	let light = Light::point([0.0,0.0,0.0], [1.0,0.5,0.5], 2.0);
+	let range = light_range(&light, 0.01).unwrap();
+	if let LightKind::Point { attenuation, .. } = light.kind {
+	    assert!((2.0*attenuation.factor(range) - 0.01).abs() < 1.0e-4);
+	}
+	assert_eq!(light_range(&Light::directional([0.0,0.0,-1.0], [1.0,1.0,1.0], 1.0), 0.01), None);
+	assert_eq!(light_range(&Light::point([0.0,0.0,0.0], [1.0,1.0,1.0], 0.001), 0.01), Some(0.0));
+
+	let uniform = |model:&ShadingModel, name:&str| deferred_uniforms(model).into_iter().find(|(n, _)| n == name).map(|(_, v)| v);
+	let lambert = ShadingModel::Lambert(LambertParameter::default());
+	assert_eq!(uniform(&lambert, "roughness"), Some(UniformValue::Float(1.0)));
+	assert_eq!(uniform(&ShadingModel::Unlit(UnlitParameter::default()), "unlit"), Some(UniformValue::Int(1)));
+	let phong = ShadingModel::Phong(PhongParameter { shininess: 126.0, ..PhongParameter::default() });
+	assert_eq!(uniform(&phong, "roughness"), Some(UniformValue::Float(0.125)));
+	assert!(expand_includes("#include deferred.glsl\n#include pbr.glsl\n").contains("pbrReflectance"));
+    }
+
+    #[test]
+    fn instances_are_packed_column_major_with_color(){
+	let mut transform = translate(-3.0);
+	transform.v[0][1] = 2.0;
+	let instance = Instance { transform, color: [0.1, 0.2, 0.3, 1.0] };
+	let data = pack_instances(&[Instance::new(Matrix4x4::identity()), instance]);
+	assert_eq!(data.len(), 2*INSTANCE_STRIDE);
+	assert_eq!(&data[INSTANCE_STRIDE..INSTANCE_STRIDE+4], &[1.0, 0.0, 0.0, 0.0]);
+	assert_eq!(&data[INSTANCE_STRIDE+4..INSTANCE_STRIDE+8], &[2.0, 1.0, 0.0, 0.0]);
+	assert_eq!(&data[INSTANCE_STRIDE+12..INSTANCE_STRIDE+16], &[0.0, 0.0, -3.0, 1.0]);
+	assert_eq!(&data[INSTANCE_STRIDE+16..], &[0.1, 0.2, 0.3, 1.0]);
+	assert_eq!(&data[16..20], &[1.0, 1.0, 1.0, 1.0]);
+
+	let mesh = VertexArrayObjectContext::new(1, gl::TRIANGLES, 6);
+	assert!(!mesh.is_instanced());
+	assert_eq!(mesh.total_triangle_count(), 2);
+	let mesh = mesh.with_instances(100);
+	assert!(mesh.is_instanced());
+	assert_eq!(mesh.total_triangle_count(), 200);
+	assert_eq!(mesh.with_instances(1).total_triangle_count(), 2);
+    }
+
+    #[test]
+    fn skinning_uniforms_are_replaced_and_inherited(){
+	let matrices = vec![translate(1.0); MAX_JOINTS + 3];
+	let uniforms = skinning_uniforms(&matrices);
+	assert_eq!(uniforms.len(), 1 + MAX_JOINTS);
+	assert_eq!(uniforms[0], ("skinned".to_string(), UniformValue::Int(1)));
+	assert_eq!(uniforms[2], ("jointMatrices[1]".to_string(), UniformValue::Mat4(translate(1.0).serialize_f32())));
+
+	let mut material = Material::new(1).with_uniform("alpha", UniformValue::Float(0.5));
+	set_skinning(&mut material, &matrices[..2]);
+	set_skinning(&mut material, &matrices[..1]);
+	assert_eq!(material.uniforms.len(), 3);
+	/* 影などのシェーダにはスキニングのuniformだけ渡る */
+	let depth = with_skinning_of(Material::new(2), &material);
+	assert_eq!(depth.shader, 2);
+	assert_eq!(depth.uniforms.len(), 2);
+	assert!(depth.uniforms.iter().all(|(name, _)| name != "alpha"));
+
+	let (joints, weights) = pack_skin_weights(&[SkinWeights::single(3), SkinWeights::new([1, 2, 0, 0], [1.0, 3.0, 0.0, 0.0])]);
+	assert_eq!(joints, vec![3, 0, 0, 0, 1, 2, 0, 0]);
+	assert_eq!(weights, vec![1.0, 0.0, 0.0, 0.0, 0.25, 0.75, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn ibl_cache_file_round_trip(){
+	let file = FloatTextureFile {
+	    cube: true,
+	    width: 4,
+	    height: 4,
+	    channels: 4,
+	    source: SourceStamp { size: 1234, modified: 1_600_000_000_000_000_000 },
+	    levels: (0..3).map(|level| {
+		let (w, h) = level_size(4, 4, level);
+		(0..w*h*4*6).map(|i| (i as f32)*0.25 - (level as f32)).collect()
+	    }).collect()
+	};
+	let bytes = file.encode();
+	assert_eq!(FloatTextureFile::decode(&bytes).unwrap(), file);
+	assert!(FloatTextureFile::decode(&bytes[..bytes.len() - 4]).is_err());
+	let mut broken = bytes.clone();
+	broken[0] = b'X';
+	assert!(FloatTextureFile::decode(&broken).is_err());
+
+	/* 壊れたヘッダの大きさで掛け算があふれてもpanicせずにエラーになる */
+	let mut huge = bytes.clone();
+	huge[12..20].copy_from_slice(&[0xff;8]);
+	assert_eq!(FloatTextureFile { width: u32::MAX, height: u32::MAX, ..file.clone() }.level_length(0), None);
+	assert!(matches!(FloatTextureFile::decode(&huge), Err(TextureError::Decode(_))));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::renderer::{Renderer,UniformValue};
+
+/* shaders/lights.glslのMAX_LIGHTSと合わせる */
+pub const MAX_LIGHTS: usize = 8;
+
+/* GLSL側のLight.lightTypeの値 */
+const LIGHT_TYPE_DIRECTIONAL: i32 = 0;
+const LIGHT_TYPE_POINT: i32 = 1;
+const LIGHT_TYPE_SPOT: i32 = 2;
+
+/* 減衰 1/(constant + linear*d + quadratic*d^2) */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32
+}
+
+impl Attenuation {
+    pub fn factor(&self, distance:f32) -> f32 {
+	1.0/(self.constant + self.linear*distance + self.quadratic*distance*distance).max(1.0e-6)
+    }
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+	Attenuation { constant: 1.0, linear: 0.09, quadratic: 0.032 }
+    }
+}
+
+/* 座標と向きはワールド座標. directionは光が進む向き */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum LightKind {
+    Directional {
+	direction: [f32;3]
+    },
+    Point {
+	position: [f32;3],
+	attenuation: Attenuation
+    },
+    Spot {
+	position: [f32;3],
+	direction: [f32;3],
+	attenuation: Attenuation,
+	inner_cone_degree: f32,
+	outer_cone_degree: f32
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32;3],
+    pub intensity: f32,
+    pub enabled: bool,
+    /* 影を落とすか. 平行光源とスポットライトのみ. see glutils::shadow */
+    pub cast_shadow: bool
+}
+
+impl Light {
+    pub fn directional(direction:[f32;3], color:[f32;3], intensity:f32) -> Self {
+	Light { kind: LightKind::Directional { direction }, color, intensity, enabled: true, cast_shadow: false }
+    }
+
+    pub fn point(position:[f32;3], color:[f32;3], intensity:f32) -> Self {
+	Light {
+	    kind: LightKind::Point { position, attenuation: Attenuation::default() },
+	    color, intensity, enabled: true, cast_shadow: false
+	}
+    }
+
+    pub fn spot(position:[f32;3], direction:[f32;3], inner_cone_degree:f32, outer_cone_degree:f32, color:[f32;3], intensity:f32) -> Self {
+	Light {
+	    kind: LightKind::Spot {
+		position, direction,
+		attenuation: Attenuation::default(),
+		inner_cone_degree, outer_cone_degree
+	    },
+	    color, intensity, enabled: true, cast_shadow: false
+	}
+    }
+
+    pub fn type_name(&self) -> &'static str {
+	match self.kind {
+	    LightKind::Directional { .. } => "directional",
+	    LightKind::Point { .. } => "point",
+	    LightKind::Spot { .. } => "spot"
+	}
+    }
+
+    /*
+      スポットライトの円錐の減衰. 光軸からの角度のcosを渡す.
+      inner以内は1,outer以遠は0,その間はsmoothstep. シェーダと同じ計算
+    */
+    pub fn spot_factor(cos_inner:f32, cos_outer:f32, cos_theta:f32) -> f32 {
+	let t = ((cos_theta - cos_outer)/(cos_inner - cos_outer).max(1.0e-6)).clamp(0.0, 1.0);
+	t*t*(3.0 - 2.0*t)
+    }
+
+    /* lights[index]のuniform */
+    pub fn uniforms(&self, index:usize) -> Vec<(String, UniformValue)> {
+	let name = |field:&str| format!("lights[{}].{}", index, field);
+	let radiance = [self.color[0]*self.intensity, self.color[1]*self.intensity, self.color[2]*self.intensity];
+	let (light_type, position, direction, attenuation, cos_inner, cos_outer) = match self.kind {
+	    LightKind::Directional { direction } =>
+		(LIGHT_TYPE_DIRECTIONAL, [0.0;3], direction, Attenuation { constant: 1.0, linear: 0.0, quadratic: 0.0 }, -1.0, -1.0),
+	    LightKind::Point { position, attenuation } =>
+		(LIGHT_TYPE_POINT, position, [0.0, 0.0, -1.0], attenuation, -1.0, -1.0),
+	    LightKind::Spot { position, direction, attenuation, inner_cone_degree, outer_cone_degree } =>
+		(LIGHT_TYPE_SPOT, position, direction, attenuation,
+		 inner_cone_degree.to_radians().cos(), outer_cone_degree.to_radians().cos())
+	};
+	vec![
+	    (name("lightType"), UniformValue::Int(light_type)),
+	    (name("position"), UniformValue::Vec3(position)),
+	    (name("direction"), UniformValue::Vec3(direction)),
+	    (name("radiance"), UniformValue::Vec3(radiance)),
+	    (name("attenuation"), UniformValue::Vec3([attenuation.constant, attenuation.linear, attenuation.quadratic])),
+	    (name("cosInner"), UniformValue::Float(cos_inner)),
+	    (name("cosOuter"), UniformValue::Float(cos_outer))
+	]
+    }
+}
+
+#[derive(Debug,Clone,Default)]
+pub struct LightSet {
+    lights: Vec<Light>
+}
+
+impl LightSet {
+    pub fn new() -> Self {
+	LightSet { lights: Vec::new() }
+    }
+
+    /* MAX_LIGHTSを超える場合は追加せずNone */
+    pub fn add(&mut self, light:Light) -> Option<usize> {
+	if self.lights.len() >= MAX_LIGHTS {
+	    return None;
+	}
+	self.lights.push(light);
+	Some(self.lights.len()-1)
+    }
+
+    pub fn remove(&mut self, index:usize) -> Light {
+	self.lights.remove(index)
+    }
+
+    pub fn len(&self) -> usize {
+	self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.lights.is_empty()
+    }
+
+    pub fn get(&self, index:usize) -> Option<&Light> {
+	self.lights.get(index)
+    }
+
+    pub fn get_mut(&mut self, index:usize) -> Option<&mut Light> {
+	self.lights.get_mut(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Light> {
+	self.lights.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Light> {
+	self.lights.iter_mut()
+    }
+
+    /*
+      影を落とす光源. 最初に見つかった有効な平行光源かスポットライト.
+      添字はuniforms()で詰めた後のlights[]の添字
+    */
+    pub fn shadow_caster(&self) -> Option<(usize, &Light)> {
+	self.lights.iter().filter(|l| l.enabled).enumerate().find(|(_, l)| {
+	    l.cast_shadow && !matches!(l.kind, LightKind::Point { .. })
+	})
+    }
+
+    /* 有効な光源だけを詰めてuniformにする. lightCountも含む */
+    pub fn uniforms(&self) -> Vec<(String, UniformValue)> {
+	let mut uniforms = Vec::new();
+	let mut count = 0;
+	for light in self.lights.iter().filter(|l| l.enabled) {
+	    uniforms.append(&mut light.uniforms(count));
+	    count += 1;
+	}
+	uniforms.push(("lightCount".to_string(), UniformValue::Int(count as i32)));
+	uniforms
+    }
+
+    /* 全ての描画に光源を設定する */
+    pub fn apply(&self, renderer:&mut Renderer) {
+	for (name, value) in self.uniforms() {
+	    renderer.set_frame_uniform(&name, value);
+	}
+    }
+}
@@ -0,0 +1,281 @@
+use gl::types::*;
+
+use crate::renderer::{Material,UniformValue};
+use crate::texture::{Texture2D,Sampler};
+use crate::shadow::SHADOW_TEXTURE_UNIT;
+use crate::cubemap::ENVIRONMENT_TEXTURE_UNIT;
+use crate::ibl::{IRRADIANCE_TEXTURE_UNIT,PREFILTERED_TEXTURE_UNIT,BRDF_LUT_TEXTURE_UNIT};
+use crate::ssao::SSAO_TEXTURE_UNIT;
+use crate::{build_program,uniform_location};
+
+static MATERIAL_VERTEX_SHADER_CODE: &str = include_str!("shaders/material.vert");
+
+static UNLIT_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/unlit.frag");
+
+static LAMBERT_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/lambert.frag");
+
+static PHONG_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/phong.frag");
+
+static PBR_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/pbr.frag");
+
+static LIGHTS_SHADER_CODE: &str = include_str!("shaders/lights.glsl");
+
+static SHADOW_SHADER_CODE: &str = include_str!("shaders/shadow.glsl");
+
+static SURFACE_SHADER_CODE: &str = include_str!("shaders/surface.glsl");
+
+static ENVIRONMENT_SHADER_CODE: &str = include_str!("shaders/environment.glsl");
+
+static IBL_SHADER_CODE: &str = include_str!("shaders/ibl.glsl");
+
+static SAMPLING_SHADER_CODE: &str = include_str!("shaders/sampling.glsl");
+
+static SSAO_SHADER_CODE: &str = include_str!("shaders/ssao.glsl");
+
+static PBR_SHADER_CODE: &str = include_str!("shaders/pbr.glsl");
+
+static DEFERRED_SHADER_CODE: &str = include_str!("shaders/deferred.glsl");
+
+/* ベースカラーマップを結びつけるテクスチャユニット */
+pub const BASE_COLOR_TEXTURE_UNIT: GLuint = 0;
+
+/* 色はリニアRGB */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct UnlitParameter {
+    pub base_color: [f32;4]
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct LambertParameter {
+    pub ambient: [f32;3],
+    pub diffuse: [f32;3]
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct PhongParameter {
+    pub ambient: [f32;3],
+    pub diffuse: [f32;3],
+    pub specular: [f32;3],
+    pub shininess: f32
+}
+
+/* metallic-roughnessモデル */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct PbrParameter {
+    pub base_color: [f32;3],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub ambient_occlusion: f32
+}
+
+impl Default for UnlitParameter {
+    fn default() -> Self {
+	UnlitParameter { base_color: [1.0, 1.0, 1.0, 1.0] }
+    }
+}
+
+impl Default for LambertParameter {
+    fn default() -> Self {
+	LambertParameter { ambient: [0.1, 0.1, 0.1], diffuse: [1.0, 1.0, 1.0] }
+    }
+}
+
+impl Default for PhongParameter {
+    fn default() -> Self {
+	PhongParameter {
+	    ambient: [0.1, 0.1, 0.1],
+	    diffuse: [1.0, 1.0, 1.0],
+	    specular: [0.5, 0.5, 0.5],
+	    shininess: 32.0
+	}
+    }
+}
+
+impl Default for PbrParameter {
+    fn default() -> Self {
+	PbrParameter {
+	    base_color: [1.0, 1.0, 1.0],
+	    metallic: 0.0,
+	    roughness: 0.5,
+	    ambient_occlusion: 1.0
+	}
+    }
+}
+
+/* シェーディングモデルとそのパラメータ */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum ShadingModel {
+    Unlit(UnlitParameter),
+    Lambert(LambertParameter),
+    Phong(PhongParameter),
+    BlinnPhong(PhongParameter),
+    Pbr(PbrParameter)
+}
+
+impl ShadingModel {
+
+    /* egui等で選択肢として並べる用 */
+    pub fn defaults() -> [ShadingModel;5] {
+	[
+	    ShadingModel::Unlit(UnlitParameter::default()),
+	    ShadingModel::Lambert(LambertParameter::default()),
+	    ShadingModel::Phong(PhongParameter::default()),
+	    ShadingModel::BlinnPhong(PhongParameter::default()),
+	    ShadingModel::Pbr(PbrParameter::default())
+	]
+    }
+
+    pub fn name(&self) -> &'static str {
+	match self {
+	    ShadingModel::Unlit(_) => "Unlit",
+	    ShadingModel::Lambert(_) => "Lambert",
+	    ShadingModel::Phong(_) => "Phong",
+	    ShadingModel::BlinnPhong(_) => "Blinn-Phong",
+	    ShadingModel::Pbr(_) => "PBR"
+	}
+    }
+
+    /* 同じシェーディングモデルか(パラメータは比較しない) */
+    pub fn same_model(&self, other:&ShadingModel) -> bool {
+	std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn uniforms(&self) -> Vec<(String, UniformValue)> {
+	let entry = |name:&str, value:UniformValue| (name.to_string(), value);
+	match self {
+	    ShadingModel::Unlit(p) => vec![
+		entry("baseColor", UniformValue::Vec4(p.base_color))
+	    ],
+	    ShadingModel::Lambert(p) => vec![
+		entry("ambient", UniformValue::Vec3(p.ambient)),
+		entry("diffuse", UniformValue::Vec3(p.diffuse))
+	    ],
+	    ShadingModel::Phong(p) | ShadingModel::BlinnPhong(p) => vec![
+		entry("ambient", UniformValue::Vec3(p.ambient)),
+		entry("diffuse", UniformValue::Vec3(p.diffuse)),
+		entry("specular", UniformValue::Vec3(p.specular)),
+		entry("shininess", UniformValue::Float(p.shininess)),
+		entry("blinn", UniformValue::Int(if let ShadingModel::BlinnPhong(_) = self { 1 } else { 0 }))
+	    ],
+	    ShadingModel::Pbr(p) => vec![
+		entry("baseColor", UniformValue::Vec3(p.base_color)),
+		entry("metallic", UniformValue::Float(p.metallic)),
+		entry("roughness", UniformValue::Float(p.roughness)),
+		entry("ambientOcclusion", UniformValue::Float(p.ambient_occlusion))
+	    ]
+	}
+    }
+}
+
+/* シェーディングモデルごとのシェーダプログラム. GLコンテキスト作成後に作る */
+pub struct MaterialPrograms {
+    unlit: GLuint,
+    lambert: GLuint,
+    phong: GLuint,
+    pbr: GLuint
+}
+
+/* GLSLには#includeが無いので,ここで表面の色,光源,影,環境マップ,IBL,SSAO,BRDF,Gバッファの定義を展開する */
+pub fn expand_includes(shader_code:&str) -> String {
+    shader_code
+	.replace("#include surface.glsl", SURFACE_SHADER_CODE)
+	.replace("#include lights.glsl", LIGHTS_SHADER_CODE)
+	.replace("#include shadow.glsl", SHADOW_SHADER_CODE)
+	.replace("#include environment.glsl", ENVIRONMENT_SHADER_CODE)
+	.replace("#include ibl.glsl", IBL_SHADER_CODE)
+	.replace("#include sampling.glsl", SAMPLING_SHADER_CODE)
+	.replace("#include ssao.glsl", SSAO_SHADER_CODE)
+	.replace("#include pbr.glsl", PBR_SHADER_CODE)
+	.replace("#include deferred.glsl", DEFERRED_SHADER_CODE)
+}
+
+/* #includeで入るsamplerと,決まったテクスチャユニット */
+const SAMPLER_UNITS: [(&str, GLuint);7] = [
+    ("baseColorMap", BASE_COLOR_TEXTURE_UNIT),
+    ("shadowMap", SHADOW_TEXTURE_UNIT),
+    ("environmentMap", ENVIRONMENT_TEXTURE_UNIT),
+    ("irradianceMap", IRRADIANCE_TEXTURE_UNIT),
+    ("prefilteredMap", PREFILTERED_TEXTURE_UNIT),
+    ("brdfLut", BRDF_LUT_TEXTURE_UNIT),
+    ("ssaoMap", SSAO_TEXTURE_UNIT)
+];
+
+/* 影,環境マップ,IBL,SSAO,テクスチャを使わない設定 */
+const DISABLED_FEATURES: [(&str, GLint);5] = [
+    ("shadowLight", -1),
+    ("hasEnvironmentMap", 0),
+    ("hasIbl", 0),
+    ("hasSsao", 0),
+    ("hasBaseColorMap", 0)
+];
+
+/*
+  リンクしたprogramのsamplerをそれぞれのテクスチャユニットにし,影などは使わない設定にしておく.
+  GLSLの既定ではsamplerがすべてユニット0になり,型の違うsamplerが同じユニットだと描画がGL_INVALID_OPERATIONになる.
+  ここで決めておけば,影や環境マップを設定しない呼び出し側でもそのまま描ける. programにないuniformは無視される
+*/
+pub(crate) fn set_default_uniforms(program:GLuint) {
+    unsafe {
+	let mut previous:GLint = 0;
+	gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut previous);
+	gl::UseProgram(program);
+	for (name, unit) in SAMPLER_UNITS.iter() {
+	    gl::Uniform1i(uniform_location(program, name), *unit as GLint);
+	}
+	for (name, value) in DISABLED_FEATURES.iter() {
+	    gl::Uniform1i(uniform_location(program, name), *value);
+	}
+	gl::UseProgram(previous as GLuint);
+    }
+}
+
+impl MaterialPrograms {
+
+    pub fn create() -> Self {
+	MaterialPrograms {
+	    unlit: build_program(MATERIAL_VERTEX_SHADER_CODE, UNLIT_FRAGMENT_SHADER_CODE),
+	    lambert: build_program(MATERIAL_VERTEX_SHADER_CODE, LAMBERT_FRAGMENT_SHADER_CODE),
+	    phong: build_program(MATERIAL_VERTEX_SHADER_CODE, PHONG_FRAGMENT_SHADER_CODE),
+	    pbr: build_program(MATERIAL_VERTEX_SHADER_CODE, PBR_FRAGMENT_SHADER_CODE)
+	}
+    }
+
+    pub fn program(&self, model:&ShadingModel) -> GLuint {
+	match model {
+	    ShadingModel::Unlit(_) => self.unlit,
+	    ShadingModel::Lambert(_) => self.lambert,
+	    ShadingModel::Phong(_) | ShadingModel::BlinnPhong(_) => self.phong,
+	    ShadingModel::Pbr(_) => self.pbr
+	}
+    }
+
+    /*
+      Rendererに渡すMaterialを作る.
+      programは共有されるので,テクスチャを使わない場合もhasBaseColorMapを0に設定しなおす.
+    */
+    pub fn material(&self, model:&ShadingModel) -> Material {
+	Material {
+	    shader: self.program(model),
+	    uniforms: model.uniforms(),
+	    textures: Vec::new()
+	}.with_uniform("hasBaseColorMap", UniformValue::Int(0))
+    }
+
+    /* 頂点色にbase_color_mapを掛ける. samplerがNoneならテクスチャ自身の設定で読む */
+    pub fn textured_material(&self, model:&ShadingModel, base_color_map:&Texture2D, sampler:Option<&Sampler>) -> Material {
+	self.material(model)
+	    .with_uniform("hasBaseColorMap", UniformValue::Int(1))
+	    .with_texture("baseColorMap", base_color_map.binding(BASE_COLOR_TEXTURE_UNIT, sampler))
+    }
+}
+
+impl Drop for MaterialPrograms {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteProgram(self.unlit);
+	    gl::DeleteProgram(self.lambert);
+	    gl::DeleteProgram(self.phong);
+	    gl::DeleteProgram(self.pbr);
+	}
+    }
+}
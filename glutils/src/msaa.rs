@@ -0,0 +1,169 @@
+use gl::types::*;
+
+/*
+  マルチサンプルのオフスクリーン描画先. シーンをここに描いてから,
+  画面のframebufferかテクスチャにBlitFramebufferで解決(resolve)する.
+  ウィンドウ自体をマルチサンプルにする場合はglfwのWindowHint::Samplesを使う.
+*/
+
+/* GL_MAX_SAMPLES. 描画先に指定できるサンプル数の上限 */
+pub fn max_samples() -> i32 {
+    let mut samples:GLint = 0;
+    unsafe {
+	gl::GetIntegerv(gl::MAX_SAMPLES, &mut samples);
+    }
+    samples
+}
+
+/* 要求されたサンプル数をmaxに収めて2のべき乗に切り下げる. 1以下はマルチサンプルしない(0) */
+pub fn clamp_samples(requested:i32, max:i32) -> i32 {
+    let samples = requested.min(max);
+    if samples <= 1 {
+	0
+    }
+    else {
+	1 << (31 - (samples as u32).leading_zeros())
+    }
+}
+
+/* UIで選ぶサンプル数の候補 */
+pub const SAMPLE_COUNTS: [i32;5] = [0, 2, 4, 8, 16];
+
+/* SAMPLE_COUNTSでcurrentの次に大きく,max以下の数. 上限を超えるか候補にない数なら0に戻る */
+pub fn next_sample_count(current:i32, max:i32) -> i32 {
+    SAMPLE_COUNTS.iter().copied()
+	.find(|&n| n > current && n <= max && SAMPLE_COUNTS.contains(&current))
+	.unwrap_or(0)
+}
+
+/* GLコンテキスト作成後に作る */
+pub struct MsaaFramebuffer {
+    framebuffer: GLuint,
+    renderbuffers: [GLuint;2],
+    color_format: GLenum,
+    /* resolve_to_textureの描画先. 最初に呼ばれたときに作る */
+    resolve_framebuffer: GLuint,
+    resolve_texture: GLuint,
+    width: i32,
+    height: i32,
+    samples: i32
+}
+
+impl MsaaFramebuffer {
+
+    /*
+      color_formatはRGBA8やRGBA16Fなど. samplesは上限に収めるので,実際の数はsamples()で見る.
+      深度は24bit
+    */
+    pub fn create(width:i32, height:i32, samples:i32, color_format:GLenum) -> Self {
+	let (width, height) = (width.max(1), height.max(1));
+	let samples = clamp_samples(samples, max_samples());
+	let mut framebuffer = 0;
+	let mut renderbuffers : [GLuint;2] = [0,0];
+	unsafe {
+	    let mut previous_framebuffer:GLint = 0;
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    gl::GenRenderbuffers(2, &mut renderbuffers[0]);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffers[0]);
+	    gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, color_format, width, height);
+	    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, renderbuffers[0]);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffers[1]);
+	    gl::RenderbufferStorageMultisample(gl::RENDERBUFFER, samples, gl::DEPTH_COMPONENT24, width, height);
+	    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, renderbuffers[1]);
+	    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+		println!("multisample framebuffer is incomplete");
+	    }
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+	}
+	MsaaFramebuffer {
+	    framebuffer, renderbuffers, color_format,
+	    resolve_framebuffer: 0,
+	    resolve_texture: 0,
+	    width, height, samples
+	}
+    }
+
+    /* 大きさかサンプル数が変わったら作りなおす. ウィンドウの大きさが変わったときやUIで選びなおしたときに呼ぶ */
+    pub fn update(&mut self, width:i32, height:i32, samples:i32) {
+	let samples = clamp_samples(samples, max_samples());
+	if self.width != width.max(1) || self.height != height.max(1) || self.samples != samples {
+	    *self = MsaaFramebuffer::create(width, height, samples, self.color_format);
+	}
+    }
+
+    pub fn width(&self) -> i32 {
+	self.width
+    }
+
+    pub fn height(&self) -> i32 {
+	self.height
+    }
+
+    /* 実際のサンプル数. 0ならマルチサンプルしていない */
+    pub fn samples(&self) -> i32 {
+	self.samples
+    }
+
+    pub fn framebuffer(&self) -> GLuint {
+	self.framebuffer
+    }
+
+    /* 描画先にしてviewportを全体に合わせる */
+    pub fn bind(&self) {
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+	    gl::Viewport(0, 0, self.width, self.height);
+	}
+    }
+
+    /*
+      framebuffer(0なら画面)に解決する. マルチサンプルのblitは拡大縮小できないので,
+      描画先も同じ大きさであること. 終わるとframebufferが描画先になる
+    */
+    pub fn resolve_to(&self, framebuffer:GLuint) {
+	unsafe {
+	    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+	    gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, framebuffer);
+	    gl::BlitFramebuffer(0, 0, self.width, self.height, 0, 0, self.width, self.height,
+				gl::COLOR_BUFFER_BIT, gl::NEAREST);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	}
+    }
+
+    /* 同じ大きさのテクスチャに解決して返す. 後処理の入力などに使う */
+    pub fn resolve_to_texture(&mut self) -> GLuint {
+	if self.resolve_framebuffer == 0 {
+	    unsafe {
+		gl::GenTextures(1, &mut self.resolve_texture);
+		gl::BindTexture(gl::TEXTURE_2D, self.resolve_texture);
+		gl::TexStorage2D(gl::TEXTURE_2D, 1, self.color_format, self.width, self.height);
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+		gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+		gl::BindTexture(gl::TEXTURE_2D, 0);
+		gl::GenFramebuffers(1, &mut self.resolve_framebuffer);
+		gl::BindFramebuffer(gl::FRAMEBUFFER, self.resolve_framebuffer);
+		gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.resolve_texture, 0);
+	    }
+	}
+	self.resolve_to(self.resolve_framebuffer);
+	self.resolve_texture
+    }
+}
+
+impl Drop for MsaaFramebuffer {
+    fn drop(&mut self) {
+	unsafe {
+	    if self.resolve_framebuffer != 0 {
+		gl::DeleteFramebuffers(1, &self.resolve_framebuffer);
+		gl::DeleteTextures(1, &self.resolve_texture);
+	    }
+	    gl::DeleteRenderbuffers(2, &self.renderbuffers[0]);
+	    gl::DeleteFramebuffers(1, &self.framebuffer);
+	}
+    }
+}
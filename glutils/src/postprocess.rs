@@ -0,0 +1,559 @@
+use gl::types::*;
+
+use crate::texture::{TextureData,TextureError};
+use crate::hdr::{ToneMapPass,ToneMapSettings,OutputEncoding};
+use crate::{build_program,uniform_location};
+
+/*
+  シーンを描いた後,eguiを描く前にかける効果. 順番は次のとおり.
+  HDRのまま: bloom
+  トーンマッピング(sRGBにする)
+  sRGBで: color grading -> vignette -> FXAA
+  最後に有効なパスが呼ぶ側のframebufferに描く. 途中は2枚のテクスチャを交互に使う.
+*/
+
+static FULLSCREEN_VERTEX_SHADER_CODE: &str = include_str!("shaders/fullscreen.vert");
+
+static BLOOM_PREFILTER_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/bloom_prefilter.frag");
+
+static KAWASE_DOWN_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/kawase_down.frag");
+
+static KAWASE_UP_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/kawase_up.frag");
+
+static BLOOM_COMPOSITE_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/bloom_composite.frag");
+
+static COLOR_GRADING_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/color_grading.frag");
+
+static VIGNETTE_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/vignette.frag");
+
+static FXAA_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/fxaa.frag");
+
+/* bloomの縮小の回数の上限 */
+pub const MAX_BLOOM_ITERATIONS: u32 = 8;
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    /* これより明るい部分(リニアな値)がにじむ */
+    pub threshold: f32,
+    pub knee: f32,
+    pub intensity: f32,
+    /* 縮小の回数. 多いほど広くにじむ */
+    pub iterations: u32
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct ColorGradingSettings {
+    pub enabled: bool,
+    /* 0なら元の色,1ならLUTの色 */
+    pub strength: f32
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct VignetteSettings {
+    pub enabled: bool,
+    pub intensity: f32,
+    pub radius: f32,
+    pub softness: f32
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct FxaaSettings {
+    pub enabled: bool
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct PostProcessSettings {
+    pub bloom: BloomSettings,
+    pub color_grading: ColorGradingSettings,
+    pub vignette: VignetteSettings,
+    pub fxaa: FxaaSettings
+}
+
+impl Default for PostProcessSettings {
+    fn default() -> Self {
+	PostProcessSettings {
+	    bloom: BloomSettings { enabled: true, threshold: 1.0, knee: 0.5, intensity: 0.3, iterations: 5 },
+	    color_grading: ColorGradingSettings { enabled: false, strength: 1.0 },
+	    vignette: VignetteSettings { enabled: false, intensity: 0.5, radius: 0.6, softness: 0.45 },
+	    fxaa: FxaaSettings { enabled: true }
+	}
+    }
+}
+
+/* トーンマッピングの後のパス */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum LdrPass {
+    ColorGrading,
+    Vignette,
+    Fxaa
+}
+
+impl PostProcessSettings {
+    /* トーンマッピングの後にかけるパスを順に並べる. FXAAは最後 */
+    pub fn ldr_passes(&self) -> Vec<LdrPass> {
+	let mut passes = Vec::new();
+	if self.color_grading.enabled {
+	    passes.push(LdrPass::ColorGrading);
+	}
+	if self.vignette.enabled {
+	    passes.push(LdrPass::Vignette);
+	}
+	if self.fxaa.enabled {
+	    passes.push(LdrPass::Fxaa);
+	}
+	passes
+    }
+}
+
+/* bloomの縮小の各段の大きさ. 最初は画面の半分で,1画素より小さくはしない */
+pub fn bloom_level_sizes(width:i32, height:i32, iterations:u32) -> Vec<(i32, i32)> {
+    let mut sizes = Vec::new();
+    let (mut w, mut h) = (width, height);
+    for _ in 0..iterations.clamp(1, MAX_BLOOM_ITERATIONS) {
+	w = (w/2).max(1);
+	h = (h/2).max(1);
+	sizes.push((w, h));
+	if w == 1 && h == 1 {
+	    break;
+	}
+    }
+    sizes
+}
+
+/*
+  3次元の色の表. pixelsはRGBA8で,rが一番速く変わり,次にg,最後にbの順.
+  色はsRGBの値で引く
+*/
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ColorLut {
+    pub size: u32,
+    pub pixels: Vec<u8>
+}
+
+impl ColorLut {
+    /* 入力の(r,g,b)(0..1)から出力の色を計算して作る */
+    pub fn from_fn<F: Fn([f32;3]) -> [f32;3]>(size:u32, f:F) -> Self {
+	let size = size.max(2);
+	let scale = 1.0/((size - 1) as f32);
+	let mut pixels = Vec::with_capacity((size*size*size*4) as usize);
+	for b in 0..size {
+	    for g in 0..size {
+		for r in 0..size {
+		    let out = f([(r as f32)*scale, (g as f32)*scale, (b as f32)*scale]);
+		    for c in out.iter() {
+			pixels.push((c.clamp(0.0, 1.0)*255.0).round() as u8);
+		    }
+		    pixels.push(255);
+		}
+	    }
+	}
+	ColorLut { size, pixels }
+    }
+
+    /* 色を変えない表 */
+    pub fn identity(size:u32) -> Self {
+	ColorLut::from_fn(size, |c| c)
+    }
+
+    /*
+      size*size枚の正方形を横に並べた画像(例えば256x16)から作る. 画像の上がg=0,
+      左からb=0,1,..の正方形が並び,正方形の中でrが右に増える
+    */
+    pub fn from_strip(image:&TextureData) -> Result<Self, TextureError> {
+	let size = image.height;
+	if size < 2 || image.width != size*size {
+	    return Err(TextureError::Decode(format!("color LUT strip must be (n*n)x n, but got {}x{}", image.width, image.height)));
+	}
+	let mut pixels = Vec::with_capacity((size*size*size*4) as usize);
+	for b in 0..size {
+	    for g in 0..size {
+		let row = (g*image.width + b*size)*4;
+		pixels.extend_from_slice(&image.pixels[row as usize..(row + size*4) as usize]);
+	    }
+	}
+	Ok(ColorLut { size, pixels })
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path:P) -> Result<Self, TextureError> {
+	ColorLut::from_strip(&TextureData::load(path)?)
+    }
+}
+
+/* 色だけを持つ描画先. ssaoでも使う */
+pub(crate) struct RenderTexture {
+    pub(crate) framebuffer: GLuint,
+    pub(crate) texture: GLuint,
+    pub(crate) width: i32,
+    pub(crate) height: i32
+}
+
+impl RenderTexture {
+    pub(crate) fn create(width:i32, height:i32, internal_format:GLenum) -> Self {
+	let mut framebuffer = 0;
+	let mut texture = 0;
+	unsafe {
+	    gl::GenTextures(1, &mut texture);
+	    gl::BindTexture(gl::TEXTURE_2D, texture);
+	    gl::TexStorage2D(gl::TEXTURE_2D, 1, internal_format, width, height);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture, 0);
+	    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+		println!("post process framebuffer is incomplete");
+	    }
+	}
+	RenderTexture { framebuffer, texture, width, height }
+    }
+
+    pub(crate) fn bind(&self) {
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+	    gl::Viewport(0, 0, self.width, self.height);
+	}
+    }
+}
+
+impl Drop for RenderTexture {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteFramebuffers(1, &self.framebuffer);
+	    gl::DeleteTextures(1, &self.texture);
+	}
+    }
+}
+
+/* ColorLutを送った3次元テクスチャ */
+struct LutTexture {
+    id: GLuint,
+    size: u32
+}
+
+impl LutTexture {
+    fn create(lut:&ColorLut) -> Self {
+	let mut id = 0;
+	let size = lut.size as GLsizei;
+	unsafe {
+	    gl::GenTextures(1, &mut id);
+	    gl::BindTexture(gl::TEXTURE_3D, id);
+	    gl::TexStorage3D(gl::TEXTURE_3D, 1, gl::RGBA8, size, size, size);
+	    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+	    gl::TexSubImage3D(gl::TEXTURE_3D, 0, 0, 0, 0, size, size, size, gl::RGBA, gl::UNSIGNED_BYTE,
+			      lut.pixels.as_ptr() as *const _);
+	    gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+	    gl::BindTexture(gl::TEXTURE_3D, 0);
+	}
+	LutTexture { id, size: lut.size }
+    }
+}
+
+impl Drop for LutTexture {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteTextures(1, &self.id);
+	}
+    }
+}
+
+/* 全画面の三角形を描くパス. uniformの位置は毎回引く */
+pub(crate) struct FullscreenPass {
+    program: GLuint
+}
+
+impl FullscreenPass {
+    pub(crate) fn create(fragment_shader_code:&str) -> Self {
+	FullscreenPass { program: build_program(FULLSCREEN_VERTEX_SHADER_CODE, fragment_shader_code) }
+    }
+
+    pub(crate) fn use_program(&self) {
+	unsafe {
+	    gl::UseProgram(self.program);
+	}
+    }
+
+    pub(crate) fn set_int(&self, name:&str, value:i32) {
+	unsafe {
+	    gl::Uniform1i(uniform_location(self.program, name), value);
+	}
+    }
+
+    pub(crate) fn set_float(&self, name:&str, value:f32) {
+	unsafe {
+	    gl::Uniform1f(uniform_location(self.program, name), value);
+	}
+    }
+
+    pub(crate) fn set_vec2(&self, name:&str, x:f32, y:f32) {
+	unsafe {
+	    gl::Uniform2f(uniform_location(self.program, name), x, y);
+	}
+    }
+
+    /* nameは配列の先頭の要素("kernel[0]"など) */
+    pub(crate) fn set_vec3_array(&self, name:&str, values:&[[f32;3]]) {
+	unsafe {
+	    gl::Uniform3fv(uniform_location(self.program, name), values.len() as GLsizei, values.as_ptr() as *const GLfloat);
+	}
+    }
+
+    /* 行優先の行列 */
+    pub(crate) fn set_matrix4(&self, name:&str, value:&[f32;16]) {
+	unsafe {
+	    gl::UniformMatrix4fv(uniform_location(self.program, name), 1, gl::TRUE, value.as_ptr());
+	}
+    }
+
+    /* unit番のテクスチャユニットにtextureを結びつけてnameのsamplerに設定する */
+    pub(crate) fn set_texture(&self, name:&str, unit:GLuint, target:GLenum, texture:GLuint) {
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE0 + unit);
+	    gl::BindTexture(target, texture);
+	    gl::Uniform1i(uniform_location(self.program, name), unit as GLint);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	}
+    }
+}
+
+impl Drop for FullscreenPass {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteProgram(self.program);
+	}
+    }
+}
+
+/* 後処理のシェーダと途中の描画先. GLコンテキスト作成後に作る */
+pub struct PostProcessStack {
+    vao: GLuint,
+    bloom_prefilter: FullscreenPass,
+    kawase_down: FullscreenPass,
+    kawase_up: FullscreenPass,
+    bloom_composite: FullscreenPass,
+    color_grading: FullscreenPass,
+    vignette: FullscreenPass,
+    fxaa: FullscreenPass,
+    tone_map: ToneMapPass,
+    lut: LutTexture,
+    /* 画面の大きさ. 変わったら描画先を作りなおす */
+    size: (i32, i32),
+    bloom_levels: Vec<RenderTexture>,
+    hdr_composite: Option<RenderTexture>,
+    ldr: Vec<RenderTexture>
+}
+
+impl PostProcessStack {
+
+    pub fn create() -> Self {
+	let mut vao = 0;
+	unsafe {
+	    gl::GenVertexArrays(1, &mut vao);
+	}
+	PostProcessStack {
+	    vao,
+	    bloom_prefilter: FullscreenPass::create(BLOOM_PREFILTER_FRAGMENT_SHADER_CODE),
+	    kawase_down: FullscreenPass::create(KAWASE_DOWN_FRAGMENT_SHADER_CODE),
+	    kawase_up: FullscreenPass::create(KAWASE_UP_FRAGMENT_SHADER_CODE),
+	    bloom_composite: FullscreenPass::create(BLOOM_COMPOSITE_FRAGMENT_SHADER_CODE),
+	    color_grading: FullscreenPass::create(COLOR_GRADING_FRAGMENT_SHADER_CODE),
+	    vignette: FullscreenPass::create(VIGNETTE_FRAGMENT_SHADER_CODE),
+	    fxaa: FullscreenPass::create(FXAA_FRAGMENT_SHADER_CODE),
+	    tone_map: ToneMapPass::create(),
+	    lut: LutTexture::create(&ColorLut::identity(16)),
+	    size: (0, 0),
+	    bloom_levels: Vec::new(),
+	    hdr_composite: None,
+	    ldr: Vec::new()
+	}
+    }
+
+    /* color gradingに使う表を差し替える */
+    pub fn set_lut(&mut self, lut:&ColorLut) {
+	self.lut = LutTexture::create(lut);
+    }
+
+    fn draw(&self) {
+	unsafe {
+	    gl::BindVertexArray(self.vao);
+	    gl::DrawArrays(gl::TRIANGLES, 0, 3);
+	    gl::BindVertexArray(0);
+	}
+    }
+
+    fn ensure_targets(&mut self, width:i32, height:i32, bloom:&BloomSettings) {
+	if self.size != (width, height) {
+	    self.size = (width, height);
+	    self.bloom_levels.clear();
+	    self.hdr_composite = None;
+	    self.ldr.clear();
+	}
+	if bloom.enabled {
+	    let sizes = bloom_level_sizes(width, height, bloom.iterations);
+	    if self.bloom_levels.len() != sizes.len() {
+		self.bloom_levels = sizes.iter().map(|(w, h)| RenderTexture::create(*w, *h, gl::RGBA16F)).collect();
+	    }
+	    if self.hdr_composite.is_none() {
+		self.hdr_composite = Some(RenderTexture::create(width, height, gl::RGBA16F));
+	    }
+	}
+	if self.ldr.is_empty() {
+	    self.ldr = (0..2).map(|_| RenderTexture::create(width, height, gl::RGBA8)).collect();
+	}
+    }
+
+    /* bloomをかけたHDRのテクスチャを返す */
+    fn render_bloom(&self, scene_texture:GLuint, bloom:&BloomSettings) -> GLuint {
+	let composite = match &self.hdr_composite {
+	    Some(composite) => composite,
+	    None => return scene_texture
+	};
+
+	self.bloom_levels[0].bind();
+	self.bloom_prefilter.use_program();
+	self.bloom_prefilter.set_texture("source", 0, gl::TEXTURE_2D, scene_texture);
+	self.bloom_prefilter.set_float("threshold", bloom.threshold);
+	self.bloom_prefilter.set_float("knee", bloom.knee.max(1.0e-4));
+	self.draw();
+
+	/* 縮小しながらぼかす */
+	self.kawase_down.use_program();
+	for pair in self.bloom_levels.windows(2) {
+	    let (source, target) = (&pair[0], &pair[1]);
+	    target.bind();
+	    self.kawase_down.set_texture("source", 0, gl::TEXTURE_2D, source.texture);
+	    self.kawase_down.set_vec2("halfPixel", 0.5/(source.width as f32), 0.5/(source.height as f32));
+	    self.draw();
+	}
+
+	/* 拡大しながら上の段に足していく */
+	self.kawase_up.use_program();
+	unsafe {
+	    gl::Enable(gl::BLEND);
+	    gl::BlendFunc(gl::ONE, gl::ONE);
+	}
+	for pair in self.bloom_levels.windows(2).rev() {
+	    let (target, source) = (&pair[0], &pair[1]);
+	    target.bind();
+	    self.kawase_up.set_texture("source", 0, gl::TEXTURE_2D, source.texture);
+	    self.kawase_up.set_vec2("halfPixel", 0.5/(source.width as f32), 0.5/(source.height as f32));
+	    self.draw();
+	}
+	unsafe {
+	    gl::Disable(gl::BLEND);
+	}
+
+	composite.bind();
+	self.bloom_composite.use_program();
+	self.bloom_composite.set_texture("source", 0, gl::TEXTURE_2D, scene_texture);
+	self.bloom_composite.set_texture("bloom", 1, gl::TEXTURE_2D, self.bloom_levels[0].texture);
+	/* 段の数で割って,回数を変えても明るさがあまり変わらないようにする */
+	self.bloom_composite.set_float("intensity", bloom.intensity/(self.bloom_levels.len() as f32));
+	self.draw();
+	composite.texture
+    }
+
+    /*
+      scene_textureにリニアな値で描いたシーンに効果をかけて,呼んだときに結びついていたframebufferに描く.
+      tone_map.outputがLinearでなければ,トーンマッピングの後はシェーダでsRGBにして,
+      それ以降のパスと最後の書き込みではFRAMEBUFFER_SRGBを使わない.
+    */
+    pub fn render(&mut self, scene_texture:GLuint, width:i32, height:i32,
+		  tone_map:&ToneMapSettings, settings:&PostProcessSettings) {
+	let (width, height) = (width.max(1), height.max(1));
+	let mut output_framebuffer:GLint = 0;
+	unsafe {
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut output_framebuffer);
+	    gl::Disable(gl::DEPTH_TEST);
+	    gl::Disable(gl::CULL_FACE);
+	    gl::Disable(gl::BLEND);
+	}
+	self.ensure_targets(width, height, &settings.bloom);
+
+	let hdr_texture = if settings.bloom.enabled {
+	    self.render_bloom(scene_texture, &settings.bloom)
+	}
+	else {
+	    scene_texture
+	};
+
+	let passes = settings.ldr_passes();
+	let bind_output = || unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, output_framebuffer as GLuint);
+	    gl::Viewport(0, 0, width, height);
+	};
+
+	if passes.is_empty() {
+	    bind_output();
+	    self.tone_map.render(hdr_texture, tone_map);
+	    return;
+	}
+	let ldr_tone_map = ToneMapSettings {
+	    output: if tone_map.output == OutputEncoding::Linear { OutputEncoding::Linear } else { OutputEncoding::ShaderSrgb },
+	    ..*tone_map
+	};
+	self.ldr[0].bind();
+	self.tone_map.render(hdr_texture, &ldr_tone_map);
+
+	let mut source = 0;
+	for (i, pass) in passes.iter().enumerate() {
+	    if i + 1 == passes.len() {
+		bind_output();
+	    }
+	    else {
+		self.ldr[1 - source].bind();
+	    }
+	    let source_texture = self.ldr[source].texture;
+	    match pass {
+		LdrPass::ColorGrading => {
+		    let p = &self.color_grading;
+		    p.use_program();
+		    p.set_texture("source", 0, gl::TEXTURE_2D, source_texture);
+		    p.set_texture("lut", 1, gl::TEXTURE_3D, self.lut.id);
+		    p.set_float("lutSize", self.lut.size as f32);
+		    p.set_float("strength", settings.color_grading.strength);
+		},
+		LdrPass::Vignette => {
+		    let p = &self.vignette;
+		    p.use_program();
+		    p.set_texture("source", 0, gl::TEXTURE_2D, source_texture);
+		    p.set_float("aspect", (width as f32)/(height as f32));
+		    p.set_float("intensity", settings.vignette.intensity);
+		    p.set_float("radius", settings.vignette.radius);
+		    p.set_float("softness", settings.vignette.softness.max(1.0e-4));
+		},
+		LdrPass::Fxaa => {
+		    let p = &self.fxaa;
+		    p.use_program();
+		    p.set_texture("source", 0, gl::TEXTURE_2D, source_texture);
+		    p.set_vec2("inverseSize", 1.0/(width as f32), 1.0/(height as f32));
+		}
+	    }
+	    self.draw();
+	    source = 1 - source;
+	}
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE1);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	    gl::BindTexture(gl::TEXTURE_3D, 0);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	    gl::UseProgram(0);
+	}
+    }
+}
+
+impl Drop for PostProcessStack {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteVertexArrays(1, &self.vao);
+	}
+    }
+}
@@ -0,0 +1,510 @@
+use std::collections::{HashMap,HashSet};
+use std::ffi::CString;
+use std::ptr;
+
+use gl::types::*;
+use linear_transform::{vector::*,matrix::*};
+
+use crate::texture::TextureBinding;
+
+/* VAOと描画に必要な情報. 各exampleのVertexArrayObjectContextと同じもの */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct VertexArrayObjectContext {
+    pub vao: GLuint,
+    pub draw_mode: GLenum,
+    pub count_of_draw_index: GLsizei,
+    /* instancedのときに描画する数. newで作ると1 */
+    pub instance_count: GLsizei,
+    /* インスタンスの属性を繋いだVAOならtrue. glDrawElementsInstancedでinstance_countだけ描画する */
+    pub instanced: bool
+}
+
+impl VertexArrayObjectContext {
+
+    /* 普通に1回描画するVAO */
+    pub fn new(vao:GLuint, draw_mode:GLenum, count_of_draw_index:GLsizei) -> Self {
+	VertexArrayObjectContext { vao, draw_mode, count_of_draw_index, instance_count: 1, instanced: false }
+    }
+
+    /* インスタンスの属性を繋いだVAOとして,instance_countだけ描画する */
+    pub fn with_instances(self, instance_count:GLsizei) -> Self {
+	VertexArrayObjectContext { instance_count, instanced: true, ..self }
+    }
+    /* 描画される三角形の数 */
+    pub fn triangle_count(&self) -> usize {
+	let count = self.count_of_draw_index.max(0) as usize;
+	match self.draw_mode {
+	    gl::TRIANGLES => count/3,
+	    gl::TRIANGLE_STRIP | gl::TRIANGLE_FAN => count.saturating_sub(2),
+	    _ => 0
+	}
+    }
+
+    pub fn is_instanced(&self) -> bool {
+	self.instanced
+    }
+
+    /* インスタンスを含めて描画される三角形の数 */
+    pub fn total_triangle_count(&self) -> usize {
+	if self.instanced { self.triangle_count()*(self.instance_count.max(0) as usize) } else { self.triangle_count() }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum UniformValue {
+    Int(GLint),
+    Float(GLfloat),
+    Vec2([GLfloat;2]),
+    Vec3([GLfloat;3]),
+    Vec4([GLfloat;4]),
+    /* 行優先. 転置してGLに渡す */
+    Mat3([GLfloat;9]),
+    Mat4([GLfloat;16])
+}
+
+/* シェーダプログラムとそれに設定するuniform,テクスチャの組 */
+#[derive(Debug,Clone,PartialEq)]
+pub struct Material {
+    pub shader: GLuint,
+    pub uniforms: Vec<(String, UniformValue)>,
+    pub textures: Vec<TextureBinding>
+}
+
+impl Material {
+    pub fn new(shader:GLuint) -> Self {
+	Material { shader, uniforms: Vec::new(), textures: Vec::new() }
+    }
+
+    pub fn with_texture(mut self, name:&str, binding:TextureBinding) -> Self {
+	self.set_texture(name, binding);
+	self
+    }
+
+    /* samplerのuniform nameにbinding.unitを設定する. 同じユニットのテクスチャは置き換える */
+    pub fn set_texture(&mut self, name:&str, binding:TextureBinding) {
+	self.set_uniform(name, UniformValue::Int(binding.unit as GLint));
+	match self.textures.iter_mut().find(|t| t.unit == binding.unit) {
+	    Some(entry) => *entry = binding,
+	    None => self.textures.push(binding)
+	}
+    }
+
+    pub fn with_uniform(mut self, name:&str, value:UniformValue) -> Self {
+	self.set_uniform(name, value);
+	self
+    }
+
+    pub fn set_uniform(&mut self, name:&str, value:UniformValue) {
+	match self.uniforms.iter_mut().find(|(n,_)| n == name) {
+	    Some(entry) => entry.1 = value,
+	    None => self.uniforms.push((name.to_string(), value))
+	}
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    Premultiplied,
+    Additive
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum CullMode {
+    None,
+    Back,
+    Front
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct RenderState {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub blend: BlendMode,
+    pub cull: CullMode,
+    pub polygon: PolygonMode,
+    /* マルチサンプルの描画先でアルファを被覆率として使う. 葉などの切り抜きの縁が滑らかになる */
+    pub alpha_to_coverage: bool
+}
+
+impl Default for RenderState {
+    fn default() -> Self {
+	RenderState {
+	    depth_test: true,
+	    depth_write: true,
+	    blend: BlendMode::Opaque,
+	    cull: CullMode::None,
+	    polygon: PolygonMode::Fill,
+	    alpha_to_coverage: false
+	}
+    }
+}
+
+impl RenderState {
+    pub fn is_transparent(&self) -> bool {
+	self.blend != BlendMode::Opaque
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct DrawItem {
+    pub mesh: VertexArrayObjectContext,
+    pub material: Material,
+    pub transform: Matrix4x4,
+    pub state: RenderState
+}
+
+#[derive(Debug,Copy,Clone,Default,PartialEq,Eq)]
+pub struct FrameStats {
+    pub draw_calls: usize,
+    pub state_changes: usize,
+    pub triangles: usize
+}
+
+/* 同じ深さとみなす幅. この幅の中ではprogram,VAOの順に並べて切り替えを減らす */
+pub const OPAQUE_DEPTH_BUCKET: f64 = 1.0;
+
+/* カメラから見たモデル原点の距離(視点座標系の-z) */
+pub fn view_depth(view:&Matrix4x4, transform:&Matrix4x4) -> f64 {
+    let origin = (*view)*(*transform)*Vector4(0.0, 0.0, 0.0, 1.0);
+    -origin.2
+}
+
+/*
+  描画順を決める.
+  不透明: 手前から奥へ(深さはOPAQUE_DEPTH_BUCKET単位), 同じ深さならprogram,VAOの順
+  半透明: 不透明の後に奥から手前へ, 同じ深さならprogram,VAOの順
+*/
+pub fn sort_draw_items(items:&[DrawItem], view:&Matrix4x4) -> Vec<usize> {
+    let depths:Vec<f64> = items.iter().map(|item| view_depth(view, &item.transform)).collect();
+    let mut order:Vec<usize> = (0..items.len()).collect();
+    order.sort_by(|&a, &b| {
+	let (ia, ib) = (&items[a], &items[b]);
+	let ta = ia.state.is_transparent();
+	let tb = ib.state.is_transparent();
+	let by_depth = if ta {
+	    depths[b].partial_cmp(&depths[a]).unwrap_or(std::cmp::Ordering::Equal)
+	}
+	else {
+	    let ba = (depths[a]/OPAQUE_DEPTH_BUCKET).floor();
+	    let bb = (depths[b]/OPAQUE_DEPTH_BUCKET).floor();
+	    ba.partial_cmp(&bb).unwrap_or(std::cmp::Ordering::Equal)
+	};
+	ta.cmp(&tb)
+	    .then(by_depth)
+	    .then(ia.material.shader.cmp(&ib.material.shader))
+	    .then(ia.mesh.vao.cmp(&ib.mesh.vao))
+    });
+    order
+}
+
+/* GLに最後に設定した状態. Noneは未設定(フレームの最初は必ず設定する) */
+#[derive(Debug,Default)]
+struct GlStateCache {
+    program: Option<GLuint>,
+    vao: Option<GLuint>,
+    depth_test: Option<bool>,
+    depth_write: Option<bool>,
+    blend: Option<BlendMode>,
+    cull: Option<CullMode>,
+    polygon: Option<PolygonMode>,
+    alpha_to_coverage: Option<bool>,
+    textures: HashMap<GLuint, TextureBinding>
+}
+
+/* programごとに最後に送ったuniformの値. 同じ値なら送らない */
+#[derive(Debug,Default)]
+pub(crate) struct UniformValueCache {
+    values: HashMap<GLuint, HashMap<String, UniformValue>>
+}
+
+impl UniformValueCache {
+    /* 送る必要があればtrueを返し,値を覚える */
+    pub(crate) fn update(&mut self, program:GLuint, name:&str, value:&UniformValue) -> bool {
+	let values = self.values.entry(program).or_default();
+	match values.get_mut(name) {
+	    Some(last) if last == value => false,
+	    Some(last) => {
+		*last = value.clone();
+		true
+	    },
+	    None => {
+		values.insert(name.to_string(), value.clone());
+		true
+	    }
+	}
+    }
+
+    pub(crate) fn forget(&mut self, program:GLuint) {
+	self.values.remove(&program);
+    }
+
+    pub(crate) fn clear(&mut self) {
+	self.values.clear();
+    }
+}
+
+pub struct Renderer {
+    queue: Vec<DrawItem>,
+    view: Matrix4x4,
+    projection: Matrix4x4,
+    cache: GlStateCache,
+    uniform_locations: HashMap<GLuint, HashMap<String, GLint>>,
+    uniform_values: UniformValueCache,
+    /* このフレームのframe_uniformsとview,projectionを送り済みのprogram */
+    frame_programs: HashSet<GLuint>,
+    frame_uniforms: Vec<(String, UniformValue)>,
+    stats: FrameStats
+}
+
+impl Renderer {
+
+    pub fn new() -> Self {
+	Renderer {
+	    queue: Vec::new(),
+	    view: Matrix4x4::identity(),
+	    projection: Matrix4x4::identity(),
+	    cache: GlStateCache::default(),
+	    uniform_locations: HashMap::new(),
+	    uniform_values: UniformValueCache::default(),
+	    frame_programs: HashSet::new(),
+	    frame_uniforms: Vec::new(),
+	    stats: FrameStats::default()
+	}
+    }
+
+    pub fn begin_frame(&mut self, view:Matrix4x4, projection:Matrix4x4) {
+	self.queue.clear();
+	self.view = view;
+	self.projection = projection;
+	self.cache = GlStateCache::default();
+	/* フレームの間に他からuniformを書き換えられていてもよいように,最初は必ず送る */
+	self.uniform_values.clear();
+	self.frame_programs.clear();
+	self.stats = FrameStats::default();
+    }
+
+    pub fn submit(&mut self, item:DrawItem) {
+	self.queue.push(item);
+    }
+
+    /* 全てのDrawItemに設定するuniform(光源など). 次に設定しなおすまで有効 */
+    pub fn set_frame_uniform(&mut self, name:&str, value:UniformValue) {
+	match self.frame_uniforms.iter_mut().find(|(n,_)| n == name) {
+	    Some(entry) => entry.1 = value,
+	    None => self.frame_uniforms.push((name.to_string(), value))
+	}
+	self.frame_programs.clear();
+    }
+
+    /*
+      programについて覚えているuniformの場所と値を捨てる.
+      GLは削除したprogramの名前を使いまわすので,programを削除して作りなおすときに呼ぶ
+    */
+    pub fn forget_program(&mut self, program:GLuint) {
+	self.uniform_locations.remove(&program);
+	self.uniform_values.forget(program);
+	self.frame_programs.remove(&program);
+	if self.cache.program == Some(program) {
+	    self.cache.program = None;
+	}
+    }
+
+    pub fn view(&self) -> Matrix4x4 {
+	self.view
+    }
+
+    pub fn projection(&self) -> Matrix4x4 {
+	self.projection
+    }
+
+    /* 直近のend_frameの統計 */
+    pub fn stats(&self) -> FrameStats {
+	self.stats
+    }
+
+    /* キューを並べ替えて描画する */
+    pub fn end_frame(&mut self) {
+	let queue = std::mem::take(&mut self.queue);
+	for index in sort_draw_items(&queue, &self.view) {
+	    self.draw(&queue[index]);
+	}
+	//exampleのように深度テストは切った状態に戻しておく. egui_glfw_glのPainterは深度テストを切らない.
+	self.apply_state(&RenderState { depth_test: false, ..RenderState::default() });
+	unsafe {
+	    gl::BindVertexArray(0);
+	}
+	self.queue = queue;
+	self.queue.clear();
+    }
+
+    /* 未キャッシュならGetUniformLocationで探す. 存在しなければ-1 */
+    pub fn uniform_location(&mut self, program:GLuint, name:&str) -> GLint {
+	if let Some(location) = self.uniform_locations.get(&program).and_then(|locations| locations.get(name)) {
+	    return *location;
+	}
+	let c_str = CString::new(name).unwrap_or_else(|_| panic!("failed to allocate string space"));
+	let location = unsafe { gl::GetUniformLocation(program, c_str.as_ptr()) };
+	self.uniform_locations.entry(program).or_default().insert(name.to_string(), location);
+	location
+    }
+
+    /* 前に送った値と同じなら何もしない. 送ったときはstate_changesに数える */
+    pub fn set_uniform(&mut self, program:GLuint, name:&str, value:&UniformValue) {
+	let location = self.uniform_location(program, name);
+	if location < 0 || !self.uniform_values.update(program, name, value) {
+	    return;
+	}
+	self.stats.state_changes += 1;
+	unsafe {
+	    match value {
+		UniformValue::Int(v) => gl::ProgramUniform1i(program, location, *v),
+		UniformValue::Float(v) => gl::ProgramUniform1f(program, location, *v),
+		UniformValue::Vec2(v) => gl::ProgramUniform2fv(program, location, 1, v.as_ptr()),
+		UniformValue::Vec3(v) => gl::ProgramUniform3fv(program, location, 1, v.as_ptr()),
+		UniformValue::Vec4(v) => gl::ProgramUniform4fv(program, location, 1, v.as_ptr()),
+		UniformValue::Mat3(v) => gl::ProgramUniformMatrix3fv(program, location, 1, gl::TRUE, v.as_ptr()),
+		UniformValue::Mat4(v) => gl::ProgramUniformMatrix4fv(program, location, 1, gl::TRUE, v.as_ptr())
+	    }
+	}
+    }
+
+    fn apply_state(&mut self, state:&RenderState) {
+	let mut changes = 0;
+	unsafe {
+	    if self.cache.depth_test != Some(state.depth_test) {
+		if state.depth_test { gl::Enable(gl::DEPTH_TEST) } else { gl::Disable(gl::DEPTH_TEST) }
+		self.cache.depth_test = Some(state.depth_test);
+		changes += 1;
+	    }
+	    if self.cache.depth_write != Some(state.depth_write) {
+		gl::DepthMask(if state.depth_write { gl::TRUE } else { gl::FALSE });
+		self.cache.depth_write = Some(state.depth_write);
+		changes += 1;
+	    }
+	    if self.cache.blend != Some(state.blend) {
+		match state.blend {
+		    BlendMode::Opaque => gl::Disable(gl::BLEND),
+		    BlendMode::Alpha => {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+		    },
+		    BlendMode::Premultiplied => {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+		    },
+		    BlendMode::Additive => {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::ONE, gl::ONE);
+		    }
+		}
+		self.cache.blend = Some(state.blend);
+		changes += 1;
+	    }
+	    if self.cache.cull != Some(state.cull) {
+		match state.cull {
+		    CullMode::None => gl::Disable(gl::CULL_FACE),
+		    CullMode::Back => {
+			gl::Enable(gl::CULL_FACE);
+			gl::CullFace(gl::BACK);
+		    },
+		    CullMode::Front => {
+			gl::Enable(gl::CULL_FACE);
+			gl::CullFace(gl::FRONT);
+		    }
+		}
+		self.cache.cull = Some(state.cull);
+		changes += 1;
+	    }
+	    if self.cache.polygon != Some(state.polygon) {
+		let mode = match state.polygon {
+		    PolygonMode::Fill => gl::FILL,
+		    PolygonMode::Line => gl::LINE,
+		    PolygonMode::Point => gl::POINT
+		};
+		gl::PolygonMode(gl::FRONT_AND_BACK, mode);
+		self.cache.polygon = Some(state.polygon);
+		changes += 1;
+	    }
+	    if self.cache.alpha_to_coverage != Some(state.alpha_to_coverage) {
+		if state.alpha_to_coverage { gl::Enable(gl::SAMPLE_ALPHA_TO_COVERAGE) } else { gl::Disable(gl::SAMPLE_ALPHA_TO_COVERAGE) }
+		self.cache.alpha_to_coverage = Some(state.alpha_to_coverage);
+		changes += 1;
+	    }
+	}
+	self.stats.state_changes += changes;
+    }
+
+    fn draw(&mut self, item:&DrawItem) {
+	self.apply_state(&item.state);
+	let program = item.material.shader;
+	unsafe {
+	    if self.cache.program != Some(program) {
+		gl::UseProgram(program);
+		self.cache.program = Some(program);
+		self.stats.state_changes += 1;
+	    }
+	}
+
+	/*
+	  view,projectionとframe_uniformsはprogramごとにフレームで一度だけ送る.
+	  マテリアルのuniformsはframe_uniformsと同じ名前を使わないこと
+	*/
+	if self.frame_programs.insert(program) {
+	    self.set_uniform(program, "view", &UniformValue::Mat4(self.view.serialize_f32()));
+	    self.set_uniform(program, "projection", &UniformValue::Mat4(self.projection.serialize_f32()));
+	    let frame_uniforms = std::mem::take(&mut self.frame_uniforms);
+	    for (name, value) in frame_uniforms.iter() {
+		self.set_uniform(program, name, value);
+	    }
+	    self.frame_uniforms = frame_uniforms;
+	}
+	/* model,mvp,instanced,skinnedはシェーダが持っているものだけ,変わったときに設定される */
+	let mvp = self.projection*self.view*item.transform;
+	self.set_uniform(program, "instanced", &UniformValue::Int(item.mesh.is_instanced() as GLint));
+	//前に描いたスキニングの設定が残らないように戻しておく. スキニングするマテリアルはuniformsで1にする
+	self.set_uniform(program, "skinned", &UniformValue::Int(0));
+	self.set_uniform(program, "model", &UniformValue::Mat4(item.transform.serialize_f32()));
+	self.set_uniform(program, "mvp", &UniformValue::Mat4(mvp.serialize_f32()));
+	for (name, value) in item.material.uniforms.iter() {
+	    self.set_uniform(program, name, value);
+	}
+	for binding in item.material.textures.iter() {
+	    if self.cache.textures.get(&binding.unit) != Some(binding) {
+		binding.bind();
+		self.cache.textures.insert(binding.unit, *binding);
+		self.stats.state_changes += 1;
+	    }
+	}
+
+	unsafe {
+	    if self.cache.vao != Some(item.mesh.vao) {
+		gl::BindVertexArray(item.mesh.vao);
+		self.cache.vao = Some(item.mesh.vao);
+		self.stats.state_changes += 1;
+	    }
+	    if item.mesh.is_instanced() {
+		gl::DrawElementsInstanced(item.mesh.draw_mode, item.mesh.count_of_draw_index, gl::UNSIGNED_INT, ptr::null(), item.mesh.instance_count);
+	    }
+	    else {
+		gl::DrawElements(item.mesh.draw_mode, item.mesh.count_of_draw_index, gl::UNSIGNED_INT, ptr::null());
+	    }
+	}
+	self.stats.draw_calls += 1;
+	self.stats.triangles += item.mesh.total_triangle_count();
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+	Renderer::new()
+    }
+}
@@ -0,0 +1,15 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source; //linear HDR scene color
+uniform sampler2D bloom; //half resolution blurred bright part
+uniform float intensity;
+
+void main(void){
+    vec3 color = texture(source, texCoord).rgb + intensity*texture(bloom, texCoord).rgb;
+    outputColor = vec4(color, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source; //linear HDR scene color
+uniform float threshold;
+uniform float knee; //width of the soft transition below threshold
+
+//keep only the bright part of the scene. the curve is quadratic around threshold so it does not pop
+void main(void){
+    vec3 color = texture(source, texCoord).rgb;
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - threshold + knee, 0.0, 2.0*knee);
+    soft = soft*soft/(4.0*knee + 1.0e-4);
+    float contribution = max(soft, brightness - threshold)/max(brightness, 1.0e-4);
+    outputColor = vec4(color*contribution, 1.0);
+}
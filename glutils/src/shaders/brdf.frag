@@ -0,0 +1,46 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord; //x: NdotV, y: roughness
+out vec4 outputColor;
+
+uniform int sampleCount;
+
+const float PI = 3.14159265359;
+
+#include sampling.glsl
+
+//Schlick-GGX with the k for image based lighting
+float geometrySmith(float NdotV, float NdotL, float roughness) {
+    float k = roughness*roughness/2.0;
+    float gv = NdotV/(NdotV*(1.0 - k) + k);
+    float gl = NdotL/(NdotL*(1.0 - k) + k);
+    return gv*gl;
+}
+
+//split sum approximation. specular = prefiltered*(F0*A + B)
+void main(void){
+    float NdotV = max(texCoord.x, 1.0e-4);
+    float roughness = texCoord.y;
+    vec3 v = vec3(sqrt(1.0 - NdotV*NdotV), 0.0, NdotV);
+    vec3 n = vec3(0.0, 0.0, 1.0);
+    uint count = uint(sampleCount);
+
+    float A = 0.0;
+    float B = 0.0;
+    for (uint i = 0u; i < count; i++) {
+        vec3 h = importanceSampleGGX(hammersley(i, count), n, roughness);
+        vec3 l = normalize(2.0*dot(v, h)*h - v);
+        float NdotL = max(l.z, 0.0);
+        float NdotH = max(h.z, 0.0);
+        float VdotH = max(dot(v, h), 0.0);
+        if (NdotL > 0.0) {
+            float visibility = geometrySmith(NdotV, NdotL, roughness)*VdotH/(NdotH*NdotV);
+            float Fc = pow(1.0 - VdotH, 5.0);
+            A += (1.0 - Fc)*visibility;
+            B += Fc*visibility;
+        }
+    }
+    outputColor = vec4(A/float(count), B/float(count), 0.0, 1.0);
+}
@@ -0,0 +1,20 @@
+#version 300 es
+
+precision highp float;
+precision highp sampler3D;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source; //tone mapped, sRGB encoded
+uniform sampler3D lut; //indexed by the sRGB color. see glutils::postprocess::ColorLut
+uniform float lutSize;
+uniform float strength;
+
+void main(void){
+    vec3 color = texture(source, texCoord).rgb;
+    //sample at texel centers so that 0 and 1 hit the first and last entries
+    vec3 coord = color*((lutSize - 1.0)/lutSize) + 0.5/lutSize;
+    vec3 graded = texture(lut, coord).rgb;
+    outputColor = vec4(mix(color, graded, strength), 1.0);
+}
@@ -0,0 +1,24 @@
+//G-buffer of glutils::deferred. expanded by glutils::material::expand_includes
+uniform sampler2D gAlbedo;
+uniform sampler2D gNormal; //view coordinates
+uniform sampler2D gMaterial; //metallic, roughness, ambient occlusion, unlit
+uniform sampler2D gDepth;
+uniform mat4 projection;
+uniform vec2 screenSize;
+
+vec2 screenCoord() {
+    return gl_FragCoord.xy/screenSize;
+}
+
+//view coordinates from the depth buffer. assumes a symmetric perspective projection
+vec3 viewPositionAt(vec2 uv) {
+    float ndcZ = 2.0*texture(gDepth, uv).r - 1.0;
+    float z = -projection[3][2]/(ndcZ + projection[2][2]);
+    vec2 ndc = 2.0*uv - 1.0;
+    return vec3(-z*ndc.x/projection[0][0], -z*ndc.y/projection[1][1], z);
+}
+
+//nothing was drawn where the depth is still cleared
+bool isBackground(vec2 uv) {
+    return texture(gDepth, uv).r == 1.0;
+}
@@ -0,0 +1,34 @@
+#version 300 es
+
+precision highp float;
+
+out vec4 outputColor;
+
+uniform mat4 view;
+uniform vec3 ambientColor; //used when hasIbl is 0
+
+#include deferred.glsl
+#include ssao.glsl
+#include ibl.glsl
+
+//ambient term and unlit surfaces. the light passes are added on top of this
+void main(void){
+    vec2 uv = screenCoord();
+    if (isBackground(uv)) {
+        discard;
+    }
+    vec3 albedo = texture(gAlbedo, uv).rgb;
+    vec4 material = texture(gMaterial, uv);
+    if (material.a > 0.5) {
+        outputColor = vec4(albedo, 1.0);
+        return;
+    }
+    vec3 n = normalize(texture(gNormal, uv).xyz);
+    vec3 v = normalize(-viewPositionAt(uv));
+    float metallic = material.r;
+    float roughness = material.g;
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+
+    vec3 ambient = hasIbl != 0 ? iblAmbient(view, n, v, albedo, F0, metallic, roughness) : ambientColor*albedo;
+    outputColor = vec4(ambient*material.b*screenSpaceOcclusion(), 1.0);
+}
@@ -0,0 +1,32 @@
+#version 300 es
+
+precision highp float;
+
+out vec4 outputColor;
+
+uniform int attachment; //see glutils::deferred::GBufferView
+
+#include deferred.glsl
+
+//shows one attachment of the G-buffer as it is
+void main(void){
+    vec2 uv = screenCoord();
+    vec3 color;
+    if (attachment == 1) {
+        color = texture(gAlbedo, uv).rgb;
+    }
+    else if (attachment == 2) {
+        color = isBackground(uv) ? vec3(0.0) : 0.5*texture(gNormal, uv).xyz + 0.5;
+    }
+    else if (attachment == 3) {
+        color = texture(gMaterial, uv).rgb;
+    }
+    else {
+        //linear depth between the near and far planes of the projection
+        float nearPlane = projection[3][2]/(projection[2][2] - 1.0);
+        float farPlane = projection[3][2]/(projection[2][2] + 1.0);
+        float d = isBackground(uv) ? farPlane : -viewPositionAt(uv).z;
+        color = vec3((d - nearPlane)/(farPlane - nearPlane));
+    }
+    outputColor = vec4(color, 1.0);
+}
@@ -0,0 +1,26 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 viewPosition;
+smooth in vec3 viewNormal;
+smooth in vec4 fragmentColor;
+layout(location = 0) out vec4 gAlbedo;
+layout(location = 1) out vec4 gNormal;
+layout(location = 2) out vec4 gMaterial;
+
+//see glutils::deferred::deferred_uniforms
+uniform vec3 baseColor;
+uniform float metallic;
+uniform float roughness;
+uniform float ambientOcclusion;
+uniform int unlit; //1: the albedo is output as it is
+
+#include surface.glsl
+
+void main(void){
+    vec4 surface = surfaceColor(fragmentColor);
+    gAlbedo = vec4(baseColor*surface.rgb, 1.0);
+    gNormal = vec4(normalize(viewNormal), 0.0);
+    gMaterial = vec4(metallic, roughness, ambientOcclusion, float(unlit));
+}
@@ -0,0 +1,36 @@
+#version 300 es
+
+precision highp float;
+
+out vec4 outputColor;
+
+uniform mat4 view;
+
+#include pbr.glsl
+#include deferred.glsl
+#include lights.glsl
+#include shadow.glsl
+
+//radiance of lights[0] reflected at each pixel. drawn additively once per light
+void main(void){
+    vec2 uv = screenCoord();
+    vec4 material = texture(gMaterial, uv);
+    if (isBackground(uv) || material.a > 0.5) {
+        discard;
+    }
+    vec3 viewPosition = viewPositionAt(uv);
+    vec3 n = normalize(texture(gNormal, uv).xyz);
+    vec3 v = normalize(-viewPosition);
+    vec3 albedo = texture(gAlbedo, uv).rgb;
+    float metallic = material.r;
+    float roughness = material.g;
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+
+    vec3 l;
+    vec3 radiance = lightIncidence(0, view, viewPosition, l);
+    float NdotL = max(dot(n, l), 0.0);
+    //view is a rigid transform, so the inverse of its rotation is the transpose
+    vec3 worldPosition = transpose(mat3(view))*(viewPosition - view[3].xyz);
+    vec3 color = pbrReflectance(n, v, l, albedo, F0, metallic, roughness)*radiance*shadowFactor(0, worldPosition, viewPosition, NdotL)*NdotL;
+    outputColor = vec4(color, 1.0);
+}
@@ -0,0 +1,7 @@
+#version 300 es
+
+precision highp float;
+
+//only the depth buffer is written
+void main(void){
+}
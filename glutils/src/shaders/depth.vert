@@ -0,0 +1,24 @@
+#version 300 es
+
+precision highp float;
+
+layout(location = 0) in vec3 position;
+layout(location = 4) in mat4 instanceModel; //per instance transform. see material.vert
+layout(location = 9) in uvec4 jointIndices; //skinning attributes. see material.vert
+layout(location = 10) in vec4 jointWeights;
+
+#define MAX_JOINTS 64
+
+uniform mat4 mvp; //light space. set by glutils::shadow::ShadowMap
+uniform int instanced;
+uniform int skinned;
+uniform mat4 jointMatrices[MAX_JOINTS];
+
+void main(void){
+    mat4 transform = instanced == 1 ? mvp*instanceModel : mvp;
+    if (skinned == 1) {
+        transform = transform*(jointWeights.x*jointMatrices[jointIndices.x] + jointWeights.y*jointMatrices[jointIndices.y]
+                               + jointWeights.z*jointMatrices[jointIndices.z] + jointWeights.w*jointMatrices[jointIndices.w]);
+    }
+    gl_Position = transform*vec4(position, 1.0);
+}
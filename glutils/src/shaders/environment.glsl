@@ -0,0 +1,16 @@
+//shared by the lit material shaders. expanded by glutils::material::expand_includes
+uniform samplerCube environmentMap; //ENVIRONMENT_TEXTURE_UNIT, set when glutils links the program
+uniform int hasEnvironmentMap; //0: no reflection
+uniform float environmentIntensity;
+
+//radiance from the environment in the mirror direction. roughness selects a blurrier mip level
+vec3 environmentReflection(mat4 view, vec3 viewPosition, vec3 n, float roughness) {
+    if (hasEnvironmentMap == 0) {
+        return vec3(0.0);
+    }
+    vec3 r = reflect(normalize(viewPosition), n);
+    //view is a rigid transform, so the inverse of its rotation is the transpose
+    vec3 worldR = transpose(mat3(view))*r;
+    float maxLod = log2(float(textureSize(environmentMap, 0).x));
+    return environmentIntensity*textureLod(environmentMap, worldR, roughness*maxLod).rgb;
+}
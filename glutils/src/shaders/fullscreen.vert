@@ -0,0 +1,13 @@
+#version 300 es
+
+precision highp float;
+
+smooth out vec2 texCoord;
+
+//one triangle covering the viewport. no vertex buffer is needed, draw 3 vertices with an empty vertex array
+//shared by the full screen passes (BRDF table, tone mapping, post processing)
+void main(void){
+    vec2 p = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+    texCoord = p;
+    gl_Position = vec4(2.0*p - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,45 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source; //sRGB encoded, after tone mapping
+uniform vec2 inverseSize; //1/size of source
+
+const float REDUCE_MIN = 1.0/128.0;
+const float REDUCE_MUL = 1.0/8.0;
+const float SPAN_MAX = 8.0;
+
+float luma(vec3 c) {
+    return dot(c, vec3(0.299, 0.587, 0.114));
+}
+
+//FXAA (Timothy Lottes), the simple variant. blur along the edge found from the luma of the neighbours
+void main(void){
+    vec3 rgbNW = texture(source, texCoord + vec2(-1.0, -1.0)*inverseSize).rgb;
+    vec3 rgbNE = texture(source, texCoord + vec2(1.0, -1.0)*inverseSize).rgb;
+    vec3 rgbSW = texture(source, texCoord + vec2(-1.0, 1.0)*inverseSize).rgb;
+    vec3 rgbSE = texture(source, texCoord + vec2(1.0, 1.0)*inverseSize).rgb;
+    vec3 rgbM = texture(source, texCoord).rgb;
+    float lumaNW = luma(rgbNW);
+    float lumaNE = luma(rgbNE);
+    float lumaSW = luma(rgbSW);
+    float lumaSE = luma(rgbSE);
+    float lumaM = luma(rgbM);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 dir = vec2(-((lumaNW + lumaNE) - (lumaSW + lumaSE)), (lumaNW + lumaSW) - (lumaNE + lumaSE));
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE)*0.25*REDUCE_MUL, REDUCE_MIN);
+    float rcpDirMin = 1.0/(min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = clamp(dir*rcpDirMin, vec2(-SPAN_MAX), vec2(SPAN_MAX))*inverseSize;
+
+    vec3 rgbA = 0.5*(texture(source, texCoord + dir*(1.0/3.0 - 0.5)).rgb +
+                     texture(source, texCoord + dir*(2.0/3.0 - 0.5)).rgb);
+    vec3 rgbB = 0.5*rgbA + 0.25*(texture(source, texCoord - 0.5*dir).rgb +
+                                 texture(source, texCoord + 0.5*dir).rgb);
+    float lumaB = luma(rgbB);
+    outputColor = vec4((lumaB < lumaMin || lumaB > lumaMax) ? rgbA : rgbB, 1.0);
+}
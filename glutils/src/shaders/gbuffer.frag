@@ -0,0 +1,12 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 viewPosition;
+smooth in vec3 viewNormal;
+out vec4 outputColor;
+
+//view space normal and depth for the SSAO pass. w is 0 where nothing was drawn
+void main(void){
+    outputColor = vec4(normalize(viewNormal), viewPosition.z);
+}
@@ -0,0 +1,23 @@
+//shared by the lit material shaders. expanded by glutils::material::expand_includes
+uniform samplerCube irradianceMap;
+uniform samplerCube prefilteredMap;
+uniform sampler2D brdfLut;
+uniform int hasIbl; //0: use the ambient term and environment.glsl instead
+uniform float iblIntensity;
+uniform float prefilteredMaxLod;
+
+//diffuse and specular ambient from the precomputed maps. see glutils::ibl
+vec3 iblAmbient(mat4 view, vec3 n, vec3 v, vec3 albedo, vec3 F0, float metallic, float roughness) {
+    //view is a rigid transform, so the inverse of its rotation is the transpose
+    mat3 toWorld = transpose(mat3(view));
+    vec3 worldN = toWorld*n;
+    vec3 worldR = toWorld*reflect(-v, n);
+    float NdotV = max(dot(n, v), 1.0e-4);
+
+    vec3 F = F0 + (max(vec3(1.0 - roughness), F0) - F0)*pow(1.0 - NdotV, 5.0);
+    vec3 kd = (vec3(1.0) - F)*(1.0 - metallic);
+    vec3 diffuse = texture(irradianceMap, worldN).rgb*albedo;
+    vec3 prefiltered = textureLod(prefilteredMap, worldR, roughness*prefilteredMaxLod).rgb;
+    vec2 brdf = texture(brdfLut, vec2(NdotV, roughness)).rg;
+    return iblIntensity*(kd*diffuse + prefiltered*(F*brdf.x + brdf.y));
+}
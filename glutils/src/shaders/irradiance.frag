@@ -0,0 +1,32 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 direction; //world coordinates. see skybox.vert
+out vec4 outputColor;
+
+uniform samplerCube environmentMap;
+
+const float PI = 3.14159265359;
+const float SAMPLE_DELTA = 0.025;
+
+//cosine weighted average of the radiance over the hemisphere around the normal
+void main(void){
+    vec3 n = normalize(direction);
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 right = normalize(cross(up, n));
+    up = cross(n, right);
+
+    vec3 irradiance = vec3(0.0);
+    float count = 0.0;
+    for (float phi = 0.0; phi < 2.0*PI; phi += SAMPLE_DELTA) {
+        for (float theta = 0.0; theta < 0.5*PI; theta += SAMPLE_DELTA) {
+            vec3 t = vec3(sin(theta)*cos(phi), sin(theta)*sin(phi), cos(theta));
+            vec3 s = t.x*right + t.y*up + t.z*n;
+            //sin(theta) is the area of the sample on the sphere
+            irradiance += texture(environmentMap, s).rgb*cos(theta)*sin(theta);
+            count += 1.0;
+        }
+    }
+    outputColor = vec4(PI*irradiance/count, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source; //one level larger than the target
+uniform vec2 halfPixel; //0.5/size of source
+
+//dual filter downsample. the center and four diagonal taps between texels
+void main(void){
+    vec3 sum = 4.0*texture(source, texCoord).rgb;
+    sum += texture(source, texCoord - halfPixel).rgb;
+    sum += texture(source, texCoord + halfPixel).rgb;
+    sum += texture(source, texCoord + vec2(halfPixel.x, -halfPixel.y)).rgb;
+    sum += texture(source, texCoord - vec2(halfPixel.x, -halfPixel.y)).rgb;
+    outputColor = vec4(sum/8.0, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source; //one level smaller than the target
+uniform vec2 halfPixel; //0.5/size of source
+
+//dual filter upsample. a ring of eight taps, the diagonal ones weighted twice
+void main(void){
+    vec3 sum = texture(source, texCoord + vec2(-2.0*halfPixel.x, 0.0)).rgb;
+    sum += 2.0*texture(source, texCoord + vec2(-halfPixel.x, halfPixel.y)).rgb;
+    sum += texture(source, texCoord + vec2(0.0, 2.0*halfPixel.y)).rgb;
+    sum += 2.0*texture(source, texCoord + vec2(halfPixel.x, halfPixel.y)).rgb;
+    sum += texture(source, texCoord + vec2(2.0*halfPixel.x, 0.0)).rgb;
+    sum += 2.0*texture(source, texCoord + vec2(halfPixel.x, -halfPixel.y)).rgb;
+    sum += texture(source, texCoord + vec2(0.0, -2.0*halfPixel.y)).rgb;
+    sum += 2.0*texture(source, texCoord + vec2(-halfPixel.x, -halfPixel.y)).rgb;
+    outputColor = vec4(sum/12.0, 1.0);
+}
@@ -0,0 +1,34 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 viewPosition;
+smooth in vec3 viewNormal;
+smooth in vec3 worldPosition;
+smooth in vec4 fragmentColor;
+out vec4 outputColor;
+
+uniform mat4 view;
+
+uniform vec3 ambient;
+uniform vec3 diffuse;
+
+#include surface.glsl
+#include lights.glsl
+#include shadow.glsl
+#include ssao.glsl
+
+void main(void){
+    vec4 surface = surfaceColor(fragmentColor);
+    vec3 n = normalize(viewNormal);
+    vec3 Kd = diffuse*surface.rgb;
+
+    vec3 color = ambient*Kd*screenSpaceOcclusion();
+    for (int i = 0; i < lightCount; i++) {
+        vec3 l;
+        vec3 radiance = lightIncidence(i, view, viewPosition, l);
+        float NdotL = max(dot(n, l), 0.0);
+        color += radiance*shadowFactor(i, worldPosition, viewPosition, NdotL)*Kd*NdotL;
+    }
+    outputColor = vec4(color, surface.a);
+}
@@ -0,0 +1,11 @@
+#version 300 es
+
+precision highp float;
+
+layout(location = 0) in vec3 position;
+
+uniform mat4 mvp; //unit cube scaled to the range of the light. set by glutils::deferred::DeferredRenderer
+
+void main(void){
+    gl_Position = mvp*vec4(position, 1.0);
+}
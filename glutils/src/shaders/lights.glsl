@@ -0,0 +1,38 @@
+//shared by the lit material shaders. expanded by glutils::material::expand_includes
+#define MAX_LIGHTS 8
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+
+struct Light {
+    int lightType;
+    vec3 position; //world coordinates
+    vec3 direction; //world coordinates. the direction light travels
+    vec3 radiance; //color*intensity
+    vec3 attenuation; //constant, linear, quadratic
+    float cosInner;
+    float cosOuter;
+};
+
+uniform Light lights[MAX_LIGHTS];
+uniform int lightCount;
+
+//returns the radiance reaching viewPosition from lights[i] and the direction to the light in view coordinates
+vec3 lightIncidence(int i, mat4 view, vec3 viewPosition, out vec3 l) {
+    Light light = lights[i];
+    if (light.lightType == LIGHT_DIRECTIONAL) {
+        l = normalize(-(mat3(view)*light.direction));
+        return light.radiance;
+    }
+
+    vec3 lightPosition = (view*vec4(light.position, 1.0)).xyz;
+    vec3 toLight = lightPosition - viewPosition;
+    float d = length(toLight);
+    l = toLight/max(d, 1.0e-6);
+    float attenuation = 1.0/max(light.attenuation.x + light.attenuation.y*d + light.attenuation.z*d*d, 1.0e-6);
+    if (light.lightType == LIGHT_SPOT) {
+        vec3 axis = normalize(mat3(view)*light.direction);
+        attenuation *= smoothstep(light.cosOuter, light.cosInner, dot(-l, axis));
+    }
+    return light.radiance*attenuation;
+}
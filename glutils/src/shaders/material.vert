@@ -0,0 +1,55 @@
+#version 300 es
+
+precision highp float;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec4 vertexColor;
+layout(location = 3) in vec2 texCoord; //(0,0) if the mesh has no texture coordinates
+//per instance attributes. set by glutils::instancing::InstanceBuffer
+layout(location = 4) in mat4 instanceModel; //uses locations 4 to 7
+layout(location = 8) in vec4 instanceColor;
+//per vertex skinning attributes. set by glutils::skinning::SkinBuffer
+layout(location = 9) in uvec4 jointIndices;
+layout(location = 10) in vec4 jointWeights;
+
+#define MAX_JOINTS 64 //same as glutils::skinning::MAX_JOINTS
+
+uniform mat4 model; //model transform matrix. to move, to scale, to rotation object
+uniform mat4 view; //view transform matrix. to move camera position
+uniform mat4 projection; //projection transform matrix. to project to screen
+uniform int instanced; //1 if drawn with glDrawElementsInstanced. set by glutils::renderer::Renderer
+uniform int skinned; //1 if the material has joint matrices. see glutils::skinning::skinning_uniforms
+uniform mat4 jointMatrices[MAX_JOINTS]; //joint world matrix times inverse bind matrix
+
+//lighting is calculated in view coordinates
+smooth out vec3 viewPosition;
+smooth out vec3 viewNormal;
+smooth out vec3 worldPosition; //for the shadow map lookup
+smooth out vec4 fragmentColor;
+smooth out vec2 fragmentTexCoord;
+
+//linear blend skinning. the weights sum to 1
+mat4 skinMatrix(void){
+    return jointWeights.x*jointMatrices[jointIndices.x] + jointWeights.y*jointMatrices[jointIndices.y]
+        + jointWeights.z*jointMatrices[jointIndices.z] + jointWeights.w*jointMatrices[jointIndices.w];
+}
+
+void main(void){
+    mat4 instanceTransform = instanced == 1 ? model*instanceModel : model;
+    //the normal matrix below is computed from the skinned matrix, so normals follow the joints too
+    if (skinned == 1) {
+        instanceTransform = instanceTransform*skinMatrix();
+    }
+    mat4 modelview = view*instanceTransform;
+    mat3 normalMatrix = transpose(inverse(mat3(modelview)));
+    vec4 worldCoords = instanceTransform*vec4(position, 1.0);
+    vec4 eyeCoords = view*worldCoords;
+
+    viewPosition = eyeCoords.xyz;
+    worldPosition = worldCoords.xyz;
+    viewNormal = normalize(normalMatrix*normal);
+    fragmentColor = instanced == 1 ? vertexColor*instanceColor : vertexColor;
+    fragmentTexCoord = texCoord;
+    gl_Position = projection*eyeCoords;
+}
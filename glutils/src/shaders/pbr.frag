@@ -0,0 +1,53 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 viewPosition;
+smooth in vec3 viewNormal;
+smooth in vec3 worldPosition;
+smooth in vec4 fragmentColor;
+out vec4 outputColor;
+
+uniform mat4 view;
+
+uniform vec3 baseColor;
+uniform float metallic;
+uniform float roughness;
+uniform float ambientOcclusion;
+
+#include pbr.glsl
+#include surface.glsl
+#include lights.glsl
+#include shadow.glsl
+#include ssao.glsl
+#include environment.glsl
+#include ibl.glsl
+
+void main(void){
+    vec4 surface = surfaceColor(fragmentColor);
+    vec3 n = normalize(viewNormal);
+    vec3 v = normalize(-viewPosition);
+    vec3 albedo = baseColor*surface.rgb;
+    float NdotV = max(dot(n, v), 1.0e-4);
+    vec3 F0 = mix(vec3(0.04), albedo, metallic);
+
+    vec3 Lo = vec3(0.0);
+    for (int i = 0; i < lightCount; i++) {
+        vec3 l;
+        vec3 radiance = lightIncidence(i, view, viewPosition, l);
+        float NdotL = max(dot(n, l), 0.0);
+        Lo += pbrReflectance(n, v, l, albedo, F0, metallic, roughness)*radiance*shadowFactor(i, worldPosition, viewPosition, NdotL)*NdotL;
+    }
+
+    vec3 ambient;
+    if (hasIbl != 0) {
+        ambient = iblAmbient(view, n, v, albedo, F0, metallic, roughness)*ambientOcclusion;
+    }
+    else {
+        //environment reflection. fresnel with roughness so that rough surfaces do not get too bright edges
+        vec3 Fr = F0 + (max(vec3(1.0 - roughness), F0) - F0)*pow(1.0 - NdotV, 5.0);
+        vec3 reflection = Fr*environmentReflection(view, viewPosition, n, roughness);
+        ambient = (vec3(0.03)*albedo + reflection)*ambientOcclusion;
+    }
+    outputColor = vec4(ambient*screenSpaceOcclusion() + Lo, surface.a);
+}
@@ -0,0 +1,37 @@
+//shared by pbr.frag and the deferred lighting pass. expanded by glutils::material::expand_includes
+const float PI = 3.14159265359;
+
+//GGX/Trowbridge-Reitz normal distribution
+float distributionGGX(float NdotH, float a) {
+    float a2 = a*a;
+    float d = NdotH*NdotH*(a2 - 1.0) + 1.0;
+    return a2/(PI*d*d);
+}
+
+//Schlick-GGX geometry term with Smith's method
+float geometrySmith(float NdotV, float NdotL, float r) {
+    float k = (r + 1.0)*(r + 1.0)/8.0;
+    float gv = NdotV/(NdotV*(1.0 - k) + k);
+    float gl = NdotL/(NdotL*(1.0 - k) + k);
+    return gv*gl;
+}
+
+vec3 fresnelSchlick(float cosTheta, vec3 F0) {
+    return F0 + (1.0 - F0)*pow(clamp(1.0 - cosTheta, 0.0, 1.0), 5.0);
+}
+
+//Cook-Torrance BRDF for the light direction l. multiply by radiance and NdotL
+vec3 pbrReflectance(vec3 n, vec3 v, vec3 l, vec3 albedo, vec3 F0, float metallic, float roughness) {
+    vec3 h = normalize(l + v);
+    float NdotV = max(dot(n, v), 1.0e-4);
+    float NdotL = max(dot(n, l), 0.0);
+    float NdotH = max(dot(n, h), 0.0);
+
+    vec3 F = fresnelSchlick(max(dot(h, v), 0.0), F0);
+    float D = distributionGGX(NdotH, roughness*roughness);
+    float G = geometrySmith(NdotV, NdotL, roughness);
+
+    vec3 specular = D*G*F/(4.0*NdotV*max(NdotL, 1.0e-4));
+    vec3 kd = (vec3(1.0) - F)*(1.0 - metallic);
+    return kd*albedo/PI + specular;
+}
@@ -0,0 +1,52 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 viewPosition;
+smooth in vec3 viewNormal;
+smooth in vec3 worldPosition;
+smooth in vec4 fragmentColor;
+out vec4 outputColor;
+
+uniform mat4 view;
+
+uniform vec3 ambient;
+uniform vec3 diffuse;
+uniform vec3 specular;
+uniform float shininess;
+uniform int blinn; //0: Phong, 1: Blinn-Phong
+
+#include surface.glsl
+#include lights.glsl
+#include shadow.glsl
+#include ssao.glsl
+#include environment.glsl
+
+void main(void){
+    vec4 surface = surfaceColor(fragmentColor);
+    vec3 n = normalize(viewNormal);
+    vec3 v = normalize(-viewPosition); //camera is at the origin in view coordinates
+    vec3 Kd = diffuse*surface.rgb;
+
+    vec3 color = ambient*Kd*screenSpaceOcclusion();
+    for (int i = 0; i < lightCount; i++) {
+        vec3 l;
+        vec3 radiance = lightIncidence(i, view, viewPosition, l);
+        float lambert = max(dot(n, l), 0.0);
+        float spec = 0.0;
+        if (lambert > 0.0) {
+            if (blinn != 0) {
+                vec3 h = normalize(l + v);
+                spec = pow(max(dot(n, h), 0.0), shininess);
+            }
+            else {
+                vec3 r = reflect(-l, n);
+                spec = pow(max(dot(r, v), 0.0), shininess);
+            }
+        }
+        color += radiance*shadowFactor(i, worldPosition, viewPosition, lambert)*(Kd*lambert + specular*spec);
+    }
+    //mirror reflection of the environment weighted by the specular color
+    color += specular*environmentReflection(view, viewPosition, n, 0.0);
+    outputColor = vec4(color, surface.a);
+}
@@ -0,0 +1,42 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 direction; //world coordinates. see skybox.vert
+out vec4 outputColor;
+
+uniform samplerCube environmentMap;
+uniform float environmentSize; //width of the level 0 face of environmentMap
+uniform float roughness;
+uniform int sampleCount;
+
+const float PI = 3.14159265359;
+
+#include sampling.glsl
+
+//convolve the environment with the GGX lobe, assuming the view direction equals the normal
+void main(void){
+    vec3 n = normalize(direction);
+    vec3 v = n;
+    uint count = uint(sampleCount);
+    //solid angle of one texel of the environment
+    float texelSolidAngle = 4.0*PI/(6.0*environmentSize*environmentSize);
+
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < count; i++) {
+        vec3 h = importanceSampleGGX(hammersley(i, count), n, roughness);
+        vec3 l = normalize(2.0*dot(v, h)*h - v);
+        float NdotL = dot(n, l);
+        if (NdotL > 0.0) {
+            //read a blurrier level where the samples are sparse, otherwise bright texels show up as dots
+            float NdotH = max(dot(n, h), 0.0);
+            float pdf = distributionGGX(NdotH, roughness*roughness)*0.25 + 1.0e-4;
+            float sampleSolidAngle = 1.0/(float(count)*pdf + 1.0e-4);
+            float lod = roughness == 0.0 ? 0.0 : 0.5*log2(sampleSolidAngle/texelSolidAngle);
+            color += textureLod(environmentMap, l, lod).rgb*NdotL;
+            weight += NdotL;
+        }
+    }
+    outputColor = vec4(color/max(weight, 1.0e-4), 1.0);
+}
@@ -0,0 +1,37 @@
+//shared by the IBL precomputation shaders. expanded by glutils::material::expand_includes
+//PI has to be defined before this file
+
+//Van der Corput radical inverse. reverses the bits of i
+float radicalInverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits)*2.3283064365386963e-10;
+}
+
+//i-th point of n points spread evenly over the unit square
+vec2 hammersley(uint i, uint n) {
+    return vec2(float(i)/float(n), radicalInverse(i));
+}
+
+//GGX/Trowbridge-Reitz normal distribution. a = roughness^2
+float distributionGGX(float NdotH, float a) {
+    float a2 = a*a;
+    float d = NdotH*NdotH*(a2 - 1.0) + 1.0;
+    return a2/(PI*d*d);
+}
+
+//half vector around n, distributed as the GGX lobe of roughness
+vec3 importanceSampleGGX(vec2 xi, vec3 n, float roughness) {
+    float a = roughness*roughness;
+    float phi = 2.0*PI*xi.x;
+    float cosTheta = sqrt((1.0 - xi.y)/(1.0 + (a*a - 1.0)*xi.y));
+    float sinTheta = sqrt(1.0 - cosTheta*cosTheta);
+    vec3 h = vec3(cos(phi)*sinTheta, sin(phi)*sinTheta, cosTheta);
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+    return normalize(tangent*h.x + bitangent*h.y + n*h.z);
+}
@@ -0,0 +1,44 @@
+//shared by the lit material shaders. expanded by glutils::material::expand_includes
+#define MAX_CASCADES 4
+
+uniform highp sampler2DArrayShadow shadowMap; //SHADOW_TEXTURE_UNIT, set when glutils links the program
+uniform int shadowLight; //index of lights[] casting shadow. -1 for none, the default after linking
+uniform int cascadeCount;
+uniform mat4 lightSpaceMatrices[MAX_CASCADES];
+uniform float cascadeSplits[MAX_CASCADES]; //far end of each cascade. distance in view coordinates
+uniform float shadowBias;
+uniform float shadowSlopeBias;
+uniform int pcfRadius;
+
+//1.0 for lit, 0.0 for in shadow. NdotL is used for the slope scaled bias
+float shadowFactor(int i, vec3 worldPosition, vec3 viewPosition, float NdotL) {
+    if (i != shadowLight || cascadeCount == 0) {
+        return 1.0;
+    }
+
+    int cascade = cascadeCount - 1;
+    for (int c = 0; c < cascadeCount; c++) {
+        if (-viewPosition.z < cascadeSplits[c]) {
+            cascade = c;
+            break;
+        }
+    }
+
+    vec4 p = lightSpaceMatrices[cascade]*vec4(worldPosition, 1.0);
+    vec3 coords = p.xyz/p.w*0.5 + 0.5;
+    if (coords.z > 1.0 || any(lessThan(coords.xy, vec2(0.0))) || any(greaterThan(coords.xy, vec2(1.0)))) {
+        return 1.0;
+    }
+
+    float bias = max(shadowSlopeBias*(1.0 - NdotL), shadowBias);
+    vec2 texel = 1.0/vec2(textureSize(shadowMap, 0).xy);
+    float lit = 0.0;
+    for (int y = -pcfRadius; y <= pcfRadius; y++) {
+        for (int x = -pcfRadius; x <= pcfRadius; x++) {
+            vec2 uv = coords.xy + vec2(float(x), float(y))*texel;
+            lit += texture(shadowMap, vec4(uv, float(cascade), coords.z - bias));
+        }
+    }
+    float width = float(2*pcfRadius + 1);
+    return lit/(width*width);
+}
@@ -0,0 +1,13 @@
+#version 300 es
+
+precision highp float;
+
+uniform samplerCube environmentMap;
+uniform float intensity;
+
+smooth in vec3 direction;
+out vec4 outputColor;
+
+void main(void){
+    outputColor = vec4(intensity*texture(environmentMap, direction).rgb, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 300 es
+
+precision highp float;
+
+layout(location = 0) in vec3 position;
+
+uniform mat4 view; //without translation. see glutils::cubemap::rotation_only
+uniform mat4 projection;
+
+smooth out vec3 direction; //world coordinates
+
+void main(void){
+    direction = position;
+    vec4 p = projection*view*vec4(position, 1.0);
+    gl_Position = p.xyww; //depth is always 1.0, the far plane
+}
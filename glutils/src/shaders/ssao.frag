@@ -0,0 +1,58 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+const int MAX_KERNEL_SIZE = 64; //same as glutils::ssao::MAX_SSAO_KERNEL_SIZE
+
+uniform sampler2D gbuffer; //xyz: view space normal, w: view space z
+uniform sampler2D noise; //random rotation around the normal, repeated over the screen
+uniform vec3 kernel[MAX_KERNEL_SIZE]; //hemisphere around +z
+uniform int kernelSize;
+uniform mat4 projection;
+uniform vec2 noiseScale; //screen size / noise size
+uniform float radius;
+uniform float bias;
+uniform float strength;
+
+//view space position from the stored z. assumes a symmetric perspective projection
+vec3 viewPositionAt(vec2 uv, float z) {
+    vec2 ndc = 2.0*uv - 1.0;
+    return vec3(-z*ndc.x/projection[0][0], -z*ndc.y/projection[1][1], z);
+}
+
+//z of the surface at uv. the background is treated as infinitely far
+float surfaceDepth(vec2 uv) {
+    float z = texture(gbuffer, uv).w;
+    return z == 0.0 ? -1.0e4 : z;
+}
+
+void main(void){
+    vec4 g = texture(gbuffer, texCoord);
+    if (g.w == 0.0) {
+        outputColor = vec4(1.0);
+        return;
+    }
+    vec3 p = viewPositionAt(texCoord, g.w);
+    vec3 n = normalize(g.xyz);
+
+    //tangent frame rotated by the noise vector (Gram-Schmidt)
+    vec3 r = vec3(texture(noise, texCoord*noiseScale).xy, 0.0);
+    vec3 t = normalize(r - n*dot(r, n));
+    mat3 tbn = mat3(t, cross(n, t), n);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < kernelSize; i++) {
+        vec3 s = p + tbn*kernel[i]*radius;
+        vec4 clip = projection*vec4(s, 1.0);
+        vec2 uv = clip.xy/clip.w*0.5 + 0.5;
+        float z = surfaceDepth(uv);
+        //samples far behind the surface do not count, so that edges of distant objects do not darken
+        float range = smoothstep(0.0, 1.0, radius/abs(p.z - z));
+        occlusion += (z >= s.z + bias ? 1.0 : 0.0)*range;
+    }
+    float ao = 1.0 - occlusion/float(max(kernelSize, 1));
+    outputColor = vec4(vec3(pow(ao, strength)), 1.0);
+}
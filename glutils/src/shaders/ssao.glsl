@@ -0,0 +1,11 @@
+//shared by the lit material shaders. expanded by glutils::material::expand_includes
+uniform sampler2D ssaoMap; //same size as the render target. SSAO_TEXTURE_UNIT
+uniform int hasSsao; //0: no screen space occlusion
+
+//multiplied to the ambient term
+float screenSpaceOcclusion() {
+    if (hasSsao == 0) {
+        return 1.0;
+    }
+    return texture(ssaoMap, gl_FragCoord.xy/vec2(textureSize(ssaoMap, 0))).r;
+}
@@ -0,0 +1,20 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source;
+uniform vec2 texelSize;
+
+//4x4 box blur. the same size as the noise texture, so that its pattern disappears
+void main(void){
+    float sum = 0.0;
+    for (int y = -2; y < 2; y++) {
+        for (int x = -2; x < 2; x++) {
+            sum += texture(source, texCoord + vec2(float(x), float(y))*texelSize).r;
+        }
+    }
+    outputColor = vec4(vec3(sum/16.0), 1.0);
+}
@@ -0,0 +1,13 @@
+//shared by the material shaders. expanded by glutils::material::expand_includes
+uniform sampler2D baseColorMap;
+uniform int hasBaseColorMap; //0: vertex color only
+
+smooth in vec2 fragmentTexCoord;
+
+//vertex color multiplied by the base color map if bound. sRGB maps are already linear when sampled
+vec4 surfaceColor(vec4 vertexColor) {
+    if (hasBaseColorMap == 0) {
+        return vertexColor;
+    }
+    return vertexColor*texture(baseColorMap, fragmentTexCoord);
+}
@@ -0,0 +1,43 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D hdrColor; //linear RGBA16F scene color
+uniform float exposure;
+uniform int toneMapper; //0: clamp, 1: Reinhard, 2: ACES filmic. see glutils::hdr::ToneMapper
+uniform int encodeSrgb; //1: write sRGB encoded values, for framebuffers without sRGB conversion
+
+//fitted curve of the ACES reference rendering transform (Krzysztof Narkowicz)
+vec3 acesFilmic(vec3 x) {
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x*(a*x + b))/(x*(c*x + d) + e), 0.0, 1.0);
+}
+
+vec3 srgbFromLinear(vec3 c) {
+    bvec3 cutoff = lessThan(c, vec3(0.0031308));
+    vec3 higher = 1.055*pow(c, vec3(1.0/2.4)) - 0.055;
+    vec3 lower = 12.92*c;
+    return mix(higher, lower, vec3(cutoff));
+}
+
+void main(void){
+    vec3 color = exposure*texture(hdrColor, texCoord).rgb;
+    if (toneMapper == 1) {
+        color = color/(vec3(1.0) + color);
+    }
+    else if (toneMapper == 2) {
+        color = acesFilmic(color);
+    }
+    color = clamp(color, 0.0, 1.0);
+    if (encodeSrgb != 0) {
+        color = srgbFromLinear(color);
+    }
+    outputColor = vec4(color, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec3 viewPosition;
+smooth in vec3 viewNormal;
+smooth in vec4 fragmentColor;
+out vec4 outputColor;
+
+uniform vec4 baseColor;
+
+#include surface.glsl
+
+void main(void){
+    outputColor = baseColor*surfaceColor(fragmentColor);
+}
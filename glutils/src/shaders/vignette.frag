@@ -0,0 +1,19 @@
+#version 300 es
+
+precision highp float;
+
+smooth in vec2 texCoord;
+out vec4 outputColor;
+
+uniform sampler2D source;
+uniform float aspect; //width/height, so that the vignette stays round
+uniform float intensity;
+uniform float radius; //distance from the center where darkening starts. 0.5 is the top and bottom edge
+uniform float softness;
+
+void main(void){
+    vec3 color = texture(source, texCoord).rgb;
+    vec2 d = (texCoord - 0.5)*vec2(aspect, 1.0);
+    float shade = smoothstep(radius + softness, radius, length(d));
+    outputColor = vec4(color*mix(1.0, shade, intensity), 1.0);
+}
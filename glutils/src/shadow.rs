@@ -0,0 +1,265 @@
+use std::ptr;
+
+use gl::types::*;
+use linear_transform::{vector::*,matrix::*};
+use graphic_math::shadow::{cascade_splits,frustum_corners,directional_light_matrix,spot_light_matrix};
+use graphic_math::camera::Camera;
+
+use crate::renderer::{Renderer,DrawItem,Material,RenderState,UniformValue};
+use crate::light::{Light,LightKind};
+use crate::skinning::with_skinning_of;
+use crate::{compile_shader,link_program};
+
+static DEPTH_VERTEX_SHADER_CODE: &str = include_str!("shaders/depth.vert");
+
+static DEPTH_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/depth.frag");
+
+/* shaders/shadow.glslのMAX_CASCADESと合わせる */
+pub const MAX_CASCADES: usize = 4;
+
+/* シャドウマップを割り当てるテクスチャユニット. マテリアルのテクスチャとぶつからないように後ろの方を使う */
+pub const SHADOW_TEXTURE_UNIT: GLuint = 8;
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct ShadowSettings {
+    /* シャドウマップの一辺のテクセル数 */
+    pub resolution: i32,
+    /* 平行光源のカスケード数. 1..=MAX_CASCADES. スポットライトは常に1 */
+    pub cascade_count: usize,
+    /* 0で等間隔,1で対数分割 */
+    pub split_lambda: f64,
+    /* 深度の比較に使うバイアス. 光に正対する面ではbias,斜めの面ほどslope_biasに近づく */
+    pub bias: f32,
+    pub slope_bias: f32,
+    /* PCFの半径. (2*pcf_radius+1)^2回サンプルする. 0でハードウェアの2x2のみ */
+    pub pcf_radius: i32,
+    /* カメラの視錐台より光源側にある物体も影を落とすようにnearを伸ばす距離 */
+    pub caster_distance: f64
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+	ShadowSettings {
+	    resolution: 2048,
+	    cascade_count: 3,
+	    split_lambda: 0.5,
+	    bias: 0.0005,
+	    slope_bias: 0.005,
+	    pcf_radius: 1,
+	    caster_distance: 10.0
+	}
+    }
+}
+
+/* カスケードのライト空間行列と,それが受け持つ視点座標系での距離の上限 */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Cascade {
+    pub matrix: Matrix4x4,
+    pub far: f64
+}
+
+/* カスケードを決めるためのカメラの視錐台. fovyはdegree */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct ViewFrustum {
+    pub eye: Vector3,
+    pub center: Vector3,
+    pub up: Vector3,
+    pub fovy: f64,
+    pub aspect: f64,
+    pub near: f64,
+    pub far: f64
+}
+
+impl ViewFrustum {
+    /* 平行投影のカメラもfovyの透視投影とみなすので,ortho_heightが大きいとカスケードの外に出る部分がある */
+    pub fn from_camera(camera:&Camera) -> Self {
+	ViewFrustum {
+	    eye: camera.eye,
+	    center: camera.center,
+	    up: camera.up,
+	    fovy: camera.fovy,
+	    aspect: camera.aspect,
+	    near: camera.near,
+	    far: camera.far
+	}
+    }
+}
+
+fn to_vector3(v:[f32;3]) -> Vector3 {
+    Vector3(v[0] as f64, v[1] as f64, v[2] as f64)
+}
+
+impl ShadowSettings {
+    /* lightの影を描くカスケード. 点光源は対応しないので空 */
+    pub fn cascades(&self, light:&Light, frustum:&ViewFrustum) -> Vec<Cascade> {
+	match light.kind {
+	    LightKind::Directional { direction } => {
+		let count = self.cascade_count.clamp(1, MAX_CASCADES);
+		let splits = cascade_splits(frustum.near, frustum.far, count, self.split_lambda);
+		splits.windows(2).map(|s| {
+		    let corners = frustum_corners(frustum.eye, frustum.center, frustum.up,
+						  frustum.fovy, frustum.aspect, s[0], s[1]);
+		    Cascade {
+			matrix: directional_light_matrix(to_vector3(direction), &corners,
+							 self.resolution.max(1) as u32, self.caster_distance),
+			far: s[1]
+		    }
+		}).collect()
+	    },
+	    LightKind::Spot { position, direction, outer_cone_degree, .. } => {
+		let position = to_vector3(position);
+		let range = (position - frustum.eye).length() + frustum.far;
+		vec![Cascade {
+		    matrix: spot_light_matrix(position, to_vector3(direction), outer_cone_degree as f64, 0.05, range),
+		    far: frustum.far
+		}]
+	    },
+	    LightKind::Point { .. } => Vec::new()
+	}
+    }
+
+    /*
+      shaders/shadow.glslのuniform. light_indexはLightSet::shadow_casterの添字.
+      Noneか,cascadesが空なら影を付けない
+    */
+    pub fn uniforms(&self, light_index:Option<usize>, cascades:&[Cascade]) -> Vec<(String, UniformValue)> {
+	let cascades = match light_index {
+	    Some(_) => &cascades[..cascades.len().min(MAX_CASCADES)],
+	    None => &cascades[..0]
+	};
+	let mut uniforms = vec![
+	    ("shadowMap".to_string(), UniformValue::Int(SHADOW_TEXTURE_UNIT as GLint)),
+	    ("shadowLight".to_string(), UniformValue::Int(light_index.map(|i| i as GLint).unwrap_or(-1))),
+	    ("cascadeCount".to_string(), UniformValue::Int(cascades.len() as GLint)),
+	    ("shadowBias".to_string(), UniformValue::Float(self.bias)),
+	    ("shadowSlopeBias".to_string(), UniformValue::Float(self.slope_bias)),
+	    ("pcfRadius".to_string(), UniformValue::Int(self.pcf_radius.max(0)))
+	];
+	for (i, cascade) in cascades.iter().enumerate() {
+	    uniforms.push((format!("lightSpaceMatrices[{}]", i), UniformValue::Mat4(cascade.matrix.serialize_f32())));
+	    uniforms.push((format!("cascadeSplits[{}]", i), UniformValue::Float(cascade.far as GLfloat)));
+	}
+	uniforms
+    }
+}
+
+/*
+  カスケードごとに1層を使う深度テクスチャ配列と,それに深度だけを描くパス.
+  GLコンテキスト作成後に作る.
+*/
+pub struct ShadowMap {
+    framebuffer: GLuint,
+    depth_texture: GLuint,
+    resolution: i32,
+    program: GLuint,
+    renderer: Renderer
+}
+
+impl ShadowMap {
+
+    pub fn create(resolution:i32) -> Self {
+	let vertex_shader = compile_shader(DEPTH_VERTEX_SHADER_CODE, gl::VERTEX_SHADER);
+	let fragment_shader = compile_shader(DEPTH_FRAGMENT_SHADER_CODE, gl::FRAGMENT_SHADER);
+	let program = link_program(vertex_shader, fragment_shader);
+	let mut framebuffer = 0;
+	let mut depth_texture = 0;
+	unsafe {
+	    gl::DeleteShader(fragment_shader);
+	    gl::DeleteShader(vertex_shader);
+
+	    gl::GenTextures(1, &mut depth_texture);
+	    gl::BindTexture(gl::TEXTURE_2D_ARRAY, depth_texture);
+	    gl::TexImage3D(gl::TEXTURE_2D_ARRAY, 0, gl::DEPTH_COMPONENT32F as GLint,
+			   resolution, resolution, MAX_CASCADES as GLsizei, 0,
+			   gl::DEPTH_COMPONENT, gl::FLOAT, ptr::null());
+	    //LINEARにしておくとsampler2DArrayShadowの比較結果がハードウェアで2x2補間される
+	    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as GLint);
+	    gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, depth_texture, 0, 0);
+	    //色は書かない
+	    let none = gl::NONE;
+	    gl::DrawBuffers(1, &none);
+	    gl::ReadBuffer(gl::NONE);
+	    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+		println!("shadow map framebuffer is incomplete");
+	    }
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	}
+	ShadowMap { framebuffer, depth_texture, resolution, program, renderer: Renderer::new() }
+    }
+
+    pub fn resolution(&self) -> i32 {
+	self.resolution
+    }
+
+    pub fn depth_texture(&self) -> GLuint {
+	self.depth_texture
+    }
+
+    /*
+      castersの深度をカスケードごとの層に描く.
+      マテリアルは深度用のものに差し替える. 終わったら元のframebufferとviewportに戻す.
+    */
+    pub fn render(&mut self, cascades:&[Cascade], casters:&[DrawItem]) {
+	let mut previous_framebuffer:GLint = 0;
+	let mut previous_viewport:[GLint;4] = [0;4];
+	unsafe {
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	    gl::GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr());
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+	    gl::Viewport(0, 0, self.resolution, self.resolution);
+	}
+
+	for (layer, cascade) in cascades.iter().take(MAX_CASCADES).enumerate() {
+	    unsafe {
+		gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, self.depth_texture, 0, layer as GLint);
+		//Rendererが最後に深度書き込みを切っていることがあるので,クリアの前に戻す
+		gl::DepthMask(gl::TRUE);
+		gl::Clear(gl::DEPTH_BUFFER_BIT);
+	    }
+	    /* ライト空間行列をviewとして渡すとRendererがmvpを計算する */
+	    self.renderer.begin_frame(cascade.matrix, Matrix4x4::identity());
+	    for caster in casters {
+		self.renderer.submit(DrawItem {
+		    mesh: caster.mesh,
+		    material: with_skinning_of(Material::new(self.program), &caster.material),
+		    transform: caster.transform,
+		    state: RenderState { cull: caster.state.cull, ..RenderState::default() }
+		});
+	    }
+	    self.renderer.end_frame();
+	}
+
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+	    gl::Viewport(previous_viewport[0], previous_viewport[1], previous_viewport[2], previous_viewport[3]);
+	}
+    }
+
+    /* SHADOW_TEXTURE_UNITにシャドウマップを結びつける */
+    pub fn bind(&self) {
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE0 + SHADOW_TEXTURE_UNIT);
+	    gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.depth_texture);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	}
+    }
+}
+
+impl Drop for ShadowMap {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteFramebuffers(1, &self.framebuffer);
+	    gl::DeleteTextures(1, &self.depth_texture);
+	    gl::DeleteProgram(self.program);
+	}
+    }
+}
@@ -0,0 +1,105 @@
+use std::mem;
+use std::ptr;
+
+use gl::types::*;
+use linear_transform::matrix::*;
+use graphic_math::skeleton::{SkinWeights,MAX_JOINT_INFLUENCES};
+
+use crate::renderer::{Material,UniformValue};
+
+/*
+  頂点シェーダでの線形ブレンドスキニング. 頂点ごとの関節番号と重みをVBOに入れてメッシュのVAOに繋ぎ,
+  graphic_math::skeleton::Skeleton::skinning_matricesの行列をマテリアルのuniformで渡す.
+  material.vert,depth.vertがjointIndices(location 9),jointWeights(location 10)で受け取る
+*/
+
+pub const JOINT_INDEX_LOCATION: GLuint = 9;
+pub const JOINT_WEIGHT_LOCATION: GLuint = 10;
+/* shaders/material.vert,depth.vertのMAX_JOINTSと合わせる. 頂点シェーダのuniformの数で決まる */
+pub const MAX_JOINTS: usize = 64;
+
+/*
+  マテリアルに足すuniform. MAX_JOINTSを超える関節は渡さない.
+  Rendererは毎回skinnedを0に戻すので,スキニングしないDrawItemには影響しない
+*/
+pub fn skinning_uniforms(matrices:&[Matrix4x4]) -> Vec<(String, UniformValue)> {
+    let mut uniforms = vec![("skinned".to_string(), UniformValue::Int(1))];
+    for (i, matrix) in matrices.iter().take(MAX_JOINTS).enumerate() {
+	uniforms.push((format!("jointMatrices[{}]", i), UniformValue::Mat4(matrix.serialize_f32())));
+    }
+    uniforms
+}
+
+fn is_skinning_uniform(name:&str) -> bool {
+    name == "skinned" || name.starts_with("jointMatrices[")
+}
+
+/* 毎フレーム姿勢が変わるので,前の行列を置き換えて設定する */
+pub fn set_skinning(material:&mut Material, matrices:&[Matrix4x4]) {
+    material.uniforms.retain(|(name, _)| !is_skinning_uniform(name));
+    material.uniforms.append(&mut skinning_uniforms(matrices));
+}
+
+/* 影やSSAOのように別のシェーダで描き直すとき,元のマテリアルのスキニングの設定を引き継ぐ */
+pub fn with_skinning_of(mut material:Material, source:&Material) -> Material {
+    material.uniforms.extend(source.uniforms.iter().filter(|(name, _)| is_skinning_uniform(name)).cloned());
+    material
+}
+
+/* VBOに詰める並び. 関節番号と重みを別のバッファにする */
+pub fn pack_skin_weights(skins:&[SkinWeights]) -> (Vec<GLuint>, Vec<GLfloat>) {
+    let mut joints = Vec::with_capacity(skins.len()*MAX_JOINT_INFLUENCES);
+    let mut weights = Vec::with_capacity(skins.len()*MAX_JOINT_INFLUENCES);
+    for skin in skins {
+	joints.extend_from_slice(&skin.joints);
+	weights.extend_from_slice(&skin.weights);
+    }
+    (joints, weights)
+}
+
+/* 頂点ごとの関節番号と重みのVBO. 頂点の並びはメッシュの位置のバッファと同じ */
+pub struct SkinBuffer {
+    vbos: [GLuint;2]
+}
+
+impl SkinBuffer {
+
+    pub fn create(skins:&[SkinWeights]) -> Self {
+	let (joints, weights) = pack_skin_weights(skins);
+	let mut vbos:[GLuint;2] = [0,0];
+	unsafe {
+	    gl::GenBuffers(2, &mut vbos[0]);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, vbos[0]);
+	    gl::BufferData(gl::ARRAY_BUFFER, (joints.len()*mem::size_of::<GLuint>()) as GLsizeiptr,
+			   joints.as_ptr() as *const _, gl::STATIC_DRAW);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, vbos[1]);
+	    gl::BufferData(gl::ARRAY_BUFFER, (weights.len()*mem::size_of::<GLfloat>()) as GLsizeiptr,
+			   weights.as_ptr() as *const _, gl::STATIC_DRAW);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+	}
+	SkinBuffer { vbos }
+    }
+
+    /* vaoのlocation 9,10にこのVBOを繋ぐ. 関節番号は整数のまま渡す */
+    pub fn attach(&self, vao:GLuint) {
+	unsafe {
+	    gl::BindVertexArray(vao);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbos[0]);
+	    gl::EnableVertexAttribArray(JOINT_INDEX_LOCATION);
+	    gl::VertexAttribIPointer(JOINT_INDEX_LOCATION, MAX_JOINT_INFLUENCES as GLint, gl::UNSIGNED_INT, 0, ptr::null());
+	    gl::BindBuffer(gl::ARRAY_BUFFER, self.vbos[1]);
+	    gl::EnableVertexAttribArray(JOINT_WEIGHT_LOCATION);
+	    gl::VertexAttribPointer(JOINT_WEIGHT_LOCATION, MAX_JOINT_INFLUENCES as GLint, gl::FLOAT, gl::FALSE, 0, ptr::null());
+	    gl::BindVertexArray(0);
+	    gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+	}
+    }
+}
+
+impl Drop for SkinBuffer {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteBuffers(2, &self.vbos[0]);
+	}
+    }
+}
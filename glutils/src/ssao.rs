@@ -0,0 +1,326 @@
+use gl::types::*;
+use linear_transform::matrix::*;
+
+use crate::renderer::{Renderer,DrawItem,Material,RenderState,UniformValue};
+use crate::postprocess::{RenderTexture,FullscreenPass};
+use crate::skinning::with_skinning_of;
+use crate::build_program;
+
+/*
+  画面空間の環境遮蔽(SSAO). 先に視点座標系の法線と深度をGバッファに描き,
+  各画素の法線方向の半球にカーネルの点を置いて,Gバッファの面より奥にある点の割合から遮蔽を求める.
+  カーネルは小さな乱数テクスチャで画素ごとに回し,その模様はぼかして消す.
+  結果はSSAO_TEXTURE_UNITに結びつけ,マテリアルのシェーダ(shaders/ssao.glsl)が環境光の項に掛ける.
+*/
+
+static MATERIAL_VERTEX_SHADER_CODE: &str = include_str!("shaders/material.vert");
+
+static GBUFFER_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/gbuffer.frag");
+
+static SSAO_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/ssao.frag");
+
+static SSAO_BLUR_FRAGMENT_SHADER_CODE: &str = include_str!("shaders/ssao_blur.frag");
+
+/* shaders/ssao.fragのMAX_KERNEL_SIZEと合わせる */
+pub const MAX_SSAO_KERNEL_SIZE: usize = 64;
+
+/* 回転の乱数テクスチャの一辺. shaders/ssao_blur.fragのぼかしの幅と合わせる */
+pub const SSAO_NOISE_SIZE: i32 = 4;
+
+/* 遮蔽のテクスチャを割り当てるテクスチャユニット. IBLの後ろ */
+pub const SSAO_TEXTURE_UNIT: GLuint = 13;
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct SsaoSettings {
+    pub enabled: bool,
+    /* 半球の半径. 視点座標系の長さ */
+    pub radius: f32,
+    /* 自分の面で遮蔽されないように,比較する深度をずらす量 */
+    pub bias: f32,
+    /* 遮蔽の強さ. 1-遮蔽の割合をこの値で累乗する */
+    pub strength: f32,
+    /* カーネルの点の数. 1..=MAX_SSAO_KERNEL_SIZE */
+    pub kernel_size: usize,
+    pub blur: bool
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+	SsaoSettings {
+	    enabled: true,
+	    radius: 0.5,
+	    bias: 0.025,
+	    strength: 1.5,
+	    kernel_size: 32,
+	    blur: true
+	}
+    }
+}
+
+/* 決まった種から作る乱数. カーネルと回転が毎回同じになるようにする */
+struct XorShift(u32);
+
+impl XorShift {
+    /* 0..1 */
+    fn next(&mut self) -> f32 {
+	let mut x = self.0;
+	x ^= x << 13;
+	x ^= x >> 17;
+	x ^= x << 5;
+	self.0 = x;
+	(x as f32)/(u32::MAX as f32)
+    }
+}
+
+/*
+  +z側の単位半球の中の点. 後の点ほど遠くに置き,面の近くを多めに調べる.
+  sizeはMAX_SSAO_KERNEL_SIZEまで
+*/
+pub fn ssao_kernel(size:usize) -> Vec<[f32;3]> {
+    let size = size.clamp(1, MAX_SSAO_KERNEL_SIZE);
+    let mut random = XorShift(0x9e37_79b9);
+    (0..size).map(|i| {
+	let v = [2.0*random.next() - 1.0, 2.0*random.next() - 1.0, random.next()];
+	let length = (v[0]*v[0] + v[1]*v[1] + v[2]*v[2]).sqrt().max(1.0e-4);
+	let t = (i as f32)/(size as f32);
+	let scale = random.next()*(0.1 + 0.9*t*t);
+	[v[0]/length*scale, v[1]/length*scale, v[2]/length*scale]
+    }).collect()
+}
+
+/* カーネルを法線の周りに回すための,xy平面の方向. SSAO_NOISE_SIZEの2乗個 */
+pub fn ssao_noise() -> Vec<[f32;2]> {
+    let mut random = XorShift(0x85eb_ca6b);
+    (0..SSAO_NOISE_SIZE*SSAO_NOISE_SIZE).map(|_| [2.0*random.next() - 1.0, 2.0*random.next() - 1.0]).collect()
+}
+
+/* shaders/ssao.glslのuniform. enabledがfalseなら遮蔽しない */
+pub fn ssao_uniforms(enabled:bool) -> Vec<(String, UniformValue)> {
+    vec![
+	("ssaoMap".to_string(), UniformValue::Int(SSAO_TEXTURE_UNIT as GLint)),
+	("hasSsao".to_string(), UniformValue::Int(if enabled { 1 } else { 0 }))
+    ]
+}
+
+/* 視点座標系の法線(xyz)と深度(w)と,深度テスト用の深度バッファ */
+struct GBuffer {
+    framebuffer: GLuint,
+    texture: GLuint,
+    depth_renderbuffer: GLuint,
+    width: i32,
+    height: i32
+}
+
+impl GBuffer {
+    fn create(width:i32, height:i32) -> Self {
+	let mut framebuffer = 0;
+	let mut texture = 0;
+	let mut depth_renderbuffer = 0;
+	unsafe {
+	    gl::GenTextures(1, &mut texture);
+	    gl::BindTexture(gl::TEXTURE_2D, texture);
+	    gl::TexStorage2D(gl::TEXTURE_2D, 1, gl::RGBA32F, width, height);
+	    //深度を補間すると物体の縁で間違った位置になるのでNEAREST
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+
+	    gl::GenRenderbuffers(1, &mut depth_renderbuffer);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, depth_renderbuffer);
+	    gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT24, width, height);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, texture, 0);
+	    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, depth_renderbuffer);
+	    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+		println!("G-buffer framebuffer is incomplete");
+	    }
+	}
+	GBuffer { framebuffer, texture, depth_renderbuffer, width, height }
+    }
+
+    fn bind(&self) {
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+	    gl::Viewport(0, 0, self.width, self.height);
+	}
+    }
+}
+
+impl Drop for GBuffer {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteFramebuffers(1, &self.framebuffer);
+	    gl::DeleteRenderbuffers(1, &self.depth_renderbuffer);
+	    gl::DeleteTextures(1, &self.texture);
+	}
+    }
+}
+
+/* Gバッファ,遮蔽,ぼかしのパスと描画先. GLコンテキスト作成後に作る */
+pub struct SsaoPass {
+    vao: GLuint,
+    gbuffer_program: GLuint,
+    renderer: Renderer,
+    ssao: FullscreenPass,
+    blur: FullscreenPass,
+    noise_texture: GLuint,
+    /* 描画先の大きさ. 変わったら作りなおす */
+    size: (i32, i32),
+    gbuffer: Option<GBuffer>,
+    /* 0が遮蔽,1がぼかした結果 */
+    occlusion: Vec<RenderTexture>,
+    /* 直前のrenderでぼかしたか */
+    blurred: bool
+}
+
+impl SsaoPass {
+
+    pub fn create() -> Self {
+	let mut vao = 0;
+	let mut noise_texture = 0;
+	let noise:Vec<[f32;3]> = ssao_noise().iter().map(|n| [n[0], n[1], 0.0]).collect();
+	unsafe {
+	    gl::GenVertexArrays(1, &mut vao);
+	    gl::GenTextures(1, &mut noise_texture);
+	    gl::BindTexture(gl::TEXTURE_2D, noise_texture);
+	    gl::TexStorage2D(gl::TEXTURE_2D, 1, gl::RGB16F, SSAO_NOISE_SIZE, SSAO_NOISE_SIZE);
+	    gl::TexSubImage2D(gl::TEXTURE_2D, 0, 0, 0, SSAO_NOISE_SIZE, SSAO_NOISE_SIZE, gl::RGB, gl::FLOAT,
+			      noise.as_ptr() as *const _);
+	    //画面全体に繰り返して貼る
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as GLint);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	}
+	SsaoPass {
+	    vao,
+	    gbuffer_program: build_program(MATERIAL_VERTEX_SHADER_CODE, GBUFFER_FRAGMENT_SHADER_CODE),
+	    renderer: Renderer::new(),
+	    ssao: FullscreenPass::create(SSAO_FRAGMENT_SHADER_CODE),
+	    blur: FullscreenPass::create(SSAO_BLUR_FRAGMENT_SHADER_CODE),
+	    noise_texture,
+	    size: (0, 0),
+	    gbuffer: None,
+	    occlusion: Vec::new(),
+	    blurred: false
+	}
+    }
+
+    fn draw(&self) {
+	unsafe {
+	    gl::BindVertexArray(self.vao);
+	    gl::DrawArrays(gl::TRIANGLES, 0, 3);
+	    gl::BindVertexArray(0);
+	}
+    }
+
+    /*
+      itemsのうち三角形のものでGバッファを描き,遮蔽を求める. projectionは左右上下対称な透視投影であること.
+      大きさはシーンの描画先と同じにする. 終わったら元のframebufferとviewportに戻す
+    */
+    pub fn render(&mut self, view:&Matrix4x4, projection:&Matrix4x4, items:&[DrawItem],
+		  width:i32, height:i32, settings:&SsaoSettings) {
+	let (width, height) = (width.max(1), height.max(1));
+	let mut previous_framebuffer:GLint = 0;
+	let mut previous_viewport:[GLint;4] = [0;4];
+	unsafe {
+	    gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+	    gl::GetIntegerv(gl::VIEWPORT, previous_viewport.as_mut_ptr());
+	}
+	if self.size != (width, height) {
+	    self.size = (width, height);
+	    self.gbuffer = Some(GBuffer::create(width, height));
+	    self.occlusion = (0..2).map(|_| RenderTexture::create(width, height, gl::R8)).collect();
+	}
+
+	if let Some(gbuffer) = &self.gbuffer {
+	    gbuffer.bind();
+	    unsafe {
+		gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+		gl::DepthMask(gl::TRUE);
+		gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT);
+	    }
+	    self.renderer.begin_frame(*view, *projection);
+	    for item in items.iter().filter(|item| item.mesh.draw_mode == gl::TRIANGLES) {
+		self.renderer.submit(DrawItem {
+		    mesh: item.mesh,
+		    material: with_skinning_of(Material::new(self.gbuffer_program), &item.material),
+		    transform: item.transform,
+		    state: RenderState { cull: item.state.cull, ..RenderState::default() }
+		});
+	    }
+	    self.renderer.end_frame();
+	}
+
+	unsafe {
+	    gl::Disable(gl::DEPTH_TEST);
+	    gl::Disable(gl::CULL_FACE);
+	    gl::Disable(gl::BLEND);
+	}
+	let kernel = ssao_kernel(settings.kernel_size);
+	self.occlusion[0].bind();
+	self.ssao.use_program();
+	if let Some(gbuffer) = &self.gbuffer {
+	    self.ssao.set_texture("gbuffer", 0, gl::TEXTURE_2D, gbuffer.texture);
+	}
+	self.ssao.set_texture("noise", 1, gl::TEXTURE_2D, self.noise_texture);
+	self.ssao.set_vec3_array("kernel[0]", &kernel);
+	self.ssao.set_int("kernelSize", kernel.len() as i32);
+	self.ssao.set_matrix4("projection", &projection.serialize_f32());
+	self.ssao.set_vec2("noiseScale", (width as f32)/(SSAO_NOISE_SIZE as f32), (height as f32)/(SSAO_NOISE_SIZE as f32));
+	self.ssao.set_float("radius", settings.radius);
+	self.ssao.set_float("bias", settings.bias);
+	self.ssao.set_float("strength", settings.strength);
+	self.draw();
+
+	self.blurred = settings.blur;
+	if settings.blur {
+	    self.occlusion[1].bind();
+	    self.blur.use_program();
+	    self.blur.set_texture("source", 0, gl::TEXTURE_2D, self.occlusion[0].texture);
+	    self.blur.set_vec2("texelSize", 1.0/(width as f32), 1.0/(height as f32));
+	    self.draw();
+	}
+
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE1);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	    gl::UseProgram(0);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as GLuint);
+	    gl::Viewport(previous_viewport[0], previous_viewport[1], previous_viewport[2], previous_viewport[3]);
+	}
+    }
+
+    /* 直前のrenderの結果. 1が遮蔽なし. まだ描いていなければ0 */
+    pub fn occlusion_texture(&self) -> GLuint {
+	let index = if self.blurred { 1 } else { 0 };
+	self.occlusion.get(index).map(|target| target.texture).unwrap_or(0)
+    }
+
+    /* SSAO_TEXTURE_UNITに遮蔽のテクスチャを結びつける */
+    pub fn bind(&self) {
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE0 + SSAO_TEXTURE_UNIT);
+	    gl::BindTexture(gl::TEXTURE_2D, self.occlusion_texture());
+	    gl::ActiveTexture(gl::TEXTURE0);
+	}
+    }
+}
+
+impl Drop for SsaoPass {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteTextures(1, &self.noise_texture);
+	    gl::DeleteProgram(self.gbuffer_program);
+	    gl::DeleteVertexArrays(1, &self.vao);
+	}
+    }
+}
@@ -0,0 +1,345 @@
+use std::fmt;
+use std::path::Path;
+
+use gl::types::*;
+
+/* glクレートのバインディングに無いので定義しておく. GL 4.6かGL_EXT_texture_filter_anisotropic */
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+#[derive(Debug)]
+pub enum TextureError {
+    /* 画像ファイルが読めない,または形式が分からない */
+    Decode(String),
+    /* width*height*4とpixelsの長さが合わない */
+    InvalidSize { width: u32, height: u32, length: usize }
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	match self {
+	    TextureError::Decode(message) => write!(f, "failed to decode image. {}", message),
+	    TextureError::InvalidSize { width, height, length } =>
+		write!(f, "{}x{} RGBA image needs {} bytes, but got {}", width, height, width*height*4, length)
+	}
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+/* 色のデータをsRGBとして扱うかリニアとして扱うか. 法線マップなどの色でないデータはLinear */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear
+}
+
+/* GPUに送る前の画像. pixelsは上の行から並べたRGBA8 */
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+impl TextureData {
+    pub fn from_rgba(width:u32, height:u32, pixels:Vec<u8>) -> Result<Self, TextureError> {
+	if width == 0 || height == 0 || pixels.len() != (width as usize)*(height as usize)*4 {
+	    return Err(TextureError::InvalidSize { width, height, length: pixels.len() });
+	}
+	Ok(TextureData { width, height, pixels })
+    }
+
+    /* PNGかJPEG */
+    pub fn load<P: AsRef<Path>>(path:P) -> Result<Self, TextureError> {
+	let image = image::open(path.as_ref())
+	    .map_err(|e| TextureError::Decode(format!("{}: {}", path.as_ref().display(), e)))?;
+	let rgba = image.to_rgba8();
+	TextureData::from_rgba(rgba.width(), rgba.height(), rgba.into_raw())
+    }
+
+    pub fn from_memory(bytes:&[u8]) -> Result<Self, TextureError> {
+	let image = image::load_from_memory(bytes).map_err(|e| TextureError::Decode(e.to_string()))?;
+	let rgba = image.to_rgba8();
+	TextureData::from_rgba(rgba.width(), rgba.height(), rgba.into_raw())
+    }
+
+    /* cells*cells升の市松模様. 画像が無いときの代わりに使う */
+    pub fn checkerboard(size:u32, cells:u32, a:[u8;4], b:[u8;4]) -> Self {
+	let cell = (size/cells.max(1)).max(1);
+	let mut pixels = Vec::with_capacity((size*size*4) as usize);
+	for y in 0..size {
+	    for x in 0..size {
+		let color = if ((x/cell + y/cell) & 1) == 0 { a } else { b };
+		pixels.extend_from_slice(&color);
+	    }
+	}
+	TextureData { width: size, height: size, pixels }
+    }
+}
+
+/* 1x1まで半分にしていったときのmipmapの段数 */
+pub fn mip_level_count(width:u32, height:u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Filter {
+    Nearest,
+    Linear
+}
+
+impl Wrap {
+    pub fn gl_enum(&self) -> GLenum {
+	match self {
+	    Wrap::Repeat => gl::REPEAT,
+	    Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+	    Wrap::ClampToEdge => gl::CLAMP_TO_EDGE
+	}
+    }
+}
+
+/* サンプリングの設定. mipmapがNoneならmipmapを使わない */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct SamplerDescription {
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    pub wrap_r: Wrap,
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mipmap: Option<Filter>,
+    /* 1.0で異方性フィルタなし. 実装の上限で切り詰める */
+    pub max_anisotropy: f32
+}
+
+impl Default for SamplerDescription {
+    fn default() -> Self {
+	SamplerDescription {
+	    wrap_s: Wrap::Repeat,
+	    wrap_t: Wrap::Repeat,
+	    wrap_r: Wrap::Repeat,
+	    min_filter: Filter::Linear,
+	    mag_filter: Filter::Linear,
+	    mipmap: Some(Filter::Linear),
+	    max_anisotropy: 1.0
+	}
+    }
+}
+
+impl SamplerDescription {
+    /* 縮小時のGL_TEXTURE_MIN_FILTER */
+    pub fn min_filter_enum(&self) -> GLenum {
+	match (self.min_filter, self.mipmap) {
+	    (Filter::Nearest, None) => gl::NEAREST,
+	    (Filter::Linear, None) => gl::LINEAR,
+	    (Filter::Nearest, Some(Filter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+	    (Filter::Linear, Some(Filter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+	    (Filter::Nearest, Some(Filter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+	    (Filter::Linear, Some(Filter::Linear)) => gl::LINEAR_MIPMAP_LINEAR
+	}
+    }
+
+    pub fn mag_filter_enum(&self) -> GLenum {
+	match self.mag_filter {
+	    Filter::Nearest => gl::NEAREST,
+	    Filter::Linear => gl::LINEAR
+	}
+    }
+}
+
+/*
+  サンプラオブジェクト. テクスチャユニットに結びつけると,そのユニットのテクスチャの設定より優先される.
+  同じテクスチャを違う設定で読むときに使う.
+*/
+pub struct Sampler {
+    id: GLuint,
+    description: SamplerDescription
+}
+
+impl Sampler {
+    pub fn create(description:SamplerDescription) -> Self {
+	let mut id = 0;
+	unsafe {
+	    gl::GenSamplers(1, &mut id);
+	    gl::SamplerParameteri(id, gl::TEXTURE_WRAP_S, description.wrap_s.gl_enum() as GLint);
+	    gl::SamplerParameteri(id, gl::TEXTURE_WRAP_T, description.wrap_t.gl_enum() as GLint);
+	    gl::SamplerParameteri(id, gl::TEXTURE_WRAP_R, description.wrap_r.gl_enum() as GLint);
+	    gl::SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, description.min_filter_enum() as GLint);
+	    gl::SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, description.mag_filter_enum() as GLint);
+	    if description.max_anisotropy > 1.0 {
+		let mut limit:GLfloat = 1.0;
+		gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut limit);
+		//拡張が無いとGetFloatvはエラーになりlimitは1.0のまま
+		while gl::GetError() != gl::NO_ERROR {}
+		if limit > 1.0 {
+		    gl::SamplerParameterf(id, TEXTURE_MAX_ANISOTROPY, description.max_anisotropy.min(limit));
+		}
+	    }
+	}
+	Sampler { id, description }
+    }
+
+    pub fn id(&self) -> GLuint {
+	self.id
+    }
+
+    pub fn description(&self) -> &SamplerDescription {
+	&self.description
+    }
+
+    pub fn bind(&self, unit:GLuint) {
+	unsafe {
+	    gl::BindSampler(unit, self.id);
+	}
+    }
+
+    pub fn unbind(unit:GLuint) {
+	unsafe {
+	    gl::BindSampler(unit, 0);
+	}
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteSamplers(1, &self.id);
+	}
+    }
+}
+
+/* RGBA8の2次元テクスチャ. GLコンテキスト作成後に作る */
+pub struct Texture2D {
+    id: GLuint,
+    width: u32,
+    height: u32,
+    color_space: ColorSpace,
+    mip_levels: u32
+}
+
+impl Texture2D {
+
+    /*
+      dataを送ってテクスチャを作る. mipmapsならmipmapを生成する.
+      テクスチャ自身のサンプリング設定はSamplerDescription::default()にしておく.
+    */
+    pub fn from_data(data:&TextureData, color_space:ColorSpace, mipmaps:bool) -> Self {
+	let mip_levels = if mipmaps { mip_level_count(data.width, data.height) } else { 1 };
+	let internal_format = match color_space {
+	    ColorSpace::Srgb => gl::SRGB8_ALPHA8,
+	    ColorSpace::Linear => gl::RGBA8
+	};
+	/* GLは下の行から並べるので上下を入れ替える. テクスチャ座標のv=0が画像の下端になる */
+	let stride = (data.width*4) as usize;
+	let flipped:Vec<u8> = data.pixels.chunks(stride).rev().flat_map(|row| row.iter().cloned()).collect();
+	let mut id = 0;
+	unsafe {
+	    gl::GenTextures(1, &mut id);
+	    gl::BindTexture(gl::TEXTURE_2D, id);
+	    gl::TexStorage2D(gl::TEXTURE_2D, mip_levels as GLsizei, internal_format,
+			     data.width as GLsizei, data.height as GLsizei);
+	    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+	    gl::TexSubImage2D(gl::TEXTURE_2D, 0, 0, 0, data.width as GLsizei, data.height as GLsizei,
+			      gl::RGBA, gl::UNSIGNED_BYTE, flipped.as_ptr() as *const _);
+	    if mipmaps {
+		gl::GenerateMipmap(gl::TEXTURE_2D);
+	    }
+	    let description = SamplerDescription {
+		mipmap: if mipmaps { Some(Filter::Linear) } else { None },
+		..SamplerDescription::default()
+	    };
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, description.wrap_s.gl_enum() as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, description.wrap_t.gl_enum() as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, description.min_filter_enum() as GLint);
+	    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, description.mag_filter_enum() as GLint);
+	    gl::BindTexture(gl::TEXTURE_2D, 0);
+	}
+	Texture2D { id, width: data.width, height: data.height, color_space, mip_levels }
+    }
+
+    pub fn load<P: AsRef<Path>>(path:P, color_space:ColorSpace, mipmaps:bool) -> Result<Self, TextureError> {
+	Ok(Texture2D::from_data(&TextureData::load(path)?, color_space, mipmaps))
+    }
+
+    /* 描画で作ったテクスチャを包む(IBLのBRDFテーブルなど). 削除はTexture2Dが行う */
+    pub fn from_raw(id:GLuint, width:u32, height:u32, color_space:ColorSpace, mip_levels:u32) -> Self {
+	Texture2D { id, width, height, color_space, mip_levels }
+    }
+
+    pub fn id(&self) -> GLuint {
+	self.id
+    }
+
+    pub fn width(&self) -> u32 {
+	self.width
+    }
+
+    pub fn height(&self) -> u32 {
+	self.height
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+	self.color_space
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+	self.mip_levels
+    }
+
+    /* unit番のテクスチャユニットに結びつける */
+    pub fn bind(&self, unit:GLuint) {
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE0 + unit);
+	    gl::BindTexture(gl::TEXTURE_2D, self.id);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	}
+    }
+
+    /* Materialに持たせる結びつけ情報 */
+    pub fn binding(&self, unit:GLuint, sampler:Option<&Sampler>) -> TextureBinding {
+	TextureBinding {
+	    unit,
+	    target: gl::TEXTURE_2D,
+	    texture: self.id,
+	    sampler: sampler.map(|s| s.id()).unwrap_or(0)
+	}
+    }
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteTextures(1, &self.id);
+	}
+    }
+}
+
+/*
+  描画の直前にテクスチャユニットに結びつけるテクスチャとサンプラ. samplerが0ならテクスチャ自身の設定を使う.
+  GLの名前だけを持つので,元のTexture2DとSamplerは描画が終わるまで生かしておくこと.
+*/
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct TextureBinding {
+    pub unit: GLuint,
+    pub target: GLenum,
+    pub texture: GLuint,
+    pub sampler: GLuint
+}
+
+impl TextureBinding {
+    pub fn bind(&self) {
+	unsafe {
+	    gl::ActiveTexture(gl::TEXTURE0 + self.unit);
+	    gl::BindTexture(self.target, self.texture);
+	    gl::BindSampler(self.unit, self.sampler);
+	    gl::ActiveTexture(gl::TEXTURE0);
+	}
+    }
+}
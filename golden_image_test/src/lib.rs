@@ -0,0 +1,129 @@
+
+pub mod scenes;
+pub mod replay;
+
+use std::path::{Path,PathBuf};
+
+use glutils::golden::{RgbaImage,compare_rgba};
+use app_framework::{AppConfig,Platform,HeadlessPlatform};
+
+use scenes::GoldenRenderer;
+
+/* チャンネルごとの差の許容値と,許容値を超えてよい画素の割合 */
+const TOLERANCE: u8 = 8;
+const MAX_MISMATCH_RATIO: f64 = 0.002;
+
+fn load_png(path:&Path) -> Option<RgbaImage> {
+    let image = image::open(path).ok()?.to_rgba8();
+    Some(RgbaImage { width: image.width(), height: image.height(), pixels: image.into_raw() })
+}
+
+fn save_png(path:&Path, image:&RgbaImage) {
+    if let Some(dir) = path.parent() {
+	std::fs::create_dir_all(dir).unwrap_or_else(|e| panic!("failed to create {}. {}", dir.display(), e));
+    }
+    image::save_buffer(path, &image.pixels, image.width, image.height, image::ColorType::Rgba8)
+	.unwrap_or_else(|e| panic!("failed to save {}. {}", path.display(), e));
+}
+
+/* golden/<name>.pngと比べる. blessなら書き換える. 一致しなければ target/golden_actual/<name>.png に結果を書いてfalse */
+fn check_image(root:&Path, name:&str, actual:&RgbaImage, bless:bool) -> bool {
+    let golden_path = root.join("golden").join(format!("{}.png", name));
+    if bless {
+	save_png(&golden_path, actual);
+	println!("{}: blessed", name);
+	return true;
+    }
+    let result = match load_png(&golden_path) {
+	Some(expected) => match compare_rgba(&expected, actual, TOLERANCE) {
+	    Some(diff) if diff.within(actual.pixels.len()/4, MAX_MISMATCH_RATIO) => Ok(()),
+	    Some(diff) => Err(format!("{} pixels differ (max difference {})", diff.mismatched_pixels, diff.max_difference)),
+	    None => Err("image size differs".to_string())
+	},
+	None => Err(format!("{} is missing. run with --bless", golden_path.display()))
+    };
+    match result {
+	Ok(()) => {
+	    println!("{}: ok", name);
+	    true
+	},
+	Err(message) => {
+	    let actual_path = root.join("target").join("golden_actual").join(format!("{}.png", name));
+	    save_png(&actual_path, actual);
+	    println!("{}: FAILED {}. actual image: {}", name, message, actual_path.display());
+	    false
+	}
+    }
+}
+
+/*
+  material.vertでスキニングした画像がskin_position,skin_normalで動かした頂点の画像と一致するか確かめ,
+  golden/skinning.pngとも比べる
+*/
+fn check_skinning(root:&Path, renderer:&mut GoldenRenderer, bless:bool) -> bool {
+    let gpu = renderer.render_skinning(true);
+    let cpu = renderer.render_skinning(false);
+    match compare_rgba(&cpu, &gpu, TOLERANCE) {
+	Some(diff) if diff.within(gpu.pixels.len()/4, MAX_MISMATCH_RATIO) => check_image(root, "skinning", &gpu, bless),
+	_ => {
+	    let actual_dir = root.join("target").join("golden_actual");
+	    save_png(&actual_dir.join("skinning_gpu.png"), &gpu);
+	    save_png(&actual_dir.join("skinning_cpu.png"), &cpu);
+	    println!("skinning: FAILED GPU skinning differs from the CPU reference. images: {}", actual_dir.display());
+	    false
+	}
+    }
+}
+
+/*
+  記録した入力を2回流し,途中と最後のフレームが2回とも同じ画像になるか確かめる.
+  最後のフレームはgolden/replay.pngとも比べる. replayは自分でコンテキストを作る
+*/
+fn check_replay(root:&Path, bless:bool) -> bool {
+    let recording = replay::recording();
+    let last_frame = recording.frames.len() as u64 - 1;
+    let capture_frames = [last_frame/2, last_frame];
+    let config = AppConfig { title: "golden image replay".to_string(), ..AppConfig::default() };
+    let run = || app_framework::replay::<replay::ReplayApp>(&config, &recording, &capture_frames)
+	.unwrap_or_else(|e| panic!("{}", e)).1;
+    let first = run();
+    let second = run();
+    if first.len() != capture_frames.len() || first != second {
+	println!("replay: FAILED two replays of the same recording gave different frames");
+	return false;
+    }
+    check_image(root, "replay", &first[first.len() - 1].1, bless)
+}
+
+/*
+  影などの描画結果とスキニングした腕をgolden/<scene>.pngと比べ,最後に入力の記録を流して同じ画像になるかも確かめる.
+  一致しなければ target/golden_actual/<scene>.png に結果を書く. blessなら今の描画結果でゴールデンイメージを作りなおす.
+  失敗した数を返す
+*/
+pub fn check_all(bless:bool) -> usize {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    //ウィンドウは出さずにEGLのコンテキストだけ使う. シーンはGoldenRendererのframebufferに描く
+    let config = AppConfig { title: "golden image test".to_string(), width: 64, height: 64, ..AppConfig::default() };
+    let mut platform = HeadlessPlatform::create(&config).unwrap_or_else(|e| panic!("{}", e));
+    gl::load_with(|symbol| platform.get_proc_address(symbol));
+
+    let mut renderer = GoldenRenderer::create();
+    let mut failed = 0;
+    for scene in scenes::scenes() {
+	let actual = renderer.render(&scene);
+	if !check_image(&root, scene.name, &actual, bless) {
+	    failed += 1;
+	}
+    }
+    if !check_skinning(&root, &mut renderer, bless) {
+	failed += 1;
+    }
+    drop(renderer);
+    drop(platform);
+
+    if !check_replay(&root, bless) {
+	failed += 1;
+    }
+    failed
+}
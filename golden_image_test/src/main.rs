@@ -0,0 +1,13 @@
+use std::process;
+
+/*
+  ゴールデンイメージとの比較はcargo testでtests/golden.rsから走る.
+  cargo run            : cargo testと同じく比較する
+  cargo run -- --bless : 今の描画結果でゴールデンイメージを作りなおす
+*/
+fn main() {
+    let bless = std::env::args().any(|a| a == "--bless");
+    if golden_image_test::check_all(bless) > 0 {
+	process::exit(1);
+    }
+}
@@ -0,0 +1,87 @@
+use std::time;
+
+use app_framework::{App,InitContext,FrameContext,LoopControl,Recording,Event,Action,Modifiers,egui};
+use app_framework::recording::RecordedFrame;
+use graphic_math::input::{ActionMap,Button,Input};
+use glutils::light::Light;
+
+use crate::scenes::{self,Scene,GoldenRenderer,IMAGE_WIDTH,IMAGE_HEIGHT};
+
+/*
+  記録した入力をreplayで流すシーン. 影の付いた太陽をLeft,Rightで回し,eguiのウィンドウに角度を出す.
+  同じ記録を2回流して同じ画像になることと,最後のフレームがゴールデンイメージと一致することを確かめる
+*/
+
+/* 1秒に回す角度(ラジアン) */
+const ROTATION_SPEED: f64 = 1.0;
+
+fn sun(azimuth:f64) -> Light {
+    let mut sun = Light::directional([-azimuth.cos() as f32, -azimuth.sin() as f32, -2.0], [1.0, 1.0, 1.0], 1.0);
+    sun.cast_shadow = true;
+    sun
+}
+
+pub struct ReplayApp {
+    renderer: GoldenRenderer,
+    scene: Scene,
+    azimuth: f64
+}
+
+impl App for ReplayApp {
+    fn init(_context:&InitContext) -> Self {
+	let scene = scenes::scenes().into_iter().find(|scene| scene.name == "shadow_directional_hard")
+	    .expect("shadow_directional_hard scene");
+	ReplayApp { renderer: GoldenRenderer::create(), scene, azimuth: 0.5 }
+    }
+
+    fn action_map(&self) -> ActionMap {
+	ActionMap::new()
+	    .button("rotate_left", Button::key("Left"))
+	    .button("rotate_right", Button::key("Right"))
+    }
+
+    fn update(&mut self, dt:time::Duration, input:&Input) {
+	let direction = (input.action_down("rotate_right") as i32 - input.action_down("rotate_left") as i32) as f64;
+	self.azimuth += direction*ROTATION_SPEED*dt.as_secs_f64();
+    }
+
+    fn ui(&mut self, egui_ctx:&egui::CtxRef, _control:&mut LoopControl) {
+	egui::Window::new("sun").show(egui_ctx, |ui| {
+	    ui.label(format!("azimuth {:.3}", self.azimuth));
+	});
+    }
+
+    fn render(&mut self, context:&FrameContext) {
+	if let Some(light) = self.scene.lights.get_mut(0) {
+	    *light = sun(self.azimuth);
+	}
+	self.renderer.render(&self.scene);
+	self.renderer.blit_to_default_framebuffer(context.width, context.height);
+    }
+}
+
+/*
+  流す入力. フレームの間隔をそろえず,Rightを押している間に固定時間刻みのupdateが何回入るかも記録どおりになるようにする
+*/
+pub fn recording() -> Recording {
+    let key = |name:&str, action:Action| Event::Key(name.to_string(), 0, action, Modifiers::default());
+    let mut millis = 0;
+    let frames = (0..30).map(|frame| {
+	millis += [16, 17, 33][frame % 3];
+	let events = match frame {
+	    2 => vec![Event::CursorPos(40.0, 200.0)],
+	    3 => vec![key("Right", Action::Press)],
+	    15 => vec![key("Right", Action::Release)],
+	    20 => vec![key("Left", Action::Press)],
+	    24 => vec![key("Left", Action::Release)],
+	    _ => Vec::new()
+	};
+	RecordedFrame { time: time::Duration::from_millis(millis), events, gamepad: None }
+    }).collect();
+    Recording {
+	framebuffer_size: (IMAGE_WIDTH, IMAGE_HEIGHT),
+	content_scale: 1.0,
+	start_time: time::Duration::ZERO,
+	frames
+    }
+}
@@ -0,0 +1,395 @@
+use std::{ptr, mem};
+use std::{f64::consts::PI};
+
+use gl::types::*;
+use linear_transform::vector::*;
+use graphic_math::graphic_math;
+use glutils::renderer::{Renderer,DrawItem,RenderState,VertexArrayObjectContext};
+use glutils::material::{MaterialPrograms,ShadingModel,LambertParameter};
+use glutils::light::{LightSet,Light};
+use glutils::shadow::{ShadowMap,ShadowSettings,ViewFrustum,Cascade};
+use glutils::golden::{RgbaImage,read_pixels};
+use glutils::cubemap::environment_uniforms;
+use glutils::ibl::ibl_uniforms;
+use glutils::ssao::ssao_uniforms;
+use glutils::skinning::{SkinBuffer,set_skinning};
+use linear_transform::{matrix::Matrix4x4,quaternion::Quaternion};
+use ::graphic_math::scene_graph::Transform;
+use ::graphic_math::skeleton::{Skeleton,SkinWeights,skin_position,skin_normal};
+
+/* 出力画像の大きさ. ゴールデンイメージもこの大きさ */
+pub const IMAGE_WIDTH: i32 = 256;
+pub const IMAGE_HEIGHT: i32 = 256;
+
+struct Mesh {
+    vbos: [GLuint;4],
+    context: VertexArrayObjectContext
+}
+
+/* 頂点属性の配置はglutilsのmaterial.vertに合わせる. position=0,normal=1,vertexColor=2 */
+fn create_mesh(positions:&[GLfloat], normals:&[GLfloat], colors:&[GLfloat], indices:&[GLuint]) -> Mesh {
+    let mut vao = 0;
+    let mut vbos : [GLuint;4] = [0,0,0,0];
+    unsafe {
+	gl::GenVertexArrays(1, &mut vao);
+	gl::BindVertexArray(vao);
+	gl::GenBuffers(4, &mut vbos[0]);
+	for (location, (data, size)) in [(positions, 3), (normals, 3), (colors, 4)].iter().enumerate() {
+	    gl::BindBuffer(gl::ARRAY_BUFFER, vbos[location]);
+	    gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(*data) as GLsizeiptr,
+			   data.as_ptr() as *const _, gl::STATIC_DRAW);
+	    gl::EnableVertexAttribArray(location as GLuint);
+	    gl::VertexAttribPointer(location as GLuint, *size, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+	}
+	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vbos[3]);
+	gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, mem::size_of_val(indices) as GLsizeiptr,
+		       indices.as_ptr() as *const _, gl::STATIC_DRAW);
+	gl::BindVertexArray(0);
+	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+	gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    }
+    Mesh {
+	vbos,
+	context: VertexArrayObjectContext::new(vao, gl::TRIANGLES, indices.len() as GLsizei)
+    }
+}
+
+fn sphere_mesh(color:[GLfloat;4], slice:u32, stack:u32) -> Mesh {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    for j in 0..=stack {
+	let theta = PI*(j as f64)/(stack as f64);
+	for i in 0..=slice {
+	    let phi = 2.0*PI*(i as f64)/(slice as f64);
+	    positions.extend_from_slice(&[(theta.sin()*phi.cos()) as GLfloat, (theta.sin()*phi.sin()) as GLfloat, theta.cos() as GLfloat]);
+	    colors.extend_from_slice(&color);
+	}
+    }
+    for j in 0..stack {
+	let k1 = j*(slice + 1);
+	let k2 = k1 + slice + 1;
+	for i in 0..slice {
+	    indices.extend_from_slice(&[k1+i, k2+i, k1+1+i, k1+1+i, k2+i, k2+1+i]);
+	}
+    }
+    /* 単位球なので法線は位置と同じ */
+    create_mesh(&positions, &positions, &colors, &indices)
+}
+
+fn delete_mesh(mesh:&Mesh) {
+    unsafe {
+	gl::DeleteBuffers(4, &mesh.vbos[0]);
+	gl::DeleteVertexArrays(1, &mesh.context.vao);
+    }
+}
+
+fn flatten(vectors:&[Vector3]) -> Vec<GLfloat> {
+    vectors.iter().flat_map(|v| vec![v.0 as GLfloat, v.1 as GLfloat, v.2 as GLfloat]).collect()
+}
+
+/* z=0に置いた一辺2の正方形 */
+fn plane_mesh(color:[GLfloat;4]) -> Mesh {
+    let positions = [ -1.0, -1.0, 0.0,  1.0, -1.0, 0.0,  1.0, 1.0, 0.0,  -1.0, 1.0, 0.0 ];
+    let normals = [ 0.0, 0.0, 1.0,  0.0, 0.0, 1.0,  0.0, 0.0, 1.0,  0.0, 0.0, 1.0 ];
+    let colors:Vec<GLfloat> = (0..4).flat_map(|_| color.iter().cloned()).collect();
+    create_mesh(&positions, &normals, &colors, &[0, 1, 2, 0, 2, 3])
+}
+
+/* 原点からz軸に沿って伸びる四角い腕. 肘の前後で肩と肘の重みを混ぜる */
+const ARM_SEGMENTS: u32 = 8;
+const ARM_HALF_WIDTH: f64 = 0.2;
+const ARM_LENGTH: f64 = 2.0;
+const ELBOW_HEIGHT: f64 = 1.0;
+/* 肘をx軸回りに曲げる角度(degree) */
+const ELBOW_ANGLE: f64 = 60.0;
+
+struct Arm {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    skins: Vec<SkinWeights>,
+    indices: Vec<GLuint>
+}
+
+/* 側面ごとに頂点を分けて法線を面に垂直にする. 上下の蓋はない */
+fn arm() -> Arm {
+    let sides = [
+	(Vector3(1.0, 0.0, 0.0), Vector3(0.0, 1.0, 0.0)),
+	(Vector3(0.0, 1.0, 0.0), Vector3(-1.0, 0.0, 0.0)),
+	(Vector3(-1.0, 0.0, 0.0), Vector3(0.0, -1.0, 0.0)),
+	(Vector3(0.0, -1.0, 0.0), Vector3(1.0, 0.0, 0.0))
+    ];
+    let mut arm = Arm { positions: Vec::new(), normals: Vec::new(), skins: Vec::new(), indices: Vec::new() };
+    for (normal, tangent) in sides.iter() {
+	let base = arm.positions.len() as GLuint;
+	for i in 0..=ARM_SEGMENTS {
+	    let z = ARM_LENGTH*(i as f64)/(ARM_SEGMENTS as f64);
+	    let elbow = ((z - ELBOW_HEIGHT)/0.5 + 0.5).clamp(0.0, 1.0) as f32;
+	    for side in [-1.0, 1.0].iter() {
+		arm.positions.push(ARM_HALF_WIDTH*(*normal + *side**tangent) + Vector3(0.0, 0.0, z));
+		arm.normals.push(*normal);
+		arm.skins.push(SkinWeights::new([0, 1, 0, 0], [1.0 - elbow, elbow, 0.0, 0.0]));
+	    }
+	}
+	for i in 0..ARM_SEGMENTS {
+	    let k = base + 2*i;
+	    arm.indices.extend_from_slice(&[k, k+1, k+3, k, k+3, k+2]);
+	}
+    }
+    arm
+}
+
+/* 肩(関節0)はそのまま,肘(関節1)をELBOW_ANGLEだけ曲げた姿勢のjointMatrices */
+fn arm_skinning_matrices() -> Vec<Matrix4x4> {
+    let mut skeleton = Skeleton::new();
+    let shoulder = skeleton.add_joint("shoulder", None, Transform::identity());
+    let elbow = skeleton.add_joint("elbow", Some(shoulder), Transform::from_translation(Vector3(0.0, 0.0, ELBOW_HEIGHT)));
+    let mut pose = skeleton.rest_pose();
+    pose.locals[elbow].rotation = Quaternion::from_axis_angle(Vector3(1.0, 0.0, 0.0), ELBOW_ANGLE);
+    skeleton.skinning_matrices(&pose)
+}
+
+/* シーンを見る視点. 画像の大きさに合わせた透視投影 */
+fn view_frustum(center:Vector3) -> ViewFrustum {
+    ViewFrustum {
+	eye: Vector3(4.0, -3.0, 3.0),
+	center,
+	up: Vector3(0.0, 0.0, 1.0),
+	fovy: 35.0,
+	aspect: (IMAGE_WIDTH as f64)/(IMAGE_HEIGHT as f64),
+	near: 1.0,
+	far: 12.0
+    }
+}
+
+/* ゴールデンイメージを作るシーン. 床の上に球が2つ */
+pub struct Scene {
+    pub name: &'static str,
+    pub lights: LightSet,
+    pub shadow: ShadowSettings
+}
+
+pub fn scenes() -> Vec<Scene> {
+    let mut sun = Light::directional([-1.0, -0.5, -2.0], [1.0, 1.0, 1.0], 1.0);
+    sun.cast_shadow = true;
+    let mut spot = Light::spot([0.0, 0.0, 3.0], [0.0, 0.0, -1.0], 25.0, 35.0, [1.0, 1.0, 1.0], 6.0);
+    spot.cast_shadow = true;
+
+    let single = |light:Light| {
+	let mut lights = LightSet::new();
+	lights.add(light);
+	lights
+    };
+    vec![
+	Scene {
+	    name: "shadow_directional_cascaded",
+	    lights: single(sun),
+	    shadow: ShadowSettings { cascade_count: 3, pcf_radius: 1, ..ShadowSettings::default() }
+	},
+	Scene {
+	    name: "shadow_directional_hard",
+	    lights: single(sun),
+	    shadow: ShadowSettings { cascade_count: 1, pcf_radius: 0, ..ShadowSettings::default() }
+	},
+	Scene {
+	    name: "shadow_spot",
+	    lights: single(spot),
+	    shadow: ShadowSettings { cascade_count: 1, pcf_radius: 2, ..ShadowSettings::default() }
+	},
+    ]
+}
+
+/*
+  オフスクリーンのframebufferにシーンを描いて読み出す.
+  ウィンドウのframebufferは見えないと中身が保証されないので使わない.
+*/
+pub struct GoldenRenderer {
+    framebuffer: GLuint,
+    renderbuffers: [GLuint;2],
+    materials: MaterialPrograms,
+    renderer: Renderer,
+    meshes: Vec<(Mesh, Vector3, f64)>
+}
+
+impl GoldenRenderer {
+
+    pub fn create() -> Self {
+	let mut framebuffer = 0;
+	let mut renderbuffers : [GLuint;2] = [0,0];
+	unsafe {
+	    gl::GenFramebuffers(1, &mut framebuffer);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+	    gl::GenRenderbuffers(2, &mut renderbuffers[0]);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffers[0]);
+	    gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, IMAGE_WIDTH, IMAGE_HEIGHT);
+	    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::RENDERBUFFER, renderbuffers[0]);
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffers[1]);
+	    gl::RenderbufferStorage(gl::RENDERBUFFER, gl::DEPTH_COMPONENT24, IMAGE_WIDTH, IMAGE_HEIGHT);
+	    gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::RENDERBUFFER, renderbuffers[1]);
+	    if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+		panic!("golden image framebuffer is incomplete");
+	    }
+	    gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	    gl::ClearDepth(1.0);
+	    gl::DepthFunc(gl::LESS);
+	}
+
+	/* メッシュ,位置,大きさ */
+	let meshes = vec![
+	    (plane_mesh([0.8, 0.8, 0.8, 1.0]), Vector3(0.0, 0.0, 0.0), 2.0),
+	    (sphere_mesh([1.0, 0.4, 0.2, 1.0], 32, 32), Vector3(0.0, 0.0, 0.8), 0.5),
+	    (sphere_mesh([0.2, 0.6, 1.0, 1.0], 24, 24), Vector3(0.9, 0.7, 0.3), 0.2),
+	];
+
+	GoldenRenderer {
+	    framebuffer,
+	    renderbuffers,
+	    materials: MaterialPrograms::create(),
+	    renderer: Renderer::new(),
+	    meshes
+	}
+    }
+
+    pub fn render(&mut self, scene:&Scene) -> RgbaImage {
+	let frustum = view_frustum(Vector3(0.0, 0.0, 0.3));
+	let view = graphic_math::look_at(frustum.eye, frustum.center, frustum.up);
+	let projection = graphic_math::perspective(frustum.fovy, frustum.aspect, frustum.near, frustum.far);
+	let shading = ShadingModel::Lambert(LambertParameter::default());
+	let material = self.materials.material(&shading);
+	let items:Vec<DrawItem> = self.meshes.iter().map(|(mesh, position, size)| DrawItem {
+	    mesh: mesh.context,
+	    material: material.clone(),
+	    transform: graphic_math::translate(*position)*graphic_math::scale(Vector3(*size, *size, *size)),
+	    state: RenderState::default()
+	}).collect();
+
+	let mut shadow_map = ShadowMap::create(scene.shadow.resolution);
+	let (shadow_light, cascades) = match scene.lights.shadow_caster() {
+	    Some((index, light)) => (Some(index), scene.shadow.cascades(light, &frustum)),
+	    None => (None, Vec::<Cascade>::new())
+	};
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+	    gl::Viewport(0, 0, IMAGE_WIDTH, IMAGE_HEIGHT);
+	}
+	shadow_map.render(&cascades, &items);
+
+	unsafe {
+	    gl::DepthMask(gl::TRUE);
+	    gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+	    gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT);
+	}
+	self.renderer.begin_frame(view, projection);
+	scene.lights.apply(&mut self.renderer);
+	//影のほかは使わないが,programは共有なので毎フレーム明示する
+	let uniforms = scene.shadow.uniforms(shadow_light, &cascades).into_iter()
+	    .chain(environment_uniforms(false, 0.0))
+	    .chain(ibl_uniforms(None, 0.0))
+	    .chain(ssao_uniforms(false));
+	for (name, value) in uniforms {
+	    self.renderer.set_frame_uniform(&name, value);
+	}
+	shadow_map.bind();
+	for item in items {
+	    self.renderer.submit(item);
+	}
+	self.renderer.end_frame();
+
+	let image = read_pixels(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32);
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	}
+	image
+    }
+
+    /*
+      床の上で肘を曲げた腕を影なしで描く. gpuならmaterial.vertのjointIndices,jointWeightsとjointMatricesで,
+      そうでなければskin_position,skin_normalで動かした頂点をそのまま描く. 2つは同じ画像になるはず
+    */
+    pub fn render_skinning(&mut self, gpu:bool) -> RgbaImage {
+	let frustum = view_frustum(Vector3(0.0, 0.0, 1.0));
+	let view = graphic_math::look_at(frustum.eye, frustum.center, frustum.up);
+	let projection = graphic_math::perspective(frustum.fovy, frustum.aspect, frustum.near, frustum.far);
+	let shading = ShadingModel::Lambert(LambertParameter::default());
+	let arm = arm();
+	let matrices = arm_skinning_matrices();
+	let colors:Vec<GLfloat> = (0..arm.positions.len()).flat_map(|_| [1.0, 0.6, 0.2, 1.0].iter().cloned()).collect();
+	let mut material = self.materials.material(&shading);
+	let (mesh, _skin_buffer) = if gpu {
+	    let mesh = create_mesh(&flatten(&arm.positions), &flatten(&arm.normals), &colors, &arm.indices);
+	    let skin_buffer = SkinBuffer::create(&arm.skins);
+	    skin_buffer.attach(mesh.context.vao);
+	    set_skinning(&mut material, &matrices);
+	    (mesh, Some(skin_buffer))
+	}
+	else {
+	    let positions:Vec<Vector3> = arm.positions.iter().zip(arm.skins.iter()).map(|(p, skin)| skin_position(*p, skin, &matrices)).collect();
+	    let normals:Vec<Vector3> = arm.normals.iter().zip(arm.skins.iter()).map(|(n, skin)| skin_normal(*n, skin, &matrices)).collect();
+	    (create_mesh(&flatten(&positions), &flatten(&normals), &colors, &arm.indices), None)
+	};
+	let (floor, floor_position, floor_size) = &self.meshes[0];
+	let items = vec![
+	    DrawItem {
+		mesh: floor.context,
+		material: self.materials.material(&shading),
+		transform: graphic_math::translate(*floor_position)*graphic_math::scale(Vector3(*floor_size, *floor_size, *floor_size)),
+		state: RenderState::default()
+	    },
+	    DrawItem { mesh: mesh.context, material, transform: Matrix4x4::identity(), state: RenderState::default() }
+	];
+
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+	    gl::Viewport(0, 0, IMAGE_WIDTH, IMAGE_HEIGHT);
+	    gl::DepthMask(gl::TRUE);
+	    gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+	    gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT);
+	}
+	let mut lights = LightSet::new();
+	lights.add(Light::directional([-1.0, -0.5, -2.0], [1.0, 1.0, 1.0], 1.0));
+	self.renderer.begin_frame(view, projection);
+	lights.apply(&mut self.renderer);
+	let uniforms = ShadowSettings::default().uniforms(None, &[]).into_iter()
+	    .chain(environment_uniforms(false, 0.0))
+	    .chain(ibl_uniforms(None, 0.0))
+	    .chain(ssao_uniforms(false));
+	for (name, value) in uniforms {
+	    self.renderer.set_frame_uniform(&name, value);
+	}
+	for item in items {
+	    self.renderer.submit(item);
+	}
+	self.renderer.end_frame();
+
+	let image = read_pixels(IMAGE_WIDTH as u32, IMAGE_HEIGHT as u32);
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	}
+	delete_mesh(&mesh);
+	image
+    }
+
+    /* 最後にrenderした画像をウィンドウのframebufferに写す. replayで使う */
+    pub fn blit_to_default_framebuffer(&self, width:i32, height:i32) {
+	unsafe {
+	    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.framebuffer);
+	    gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+	    gl::BlitFramebuffer(0, 0, IMAGE_WIDTH, IMAGE_HEIGHT, 0, 0, width, height, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	    gl::Viewport(0, 0, width, height);
+	}
+    }
+}
+
+impl Drop for GoldenRenderer {
+    fn drop(&mut self) {
+	for (mesh, _, _) in self.meshes.iter() {
+	    delete_mesh(mesh);
+	}
+	unsafe {
+	    gl::DeleteRenderbuffers(2, &self.renderbuffers[0]);
+	    gl::DeleteFramebuffers(1, &self.framebuffer);
+	}
+    }
+}
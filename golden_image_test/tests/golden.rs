@@ -0,0 +1,5 @@
+/* EGLのコンテキストは1つずつ作るので,シーンと再生の比較を1つのテストで順に行う */
+#[test]
+fn golden_images_match(){
+    assert_eq!(golden_image_test::check_all(false), 0, "see target/golden_actual for the rendered images");
+}
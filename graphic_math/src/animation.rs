@@ -0,0 +1,432 @@
+use std::time::Duration;
+use std::f64::consts::PI;
+
+use linear_transform::{vector::*,quaternion::*};
+
+/*
+  キーフレームアニメーション. 値の型ごとのトラックを名前をつけてクリップにまとめ,
+  AnimationPlayerがメインループから渡されるintervalで時刻を進める.
+  描画には依存しないので,時刻を決めればテストで値を確かめられる
+*/
+
+/* キーフレームから次のキーフレームまでの進み方 */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Easing {
+    /* 次のキーフレームまで値を変えない */
+    Step,
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    SineInOut,
+    /* 行き過ぎてから揺れて止まる */
+    ElasticOut,
+    /* 跳ねて止まる */
+    BounceOut,
+    /* 少し行き過ぎて戻る */
+    BackOut
+}
+
+pub const EASINGS: [Easing;12] = [Easing::Step, Easing::Linear, Easing::QuadIn, Easing::QuadOut, Easing::QuadInOut,
+				  Easing::CubicIn, Easing::CubicOut, Easing::CubicInOut, Easing::SineInOut,
+				  Easing::ElasticOut, Easing::BounceOut, Easing::BackOut];
+
+impl Easing {
+    pub fn name(self) -> &'static str {
+	match self {
+	    Easing::Step => "step",
+	    Easing::Linear => "linear",
+	    Easing::QuadIn => "quad in",
+	    Easing::QuadOut => "quad out",
+	    Easing::QuadInOut => "quad in-out",
+	    Easing::CubicIn => "cubic in",
+	    Easing::CubicOut => "cubic out",
+	    Easing::CubicInOut => "cubic in-out",
+	    Easing::SineInOut => "sine in-out",
+	    Easing::ElasticOut => "elastic out",
+	    Easing::BounceOut => "bounce out",
+	    Easing::BackOut => "back out"
+	}
+    }
+
+    /* 0-1の進み具合tを値の補間の割合にする. 0なら0,1なら1. ElasticOutとBackOutは途中で1を超える */
+    pub fn apply(self, t:f64) -> f64 {
+	let t = t.clamp(0.0, 1.0);
+	match self {
+	    Easing::Step => if t < 1.0 { 0.0 } else { 1.0 },
+	    Easing::Linear => t,
+	    Easing::QuadIn => t*t,
+	    Easing::QuadOut => 1.0 - (1.0 - t)*(1.0 - t),
+	    Easing::QuadInOut => if t < 0.5 { 2.0*t*t } else { 1.0 - 0.5*(2.0 - 2.0*t).powi(2) },
+	    Easing::CubicIn => t*t*t,
+	    Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+	    Easing::CubicInOut => if t < 0.5 { 4.0*t*t*t } else { 1.0 - 0.5*(2.0 - 2.0*t).powi(3) },
+	    Easing::SineInOut => 0.5*(1.0 - (PI*t).cos()),
+	    Easing::ElasticOut => {
+		if t == 0.0 || t == 1.0 {
+		    t
+		}
+		else {
+		    2f64.powf(-10.0*t)*((10.0*t - 0.75)*(2.0*PI/3.0)).sin() + 1.0
+		}
+	    },
+	    Easing::BounceOut => {
+		let (n, d) = (7.5625, 2.75);
+		if t < 1.0/d {
+		    n*t*t
+		}
+		else if t < 2.0/d {
+		    let t = t - 1.5/d;
+		    n*t*t + 0.75
+		}
+		else if t < 2.5/d {
+		    let t = t - 2.25/d;
+		    n*t*t + 0.9375
+		}
+		else {
+		    let t = t - 2.625/d;
+		    n*t*t + 0.984375
+		}
+	    },
+	    Easing::BackOut => {
+		let c = 1.70158;
+		1.0 + (c + 1.0)*(t - 1.0).powi(3) + c*(t - 1.0).powi(2)
+	    }
+	}
+    }
+}
+
+/* キーフレームの間を補間できる値. tは0-1の外になることもある */
+pub trait Animatable: Copy {
+    fn interpolate(a:Self, b:Self, t:f64) -> Self;
+}
+
+impl Animatable for f64 {
+    fn interpolate(a:Self, b:Self, t:f64) -> Self {
+	a + t*(b - a)
+    }
+}
+
+impl Animatable for Vector3 {
+    fn interpolate(a:Self, b:Self, t:f64) -> Self {
+	a + t*(b - a)
+    }
+}
+
+/* 回転は球面線形補間. 単位四元数であること */
+impl Animatable for Quaternion {
+    fn interpolate(a:Self, b:Self, t:f64) -> Self {
+	a.slerp(b, t)
+    }
+}
+
+/* RGBAの色. 行き過ぎても負の色にはしない */
+impl Animatable for [f32;4] {
+    fn interpolate(a:Self, b:Self, t:f64) -> Self {
+	let mut color = [0.0;4];
+	for (c, (x, y)) in color.iter_mut().zip(a.iter().zip(b.iter())) {
+	    *c = (*x + (t as f32)*(*y - *x)).max(0.0);
+	}
+	color
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Keyframe<T> {
+    /* 秒 */
+    pub time: f64,
+    pub value: T,
+    /* このキーフレームから次のキーフレームまでの進み方 */
+    pub easing: Easing
+}
+
+/* 1つの値の時間変化. キーフレームは時刻順に並べておく */
+#[derive(Debug,Clone,PartialEq)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new() -> Self {
+	Track { keyframes: Vec::new() }
+    }
+
+    /* 続けて書けるようにselfを返すadd */
+    pub fn key(mut self, time:f64, value:T, easing:Easing) -> Self {
+	self.add(Keyframe { time, value, easing });
+	self
+    }
+
+    /* 時刻順の位置に入れる. 同じ時刻のキーフレームは置き換える */
+    pub fn add(&mut self, keyframe:Keyframe<T>) {
+	match self.keyframes.iter().position(|k| k.time >= keyframe.time) {
+	    Some(index) if self.keyframes[index].time == keyframe.time => self.keyframes[index] = keyframe,
+	    Some(index) => self.keyframes.insert(index, keyframe),
+	    None => self.keyframes.push(keyframe)
+	}
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+	&self.keyframes
+    }
+
+    pub fn end_time(&self) -> f64 {
+	self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    /* 範囲の外は端のキーフレームの値. キーフレームがなければNone */
+    pub fn sample(&self, time:f64) -> Option<T> {
+	let first = self.keyframes.first()?;
+	if time <= first.time {
+	    return Some(first.value);
+	}
+	match self.keyframes.iter().position(|k| k.time > time) {
+	    Some(index) => {
+		let (k1, k2) = (&self.keyframes[index - 1], &self.keyframes[index]);
+		let t = k1.easing.apply((time - k1.time)/(k2.time - k1.time));
+		Some(T::interpolate(k1.value, k2.value, t))
+	    },
+	    None => self.keyframes.last().map(|k| k.value)
+	}
+    }
+}
+
+impl<T: Animatable> Default for Track<T> {
+    fn default() -> Self {
+	Track::new()
+    }
+}
+
+/* クリップに入れるときに型をまとめる */
+#[derive(Debug,Clone,PartialEq)]
+pub enum AnimationTrack {
+    Scalar(Track<f64>),
+    Vector(Track<Vector3>),
+    Rotation(Track<Quaternion>),
+    Color(Track<[f32;4]>)
+}
+
+impl AnimationTrack {
+    pub fn end_time(&self) -> f64 {
+	match self {
+	    AnimationTrack::Scalar(track) => track.end_time(),
+	    AnimationTrack::Vector(track) => track.end_time(),
+	    AnimationTrack::Rotation(track) => track.end_time(),
+	    AnimationTrack::Color(track) => track.end_time()
+	}
+    }
+
+    pub fn sample(&self, time:f64) -> Option<AnimationValue> {
+	match self {
+	    AnimationTrack::Scalar(track) => track.sample(time).map(AnimationValue::Scalar),
+	    AnimationTrack::Vector(track) => track.sample(time).map(AnimationValue::Vector),
+	    AnimationTrack::Rotation(track) => track.sample(time).map(AnimationValue::Rotation),
+	    AnimationTrack::Color(track) => track.sample(time).map(AnimationValue::Color)
+	}
+    }
+}
+
+impl From<Track<f64>> for AnimationTrack {
+    fn from(track:Track<f64>) -> Self {
+	AnimationTrack::Scalar(track)
+    }
+}
+
+impl From<Track<Vector3>> for AnimationTrack {
+    fn from(track:Track<Vector3>) -> Self {
+	AnimationTrack::Vector(track)
+    }
+}
+
+impl From<Track<Quaternion>> for AnimationTrack {
+    fn from(track:Track<Quaternion>) -> Self {
+	AnimationTrack::Rotation(track)
+    }
+}
+
+impl From<Track<[f32;4]>> for AnimationTrack {
+    fn from(track:Track<[f32;4]>) -> Self {
+	AnimationTrack::Color(track)
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum AnimationValue {
+    Scalar(f64),
+    Vector(Vector3),
+    Rotation(Quaternion),
+    Color([f32;4])
+}
+
+/* クリップの長さを過ぎたあとの時刻の扱い */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum PlaybackMode {
+    /* 最後の値で止まる */
+    Once,
+    Loop,
+    /* 最後まで行ったら逆向きに戻る */
+    PingPong
+}
+
+impl PlaybackMode {
+    /* 長さdurationのクリップで,再生を始めてからの時刻timeをクリップの中の時刻にする */
+    pub fn local_time(self, duration:f64, time:f64) -> f64 {
+	if duration <= 0.0 {
+	    return 0.0;
+	}
+	match self {
+	    PlaybackMode::Once => time.clamp(0.0, duration),
+	    PlaybackMode::Loop => time.rem_euclid(duration),
+	    PlaybackMode::PingPong => {
+		let t = time.rem_euclid(2.0*duration);
+		if t > duration { 2.0*duration - t } else { t }
+	    }
+	}
+    }
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct AnimationClip {
+    /* 秒. トラックの最後のキーフレームより短くてもよい */
+    pub duration: f64,
+    pub mode: PlaybackMode,
+    tracks: Vec<(String, AnimationTrack)>
+}
+
+impl AnimationClip {
+    pub fn new(duration:f64, mode:PlaybackMode) -> Self {
+	AnimationClip { duration, mode, tracks: Vec::new() }
+    }
+
+    /* 続けて書けるようにselfを返すadd_track */
+    pub fn track<T: Into<AnimationTrack>>(mut self, name:&str, track:T) -> Self {
+	self.add_track(name, track);
+	self
+    }
+
+    /* 同じ名前のトラックは置き換える */
+    pub fn add_track<T: Into<AnimationTrack>>(&mut self, name:&str, track:T) {
+	let track = track.into();
+	match self.tracks.iter_mut().find(|(n, _)| n == name) {
+	    Some(entry) => entry.1 = track,
+	    None => self.tracks.push((name.to_owned(), track))
+	}
+    }
+
+    pub fn track_names(&self) -> impl Iterator<Item=&str> {
+	self.tracks.iter().map(|(name, _)| name.as_str())
+    }
+
+    /* 再生を始めてからの時刻を,クリップの中の時刻(0-duration)にする */
+    pub fn local_time(&self, time:f64) -> f64 {
+	self.mode.local_time(self.duration, time)
+    }
+
+    /* Onceで最後まで(逆再生なら最初まで)行ったか */
+    pub fn is_finished(&self, time:f64) -> bool {
+	self.mode == PlaybackMode::Once && (time >= self.duration || time < 0.0)
+    }
+
+    /* timeは再生を始めてからの時刻 */
+    pub fn sample(&self, name:&str, time:f64) -> Option<AnimationValue> {
+	let local = self.local_time(time);
+	self.tracks.iter().find(|(n, _)| n == name).and_then(|(_, track)| track.sample(local))
+    }
+
+    pub fn scalar(&self, name:&str, time:f64) -> Option<f64> {
+	match self.sample(name, time) {
+	    Some(AnimationValue::Scalar(value)) => Some(value),
+	    _ => None
+	}
+    }
+
+    pub fn vector(&self, name:&str, time:f64) -> Option<Vector3> {
+	match self.sample(name, time) {
+	    Some(AnimationValue::Vector(value)) => Some(value),
+	    _ => None
+	}
+    }
+
+    pub fn rotation(&self, name:&str, time:f64) -> Option<Quaternion> {
+	match self.sample(name, time) {
+	    Some(AnimationValue::Rotation(value)) => Some(value),
+	    _ => None
+	}
+    }
+
+    pub fn color(&self, name:&str, time:f64) -> Option<[f32;4]> {
+	match self.sample(name, time) {
+	    Some(AnimationValue::Color(value)) => Some(value),
+	    _ => None
+	}
+    }
+}
+
+/*
+  クリップを再生する. メインループのintervalをupdateに渡して進め,scalarなどで今の値を取り出す.
+  speedが負なら逆再生
+*/
+#[derive(Debug,Clone,PartialEq)]
+pub struct AnimationPlayer {
+    clip: AnimationClip,
+    time: f64,
+    pub speed: f64,
+    pub playing: bool
+}
+
+impl AnimationPlayer {
+    pub fn new(clip:AnimationClip) -> Self {
+	AnimationPlayer { clip, time: 0.0, speed: 1.0, playing: true }
+    }
+
+    pub fn clip(&self) -> &AnimationClip {
+	&self.clip
+    }
+
+    pub fn update(&mut self, interval:Duration) {
+	if self.playing {
+	    self.time += interval.as_secs_f64()*self.speed;
+	}
+    }
+
+    /* 再生を始めてからの時刻 */
+    pub fn time(&self) -> f64 {
+	self.time
+    }
+
+    pub fn seek(&mut self, time:f64) {
+	self.time = time;
+    }
+
+    /* クリップの中の時刻 */
+    pub fn local_time(&self) -> f64 {
+	self.clip.local_time(self.time)
+    }
+
+    pub fn is_finished(&self) -> bool {
+	self.clip.is_finished(self.time)
+    }
+
+    pub fn sample(&self, name:&str) -> Option<AnimationValue> {
+	self.clip.sample(name, self.time)
+    }
+
+    pub fn scalar(&self, name:&str) -> Option<f64> {
+	self.clip.scalar(name, self.time)
+    }
+
+    pub fn vector(&self, name:&str) -> Option<Vector3> {
+	self.clip.vector(name, self.time)
+    }
+
+    pub fn rotation(&self, name:&str) -> Option<Quaternion> {
+	self.clip.rotation(name, self.time)
+    }
+
+    pub fn color(&self, name:&str) -> Option<[f32;4]> {
+	self.clip.color(name, self.time)
+    }
+}
@@ -0,0 +1,866 @@
+use linear_transform::{vector::*,matrix::*,quaternion::*};
+
+use crate::graphic_math;
+
+/*
+  マウスとキーボードで視点を動かすコントローラ.
+  graphic_mathはウィンドウのライブラリに依存しないので,各exampleがglfwのイベントを
+  mouse_button,cursor_moved,scroll,key(ゲームパッドはanalog)に変換して渡し,毎フレームupdateを呼んでからlook_atの引数を取り出す.
+  角度はgraphic_math::rotateと同じくdegree. 座標系はexampleと同じくz軸が上
+*/
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle
+}
+
+/* 一人称視点で移動に使うキー. どのキーに割り当てるかはexampleが決める */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum MoveKey {
+    Forward,
+    Backward,
+    Left,
+    Right,
+    Up,
+    Down
+}
+
+/*
+  ゲームパッドのスティックのように,倒している間続く入力. analogで渡した値をupdateでdt秒分使う.
+  0にするまで同じ値が続く
+*/
+#[derive(Debug,Copy,Clone,PartialEq,Default)]
+pub struct AnalogInput {
+    /* 1秒に見回す角度(degree). ドラッグと同じく右,下が正 */
+    pub look: (f64,f64),
+    /* 右,前,上. -1から1で,絶対値1が最も速い */
+    pub movement: (f64,f64,f64),
+    /* 1秒のスクロール量. 上に回すのと同じ向きが正 */
+    pub zoom: f64
+}
+
+impl AnalogInput {
+    pub fn is_zero(&self) -> bool {
+	*self == AnalogInput::default()
+    }
+}
+
+const MOVE_KEYS: [MoveKey;6] = [MoveKey::Forward, MoveKey::Backward, MoveKey::Left, MoveKey::Right, MoveKey::Up, MoveKey::Down];
+
+const WORLD_UP: Vector3 = Vector3(0.0, 0.0, 1.0);
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProjectionKind {
+    Perspective,
+    Orthographic
+}
+
+/*
+  視点と投影のパラメータをまとめたもの. fovyはdegree.
+  aspectはresizeでウィンドウに合わせる
+*/
+#[derive(Debug,Copy,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Camera {
+    pub eye: Vector3,
+    pub center: Vector3,
+    pub up: Vector3,
+    pub projection: ProjectionKind,
+    pub fovy: f64,
+    /* 平行投影で見える範囲の高さの半分 */
+    pub ortho_height: f64,
+    pub near: f64,
+    pub far: f64,
+    pub aspect: f64
+}
+
+impl Camera {
+    /* 投影はexampleで使っていたperspective(30.0, aspect, 1.0, 11.0)と同じ */
+    pub fn new(eye:Vector3, center:Vector3, up:Vector3) -> Self {
+	Camera {
+	    eye, center, up,
+	    projection: ProjectionKind::Perspective,
+	    fovy: 30.0,
+	    ortho_height: 1.5,
+	    near: 1.0,
+	    far: 11.0,
+	    aspect: 4.0/3.0
+	}
+    }
+
+    /* centerを中心とした球面座標(degree)の位置から見る. upはz軸 */
+    pub fn from_spherical(center:Vector3, distance:f64, theta:f64, phi:f64) -> Self {
+	let eye = OrbitState { center, distance, theta, phi }.eye();
+	Camera::new(eye, center, WORLD_UP)
+    }
+
+    /* 高さが0のとき(最小化したウィンドウなど)はaspectを変えない */
+    pub fn resize(&mut self, width:i32, height:i32) {
+	if width > 0 && height > 0 {
+	    self.aspect = (width as f64)/(height as f64);
+	}
+    }
+
+    pub fn view(&self) -> Matrix4x4 {
+	graphic_math::look_at(self.eye, self.center, self.up)
+    }
+
+    pub fn projection(&self) -> Matrix4x4 {
+	match self.projection {
+	    ProjectionKind::Perspective => graphic_math::perspective(self.fovy, self.aspect, self.near, self.far),
+	    ProjectionKind::Orthographic => {
+		let (w, h) = (self.ortho_height*self.aspect, self.ortho_height);
+		graphic_math::orthogonal(-w, w, -h, h, self.near, self.far)
+	    }
+	}
+    }
+
+    pub fn view_projection(&self) -> Matrix4x4 {
+	self.projection()*self.view()
+    }
+
+    /* コントローラの視点を写す. 投影のパラメータはそのまま */
+    pub fn follow(&mut self, controller:&dyn CameraController) {
+	let (eye, center, up) = controller.eye_center_up();
+	self.eye = eye;
+	self.center = center;
+	self.up = up;
+    }
+
+    /*
+      selfからotherへtの割合(0-1)だけ近づけたカメラ. 注視点からの向きと距離を分けて補間するので,
+      視点が注視点を突き抜けずに回り込む. 投影の種類は途中で切り替わり,aspectはselfのまま
+    */
+    pub fn interpolate(&self, other:&Camera, t:f64) -> Camera {
+	let (from, to) = (self.eye - self.center, other.eye - other.center);
+	let center = lerp(self.center, other.center, t);
+	let distance = from.length() + t*(to.length() - from.length());
+	//真逆の向きのときは途中で長さが0になるので,行き先の向きにする
+	let direction = lerp(from.normalize(), to.normalize(), t);
+	let direction = if direction.length() < 1.0e-9 { to.normalize() } else { direction.normalize() };
+	let up = lerp(self.up.normalize(), other.up.normalize(), t);
+	let up = if up.length() < 1.0e-9 { other.up } else { up.normalize() };
+	let mix = |a:f64, b:f64| a + t*(b - a);
+	Camera {
+	    eye: center + distance*direction,
+	    center,
+	    up,
+	    projection: if t < 0.5 { self.projection } else { other.projection },
+	    fovy: mix(self.fovy, other.fovy),
+	    ortho_height: mix(self.ortho_height, other.ortho_height),
+	    near: mix(self.near, other.near),
+	    far: mix(self.far, other.far),
+	    aspect: self.aspect
+	}
+    }
+}
+
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+    fn mouse_button(&mut self, button:MouseButton, pressed:bool);
+    /* ウィンドウ座標(左上が原点,下向きがy) */
+    fn cursor_moved(&mut self, x:f64, y:f64);
+    /* 上に回すと正 */
+    fn scroll(&mut self, delta:f64);
+    fn key(&mut self, _key:MoveKey, _pressed:bool) {}
+    fn analog(&mut self, _input:AnalogInput) {}
+    /* ドラッグ量を角度や距離に直すのに使う. ウィンドウの大きさが変わったら呼ぶ */
+    fn set_viewport(&mut self, width:f64, height:f64);
+    /* dtは秒. 押されているキーによる移動と,目標の状態への平滑化を進める */
+    fn update(&mut self, dt:f64);
+    /*
+      入力欄などから視点を直接決める. 平滑化せずにすぐその位置にする.
+      eyeとcenterが同じ位置,または有限でない値のときは視点が決まらないので何もしない
+    */
+    fn set_look_at(&mut self, eye:Vector3, center:Vector3, up:Vector3);
+    /* look_atの引数 (eye, center, up) */
+    fn eye_center_up(&self) -> (Vector3, Vector3, Vector3);
+
+    fn view(&self) -> Matrix4x4 {
+	let (eye, center, up) = self.eye_center_up();
+	graphic_math::look_at(eye, center, up)
+    }
+}
+
+/* smoothing秒の時定数で目標に近づける割合. 0なら平滑化しない(すぐ目標になる) */
+pub fn smoothing_factor(smoothing:f64, dt:f64) -> f64 {
+    if smoothing <= 0.0 {
+	1.0
+    }
+    else {
+	1.0 - (-dt/smoothing).exp()
+    }
+}
+
+fn lerp(a:Vector3, b:Vector3, t:f64) -> Vector3 {
+    a + t*(b - a)
+}
+
+/* 押されているボタンと直前のカーソル位置. cursor_movedでドラッグ量を返す */
+#[derive(Debug,Copy,Clone,PartialEq)]
+struct Pointer {
+    button: Option<MouseButton>,
+    position: (f64, f64),
+    viewport: (f64, f64)
+}
+
+impl Pointer {
+    fn new() -> Self {
+	Pointer { button: None, position: (0.0, 0.0), viewport: (800.0, 600.0) }
+    }
+
+    fn button(&mut self, button:MouseButton, pressed:bool) {
+	if pressed {
+	    self.button = Some(button);
+	}
+	else if self.button == Some(button) {
+	    self.button = None;
+	}
+    }
+
+    fn moved(&mut self, x:f64, y:f64) -> Option<(MouseButton, f64, f64)> {
+	let (dx, dy) = (x - self.position.0, y - self.position.1);
+	self.position = (x, y);
+	self.button.map(|button| (button, dx, dy))
+    }
+
+    /* 距離distanceの位置で画面の高さが何単位になるか */
+    fn world_per_screen(&self, distance:f64, fovy:f64) -> f64 {
+	2.0*distance*(0.5*fovy.to_radians()).tan()
+    }
+
+    /* 距離distanceの位置で1ピクセルが何単位になるか */
+    fn world_per_pixel(&self, distance:f64, fovy:f64) -> f64 {
+	self.world_per_screen(distance, fovy)/self.viewport.1.max(1.0)
+    }
+}
+
+/* offset(注視点から視点へのベクトル)とupから,カメラの右,上,後ろ向きの単位ベクトル */
+/* set_look_atに渡せる値か. 入力欄はNaNやinfも読めてしまう */
+pub(crate) fn is_valid_look_at(eye:Vector3, center:Vector3, up:Vector3) -> bool {
+    let finite = [eye, center, up].iter().all(|v| v.0.is_finite() && v.1.is_finite() && v.2.is_finite());
+    finite && (eye - center).length() > 0.0
+}
+
+pub(crate) fn camera_basis(offset:Vector3, up:Vector3) -> (Vector3, Vector3, Vector3) {
+    let z = offset.normalize();
+    let x = Vector3::cross_product(up, z).normalize();
+    let y = Vector3::cross_product(z, x);
+    (x, y, z)
+}
+
+/* 距離の範囲とthetaの範囲(z軸からの角度,degree). thetaを0や180にするとupと視線が重なる */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct OrbitLimits {
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub min_theta: f64,
+    pub max_theta: f64
+}
+
+impl Default for OrbitLimits {
+    fn default() -> Self {
+	OrbitLimits { min_distance: 0.5, max_distance: 50.0, min_theta: 1.0, max_theta: 179.0 }
+    }
+}
+
+/* centerを中心とした球面座標. exampleのr,theta,phiと同じ */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct OrbitState {
+    pub center: Vector3,
+    pub distance: f64,
+    pub theta: f64,
+    pub phi: f64
+}
+
+impl OrbitState {
+    pub fn eye(&self) -> Vector3 {
+	let (theta, phi) = (self.theta.to_radians(), self.phi.to_radians());
+	self.center + self.distance*Vector3(theta.sin()*phi.cos(), theta.sin()*phi.sin(), theta.cos())
+    }
+}
+
+/*
+  注視点の周りを回る. 左ドラッグで回転,右か中ドラッグで平行移動,スクロールで近づく.
+  upは常にz軸
+*/
+#[derive(Debug,Clone,PartialEq)]
+pub struct OrbitController {
+    target: OrbitState,
+    current: OrbitState,
+    pointer: Pointer,
+    analog: AnalogInput,
+    pub limits: OrbitLimits,
+    /* 1ピクセルのドラッグで回る角度(degree) */
+    pub rotate_speed: f64,
+    /* スクロール1段で距離がexp(-zoom_speed)倍になる */
+    pub zoom_speed: f64,
+    /* 目標に近づく時定数(秒) */
+    pub smoothing: f64,
+    /* 平行移動の量を合わせるための視野角(degree) */
+    pub fovy: f64
+}
+
+impl OrbitController {
+    pub fn new(center:Vector3, distance:f64, theta:f64, phi:f64) -> Self {
+	let mut controller = OrbitController {
+	    target: OrbitState { center, distance, theta, phi },
+	    current: OrbitState { center, distance, theta, phi },
+	    pointer: Pointer::new(),
+	    analog: AnalogInput::default(),
+	    limits: OrbitLimits::default(),
+	    rotate_speed: 0.3,
+	    zoom_speed: 0.1,
+	    smoothing: 0.08,
+	    fovy: 30.0
+	};
+	controller.target = controller.clamp(controller.target);
+	controller.current = controller.target;
+	controller
+    }
+
+    pub fn state(&self) -> OrbitState {
+	self.current
+    }
+
+    pub fn target(&self) -> OrbitState {
+	self.target
+    }
+
+    fn clamp(&self, state:OrbitState) -> OrbitState {
+	OrbitState {
+	    distance: state.distance.clamp(self.limits.min_distance, self.limits.max_distance),
+	    theta: state.theta.clamp(self.limits.min_theta, self.limits.max_theta),
+	    ..state
+	}
+    }
+
+    pub fn rotate(&mut self, delta_theta:f64, delta_phi:f64) {
+	self.target.theta += delta_theta;
+	self.target.phi += delta_phi;
+	self.target = self.clamp(self.target);
+    }
+
+    /* 画面上でright,up方向に動かす */
+    pub fn pan(&mut self, right:f64, up:f64) {
+	let (x, y, _) = camera_basis(self.target.eye() - self.target.center, WORLD_UP);
+	self.target.center = self.target.center + right*x + up*y;
+    }
+
+    pub fn zoom(&mut self, steps:f64) {
+	self.target.distance *= (-self.zoom_speed*steps).exp();
+	self.target = self.clamp(self.target);
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &'static str {
+	"orbit"
+    }
+
+    fn mouse_button(&mut self, button:MouseButton, pressed:bool) {
+	self.pointer.button(button, pressed);
+    }
+
+    fn cursor_moved(&mut self, x:f64, y:f64) {
+	match self.pointer.moved(x, y) {
+	    Some((MouseButton::Left, dx, dy)) => {
+		let speed = self.rotate_speed;
+		self.rotate(-dy*speed, -dx*speed);
+	    },
+	    Some((_, dx, dy)) => {
+		//掴んだ点がカーソルに付いてくるように,視点は逆向きに動かす
+		let scale = self.pointer.world_per_pixel(self.target.distance, self.fovy);
+		self.pan(-dx*scale, dy*scale);
+	    },
+	    None => {}
+	}
+    }
+
+    fn scroll(&mut self, delta:f64) {
+	self.zoom(delta);
+    }
+
+    fn analog(&mut self, input:AnalogInput) {
+	self.analog = input;
+    }
+
+    fn set_viewport(&mut self, width:f64, height:f64) {
+	self.pointer.viewport = (width, height);
+    }
+
+    fn update(&mut self, dt:f64) {
+	//見回しはドラッグと同じ向きに回し,左右と上下の移動は平行移動,前後は近づく
+	let AnalogInput { look, movement, zoom } = self.analog;
+	if !self.analog.is_zero() {
+	    self.rotate(-look.1*dt, -look.0*dt);
+	    let scale = self.pointer.world_per_screen(self.target.distance, self.fovy)*dt;
+	    self.pan(movement.0*scale, movement.2*scale);
+	    self.zoom((zoom + movement.1/self.zoom_speed)*dt);
+	}
+	let t = smoothing_factor(self.smoothing, dt);
+	self.current = OrbitState {
+	    center: lerp(self.current.center, self.target.center, t),
+	    distance: self.current.distance + t*(self.target.distance - self.current.distance),
+	    theta: self.current.theta + t*(self.target.theta - self.current.theta),
+	    phi: self.current.phi + t*(self.target.phi - self.current.phi)
+	};
+    }
+
+    fn set_look_at(&mut self, eye:Vector3, center:Vector3, up:Vector3) {
+	if !is_valid_look_at(eye, center, up) {
+	    return;
+	}
+	let offset = eye - center;
+	let distance = offset.length();
+	let theta = (offset.2/distance).clamp(-1.0, 1.0).acos().to_degrees();
+	let phi = offset.1.atan2(offset.0).to_degrees();
+	self.target = self.clamp(OrbitState { center, distance, theta, phi });
+	self.current = self.target;
+    }
+
+    fn eye_center_up(&self) -> (Vector3, Vector3, Vector3) {
+	(self.current.eye(), self.current.center, WORLD_UP)
+    }
+}
+
+/* 注視点から視点へのベクトルと上方向 */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct ArcballState {
+    pub center: Vector3,
+    pub offset: Vector3,
+    pub up: Vector3
+}
+
+/*
+  画面に内接する仮想の球を掴んで回す. upも一緒に回るので,真上や真下を越えて回せる.
+  左ドラッグで回転,右か中ドラッグで平行移動,スクロールで近づく
+*/
+#[derive(Debug,Clone,PartialEq)]
+pub struct ArcballController {
+    target: ArcballState,
+    current: ArcballState,
+    pointer: Pointer,
+    analog: AnalogInput,
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub zoom_speed: f64,
+    pub smoothing: f64,
+    pub fovy: f64
+}
+
+/* ウィンドウ座標を,画面に内接する半径1の球の上の点にする. 球の外は縁に寄せる */
+pub fn arcball_point(x:f64, y:f64, width:f64, height:f64) -> Vector3 {
+    let size = width.min(height).max(1.0);
+    let px = (2.0*x - width)/size;
+    let py = (height - 2.0*y)/size;
+    let d2 = px*px + py*py;
+    if d2 <= 1.0 {
+	Vector3(px, py, (1.0 - d2).sqrt())
+    }
+    else {
+	Vector3(px, py, 0.0)/d2.sqrt()
+    }
+}
+
+impl ArcballController {
+    pub fn new(eye:Vector3, center:Vector3, up:Vector3) -> Self {
+	let mut controller = ArcballController {
+	    target: ArcballState { center, offset: eye - center, up },
+	    current: ArcballState { center, offset: eye - center, up },
+	    pointer: Pointer::new(),
+	    analog: AnalogInput::default(),
+	    min_distance: 0.5,
+	    max_distance: 50.0,
+	    zoom_speed: 0.1,
+	    smoothing: 0.08,
+	    fovy: 30.0
+	};
+	controller.set_look_at(eye, center, up);
+	controller
+    }
+
+    pub fn state(&self) -> ArcballState {
+	self.current
+    }
+
+    /* 球の上の点fromをtoへ動かすように物体を回す. 視点は逆に回る */
+    pub fn drag(&mut self, from:Vector3, to:Vector3) {
+	let axis = Vector3::cross_product(from, to);
+	if axis.length() < 1.0e-9 {
+	    return;
+	}
+	let angle = (from*to).clamp(-1.0, 1.0).acos().to_degrees();
+	let (x, y, z) = camera_basis(self.target.offset, self.target.up);
+	let world_axis = axis.0*x + axis.1*y + axis.2*z;
+	let rotation = Quaternion::from_axis_angle(world_axis, -angle);
+	self.target.offset = rotation.rotate_vector(self.target.offset);
+	self.target.up = rotation.rotate_vector(y);
+    }
+
+    pub fn pan(&mut self, right:f64, up:f64) {
+	let (x, y, _) = camera_basis(self.target.offset, self.target.up);
+	self.target.center = self.target.center + right*x + up*y;
+    }
+
+    pub fn zoom(&mut self, steps:f64) {
+	let distance = (self.target.offset.length()*(-self.zoom_speed*steps).exp())
+	    .clamp(self.min_distance, self.max_distance);
+	self.target.offset = distance*self.target.offset.normalize();
+    }
+}
+
+impl CameraController for ArcballController {
+    fn name(&self) -> &'static str {
+	"arcball"
+    }
+
+    fn mouse_button(&mut self, button:MouseButton, pressed:bool) {
+	self.pointer.button(button, pressed);
+    }
+
+    fn cursor_moved(&mut self, x:f64, y:f64) {
+	let previous = self.pointer.position;
+	let (width, height) = self.pointer.viewport;
+	match self.pointer.moved(x, y) {
+	    Some((MouseButton::Left, _, _)) => {
+		self.drag(arcball_point(previous.0, previous.1, width, height), arcball_point(x, y, width, height));
+	    },
+	    Some((_, dx, dy)) => {
+		let scale = self.pointer.world_per_pixel(self.target.offset.length(), self.fovy);
+		self.pan(-dx*scale, dy*scale);
+	    },
+	    None => {}
+	}
+    }
+
+    fn scroll(&mut self, delta:f64) {
+	self.zoom(delta);
+    }
+
+    fn analog(&mut self, input:AnalogInput) {
+	self.analog = input;
+    }
+
+    fn set_viewport(&mut self, width:f64, height:f64) {
+	self.pointer.viewport = (width, height);
+    }
+
+    fn update(&mut self, dt:f64) {
+	let AnalogInput { look, movement, zoom } = self.analog;
+	if !self.analog.is_zero() {
+	    //球の正面の点を見回す角度だけ動かしたのと同じにする
+	    let (yaw, pitch) = ((look.0*dt).to_radians(), (look.1*dt).to_radians());
+	    self.drag(Vector3(0.0, 0.0, 1.0), Vector3(yaw.tan(), -pitch.tan(), 1.0).normalize());
+	    let scale = self.pointer.world_per_screen(self.target.offset.length(), self.fovy)*dt;
+	    self.pan(movement.0*scale, movement.2*scale);
+	    self.zoom((zoom + movement.1/self.zoom_speed)*dt);
+	}
+	let t = smoothing_factor(self.smoothing, dt);
+	let (from, to) = (self.current, self.target);
+	let distance = from.offset.length() + t*(to.offset.length() - from.offset.length());
+	//向きと長さを分けて補間し,upは視線と直交させなおす
+	let offset = distance*lerp(from.offset.normalize(), to.offset.normalize(), t).normalize();
+	let (_, up, _) = camera_basis(offset, lerp(from.up, to.up, t));
+	self.current = ArcballState { center: lerp(from.center, to.center, t), offset, up };
+    }
+
+    fn set_look_at(&mut self, eye:Vector3, center:Vector3, up:Vector3) {
+	if !is_valid_look_at(eye, center, up) {
+	    return;
+	}
+	let offset = eye - center;
+	let distance = offset.length().clamp(self.min_distance, self.max_distance);
+	let offset = distance*offset.normalize();
+	let (_, up, _) = camera_basis(offset, up);
+	self.target = ArcballState { center, offset, up };
+	self.current = self.target;
+    }
+
+    fn eye_center_up(&self) -> (Vector3, Vector3, Vector3) {
+	(self.current.center + self.current.offset, self.current.center, self.current.up)
+    }
+}
+
+/* 視点の位置と向き. yawはx軸からz軸回り,pitchは水平からの角度(degree) */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct FlyState {
+    pub position: Vector3,
+    pub yaw: f64,
+    pub pitch: f64
+}
+
+impl FlyState {
+    pub fn forward(&self) -> Vector3 {
+	let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+	Vector3(pitch.cos()*yaw.cos(), pitch.cos()*yaw.sin(), pitch.sin())
+    }
+
+    /* 水平な右向き */
+    pub fn right(&self) -> Vector3 {
+	let yaw = self.yaw.to_radians();
+	Vector3(yaw.sin(), -yaw.cos(), 0.0)
+    }
+}
+
+/*
+  一人称視点で飛び回る. ドラッグで見回し,MoveKeyで向いている方向に進む.
+  スクロールで移動の速さを変える
+*/
+#[derive(Debug,Clone,PartialEq)]
+pub struct FlyController {
+    target: FlyState,
+    current: FlyState,
+    pointer: Pointer,
+    pressed: [bool;6],
+    analog: AnalogInput,
+    /* 1秒に進む距離 */
+    pub move_speed: f64,
+    /* 1ピクセルのドラッグで向きが変わる角度(degree) */
+    pub look_speed: f64,
+    /* pitchの上限. 90にするとupと視線が重なる */
+    pub max_pitch: f64,
+    pub smoothing: f64
+}
+
+impl FlyController {
+    pub fn new(position:Vector3, yaw:f64, pitch:f64) -> Self {
+	let state = FlyState { position, yaw, pitch: pitch.clamp(-89.0, 89.0) };
+	FlyController {
+	    target: state,
+	    current: state,
+	    pointer: Pointer::new(),
+	    pressed: [false;6],
+	    analog: AnalogInput::default(),
+	    move_speed: 2.0,
+	    look_speed: 0.2,
+	    max_pitch: 89.0,
+	    smoothing: 0.05
+	}
+    }
+
+    pub fn state(&self) -> FlyState {
+	self.current
+    }
+
+    pub fn look(&mut self, delta_yaw:f64, delta_pitch:f64) {
+	self.target.yaw += delta_yaw;
+	self.target.pitch = (self.target.pitch + delta_pitch).clamp(-self.max_pitch, self.max_pitch);
+    }
+
+    /* 押されているキーの方向の単位ベクトル. 何も押されていなければ0 */
+    fn move_direction(&self) -> Vector3 {
+	let forward = self.target.forward();
+	let right = self.target.right();
+	let direction = MOVE_KEYS.iter().zip(self.pressed.iter()).filter(|(_, pressed)| **pressed)
+	    .fold(Vector3(0.0, 0.0, 0.0), |sum, (key, _)| sum + match key {
+		MoveKey::Forward => forward,
+		MoveKey::Backward => -forward,
+		MoveKey::Right => right,
+		MoveKey::Left => -right,
+		MoveKey::Up => WORLD_UP,
+		MoveKey::Down => -WORLD_UP
+	    });
+	if direction.length() < 1.0e-9 { direction } else { direction.normalize() }
+    }
+}
+
+impl CameraController for FlyController {
+    fn name(&self) -> &'static str {
+	"fly"
+    }
+
+    fn mouse_button(&mut self, button:MouseButton, pressed:bool) {
+	self.pointer.button(button, pressed);
+    }
+
+    fn cursor_moved(&mut self, x:f64, y:f64) {
+	if let Some((_, dx, dy)) = self.pointer.moved(x, y) {
+	    let speed = self.look_speed;
+	    self.look(-dx*speed, -dy*speed);
+	}
+    }
+
+    fn scroll(&mut self, delta:f64) {
+	self.move_speed = (self.move_speed*(0.1*delta).exp()).clamp(0.01, 100.0);
+    }
+
+    fn key(&mut self, key:MoveKey, pressed:bool) {
+	if let Some(index) = MOVE_KEYS.iter().position(|k| *k == key) {
+	    self.pressed[index] = pressed;
+	}
+    }
+
+    fn analog(&mut self, input:AnalogInput) {
+	self.analog = input;
+    }
+
+    fn set_viewport(&mut self, width:f64, height:f64) {
+	self.pointer.viewport = (width, height);
+    }
+
+    fn update(&mut self, dt:f64) {
+	let AnalogInput { look, movement, zoom } = self.analog;
+	if !self.analog.is_zero() {
+	    self.look(-look.0*dt, -look.1*dt);
+	    self.scroll(zoom*dt);
+	}
+	//スティックの移動はキーの移動に足す. 両方で最も速い速さを超えないようにする
+	let stick = movement.0*self.target.right() + movement.1*self.target.forward() + movement.2*WORLD_UP;
+	let direction = self.move_direction() + stick;
+	let direction = if direction.length() > 1.0 { direction.normalize() } else { direction };
+	self.target.position = self.target.position + (self.move_speed*dt)*direction;
+	let t = smoothing_factor(self.smoothing, dt);
+	self.current = FlyState {
+	    position: lerp(self.current.position, self.target.position, t),
+	    yaw: self.current.yaw + t*(self.target.yaw - self.current.yaw),
+	    pitch: self.current.pitch + t*(self.target.pitch - self.current.pitch)
+	};
+    }
+
+    fn set_look_at(&mut self, eye:Vector3, center:Vector3, up:Vector3) {
+	if !is_valid_look_at(eye, center, up) {
+	    return;
+	}
+	let forward = (center - eye).normalize();
+	let pitch = forward.2.clamp(-1.0, 1.0).asin().to_degrees();
+	self.target = FlyState {
+	    position: eye,
+	    yaw: forward.1.atan2(forward.0).to_degrees(),
+	    pitch: pitch.clamp(-self.max_pitch, self.max_pitch)
+	};
+	self.current = self.target;
+    }
+
+    fn eye_center_up(&self) -> (Vector3, Vector3, Vector3) {
+	(self.current.position, self.current.position + self.current.forward(), WORLD_UP)
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct PanZoomState {
+    pub center: Vector3,
+    pub distance: f64
+}
+
+/*
+  向きを変えずに平行移動と拡大縮小だけをする. 図面や地図のように真上から見るときに使う.
+  どのボタンのドラッグでも平行移動,スクロールでカーソルの方ではなく画面の中心に向かって近づく
+*/
+#[derive(Debug,Clone,PartialEq)]
+pub struct PanZoomController {
+    target: PanZoomState,
+    current: PanZoomState,
+    pointer: Pointer,
+    analog: AnalogInput,
+    /* 視線の向き(単位ベクトル)と画面の上方向 */
+    direction: Vector3,
+    up: Vector3,
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub zoom_speed: f64,
+    pub smoothing: f64,
+    pub fovy: f64
+}
+
+impl PanZoomController {
+    pub fn new(eye:Vector3, center:Vector3, up:Vector3) -> Self {
+	let state = PanZoomState { center, distance: 1.0 };
+	let mut controller = PanZoomController {
+	    target: state,
+	    current: state,
+	    pointer: Pointer::new(),
+	    analog: AnalogInput::default(),
+	    direction: Vector3(0.0, 0.0, -1.0),
+	    up,
+	    min_distance: 0.5,
+	    max_distance: 50.0,
+	    zoom_speed: 0.1,
+	    smoothing: 0.08,
+	    fovy: 30.0
+	};
+	controller.set_look_at(eye, center, up);
+	controller
+    }
+
+    pub fn state(&self) -> PanZoomState {
+	self.current
+    }
+
+    pub fn pan(&mut self, right:f64, up:f64) {
+	let (x, y, _) = camera_basis(-self.direction, self.up);
+	self.target.center = self.target.center + right*x + up*y;
+    }
+
+    pub fn zoom(&mut self, steps:f64) {
+	self.target.distance = (self.target.distance*(-self.zoom_speed*steps).exp())
+	    .clamp(self.min_distance, self.max_distance);
+    }
+}
+
+impl CameraController for PanZoomController {
+    fn name(&self) -> &'static str {
+	"pan/zoom"
+    }
+
+    fn mouse_button(&mut self, button:MouseButton, pressed:bool) {
+	self.pointer.button(button, pressed);
+    }
+
+    fn cursor_moved(&mut self, x:f64, y:f64) {
+	if let Some((_, dx, dy)) = self.pointer.moved(x, y) {
+	    let scale = self.pointer.world_per_pixel(self.target.distance, self.fovy);
+	    self.pan(-dx*scale, dy*scale);
+	}
+    }
+
+    fn scroll(&mut self, delta:f64) {
+	self.zoom(delta);
+    }
+
+    fn analog(&mut self, input:AnalogInput) {
+	self.analog = input;
+    }
+
+    fn set_viewport(&mut self, width:f64, height:f64) {
+	self.pointer.viewport = (width, height);
+    }
+
+    fn update(&mut self, dt:f64) {
+	//向きは変えないので見回しは使わない
+	let AnalogInput { movement, zoom, .. } = self.analog;
+	if !self.analog.is_zero() {
+	    let scale = self.pointer.world_per_screen(self.target.distance, self.fovy)*dt;
+	    self.pan(movement.0*scale, movement.2*scale);
+	    self.zoom((zoom + movement.1/self.zoom_speed)*dt);
+	}
+	let t = smoothing_factor(self.smoothing, dt);
+	self.current = PanZoomState {
+	    center: lerp(self.current.center, self.target.center, t),
+	    distance: self.current.distance + t*(self.target.distance - self.current.distance)
+	};
+    }
+
+    fn set_look_at(&mut self, eye:Vector3, center:Vector3, up:Vector3) {
+	if !is_valid_look_at(eye, center, up) {
+	    return;
+	}
+	let offset = center - eye;
+	self.direction = offset.normalize();
+	let (_, y, _) = camera_basis(-self.direction, up);
+	self.up = y;
+	self.target = PanZoomState {
+	    center,
+	    distance: offset.length().clamp(self.min_distance, self.max_distance)
+	};
+	self.current = self.target;
+    }
+
+    fn eye_center_up(&self) -> (Vector3, Vector3, Vector3) {
+	(self.current.center - self.current.distance*self.direction, self.current.center, self.up)
+    }
+}
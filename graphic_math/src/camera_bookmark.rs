@@ -0,0 +1,181 @@
+use crate::camera::Camera;
+
+/*
+  名前をつけて覚えておく視点と,視点の間をなめらかに移るアニメーション.
+  serde featureを有効にするとRONのファイルに保存して次に起動したときに読み込める.
+  CameraもSerialize,Deserializeできるので,JSONなど他の形式にはserde_jsonなどでそのまま書ける
+*/
+
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraBookmark {
+    pub name: String,
+    pub camera: Camera
+}
+
+/* 追加した順に並べる. 名前は重ならない */
+#[derive(Debug,Clone,PartialEq,Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraBookmarks {
+    bookmarks: Vec<CameraBookmark>
+}
+
+impl CameraBookmarks {
+    pub fn new() -> Self {
+	CameraBookmarks { bookmarks: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+	self.bookmarks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.bookmarks.is_empty()
+    }
+
+    pub fn get(&self, index:usize) -> Option<&CameraBookmark> {
+	self.bookmarks.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, CameraBookmark> {
+	self.bookmarks.iter()
+    }
+
+    pub fn find(&self, name:&str) -> Option<usize> {
+	self.bookmarks.iter().position(|bookmark| bookmark.name == name)
+    }
+
+    /* 同じ名前があれば視点を上書きする. 位置を返す */
+    pub fn save(&mut self, name:&str, camera:Camera) -> usize {
+	match self.find(name) {
+	    Some(index) => {
+		self.bookmarks[index].camera = camera;
+		index
+	    },
+	    None => {
+		self.bookmarks.push(CameraBookmark { name: name.to_owned(), camera });
+		self.bookmarks.len() - 1
+	    }
+	}
+    }
+
+    /* 他のブックマークと同じ名前にはできない. できたらtrue */
+    pub fn rename(&mut self, index:usize, name:&str) -> bool {
+	if index >= self.bookmarks.len() || self.find(name).is_some_and(|found| found != index) {
+	    return false;
+	}
+	self.bookmarks[index].name = name.to_owned();
+	true
+    }
+
+    pub fn remove(&mut self, index:usize) -> Option<CameraBookmark> {
+	if index < self.bookmarks.len() {
+	    Some(self.bookmarks.remove(index))
+	}
+	else {
+	    None
+	}
+    }
+
+    /* まだ使われていない"view N"という名前 */
+    pub fn unused_name(&self) -> String {
+	(1..).map(|n| format!("view {}", n)).find(|name| self.find(name).is_none()).unwrap()
+    }
+}
+
+#[cfg(feature = "serde")]
+mod file {
+    use std::{fmt,fs,io};
+    use std::path::Path;
+    use super::CameraBookmarks;
+
+    #[derive(Debug)]
+    pub enum BookmarkError {
+	Io(io::Error),
+	/* RONとして読めない,または書けない */
+	Format(String)
+    }
+
+    impl fmt::Display for BookmarkError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	    match self {
+		BookmarkError::Io(e) => write!(f, "failed to access bookmark file. {}", e),
+		BookmarkError::Format(message) => write!(f, "invalid bookmark file. {}", message)
+	    }
+	}
+    }
+
+    impl std::error::Error for BookmarkError {}
+
+    impl From<io::Error> for BookmarkError {
+	fn from(e:io::Error) -> Self {
+	    BookmarkError::Io(e)
+	}
+    }
+
+    impl CameraBookmarks {
+	pub fn to_ron(&self) -> Result<String, BookmarkError> {
+	    ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+		.map_err(|e| BookmarkError::Format(e.to_string()))
+	}
+
+	pub fn from_ron(text:&str) -> Result<Self, BookmarkError> {
+	    ron::from_str(text).map_err(|e| BookmarkError::Format(e.to_string()))
+	}
+
+	pub fn save_file<P: AsRef<Path>>(&self, path:P) -> Result<(), BookmarkError> {
+	    fs::write(path, self.to_ron()?)?;
+	    Ok(())
+	}
+
+	/* ファイルがまだなければ空にする */
+	pub fn load_file<P: AsRef<Path>>(path:P) -> Result<Self, BookmarkError> {
+	    match fs::read_to_string(path) {
+		Ok(text) => CameraBookmarks::from_ron(&text),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CameraBookmarks::new()),
+		Err(e) => Err(BookmarkError::Io(e))
+	    }
+	}
+    }
+}
+
+#[cfg(feature = "serde")]
+pub use file::BookmarkError;
+
+/* 0-1を両端がなめらかになるように変える */
+fn smoothstep(t:f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    t*t*(3.0 - 2.0*t)
+}
+
+/* fromからtoへduration秒かけて移る. 毎フレームupdateで途中のカメラを得る */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct CameraTransition {
+    from: Camera,
+    to: Camera,
+    duration: f64,
+    elapsed: f64
+}
+
+impl CameraTransition {
+    pub fn new(from:Camera, to:Camera, duration:f64) -> Self {
+	CameraTransition { from, to, duration, elapsed: 0.0 }
+    }
+
+    pub fn target(&self) -> Camera {
+	self.to
+    }
+
+    pub fn is_finished(&self) -> bool {
+	self.elapsed >= self.duration
+    }
+
+    /* dtは秒. 終わったあとはtoを返す */
+    pub fn update(&mut self, dt:f64) -> Camera {
+	self.elapsed += dt;
+	if self.is_finished() {
+	    return self.to;
+	}
+	self.from.interpolate(&self.to, smoothstep(self.elapsed/self.duration))
+    }
+}
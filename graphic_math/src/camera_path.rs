@@ -0,0 +1,254 @@
+use std::ops::{Add,Sub,Mul};
+
+use linear_transform::{vector::*,quaternion::*};
+
+use crate::camera::{Camera,camera_basis};
+
+/*
+  キーフレームを通るカメラの動き. 視点と注視点,upとfovyを時刻で補間する.
+  再生はCameraPathPlayerで実時間に合わせて進め,動画に書き出すときはsample_frameで
+  フレーム番号から時刻を決めるので,何度描いても同じ位置になる
+*/
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathInterpolation {
+    Linear,
+    /* キーフレームを必ず通る. 接線は前後のキーフレームから決める */
+    CatmullRom,
+    /* キーフレームのhandleを制御点にする. handleがなければCatmullRomと同じ曲線 */
+    Bezier
+}
+
+/* カメラの向きの決め方 */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathOrientation {
+    /* 注視点とupも視点と同じ曲線で補間する */
+    LookAt,
+    /* 向きは四元数の球面線形補間. 注視点は視点から補間した距離だけ前にする */
+    Slerp
+}
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraKeyframe {
+    /* 秒 */
+    pub time: f64,
+    pub camera: Camera,
+    /*
+      Bezierのとき,このキーフレームから出ていく側の制御点の視点,注視点からのずれ.
+      入ってくる側は反対向きにする. Noneなら前後のキーフレームから決める
+    */
+    pub eye_handle: Option<Vector3>,
+    pub center_handle: Option<Vector3>
+}
+
+impl CameraKeyframe {
+    pub fn new(time:f64, camera:Camera) -> Self {
+	CameraKeyframe { time, camera, eye_handle: None, center_handle: None }
+    }
+}
+
+/* CatmullRomでp0,p1,p2,p3に掛ける重み */
+fn catmull_rom_weights(u:f64) -> [f64;4] {
+    let (u2, u3) = (u*u, u*u*u);
+    [0.5*(-u + 2.0*u2 - u3), 0.5*(2.0 - 5.0*u2 + 3.0*u3), 0.5*(u + 4.0*u2 - 3.0*u3), 0.5*(u3 - u2)]
+}
+
+/* 3次のBezierで始点,制御点2つ,終点に掛ける重み */
+fn bezier_weights(u:f64) -> [f64;4] {
+    let v = 1.0 - u;
+    [v*v*v, 3.0*v*v*u, 3.0*v*u*u, u*u*u]
+}
+
+/* p1からp2への区間のu(0-1)の位置. p0,p3は前後の点で,端では端の点を重ねる */
+fn curve<T>(interpolation:PathInterpolation, p:[T;4], handles:(Option<T>, Option<T>), u:f64) -> T
+where T: Copy + Add<Output=T> + Sub<Output=T>, f64: Mul<T, Output=T> {
+    let [p0, p1, p2, p3] = p;
+    match interpolation {
+	PathInterpolation::Linear => p1 + u*(p2 - p1),
+	PathInterpolation::CatmullRom => {
+	    let w = catmull_rom_weights(u);
+	    w[0]*p0 + w[1]*p1 + w[2]*p2 + w[3]*p3
+	},
+	PathInterpolation::Bezier => {
+	    //CatmullRomの接線の1/3が制御点までのずれになる
+	    let c1 = p1 + handles.0.unwrap_or_else(|| (1.0/6.0)*(p2 - p0));
+	    let c2 = p2 - handles.1.unwrap_or_else(|| (1.0/6.0)*(p3 - p1));
+	    let w = bezier_weights(u);
+	    w[0]*p1 + w[1]*c1 + w[2]*c2 + w[3]*p2
+	}
+    }
+}
+
+/* カメラの向きを表す回転. 回転後のz軸が後ろ向き,y軸が上になる */
+fn orientation(camera:&Camera) -> Quaternion {
+    let (x, y, z) = camera_basis(camera.eye - camera.center, camera.up);
+    Quaternion::from_basis(x, y, z)
+}
+
+/* キーフレームは時刻順に並べておく */
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+    pub interpolation: PathInterpolation,
+    pub orientation: PathOrientation
+}
+
+impl CameraPath {
+    pub fn new(interpolation:PathInterpolation, orientation:PathOrientation) -> Self {
+	CameraPath { keyframes: Vec::new(), interpolation, orientation }
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+	&self.keyframes
+    }
+
+    pub fn len(&self) -> usize {
+	self.keyframes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.keyframes.is_empty()
+    }
+
+    /* 時刻順の位置に入れる. 同じ時刻のキーフレームは置き換える */
+    pub fn add(&mut self, keyframe:CameraKeyframe) {
+	match self.keyframes.iter().position(|k| k.time >= keyframe.time) {
+	    Some(index) if self.keyframes[index].time == keyframe.time => self.keyframes[index] = keyframe,
+	    Some(index) => self.keyframes.insert(index, keyframe),
+	    None => self.keyframes.push(keyframe)
+	}
+    }
+
+    pub fn remove(&mut self, index:usize) -> Option<CameraKeyframe> {
+	if index < self.keyframes.len() {
+	    Some(self.keyframes.remove(index))
+	}
+	else {
+	    None
+	}
+    }
+
+    pub fn clear(&mut self) {
+	self.keyframes.clear();
+    }
+
+    pub fn start_time(&self) -> f64 {
+	self.keyframes.first().map_or(0.0, |k| k.time)
+    }
+
+    pub fn end_time(&self) -> f64 {
+	self.keyframes.last().map_or(0.0, |k| k.time)
+    }
+
+    pub fn duration(&self) -> f64 {
+	self.end_time() - self.start_time()
+    }
+
+    /* timeの位置のカメラ. 範囲の外は端のキーフレームのまま. キーフレームがなければNone */
+    pub fn sample(&self, time:f64) -> Option<Camera> {
+	let last = self.keyframes.len().checked_sub(1)?;
+	let i = self.keyframes.iter().rposition(|k| k.time <= time).unwrap_or(0).min(last.saturating_sub(1));
+	let j = (i + 1).min(last);
+	let (k1, k2) = (&self.keyframes[i], &self.keyframes[j]);
+	if i == j || k2.time <= k1.time {
+	    return Some(if time < k2.time { k1.camera } else { k2.camera });
+	}
+	let u = ((time - k1.time)/(k2.time - k1.time)).clamp(0.0, 1.0);
+	let (k0, k3) = (&self.keyframes[i.saturating_sub(1)], &self.keyframes[(j + 1).min(last)]);
+	let cameras = [k0.camera, k1.camera, k2.camera, k3.camera];
+	let points = |f:&dyn Fn(&Camera) -> Vector3| [f(&cameras[0]), f(&cameras[1]), f(&cameras[2]), f(&cameras[3])];
+	let scalars = |f:&dyn Fn(&Camera) -> f64| [f(&cameras[0]), f(&cameras[1]), f(&cameras[2]), f(&cameras[3])];
+
+	let eye = curve(self.interpolation, points(&|c| c.eye), (k1.eye_handle, k2.eye_handle), u);
+	let (center, up) = match self.orientation {
+	    PathOrientation::LookAt => {
+		let center = curve(self.interpolation, points(&|c| c.center), (k1.center_handle, k2.center_handle), u);
+		let up = curve(self.interpolation, points(&|c| c.up), (None, None), u);
+		(center, up.normalize())
+	    },
+	    PathOrientation::Slerp => {
+		let rotation = orientation(&k1.camera).slerp(orientation(&k2.camera), u);
+		let (d1, d2) = ((k1.camera.eye - k1.camera.center).length(), (k2.camera.eye - k2.camera.center).length());
+		let back = rotation.rotate_vector(Vector3(0.0, 0.0, 1.0));
+		(eye - (d1 + u*(d2 - d1))*back, rotation.rotate_vector(Vector3(0.0, 1.0, 0.0)))
+	    }
+	};
+	let mix = |a:f64, b:f64| a + u*(b - a);
+	Some(Camera {
+	    eye,
+	    center,
+	    up,
+	    fovy: curve(self.interpolation, scalars(&|c| c.fovy), (None, None), u),
+	    ortho_height: mix(k1.camera.ortho_height, k2.camera.ortho_height),
+	    near: mix(k1.camera.near, k2.camera.near),
+	    far: mix(k1.camera.far, k2.camera.far),
+	    ..k1.camera
+	})
+    }
+
+    /* fps枚/秒で書き出すときのフレーム数. 最後のキーフレームの時刻を含む */
+    pub fn frame_count(&self, fps:f64) -> usize {
+	if self.keyframes.is_empty() || fps <= 0.0 {
+	    return 0;
+	}
+	(self.duration()*fps + 1.0e-9).floor() as usize + 1
+    }
+
+    /* frame番目のフレームのカメラ. 時刻を足し合わせずに番号から求めるので誤差が溜まらない */
+    pub fn sample_frame(&self, frame:usize, fps:f64) -> Option<Camera> {
+	self.sample(self.start_time() + (frame as f64)/fps)
+    }
+}
+
+/* 実時間で再生する. 止めている間もtimeを変えれば好きな位置を見られる */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct CameraPathPlayer {
+    pub time: f64,
+    pub playing: bool,
+    /* 最後まで行ったら最初に戻る. falseなら最後で止まる */
+    pub looping: bool
+}
+
+impl CameraPathPlayer {
+    pub fn new() -> Self {
+	CameraPathPlayer { time: 0.0, playing: false, looping: false }
+    }
+
+    /* 最初から再生する */
+    pub fn play(&mut self, path:&CameraPath) {
+	self.time = path.start_time();
+	self.playing = true;
+    }
+
+    pub fn stop(&mut self) {
+	self.playing = false;
+    }
+
+    /* dtは秒. 再生中なら進めて,今の位置のカメラを返す. 止まっているときはNone */
+    pub fn update(&mut self, path:&CameraPath, dt:f64) -> Option<Camera> {
+	if !self.playing {
+	    return None;
+	}
+	self.time += dt;
+	if self.time > path.end_time() {
+	    if self.looping && path.duration() > 0.0 {
+		self.time = path.start_time() + (self.time - path.start_time()) % path.duration();
+	    }
+	    else {
+		self.time = path.end_time();
+		self.playing = false;
+	    }
+	}
+	path.sample(self.time)
+    }
+}
+
+impl Default for CameraPathPlayer {
+    fn default() -> Self {
+	CameraPathPlayer::new()
+    }
+}
@@ -0,0 +1,589 @@
+use std::collections::HashSet;
+
+use crate::camera::{MouseButton,MoveKey,CameraController,AnalogInput};
+
+/*
+  キーとマウスの状態と,名前をつけた操作(アクション)への割り当て.
+  押されているか,このステップで押されたか離されたかと,カーソルの移動量,スクロール量を持つ.
+  ウィンドウのライブラリには依存しないので,キーは名前で区別する. app_frameworkではglfw::KeyのDebug表示
+  ("W","Space","Escape"など)を名前にしてイベントを渡す.
+  ゲームパッドはイベントではなく毎フレームの状態(GamepadState)を渡し,前の状態との違いを押した,離したにする.
+  割り当てはActionMapにまとめ,serde featureを有効にするとRONのファイルから読み込める
+*/
+
+#[derive(Debug,Clone,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Button {
+    Key(String),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton)
+}
+
+impl Button {
+    pub fn key(name:&str) -> Self {
+	Button::Key(name.to_owned())
+    }
+}
+
+/* GLFWのゲームパッドの割り当て(SDLのgamecontrollerdb)と同じ並び. Aは下,Bは右のボタン */
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    LeftBumper,
+    RightBumper,
+    Back,
+    Start,
+    Guide,
+    LeftThumb,
+    RightThumb,
+    DpadUp,
+    DpadRight,
+    DpadDown,
+    DpadLeft
+}
+
+pub const GAMEPAD_BUTTONS: [GamepadButton;15] = [
+    GamepadButton::A, GamepadButton::B, GamepadButton::X, GamepadButton::Y,
+    GamepadButton::LeftBumper, GamepadButton::RightBumper,
+    GamepadButton::Back, GamepadButton::Start, GamepadButton::Guide,
+    GamepadButton::LeftThumb, GamepadButton::RightThumb,
+    GamepadButton::DpadUp, GamepadButton::DpadRight, GamepadButton::DpadDown, GamepadButton::DpadLeft
+];
+
+/* スティックは-1から1で右,下が正. トリガーは離して0,引ききって1 */
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger
+}
+
+pub const GAMEPAD_AXES: [GamepadAxis;6] = [
+    GamepadAxis::LeftX, GamepadAxis::LeftY, GamepadAxis::RightX, GamepadAxis::RightY,
+    GamepadAxis::LeftTrigger, GamepadAxis::RightTrigger
+];
+
+/* ある時点のゲームパッドの状態. テストでは作った状態をInputState::set_gamepadに渡す */
+#[derive(Debug,Copy,Clone,PartialEq,Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadState {
+    pub buttons: [bool;15],
+    pub axes: [f64;6]
+}
+
+impl GamepadState {
+    pub fn new() -> Self {
+	GamepadState::default()
+    }
+
+    pub fn with_button(mut self, button:GamepadButton, pressed:bool) -> Self {
+	self.buttons[button as usize] = pressed;
+	self
+    }
+
+    pub fn with_axis(mut self, axis:GamepadAxis, value:f64) -> Self {
+	self.axes[axis as usize] = value;
+	self
+    }
+
+    pub fn is_pressed(&self, button:GamepadButton) -> bool {
+	self.buttons[button as usize]
+    }
+
+    pub fn axis(&self, axis:GamepadAxis) -> f64 {
+	self.axes[axis as usize]
+    }
+}
+
+/*
+  これより小さい入力は0にする. スティックは中心からの距離で判定し,残りを0から1に広げなおす.
+  使い込んだスティックは離しても中心に戻りきらないので,既定では少し大きめにする
+*/
+#[derive(Debug,Copy,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeadZone {
+    pub stick: f64,
+    pub trigger: f64
+}
+
+impl Default for DeadZone {
+    fn default() -> Self {
+	DeadZone { stick: 0.2, trigger: 0.1 }
+    }
+}
+
+fn rescale(length:f64, dead_zone:f64) -> f64 {
+    if length <= dead_zone {
+	0.0
+    }
+    else {
+	((length - dead_zone)/(1.0 - dead_zone).max(1.0e-9)).min(1.0)
+    }
+}
+
+impl DeadZone {
+    /* 2軸をまとめて. 斜めに倒しても軸ごとに切るより自然に動く */
+    pub fn apply_stick(&self, x:f64, y:f64) -> (f64,f64) {
+	let length = (x*x + y*y).sqrt();
+	if length <= self.stick {
+	    (0.0, 0.0)
+	}
+	else {
+	    let scale = rescale(length, self.stick)/length;
+	    (x*scale, y*scale)
+	}
+    }
+
+    pub fn apply_trigger(&self, value:f64) -> f64 {
+	rescale(value.max(0.0), self.trigger)
+    }
+
+    pub fn apply(&self, state:&GamepadState) -> GamepadState {
+	let mut result = *state;
+	for (x, y) in [(GamepadAxis::LeftX, GamepadAxis::LeftY), (GamepadAxis::RightX, GamepadAxis::RightY)].iter() {
+	    let (vx, vy) = self.apply_stick(state.axis(*x), state.axis(*y));
+	    result = result.with_axis(*x, vx).with_axis(*y, vy);
+	}
+	for axis in [GamepadAxis::LeftTrigger, GamepadAxis::RightTrigger].iter() {
+	    result = result.with_axis(*axis, self.apply_trigger(state.axis(*axis)));
+	}
+	result
+    }
+}
+
+/* つながった,外れた */
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum GamepadConnection {
+    Connected,
+    Disconnected
+}
+
+/* 軸の値の元 */
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AxisSource {
+    /* カーソルの移動量(ピクセル). 右,下が正 */
+    CursorX,
+    CursorY,
+    /* 上に回すと正 */
+    Scroll,
+    /* 1つ目が押されていれば-1,2つ目なら1 */
+    Buttons(Button, Button),
+    /* 遊びを除いた値 */
+    Gamepad(GamepadAxis)
+}
+
+#[derive(Debug,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AxisBinding {
+    pub source: AxisSource,
+    pub scale: f64
+}
+
+impl AxisBinding {
+    pub fn new(source:AxisSource, scale:f64) -> Self {
+	AxisBinding { source, scale }
+    }
+}
+
+/* アクションの名前と割り当て. 1つのアクションに複数割り当てられる */
+#[derive(Debug,Clone,PartialEq,Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ActionMap {
+    buttons: Vec<(String, Vec<Button>)>,
+    axes: Vec<(String, Vec<AxisBinding>)>
+}
+
+/* drive_cameraが使うアクション */
+pub const ORBIT: &str = "orbit";
+pub const PAN: &str = "pan";
+pub const ZOOM: &str = "zoom";
+/* 押している間続く入力. AnalogInputと同じ単位 */
+pub const LOOK_X: &str = "look_x";
+pub const LOOK_Y: &str = "look_y";
+pub const MOVE_X: &str = "move_x";
+pub const MOVE_Y: &str = "move_y";
+pub const MOVE_Z: &str = "move_z";
+pub const ZOOM_RATE: &str = "zoom_rate";
+pub const MOVE_ACTIONS: [(&str, MoveKey);6] = [
+    ("move_forward", MoveKey::Forward),
+    ("move_backward", MoveKey::Backward),
+    ("move_left", MoveKey::Left),
+    ("move_right", MoveKey::Right),
+    ("move_up", MoveKey::Up),
+    ("move_down", MoveKey::Down)
+];
+
+fn set_bindings<T>(list:&mut Vec<(String, Vec<T>)>, name:&str, bindings:Vec<T>) {
+    match list.iter_mut().find(|(n, _)| n == name) {
+	Some(entry) => entry.1 = bindings,
+	None => list.push((name.to_owned(), bindings))
+    }
+}
+
+fn bindings<'a, T>(list:&'a [(String, Vec<T>)], name:&str) -> &'a [T] {
+    list.iter().find(|(n, _)| n == name).map_or(&[], |(_, b)| b.as_slice())
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+	ActionMap { buttons: Vec::new(), axes: Vec::new() }
+    }
+
+    /*
+      exampleの視点操作の既定. 左ドラッグで回転,右ドラッグで平行移動,スクロールで拡大,WASDQEで移動.
+      ゲームパッドは右スティックで見回し,左スティックで移動,LB,RBで下と上,トリガーで拡大と縮小
+    */
+    pub fn camera_defaults() -> Self {
+	let mut map = ActionMap::new()
+	    .button(ORBIT, Button::Mouse(MouseButton::Left))
+	    .button(PAN, Button::Mouse(MouseButton::Right))
+	    .axis(ZOOM, AxisBinding::new(AxisSource::Scroll, 1.0));
+	for ((action, _), key) in MOVE_ACTIONS.iter().zip(["W", "S", "A", "D", "E", "Q"].iter()) {
+	    map = map.button(action, Button::key(key));
+	}
+	map.axis(LOOK_X, AxisBinding::new(AxisSource::Gamepad(GamepadAxis::RightX), 90.0))
+	    .axis(LOOK_Y, AxisBinding::new(AxisSource::Gamepad(GamepadAxis::RightY), 90.0))
+	    .axis(MOVE_X, AxisBinding::new(AxisSource::Gamepad(GamepadAxis::LeftX), 1.0))
+	    //スティックは下が正なので,前に倒すと前に進むように反転する
+	    .axis(MOVE_Y, AxisBinding::new(AxisSource::Gamepad(GamepadAxis::LeftY), -1.0))
+	    .axis(MOVE_Z, AxisBinding::new(AxisSource::Buttons(Button::Gamepad(GamepadButton::LeftBumper),
+								 Button::Gamepad(GamepadButton::RightBumper)), 1.0))
+	    .axis(ZOOM_RATE, AxisBinding::new(AxisSource::Gamepad(GamepadAxis::RightTrigger), 5.0))
+	    .axis(ZOOM_RATE, AxisBinding::new(AxisSource::Gamepad(GamepadAxis::LeftTrigger), -5.0))
+    }
+
+    /* 割り当てを足す */
+    pub fn button(mut self, action:&str, button:Button) -> Self {
+	let mut buttons = self.buttons(action).to_vec();
+	buttons.push(button);
+	self.set_buttons(action, buttons);
+	self
+    }
+
+    pub fn axis(mut self, action:&str, binding:AxisBinding) -> Self {
+	let mut axes = self.axis_bindings(action).to_vec();
+	axes.push(binding);
+	self.set_axis(action, axes);
+	self
+    }
+
+    /* 前の割り当てを置き換える */
+    pub fn set_buttons(&mut self, action:&str, buttons:Vec<Button>) {
+	set_bindings(&mut self.buttons, action, buttons);
+    }
+
+    pub fn set_axis(&mut self, action:&str, bindings:Vec<AxisBinding>) {
+	set_bindings(&mut self.axes, action, bindings);
+    }
+
+    pub fn buttons(&self, action:&str) -> &[Button] {
+	bindings(&self.buttons, action)
+    }
+
+    pub fn axis_bindings(&self, action:&str) -> &[AxisBinding] {
+	bindings(&self.axes, action)
+    }
+
+    pub fn button_actions(&self) -> impl Iterator<Item=&str> {
+	self.buttons.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn axis_actions(&self) -> impl Iterator<Item=&str> {
+	self.axes.iter().map(|(name, _)| name.as_str())
+    }
+
+    /* otherにあるアクションはotherの割り当てにする. ファイルの設定で既定を上書きするのに使う */
+    pub fn merge(&mut self, other:&ActionMap) {
+	for (name, buttons) in other.buttons.iter() {
+	    self.set_buttons(name, buttons.clone());
+	}
+	for (name, axes) in other.axes.iter() {
+	    self.set_axis(name, axes.clone());
+	}
+    }
+}
+
+#[cfg(feature = "serde")]
+mod file {
+    use std::{fmt,fs,io};
+    use std::path::Path;
+    use super::ActionMap;
+
+    #[derive(Debug)]
+    pub enum ActionMapError {
+	Io(io::Error),
+	/* RONとして読めない,または書けない */
+	Format(String)
+    }
+
+    impl fmt::Display for ActionMapError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	    match self {
+		ActionMapError::Io(e) => write!(f, "failed to access input binding file. {}", e),
+		ActionMapError::Format(message) => write!(f, "invalid input binding file. {}", message)
+	    }
+	}
+    }
+
+    impl std::error::Error for ActionMapError {}
+
+    impl From<io::Error> for ActionMapError {
+	fn from(e:io::Error) -> Self {
+	    ActionMapError::Io(e)
+	}
+    }
+
+    impl ActionMap {
+	pub fn to_ron(&self) -> Result<String, ActionMapError> {
+	    ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+		.map_err(|e| ActionMapError::Format(e.to_string()))
+	}
+
+	pub fn from_ron(text:&str) -> Result<Self, ActionMapError> {
+	    ron::from_str(text).map_err(|e| ActionMapError::Format(e.to_string()))
+	}
+
+	pub fn save_file<P: AsRef<Path>>(&self, path:P) -> Result<(), ActionMapError> {
+	    fs::write(path, self.to_ron()?)?;
+	    Ok(())
+	}
+
+	pub fn load_file<P: AsRef<Path>>(path:P) -> Result<Self, ActionMapError> {
+	    ActionMap::from_ron(&fs::read_to_string(path)?)
+	}
+    }
+}
+
+#[cfg(feature = "serde")]
+pub use file::ActionMapError;
+
+/*
+  押されたこと,離されたこと,移動量とスクロール量は,end_stepを呼ぶまで溜めておく.
+  app_frameworkはApp::updateを1回呼ぶごとにend_stepを呼ぶので,押したことはどれか1回のupdateにだけ伝わる
+*/
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct InputState {
+    down: HashSet<Button>,
+    pressed: HashSet<Button>,
+    released: HashSet<Button>,
+    cursor: Option<(f64,f64)>,
+    cursor_delta: (f64,f64),
+    scroll: f64,
+    /* eguiの入力欄などが使っている間はキーやマウスの押下を受け付けない */
+    ui_keyboard: bool,
+    ui_pointer: bool,
+    /* 遊びを除いたゲームパッドの状態. つながっていなければNone */
+    gamepad: Option<GamepadState>,
+    gamepad_connection: Option<GamepadConnection>,
+    pub dead_zone: DeadZone
+}
+
+impl InputState {
+    pub fn new() -> Self {
+	InputState::default()
+    }
+
+    /* 毎フレームイベントを渡す前に,eguiのwants_keyboard_input,wants_pointer_inputを渡す */
+    pub fn set_ui_focus(&mut self, keyboard:bool, pointer:bool) {
+	self.ui_keyboard = keyboard;
+	self.ui_pointer = pointer;
+    }
+
+    /* 押下はUIが使っていれば捨てるが,離したことは必ず受け付けて押したままにならないようにする */
+    pub fn button_event(&mut self, button:Button, pressed:bool) {
+	if pressed {
+	    //eguiはゲームパッドを使わないので,いつでも受け付ける
+	    let ui_focus = match button {
+		Button::Key(_) => self.ui_keyboard,
+		Button::Mouse(_) => self.ui_pointer,
+		Button::Gamepad(_) => false
+	    };
+	    if !ui_focus && self.down.insert(button.clone()) {
+		self.pressed.insert(button);
+	    }
+	}
+	else if self.down.remove(&button) {
+	    self.released.insert(button);
+	}
+    }
+
+    /* ウィンドウ座標(左上が原点,下向きがy). 最初の位置では移動量にしない */
+    pub fn cursor_moved(&mut self, x:f64, y:f64) {
+	if let Some((px, py)) = self.cursor {
+	    self.cursor_delta.0 += x - px;
+	    self.cursor_delta.1 += y - py;
+	}
+	self.cursor = Some((x, y));
+    }
+
+    pub fn scrolled(&mut self, delta:f64) {
+	if !self.ui_pointer {
+	    self.scroll += delta;
+	}
+    }
+
+    /*
+      毎フレーム,ゲームパッドの状態を渡す. つながっていなければNone.
+      ボタンは前の状態と比べて押した,離したにし,軸は遊びを除いて持っておく
+    */
+    pub fn set_gamepad(&mut self, state:Option<GamepadState>) {
+	match (self.gamepad.is_some(), state.is_some()) {
+	    (false, true) => self.gamepad_connection = Some(GamepadConnection::Connected),
+	    (true, false) => self.gamepad_connection = Some(GamepadConnection::Disconnected),
+	    _ => {}
+	}
+	//外れたときは押されていたボタンをすべて離す
+	let buttons = state.map_or([false;15], |state| state.buttons);
+	for (button, pressed) in GAMEPAD_BUTTONS.iter().zip(buttons.iter()) {
+	    let button = Button::Gamepad(*button);
+	    if *pressed != self.down.contains(&button) {
+		self.button_event(button, *pressed);
+	    }
+	}
+	self.gamepad = state.map(|state| self.dead_zone.apply(&state));
+    }
+
+    /* フォーカスが外れたときなど. 押されているものはすべて離したことにする */
+    pub fn release_all(&mut self) {
+	for button in self.down.drain() {
+	    self.released.insert(button);
+	}
+    }
+
+    pub fn end_step(&mut self) {
+	self.pressed.clear();
+	self.released.clear();
+	self.cursor_delta = (0.0, 0.0);
+	self.scroll = 0.0;
+	self.gamepad_connection = None;
+    }
+
+    pub fn is_down(&self, button:&Button) -> bool {
+	self.down.contains(button)
+    }
+
+    pub fn was_pressed(&self, button:&Button) -> bool {
+	self.pressed.contains(button)
+    }
+
+    pub fn was_released(&self, button:&Button) -> bool {
+	self.released.contains(button)
+    }
+
+    pub fn cursor(&self) -> Option<(f64,f64)> {
+	self.cursor
+    }
+
+    pub fn cursor_delta(&self) -> (f64,f64) {
+	self.cursor_delta
+    }
+
+    pub fn scroll(&self) -> f64 {
+	self.scroll
+    }
+
+    /* 遊びを除いた状態 */
+    pub fn gamepad(&self) -> Option<&GamepadState> {
+	self.gamepad.as_ref()
+    }
+
+    pub fn gamepad_axis(&self, axis:GamepadAxis) -> f64 {
+	self.gamepad.map_or(0.0, |state| state.axis(axis))
+    }
+
+    /* このステップでつながったか外れたか */
+    pub fn gamepad_connection(&self) -> Option<GamepadConnection> {
+	self.gamepad_connection
+    }
+}
+
+/* 状態と割り当てから,アクションの名前で問い合わせる */
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Input {
+    pub state: InputState,
+    pub map: ActionMap
+}
+
+impl Input {
+    pub fn new(map:ActionMap) -> Self {
+	Input { state: InputState::new(), map }
+    }
+
+    pub fn action_down(&self, action:&str) -> bool {
+	self.map.buttons(action).iter().any(|b| self.state.is_down(b))
+    }
+
+    /* 割り当てのどれかが押されて,他はまだ押されていなかった */
+    pub fn action_pressed(&self, action:&str) -> bool {
+	let buttons = self.map.buttons(action);
+	buttons.iter().any(|b| self.state.was_pressed(b))
+	    && buttons.iter().all(|b| !self.state.is_down(b) || self.state.was_pressed(b))
+    }
+
+    /* 割り当てのどれかが離されて,もうどれも押されていない */
+    pub fn action_released(&self, action:&str) -> bool {
+	self.map.buttons(action).iter().any(|b| self.state.was_released(b)) && !self.action_down(action)
+    }
+
+    /* 割り当ての値にscaleを掛けて足し合わせる */
+    pub fn axis(&self, action:&str) -> f64 {
+	self.map.axis_bindings(action).iter().map(|binding| {
+	    let value = match &binding.source {
+		AxisSource::CursorX => self.state.cursor_delta.0,
+		AxisSource::CursorY => self.state.cursor_delta.1,
+		AxisSource::Scroll => self.state.scroll,
+		AxisSource::Buttons(negative, positive) =>
+		    (self.state.is_down(positive) as i32 - self.state.is_down(negative) as i32) as f64,
+		AxisSource::Gamepad(axis) => self.state.gamepad_axis(*axis)
+	    };
+	    value*binding.scale
+	}).sum()
+    }
+}
+
+/*
+  ORBIT,PAN,ZOOM,MOVE_ACTIONSのアクションをCameraControllerの入力にする.
+  LOOK_X,LOOK_Y,MOVE_X,MOVE_Y,MOVE_Z,ZOOM_RATEはまとめてanalogで渡す.
+  updateごとに1回,controller.updateの前に呼ぶ
+*/
+pub fn drive_camera(input:&Input, controller:&mut dyn CameraController) {
+    //押した位置が分かるように,動いていなくても今の位置を渡す
+    if let Some((x, y)) = input.state.cursor() {
+	controller.cursor_moved(x, y);
+    }
+    for (action, button) in [(ORBIT, MouseButton::Left), (PAN, MouseButton::Right)].iter() {
+	if input.action_pressed(action) {
+	    controller.mouse_button(*button, true);
+	}
+	else if input.action_released(action) {
+	    controller.mouse_button(*button, false);
+	}
+    }
+    let zoom = input.axis(ZOOM);
+    if zoom != 0.0 {
+	controller.scroll(zoom);
+    }
+    for (action, key) in MOVE_ACTIONS.iter() {
+	if input.action_pressed(action) {
+	    controller.key(*key, true);
+	}
+	else if input.action_released(action) {
+	    controller.key(*key, false);
+	}
+    }
+    //離したときに止まるように0でも渡す
+    controller.analog(AnalogInput {
+	look: (input.axis(LOOK_X), input.axis(LOOK_Y)),
+	movement: (input.axis(MOVE_X), input.axis(MOVE_Y), input.axis(MOVE_Z)),
+	zoom: input.axis(ZOOM_RATE)
+    });
+}
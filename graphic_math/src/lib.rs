@@ -1,9 +1,706 @@
 pub mod graphic_math;
+pub mod scene_graph;
+pub mod shadow;
+pub mod camera;
+pub mod camera_bookmark;
+pub mod camera_path;
+pub mod animation;
+pub mod skeleton;
+pub mod timestep;
+pub mod input;
 
 #[cfg(test)]
 mod tests {
+    use linear_transform::{vector::*,matrix::*,quaternion::*};
+    use super::graphic_math;
+    use super::scene_graph::*;
+    use super::shadow::*;
+    use super::camera::*;
+    use super::camera_bookmark::*;
+    use super::camera_path::*;
+    use super::animation::*;
+    use super::skeleton::*;
+    use super::timestep::*;
+    use super::input::*;
+
+    fn matrix_approx_eq(a:Matrix4x4, b:Matrix4x4) -> bool {
+	(0..4).all(|i| (0..4).all(|j| (a[i][j]-b[i][j]).abs() < 1.0e-9))
+    }
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn transform_matrix_is_translate_rotate_scale(){
+	let t = Transform {
+	    translation: Vector3(1.0, 2.0, 3.0),
+	    rotation: Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 45.0),
+	    scale: Vector3(0.5, 0.5, 0.5)
+	};
+	let expected = graphic_math::translate(Vector3(1.0, 2.0, 3.0))*
+	    graphic_math::rotate(Vector3(0.0, 0.0, 1.0), 45.0)*
+	    graphic_math::scale(Vector3(0.5, 0.5, 0.5));
+	assert!(matrix_approx_eq(t.matrix(), expected));
+    }
+
+    #[test]
+    fn scene_graph_world_matrix(){
+	let mut scene = SceneGraph::new();
+	let root = scene.root();
+	let sun = scene.add_node(root, "sun", Transform::from_translation(Vector3(1.0, 0.0, 0.0)));
+	let orbit = scene.add_node(sun, "orbit", Transform::from_rotation(Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 90.0)));
+	let planet = scene.add_node(orbit, "planet", Transform::from_translation(Vector3(2.0, 0.0, 0.0)));
+	scene.update_world_matrices();
+
+	let expected = graphic_math::translate(Vector3(1.0, 0.0, 0.0))*
+	    graphic_math::rotate(Vector3(0.0, 0.0, 1.0), 90.0)*
+	    graphic_math::translate(Vector3(2.0, 0.0, 0.0));
+	assert!(matrix_approx_eq(scene.world_matrix(planet), expected));
+	assert!((scene.world_position(planet)-Vector3(1.0, 2.0, 0.0)).length() < 1.0e-9);
+    }
+
+    #[test]
+    fn scene_graph_dirty_propagation(){
+	let mut scene = SceneGraph::new();
+	let root = scene.root();
+	let parent = scene.add_node(root, "parent", Transform::identity());
+	let child = scene.add_node(parent, "child", Transform::from_translation(Vector3(0.0, 1.0, 0.0)));
+	scene.update_world_matrices();
+	assert!(!scene.is_dirty(parent) && !scene.is_dirty(child));
+
+	scene.set_translation(parent, Vector3(5.0, 0.0, 0.0));
+	assert!(scene.is_dirty(parent));
+	assert!(!scene.is_dirty(child));
+	scene.update_world_matrices();
+	assert!((scene.world_position(child)-Vector3(5.0, 1.0, 0.0)).length() < 1.0e-9);
+    }
+
+    #[test]
+    fn scene_graph_reparent(){
+	let mut scene = SceneGraph::new();
+	let root = scene.root();
+	let a = scene.add_node(root, "a", Transform::from_translation(Vector3(1.0, 0.0, 0.0)));
+	let b = scene.add_node(root, "b", Transform::from_translation(Vector3(0.0, 1.0, 0.0)));
+	let c = scene.add_node(a, "c", Transform::from_translation(Vector3(0.0, 0.0, 1.0)));
+
+	assert!(!scene.set_parent(a, c));
+	assert!(scene.set_parent(c, b));
+	assert_eq!(scene.parent(c), Some(b));
+	assert!(scene.children(a).is_empty());
+	scene.update_world_matrices();
+	assert!((scene.world_position(c)-Vector3(0.0, 1.0, 1.0)).length() < 1.0e-9);
+	assert_eq!(scene.find("c"), Some(c));
+    }
+
+    #[test]
+    fn scene_graph_traverse_attachments(){
+	let mut scene = SceneGraph::new();
+	let root = scene.root();
+	let a = scene.add_node(root, "a", Transform::identity());
+	let b = scene.add_node(a, "b", Transform::identity());
+	scene.attach(a, Attachment::Mesh(0));
+	scene.attach(b, Attachment::Mesh(1));
+	scene.attach(b, Attachment::Light(0));
+	scene.update_world_matrices();
+
+	let mut visited = Vec::new();
+	scene.traverse(|_, node, _, attachments| {
+	    for at in attachments {
+		visited.push((node.name.clone(), *at));
+	    }
+	});
+	assert_eq!(visited, vec![("a".to_string(), Attachment::Mesh(0)),
+				 ("b".to_string(), Attachment::Mesh(1)),
+				 ("b".to_string(), Attachment::Light(0))]);
+    }
+
+    #[test]
+    fn cascade_splits_uniform_and_logarithmic(){
+	let uniform = cascade_splits(1.0, 9.0, 4, 0.0);
+	assert_eq!(uniform, vec![1.0, 3.0, 5.0, 7.0, 9.0]);
+	let logarithmic = cascade_splits(1.0, 16.0, 4, 1.0);
+	for (s, e) in logarithmic.iter().zip([1.0, 2.0, 4.0, 8.0, 16.0].iter()) {
+	    assert!((s-e).abs() < 1.0e-9);
+	}
+	let practical = cascade_splits(1.0, 11.0, 3, 0.5);
+	assert!(practical.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn directional_light_matrix_contains_frustum(){
+	let corners = frustum_corners(Vector3(3.0, 0.0, 1.0), Vector3(0.0, 0.0, 0.0), Vector3(0.0, 0.0, 1.0),
+				      30.0, 1.5, 1.0, 5.0);
+	let m = directional_light_matrix(Vector3(-1.0, -0.5, -2.0), &corners, 1024, 2.0);
+	for c in corners.iter() {
+	    let p = m*Vector4(c.0, c.1, c.2, 1.0);
+	    assert!(p.0.abs() <= 1.0+1.0e-9 && p.1.abs() <= 1.0+1.0e-9 && p.2.abs() <= 1.0+1.0e-9);
+	}
+    }
+
+    #[test]
+    fn directional_light_matrix_is_stable_under_sub_texel_moves(){
+	let direction = Vector3(-1.0, -0.5, -2.0);
+	let resolution = 1024;
+	let corners_at = |offset:Vector3| frustum_corners(Vector3(3.0, 0.0, 1.0) + offset, offset, Vector3(0.0, 0.0, 1.0),
+							  30.0, 1.5, 1.0, 5.0);
+	//ライトの視点の右方向に,1テクセルの1/10ずつ2テクセル分動かす
+	let view = graphic_math::look_at(Vector3(0.0, 0.0, 0.0), direction, Vector3(0.0, 0.0, 1.0));
+	let right = Vector3(view[0][0], view[0][1], view[0][2]);
+	let corners = corners_at(Vector3(0.0, 0.0, 0.0));
+	let center = (1.0/8.0)*corners.iter().fold(Vector3(0.0, 0.0, 0.0), |s, c| s + *c);
+	let radius = corners.iter().map(|c| (*c - center).length()).fold(0.0, f64::max);
+	let texel = 2.0*radius/(resolution as f64);
+	let matrices:Vec<Matrix4x4> = (0..20).map(|i| {
+	    directional_light_matrix(direction, &corners_at((0.1*texel*(i as f64))*right), resolution, 2.0)
+	}).collect();
+	let changes = matrices.windows(2).filter(|w| !matrix_approx_eq(w[0], w[1])).count();
+	assert!((1..=2).contains(&changes), "{} changes", changes);
+	assert!(matrices.windows(2).filter(|w| w[0] == w[1]).count() >= 17);
+    }
+
+    #[test]
+    fn spot_light_matrix_looks_along_direction(){
+	let m = spot_light_matrix(Vector3(0.0, 0.0, 2.0), Vector3(0.0, 0.0, -1.0), 30.0, 0.1, 10.0);
+	let on_axis = m*Vector4(0.0, 0.0, 0.0, 1.0);
+	assert!((on_axis.0/on_axis.3).abs() < 1.0e-9 && (on_axis.1/on_axis.3).abs() < 1.0e-9);
+	let depth = on_axis.2/on_axis.3;
+	assert!(depth > -1.0 && depth < 1.0);
+	/* 円錐の外側の点は画面外 */
+	let outside = m*Vector4(2.0, 0.0, 0.0, 1.0);
+	assert!((outside.0/outside.3).abs() > 1.0);
+    }
+
+    fn vector_approx_eq(a:Vector3, b:Vector3) -> bool {
+	(a-b).length() < 1.0e-9
+    }
+
+    #[test]
+    fn orbit_eye_is_spherical_coordinates_and_clamped(){
+	let mut orbit = OrbitController::new(Vector3(0.0, 0.0, 0.0), 4.0, 60.0, 45.0);
+	let (theta, phi) = (60.0f64.to_radians(), 45.0f64.to_radians());
+	let (eye, center, up) = orbit.eye_center_up();
+	assert!(vector_approx_eq(eye, 4.0*Vector3(theta.sin()*phi.cos(), theta.sin()*phi.sin(), theta.cos())));
+	assert!(vector_approx_eq(center, Vector3(0.0, 0.0, 0.0)) && vector_approx_eq(up, Vector3(0.0, 0.0, 1.0)));
+
+	orbit.smoothing = 0.0;
+	orbit.rotate(-200.0, 0.0);
+	orbit.zoom(1000.0);
+	orbit.update(0.016);
+	assert_eq!(orbit.state().theta, orbit.limits.min_theta);
+	assert_eq!(orbit.state().distance, orbit.limits.min_distance);
+
+	/* set_look_atは同じ視点を球面座標に戻す */
+	orbit.set_look_at(Vector3(1.0, 2.0, 3.0), Vector3(0.0, 1.0, 1.0), Vector3(0.0, 0.0, 1.0));
+	assert!(vector_approx_eq(orbit.eye_center_up().0, Vector3(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn set_look_at_ignores_degenerate_eye(){
+	let mut controllers:Vec<Box<dyn CameraController>> = vec![
+	    Box::new(OrbitController::new(Vector3(0.0, 0.0, 0.0), 4.0, 90.0, 0.0)),
+	    Box::new(ArcballController::new(Vector3(0.0, -4.0, 0.0), Vector3(0.0, 0.0, 0.0), Vector3(0.0, 0.0, 1.0))),
+	    Box::new(FlyController::new(Vector3(0.0, 0.0, 1.0), 90.0, 0.0)),
+	    Box::new(PanZoomController::new(Vector3(0.0, 0.0, 5.0), Vector3(0.0, 0.0, 0.0), Vector3(0.0, 1.0, 0.0)))
+	];
+	for controller in controllers.iter_mut() {
+	    let before = controller.eye_center_up();
+	    /* eyeとcenterが同じ位置,NaN,infの視点は無視して前の状態のままにする */
+	    controller.set_look_at(Vector3(1.0, 1.0, 1.0), Vector3(1.0, 1.0, 1.0), Vector3(0.0, 0.0, 1.0));
+	    controller.set_look_at(Vector3(f64::NAN, 0.0, 0.0), Vector3(0.0, 0.0, 0.0), Vector3(0.0, 0.0, 1.0));
+	    controller.set_look_at(Vector3(0.0, 0.0, 1.0), Vector3(f64::INFINITY, 0.0, 0.0), Vector3(0.0, 0.0, 1.0));
+	    let after = controller.eye_center_up();
+	    assert!(vector_approx_eq(before.0, after.0), "{}", controller.name());
+	    assert!(vector_approx_eq(before.1, after.1), "{}", controller.name());
+	    assert!(vector_approx_eq(before.2, after.2), "{}", controller.name());
+	}
+    }
+
+    #[test]
+    fn orbit_smoothing_approaches_target_and_pan_moves_eye_and_center(){
+	let mut orbit = OrbitController::new(Vector3(0.0, 0.0, 0.0), 4.0, 90.0, 0.0);
+	orbit.set_viewport(800.0, 600.0);
+	orbit.mouse_button(MouseButton::Right, true);
+	orbit.cursor_moved(100.0, 0.0);
+	orbit.mouse_button(MouseButton::Right, false);
+	orbit.cursor_moved(500.0, 500.0);
+	let target = orbit.target();
+	/* 視点は+x軸上なので,右に掴んで動かすと注視点は-y方向へ動く */
+	assert!(target.center.1 < 0.0 && target.center.0.abs() < 1.0e-9 && target.center.2.abs() < 1.0e-9);
+	orbit.update(0.016);
+	let halfway = orbit.state().center.1;
+	assert!(halfway < 0.0 && halfway > target.center.1);
+	for _ in 0..200 {
+	    orbit.update(0.016);
+	}
+	assert!(vector_approx_eq(orbit.state().center, target.center));
+	let (eye, center, _) = orbit.eye_center_up();
+	assert!(((eye-center).length()-4.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn arcball_drag_keeps_distance_and_orthogonal_up(){
+	assert!(vector_approx_eq(arcball_point(400.0, 300.0, 800.0, 600.0), Vector3(0.0, 0.0, 1.0)));
+	assert!((arcball_point(0.0, 0.0, 800.0, 600.0).length()-1.0).abs() < 1.0e-9);
+
+	let mut arcball = ArcballController::new(Vector3(0.0, -4.0, 0.0), Vector3(0.0, 0.0, 0.0), Vector3(0.0, 0.0, 1.0));
+	arcball.smoothing = 0.0;
+	/* 球の正面の点を右へ90度動かすと,視点は左へ90度回り込む */
+	arcball.drag(Vector3(0.0, 0.0, 1.0), Vector3(1.0, 0.0, 0.0));
+	arcball.update(0.016);
+	let (eye, center, up) = arcball.eye_center_up();
+	assert!(vector_approx_eq(eye, Vector3(-4.0, 0.0, 0.0)));
+	assert!(vector_approx_eq(center, Vector3(0.0, 0.0, 0.0)));
+	assert!(vector_approx_eq(up, Vector3(0.0, 0.0, 1.0)));
+
+	arcball.drag(Vector3(0.0, 0.0, 1.0), Vector3(0.0, 0.6, 0.8));
+	arcball.update(0.016);
+	let (eye, _, up) = arcball.eye_center_up();
+	assert!((eye.length()-4.0).abs() < 1.0e-9);
+	assert!((eye*up).abs() < 1.0e-9 && (up.length()-1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn fly_moves_along_view_direction_and_limits_pitch(){
+	let mut fly = FlyController::new(Vector3(0.0, 0.0, 1.0), 90.0, 0.0);
+	fly.smoothing = 0.0;
+	fly.key(MoveKey::Forward, true);
+	fly.update(0.5);
+	fly.key(MoveKey::Forward, false);
+	fly.update(0.5);
+	assert!(vector_approx_eq(fly.state().position, Vector3(0.0, 1.0, 1.0)));
+	let (eye, center, _) = fly.eye_center_up();
+	assert!(vector_approx_eq(center-eye, Vector3(0.0, 1.0, 0.0)));
+
+	fly.look(0.0, 120.0);
+	fly.update(0.016);
+	assert_eq!(fly.state().pitch, fly.max_pitch);
+
+	fly.set_look_at(Vector3(1.0, 1.0, 0.0), Vector3(1.0, 3.0, 2.0), Vector3(0.0, 0.0, 1.0));
+	let state = fly.state();
+	assert!((state.yaw-90.0).abs() < 1.0e-9 && (state.pitch-45.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn pan_zoom_keeps_direction(){
+	let mut pan_zoom = PanZoomController::new(Vector3(0.0, 0.0, 5.0), Vector3(0.0, 0.0, 0.0), Vector3(0.0, 1.0, 0.0));
+	pan_zoom.smoothing = 0.0;
+	pan_zoom.pan(1.0, 2.0);
+	pan_zoom.zoom(-1000.0);
+	pan_zoom.update(0.016);
+	let (eye, center, up) = pan_zoom.eye_center_up();
+	assert!(vector_approx_eq(center, Vector3(1.0, 2.0, 0.0)));
+	assert!(vector_approx_eq(eye, Vector3(1.0, 2.0, pan_zoom.max_distance)));
+	assert!(vector_approx_eq(up, Vector3(0.0, 1.0, 0.0)));
+	assert!((smoothing_factor(0.1, 0.1)-(1.0-(-1.0f64).exp())).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn camera_matrices_and_resize(){
+	let mut camera = Camera::from_spherical(Vector3(0.0, 0.0, 0.0), 4.0, 60.0, 45.0);
+	camera.resize(800, 400);
+	assert_eq!(camera.aspect, 2.0);
+	camera.resize(800, 0);
+	assert_eq!(camera.aspect, 2.0);
+	let expected = graphic_math::perspective(30.0, 2.0, 1.0, 11.0)*graphic_math::look_at(camera.eye, camera.center, camera.up);
+	assert!(matrix_approx_eq(camera.view_projection(), expected));
+
+	/* 平行投影では距離によらず,ortho_heightの高さが画面の上端になる */
+	camera.projection = ProjectionKind::Orthographic;
+	let top = camera.projection()*Vector4(0.0, camera.ortho_height, -5.0, 1.0);
+	assert!((top.1/top.3-1.0).abs() < 1.0e-9);
+
+	let orbit = OrbitController::new(Vector3(1.0, 0.0, 0.0), 2.0, 90.0, 90.0);
+	camera.follow(&orbit);
+	assert!(vector_approx_eq(camera.eye, Vector3(1.0, 2.0, 0.0)));
+	assert_eq!(camera.projection, ProjectionKind::Orthographic);
+    }
+
+    #[test]
+    fn camera_interpolation_and_transition(){
+	let from = Camera::from_spherical(Vector3(0.0, 0.0, 0.0), 2.0, 90.0, 0.0);
+	let mut to = Camera::from_spherical(Vector3(0.0, 0.0, 1.0), 4.0, 90.0, 90.0);
+	to.fovy = 50.0;
+	assert_eq!(from.interpolate(&to, 0.0), from);
+	let half = from.interpolate(&to, 0.5);
+	/* 注視点からの距離は平均になり,視点は注視点を回り込む */
+	assert!(vector_approx_eq(half.center, Vector3(0.0, 0.0, 0.5)));
+	assert!(((half.eye-half.center).length()-3.0).abs() < 1.0e-9);
+	assert!((half.fovy-40.0).abs() < 1.0e-9);
+
+	let mut transition = CameraTransition::new(from, to, 1.0);
+	let first = transition.update(0.25);
+	assert!((first.fovy-30.0).abs() < 20.0*0.25);
+	assert!(!transition.is_finished());
+	assert_eq!(transition.update(1.0), to);
+	assert!(transition.is_finished());
+    }
+
+    #[test]
+    fn camera_bookmarks(){
+	let mut bookmarks = CameraBookmarks::new();
+	let camera = Camera::from_spherical(Vector3(0.0, 0.0, 0.0), 4.0, 60.0, 45.0);
+	assert_eq!(bookmarks.unused_name(), "view 1");
+	assert_eq!(bookmarks.save("top", camera), 0);
+	assert_eq!(bookmarks.save("side", camera), 1);
+	let mut moved = camera;
+	moved.eye = Vector3(0.0, -4.0, 0.0);
+	/* 同じ名前は上書き */
+	assert_eq!(bookmarks.save("top", moved), 0);
+	assert_eq!(bookmarks.len(), 2);
+	assert_eq!(bookmarks.get(0).unwrap().camera, moved);
+	assert!(!bookmarks.rename(1, "top"));
+	assert!(bookmarks.rename(1, "front"));
+	assert_eq!(bookmarks.find("front"), Some(1));
+	assert_eq!(bookmarks.remove(0).unwrap().name, "top");
+	assert_eq!(bookmarks.remove(5), None);
+    }
+
+    #[test]
+    fn camera_path_interpolation(){
+	let key = |time:f64, eye:Vector3, fovy:f64| {
+	    let mut camera = Camera::new(eye, Vector3(0.0, 0.0, 0.0), Vector3(0.0, 0.0, 1.0));
+	    camera.fovy = fovy;
+	    CameraKeyframe::new(time, camera)
+	};
+	let mut path = CameraPath::new(PathInterpolation::Linear, PathOrientation::LookAt);
+	assert_eq!(path.sample(0.0), None);
+	path.add(key(2.0, Vector3(0.0, 4.0, 0.0), 50.0));
+	path.add(key(0.0, Vector3(4.0, 0.0, 0.0), 30.0));
+	path.add(key(4.0, Vector3(-4.0, 0.0, 0.0), 30.0));
+	assert_eq!(path.keyframes()[1].time, 2.0);
+	assert_eq!(path.duration(), 4.0);
+
+	/* どの補間でもキーフレームの時刻ではキーフレームの値になり,範囲の外は端の値 */
+	for interpolation in [PathInterpolation::Linear, PathInterpolation::CatmullRom, PathInterpolation::Bezier] {
+	    path.interpolation = interpolation;
+	    for k in path.keyframes() {
+		assert!(vector_approx_eq(path.sample(k.time).unwrap().eye, k.camera.eye));
+	    }
+	    assert!(vector_approx_eq(path.sample(-1.0).unwrap().eye, Vector3(4.0, 0.0, 0.0)));
+	    assert!(vector_approx_eq(path.sample(10.0).unwrap().eye, Vector3(-4.0, 0.0, 0.0)));
+	}
+	path.interpolation = PathInterpolation::Linear;
+	let mid = path.sample(1.0).unwrap();
+	assert!(vector_approx_eq(mid.eye, Vector3(2.0, 2.0, 0.0)));
+	assert!((mid.fovy-40.0).abs() < 1.0e-9);
+	/* CatmullRomは曲線になるので,同じ時刻で線分より外側を通る */
+	path.interpolation = PathInterpolation::CatmullRom;
+	assert!(path.sample(1.0).unwrap().eye.length() > mid.eye.length());
+	/* Bezierの制御点を自分で決める */
+	path.interpolation = PathInterpolation::Bezier;
+	let mut first = path.keyframes()[0];
+	first.eye_handle = Some(Vector3(0.0, 0.0, 3.0));
+	path.add(first);
+	assert!(path.sample(0.5).unwrap().eye.2 > 0.5);
+
+	/* Slerpでは注視点からの距離を保ったまま向きが回る */
+	path.orientation = PathOrientation::Slerp;
+	path.interpolation = PathInterpolation::Linear;
+	let camera = path.sample(3.0).unwrap();
+	assert!(vector_approx_eq(camera.center, camera.eye - 4.0*(camera.eye - camera.center).normalize()));
+	assert!(((camera.eye - camera.center).length()-4.0).abs() < 1.0e-9);
+	assert!((camera.up*Vector3(0.0, 0.0, 1.0)-1.0).abs() < 1.0e-9);
+
+	/* 書き出すフレームは番号だけで決まる */
+	assert_eq!(path.frame_count(30.0), 121);
+	assert_eq!(path.sample_frame(60, 30.0), path.sample(2.0));
+	let mut player = CameraPathPlayer::new();
+	player.looping = true;
+	player.play(&path);
+	player.update(&path, 5.0);
+	assert!((player.time-1.0).abs() < 1.0e-9);
+	player.looping = false;
+	assert_eq!(player.update(&path, 5.0), path.sample(4.0));
+	assert!(!player.playing);
+	assert_eq!(player.update(&path, 1.0), None);
+    }
+
+    #[test]
+    fn easing_functions(){
+	for easing in EASINGS.iter() {
+	    assert!(easing.apply(0.0).abs() < 1.0e-12, "{}", easing.name());
+	    assert!((easing.apply(1.0)-1.0).abs() < 1.0e-12, "{}", easing.name());
+	}
+	assert_eq!(Easing::Linear.apply(0.25), 0.25);
+	assert_eq!(Easing::Step.apply(0.99), 0.0);
+	assert!((Easing::CubicIn.apply(0.5)-0.125).abs() < 1.0e-12);
+	assert!((Easing::CubicInOut.apply(0.5)-0.5).abs() < 1.0e-12);
+	/* 行き過ぎるもの */
+	assert!((0..100).any(|i| Easing::ElasticOut.apply(i as f64/100.0) > 1.0));
+	assert!((0..100).any(|i| Easing::BackOut.apply(i as f64/100.0) > 1.0));
+	assert!((0..=100).all(|i| Easing::BounceOut.apply(i as f64/100.0) <= 1.0));
+    }
+
+    #[test]
+    fn animation_tracks_and_clips(){
+	let track = Track::new().key(1.0, 10.0, Easing::Linear).key(0.0, 0.0, Easing::QuadIn).key(2.0, 20.0, Easing::Linear);
+	assert_eq!(track.keyframes()[0].time, 0.0);
+	assert_eq!(track.sample(-1.0), Some(0.0));
+	assert_eq!(track.sample(0.5), Some(2.5));
+	assert_eq!(track.sample(1.5), Some(15.0));
+	assert_eq!(track.sample(3.0), Some(20.0));
+	assert_eq!(Track::<f64>::new().sample(0.0), None);
+
+	let rotation = Track::new().key(0.0, Quaternion::identity(), Easing::Linear)
+	    .key(1.0, Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 90.0), Easing::Linear);
+	let color = Track::new().key(0.0, [1.0f32, 0.0, 0.0, 1.0], Easing::Linear).key(1.0, [0.0, 0.0, 1.0, 1.0], Easing::Linear);
+	let mut clip = AnimationClip::new(2.0, PlaybackMode::Loop)
+	    .track("value", track)
+	    .track("position", Track::new().key(0.0, Vector3(0.0, 0.0, 0.0), Easing::Linear).key(2.0, Vector3(2.0, 0.0, 0.0), Easing::Linear))
+	    .track("rotation", rotation)
+	    .track("color", color);
+	assert_eq!(clip.track_names().count(), 4);
+	assert!(vector_approx_eq(clip.vector("position", 2.5).unwrap(), Vector3(0.5, 0.0, 0.0)));
+	let q = clip.rotation("rotation", 0.5).unwrap();
+	assert!(q.dot(Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 45.0)) > 1.0-1.0e-9);
+	assert_eq!(clip.color("color", 0.5), Some([0.5, 0.0, 0.5, 1.0]));
+	/* 型が違うときと,ないトラックはNone */
+	assert_eq!(clip.vector("value", 0.5), None);
+	assert_eq!(clip.scalar("missing", 0.5), None);
+
+	clip.mode = PlaybackMode::PingPong;
+	assert!((clip.local_time(2.5)-1.5).abs() < 1.0e-12);
+	assert!((clip.local_time(4.5)-0.5).abs() < 1.0e-12);
+	clip.mode = PlaybackMode::Once;
+	assert_eq!(clip.local_time(5.0), 2.0);
+	assert!(clip.is_finished(2.0));
+
+	let mut player = AnimationPlayer::new(clip);
+	player.speed = 2.0;
+	player.update(std::time::Duration::from_millis(250));
+	assert_eq!(player.scalar("value"), Some(2.5));
+	player.playing = false;
+	player.update(std::time::Duration::from_secs(1));
+	assert_eq!(player.time(), 0.5);
+	player.seek(10.0);
+	assert!(player.is_finished());
+	assert_eq!(player.scalar("value"), Some(20.0));
+    }
+
+    #[test]
+    fn skeleton_pose_and_cpu_skinning(){
+	/* 原点から+x向きに伸びる腕. 肘はx=1にある */
+	let mut skeleton = Skeleton::new();
+	let shoulder = skeleton.add_joint("shoulder", None, Transform::identity());
+	let elbow = skeleton.add_joint("elbow", Some(shoulder), Transform::from_translation(Vector3(1.0, 0.0, 0.0)));
+	assert_eq!(skeleton.find("elbow"), Some(elbow));
+	let rest = skeleton.rest_pose();
+	for m in skeleton.skinning_matrices(&rest) {
+	    assert!(matrix_approx_eq(m, Matrix4x4::identity()));
+	}
+
+	/* 肘を90度曲げると,先の頂点は+y向きに回る */
+	let mut clip = SkeletalClip::new(1.0, PlaybackMode::Once);
+	clip.set_rotation(elbow, Track::new()
+			  .key(0.0, Quaternion::identity(), Easing::Linear)
+			  .key(1.0, Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 90.0), Easing::Linear));
+	let pose = clip.sample(&skeleton, 1.0);
+	let worlds = skeleton.world_matrices(&pose);
+	let elbow_position = worlds[elbow]*Vector4(0.0, 0.0, 0.0, 1.0);
+	assert!(vector_approx_eq(Vector3(elbow_position.0, elbow_position.1, elbow_position.2), Vector3(1.0, 0.0, 0.0)));
+	let matrices = skeleton.skinning_matrices(&pose);
+	let hand = SkinWeights::single(elbow as u32);
+	assert!(vector_approx_eq(skin_position(Vector3(2.0, 0.0, 0.0), &hand, &matrices), Vector3(1.0, 1.0, 0.0)));
+	assert!(vector_approx_eq(skin_normal(Vector3(0.0, 1.0, 0.0), &hand, &matrices), Vector3(-1.0, 0.0, 0.0)));
+
+	/* 肘の上の頂点は半分ずつ従う. 重みは正規化される */
+	let joint = SkinWeights::new([shoulder as u32, elbow as u32, 0, 0], [2.0, 2.0, 0.0, 0.0]);
+	assert_eq!(joint.weights, [0.5, 0.5, 0.0, 0.0]);
+	assert!(vector_approx_eq(skin_position(Vector3(1.5, 0.0, 0.0), &joint, &matrices), Vector3(1.25, 0.25, 0.0)));
+	/* 途中の時刻はslerpした回転 */
+	let half = skeleton.skinning_matrices(&clip.sample(&skeleton, 0.5));
+	let p = skin_position(Vector3(2.0, 0.0, 0.0), &hand, &half);
+	let s = 0.5f64.sqrt();
+	assert!(vector_approx_eq(p, Vector3(1.0 + s, s, 0.0)));
+    }
+
+    #[test]
+    fn skeleton_inverse_bind_with_scale_and_rotation(){
+	let mut skeleton = Skeleton::new();
+	let root = skeleton.add_joint("root", None, Transform {
+	    translation: Vector3(1.0, 2.0, 3.0),
+	    rotation: Quaternion::from_axis_angle(Vector3(1.0, 1.0, 0.0), 30.0),
+	    scale: Vector3(2.0, 1.0, 0.5)
+	});
+	skeleton.add_joint("child", Some(root), Transform::from_rotation(Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 45.0)));
+	let rest = skeleton.rest_pose();
+	for (world, joint) in skeleton.world_matrices(&rest).iter().zip(skeleton.joints()) {
+	    assert!(matrix_approx_eq(*world*joint.inverse_bind, Matrix4x4::identity()));
+	}
+	/* 拡大縮小があっても法線は面に垂直なまま */
+	let mut pose = rest.clone();
+	pose.locals[root].scale = Vector3(1.0, 4.0, 1.0);
+	let matrices = skeleton.skinning_matrices(&pose);
+	let skin = SkinWeights::single(root as u32);
+	let (a, b) = (Vector3(1.0, 0.0, 0.0), Vector3(0.0, 0.0, 1.0));
+	let n = skin_normal(Vector3::cross_product(a, b), &skin, &matrices);
+	let origin = skin_position(Vector3(0.0, 0.0, 0.0), &skin, &matrices);
+	assert!((n*(skin_position(a, &skin, &matrices)-origin)).abs() < 1.0e-9);
+	assert!((n*(skin_position(b, &skin, &matrices)-origin)).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn fixed_timestep_accumulates_and_clamps(){
+	let ms = std::time::Duration::from_millis;
+	let mut timestep = FixedTimestep::new(ms(10));
+	assert_eq!(timestep.advance(ms(25)), 2);
+	assert!((timestep.alpha()-0.5).abs() < 1.0e-12);
+	assert_eq!(timestep.advance(ms(4)), 0);
+	assert_eq!(timestep.advance(ms(1)), 1);
+	assert_eq!(timestep.alpha(), 0.0);
+	assert_eq!(timestep.time(), ms(30));
+	/* 長く止まっていても上限までしか進めない */
+	let mut timestep = FixedTimestep::new(ms(10)).with_max_frame_time(ms(50));
+	assert_eq!(timestep.advance(std::time::Duration::from_secs(3)), 5);
+	assert_eq!(timestep.steps(), 5);
+	timestep.reset();
+	assert_eq!(timestep.time(), ms(0));
+
+	let pacer = FramePacer::new(Some(50.0));
+	assert_eq!(pacer.wait_time(ms(5)), ms(15));
+	assert_eq!(pacer.wait_time(ms(30)), ms(0));
+	assert_eq!(FramePacer::new(None).wait_time(ms(5)), ms(0));
+    }
+
+    #[test]
+    fn input_actions_and_ui_focus(){
+	let map = ActionMap::camera_defaults()
+	    .button("toggle_wireframe", Button::key("F"))
+	    .button("toggle_wireframe", Button::key("F1"))
+	    .axis("turn", AxisBinding::new(AxisSource::Buttons(Button::key("Left"), Button::key("Right")), 2.0));
+	let mut input = Input::new(map);
+	input.state.button_event(Button::key("F"), true);
+	assert!(input.action_pressed("toggle_wireframe"));
+	assert!(input.action_down("toggle_wireframe"));
+	input.state.end_step();
+	assert!(!input.action_pressed("toggle_wireframe"));
+	/* 別の割り当ても押されたが,もう押されていたので押したことにはしない */
+	input.state.button_event(Button::key("F1"), true);
+	assert!(!input.action_pressed("toggle_wireframe"));
+	input.state.button_event(Button::key("F"), false);
+	assert!(!input.action_released("toggle_wireframe"));
+	input.state.button_event(Button::key("F1"), false);
+	assert!(input.action_released("toggle_wireframe"));
+
+	input.state.button_event(Button::key("Right"), true);
+	assert_eq!(input.axis("turn"), 2.0);
+	input.state.cursor_moved(10.0, 10.0);
+	input.state.cursor_moved(13.0, 6.0);
+	input.state.scrolled(1.5);
+	assert_eq!(input.state.cursor_delta(), (3.0, -4.0));
+	assert_eq!(input.axis(ZOOM), 1.5);
+	input.state.end_step();
+	assert_eq!(input.axis(ZOOM), 0.0);
+
+	/* 入力欄に打っている間は押下を捨てる. 離したことは受け付ける */
+	input.state.set_ui_focus(true, true);
+	input.state.button_event(Button::key("W"), true);
+	input.state.scrolled(1.0);
+	assert!(!input.action_down("move_forward"));
+	assert_eq!(input.axis(ZOOM), 0.0);
+	input.state.button_event(Button::key("Right"), false);
+	assert_eq!(input.axis("turn"), 0.0);
+	input.state.set_ui_focus(false, false);
+	input.state.button_event(Button::Mouse(MouseButton::Left), true);
+	input.state.release_all();
+	assert!(input.action_released(ORBIT));
+
+	let mut overrides = ActionMap::new().button(ORBIT, Button::Mouse(MouseButton::Middle));
+	overrides.set_buttons("toggle_wireframe", vec![Button::key("G")]);
+	input.map.merge(&overrides);
+	assert_eq!(input.map.buttons(ORBIT), &[Button::Mouse(MouseButton::Middle)]);
+	assert_eq!(input.map.buttons("toggle_wireframe"), &[Button::key("G")]);
+	assert_eq!(input.map.buttons(PAN), &[Button::Mouse(MouseButton::Right)]);
+    }
+
+    #[test]
+    fn drive_camera_orbits_with_actions(){
+	let mut controller = OrbitController::new(Vector3(0.0, 0.0, 0.0), 5.0, 60.0, 0.0);
+	controller.smoothing = 0.0;
+	let mut input = Input::new(ActionMap::camera_defaults());
+	input.state.cursor_moved(100.0, 100.0);
+	input.state.button_event(Button::Mouse(MouseButton::Left), true);
+	drive_camera(&input, &mut controller);
+	input.state.end_step();
+	input.state.cursor_moved(110.0, 100.0);
+	input.state.scrolled(1.0);
+	drive_camera(&input, &mut controller);
+	controller.update(0.1);
+	let target = controller.target();
+	assert!((target.phi - (-10.0*controller.rotate_speed)).abs() < 1.0e-9);
+	assert!(target.distance < 5.0);
+	input.state.end_step();
+	input.state.button_event(Button::Mouse(MouseButton::Left), false);
+	drive_camera(&input, &mut controller);
+	input.state.end_step();
+	input.state.cursor_moved(200.0, 100.0);
+	drive_camera(&input, &mut controller);
+	assert!((controller.target().phi - target.phi).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn gamepad_dead_zone_buttons_and_fly(){
+	let dead_zone = DeadZone { stick: 0.2, trigger: 0.1 };
+	assert_eq!(dead_zone.apply_stick(0.1, -0.1), (0.0, 0.0));
+	let (x, y) = dead_zone.apply_stick(0.0, -0.6);
+	assert!(x == 0.0 && (y - (-0.5)).abs() < 1.0e-9);
+	assert!((dead_zone.apply_trigger(1.0) - 1.0).abs() < 1.0e-9);
+
+	let mut input = Input::new(ActionMap::camera_defaults());
+	input.state.dead_zone = dead_zone;
+	let pad = GamepadState::new().with_button(GamepadButton::RightBumper, true).with_axis(GamepadAxis::LeftY, -1.0);
+	input.state.set_gamepad(Some(pad));
+	assert_eq!(input.state.gamepad_connection(), Some(GamepadConnection::Connected));
+	assert!(input.state.was_pressed(&Button::Gamepad(GamepadButton::RightBumper)));
+	assert_eq!(input.axis(MOVE_Y), 1.0);
+	assert_eq!(input.axis(MOVE_Z), 1.0);
+
+	//前に倒して上昇しながら飛ぶ
+	let mut controller = FlyController::new(Vector3(0.0, 0.0, 0.0), 0.0, 0.0);
+	controller.smoothing = 0.0;
+	drive_camera(&input, &mut controller);
+	controller.update(0.5);
+	let position = controller.state().position;
+	let expected = controller.move_speed*0.5/2.0_f64.sqrt();
+	assert!((position.0 - expected).abs() < 1.0e-9 && (position.2 - expected).abs() < 1.0e-9);
+
+	//外れると押されていたボタンは離され,止まる
+	input.state.end_step();
+	input.state.set_gamepad(None);
+	assert_eq!(input.state.gamepad_connection(), Some(GamepadConnection::Disconnected));
+	assert!(input.state.was_released(&Button::Gamepad(GamepadButton::RightBumper)));
+	drive_camera(&input, &mut controller);
+	controller.update(0.5);
+	assert_eq!(controller.state().position, position);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn camera_bookmarks_ron_round_trip(){
+	let mut bookmarks = CameraBookmarks::new();
+	let mut camera = Camera::from_spherical(Vector3(1.0, 2.0, 3.0), 4.0, 60.0, 45.0);
+	camera.projection = ProjectionKind::Orthographic;
+	bookmarks.save("ortho", camera);
+	let text = bookmarks.to_ron().unwrap();
+	assert_eq!(CameraBookmarks::from_ron(&text).unwrap(), bookmarks);
+	assert!(CameraBookmarks::from_ron("not ron").is_err());
+
+	let path = std::env::temp_dir().join(format!("graphic_math_bookmarks_{}.ron", std::process::id()));
+	bookmarks.save_file(&path).unwrap();
+	assert_eq!(CameraBookmarks::load_file(&path).unwrap(), bookmarks);
+	std::fs::remove_file(&path).unwrap();
+	assert!(CameraBookmarks::load_file(&path).unwrap().is_empty());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn action_map_ron_round_trip(){
+	let map = ActionMap::camera_defaults()
+	    .axis("turn", AxisBinding::new(AxisSource::Buttons(Button::key("Left"), Button::key("Right")), 2.0));
+	assert_eq!(ActionMap::from_ron(&map.to_ron().unwrap()).unwrap(), map);
+	assert!(ActionMap::from_ron("(buttons: [(\"orbit\", [Mouse(Left)])], axes: [])").is_ok());
+	assert!(ActionMap::load_file("no_such_bindings.ron").is_err());
+    }
 }
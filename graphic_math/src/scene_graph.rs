@@ -0,0 +1,239 @@
+use linear_transform::{vector::*,matrix::*,quaternion::*};
+
+use crate::graphic_math;
+
+/* ノードのローカル変換. translate*rotation*scaleの順で合成する */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Transform {
+    pub translation: Vector3,
+    pub rotation: Quaternion,
+    pub scale: Vector3
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+	Transform {
+	    translation: Vector3(0.0, 0.0, 0.0),
+	    rotation: Quaternion::identity(),
+	    scale: Vector3(1.0, 1.0, 1.0)
+	}
+    }
+
+    pub fn from_translation(translation:Vector3) -> Self {
+	Transform { translation, ..Transform::identity() }
+    }
+
+    pub fn from_rotation(rotation:Quaternion) -> Self {
+	Transform { rotation, ..Transform::identity() }
+    }
+
+    pub fn from_scale(scale:Vector3) -> Self {
+	Transform { scale, ..Transform::identity() }
+    }
+
+    pub fn matrix(&self) -> Matrix4x4 {
+	graphic_math::translate(self.translation)*self.rotation.to_matrix()*graphic_math::scale(self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+	Transform::identity()
+    }
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash)]
+pub struct NodeId(usize);
+
+/*
+  ノードに取り付けるもの.
+  GLのリソースはgraphic_mathでは扱わないので,各exampleが持つテーブルへの添字を持たせる.
+*/
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Attachment {
+    Mesh(usize),
+    Camera(usize),
+    Light(usize)
+}
+
+#[derive(Debug,Clone)]
+pub struct Node {
+    pub name: String,
+    local: Transform,
+    world: Matrix4x4,
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    attachments: Vec<Attachment>
+}
+
+impl Node {
+    fn new(name:&str, local:Transform, parent:Option<NodeId>) -> Self {
+	Node {
+	    name: name.to_string(),
+	    local,
+	    world: Matrix4x4::identity(),
+	    dirty: true,
+	    parent,
+	    children: Vec::new(),
+	    attachments: Vec::new()
+	}
+    }
+}
+
+pub struct SceneGraph {
+    nodes: Vec<Node>
+}
+
+impl SceneGraph {
+
+    pub fn new() -> Self {
+	SceneGraph {
+	    nodes: vec![Node::new("root", Transform::identity(), None)]
+	}
+    }
+
+    pub fn root(&self) -> NodeId {
+	NodeId(0)
+    }
+
+    pub fn add_node(&mut self, parent:NodeId, name:&str, local:Transform) -> NodeId {
+	let id = NodeId(self.nodes.len());
+	self.nodes.push(Node::new(name, local, Some(parent)));
+	self.nodes[parent.0].children.push(id);
+	id
+    }
+
+    pub fn node(&self, id:NodeId) -> &Node {
+	&self.nodes[id.0]
+    }
+
+    pub fn find(&self, name:&str) -> Option<NodeId> {
+	self.nodes.iter().position(|n| n.name == name).map(NodeId)
+    }
+
+    pub fn parent(&self, id:NodeId) -> Option<NodeId> {
+	self.nodes[id.0].parent
+    }
+
+    pub fn children(&self, id:NodeId) -> &[NodeId] {
+	&self.nodes[id.0].children
+    }
+
+    /* idの祖先にancestorが含まれるか */
+    pub fn is_descendant_of(&self, id:NodeId, ancestor:NodeId) -> bool {
+	let mut current = Some(id);
+	while let Some(n) = current {
+	    if n == ancestor {
+		return true;
+	    }
+	    current = self.nodes[n.0].parent;
+	}
+	false
+    }
+
+    /* 親を付け替える. 循環する場合やrootを動かす場合は何もせずfalseを返す */
+    pub fn set_parent(&mut self, id:NodeId, parent:NodeId) -> bool {
+	if id == self.root() || self.is_descendant_of(parent, id) {
+	    return false;
+	}
+	if let Some(old) = self.nodes[id.0].parent {
+	    self.nodes[old.0].children.retain(|c| *c != id);
+	}
+	self.nodes[id.0].parent = Some(parent);
+	self.nodes[parent.0].children.push(id);
+	self.nodes[id.0].dirty = true;
+	true
+    }
+
+    pub fn local_transform(&self, id:NodeId) -> &Transform {
+	&self.nodes[id.0].local
+    }
+
+    pub fn set_local_transform(&mut self, id:NodeId, local:Transform) {
+	let node = &mut self.nodes[id.0];
+	node.local = local;
+	node.dirty = true;
+    }
+
+    pub fn set_translation(&mut self, id:NodeId, translation:Vector3) {
+	let local = Transform { translation, ..self.nodes[id.0].local };
+	self.set_local_transform(id, local);
+    }
+
+    pub fn set_rotation(&mut self, id:NodeId, rotation:Quaternion) {
+	let local = Transform { rotation, ..self.nodes[id.0].local };
+	self.set_local_transform(id, local);
+    }
+
+    pub fn set_scale(&mut self, id:NodeId, scale:Vector3) {
+	let local = Transform { scale, ..self.nodes[id.0].local };
+	self.set_local_transform(id, local);
+    }
+
+    pub fn attach(&mut self, id:NodeId, attachment:Attachment) {
+	self.nodes[id.0].attachments.push(attachment);
+    }
+
+    pub fn attachments(&self, id:NodeId) -> &[Attachment] {
+	&self.nodes[id.0].attachments
+    }
+
+    pub fn is_dirty(&self, id:NodeId) -> bool {
+	self.nodes[id.0].dirty
+    }
+
+    /*
+      dirtyなノードとその子孫のワールド行列を計算しなおす.
+      親が更新されたら子はdirtyでなくても計算しなおす.
+     */
+    pub fn update_world_matrices(&mut self) {
+	let mut stack:Vec<(NodeId, bool)> = vec![(self.root(), false)];
+	while let Some((id, parent_updated)) = stack.pop() {
+	    let updated = parent_updated || self.nodes[id.0].dirty;
+	    if updated {
+		let parent_world = match self.nodes[id.0].parent {
+		    Some(p) => self.nodes[p.0].world,
+		    None => Matrix4x4::identity()
+		};
+		let node = &mut self.nodes[id.0];
+		node.world = parent_world*node.local.matrix();
+		node.dirty = false;
+	    }
+	    for c in self.nodes[id.0].children.iter().rev() {
+		stack.push((*c, updated));
+	    }
+	}
+    }
+
+    /* update_world_matrices後のワールド行列 */
+    pub fn world_matrix(&self, id:NodeId) -> Matrix4x4 {
+	self.nodes[id.0].world
+    }
+
+    /* ワールド座標での原点の位置 */
+    pub fn world_position(&self, id:NodeId) -> Vector3 {
+	let w = self.nodes[id.0].world;
+	Vector3(w[0][3], w[1][3], w[2][3])
+    }
+
+    /* rootから深さ優先で辿る. fにはワールド行列と取り付けられたものを渡す */
+    pub fn traverse<F>(&self, mut f:F)
+    where F: FnMut(NodeId, &Node, &Matrix4x4, &[Attachment])
+    {
+	let mut stack:Vec<NodeId> = vec![self.root()];
+	while let Some(id) = stack.pop() {
+	    let node = &self.nodes[id.0];
+	    f(id, node, &node.world, &node.attachments);
+	    for c in node.children.iter().rev() {
+		stack.push(*c);
+	    }
+	}
+    }
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+	SceneGraph::new()
+    }
+}
@@ -0,0 +1,77 @@
+use linear_transform::{vector::*,matrix::*};
+
+use crate::graphic_math;
+
+/*
+  カスケードの分割位置. 視点座標系での距離をnearからfarまでcount+1個返す.
+  lambda=0で等間隔,lambda=1で対数分割(practical split scheme)
+*/
+pub fn cascade_splits(near:f64, far:f64, count:usize, lambda:f64) -> Vec<f64> {
+    let count = count.max(1);
+    (0..=count).map(|i| {
+	let t = (i as f64)/(count as f64);
+	let logarithmic = near*(far/near).powf(t);
+	let uniform = near + (far - near)*t;
+	lambda*logarithmic + (1.0 - lambda)*uniform
+    }).collect()
+}
+
+/* 視錐台のnearからfarまでの部分の8頂点(ワールド座標). fovyはdegree */
+pub fn frustum_corners(eye:Vector3, center:Vector3, up:Vector3, fovy:f64, aspect:f64, near:f64, far:f64) -> [Vector3;8] {
+    let forward = (center-eye).normalize();
+    let right = Vector3::cross_product(forward, up).normalize();
+    let up = Vector3::cross_product(right, forward);
+    let tan_half = (fovy.to_radians()*0.5).tan();
+    let mut corners = [Vector3(0.0, 0.0, 0.0);8];
+    for (i, d) in [near, far].iter().enumerate() {
+	let h = d*tan_half;
+	let w = h*aspect;
+	let c = eye + (*d)*forward;
+	corners[4*i]   = c - w*right - h*up;
+	corners[4*i+1] = c + w*right - h*up;
+	corners[4*i+2] = c + w*right + h*up;
+	corners[4*i+3] = c - w*right + h*up;
+    }
+    corners
+}
+
+/* 光の向きと平行にならないup */
+fn light_up(direction:Vector3) -> Vector3 {
+    if (direction.normalize()*Vector3(0.0, 0.0, 1.0)).abs() > 0.99 {
+	Vector3(0.0, 1.0, 0.0)
+    }
+    else {
+	Vector3(0.0, 0.0, 1.0)
+    }
+}
+
+/*
+  平行光源のライト空間行列(projection*view). cornersを包む球に合わせた平行投影.
+  ライトの視点は原点に固定して向きだけで決め,球の中心をその座標系でテクセル単位に丸める.
+  球の大きさはカメラの向きで変わらないので,カメラが動いても1テクセル未満なら行列は変わらず,影がちらつかない.
+  casterはcornersより光源側にある物体も影を落とすようにnearを伸ばす距離.
+*/
+pub fn directional_light_matrix(direction:Vector3, corners:&[Vector3], resolution:u32, caster:f64) -> Matrix4x4 {
+    let direction = direction.normalize();
+    let n = corners.len().max(1) as f64;
+    let center = (1.0/n)*corners.iter().fold(Vector3(0.0, 0.0, 0.0), |s, c| s + *c);
+    let radius = corners.iter().map(|c| (*c - center).length()).fold(0.0, f64::max).max(1.0e-6);
+    //計算誤差で大きさが揺れないように1/16単位に切り上げる
+    let radius = (radius*16.0).ceil()/16.0;
+
+    let view = graphic_math::look_at(Vector3(0.0, 0.0, 0.0), direction, light_up(direction));
+    //丸めで中心がずれても球がはみ出さないように,両側に1テクセルずつ余白を取る
+    let texel = 2.0*radius/((resolution.max(3) - 2) as f64);
+    let half = radius + texel;
+    let c = view*Vector4(center.0, center.1, center.2, 1.0);
+    let snap = |v:f64| (v/texel).round()*texel;
+    let (x, y, depth) = (snap(c.0), snap(c.1), snap(-c.2));
+    graphic_math::orthogonal(x-half, x+half, y-half, y+half, depth-half-caster, depth+half)*view
+}
+
+/* スポットライトのライト空間行列. 円錐の外側の角度を画角にした透視投影 */
+pub fn spot_light_matrix(position:Vector3, direction:Vector3, outer_cone_degree:f64, near:f64, far:f64) -> Matrix4x4 {
+    let direction = direction.normalize();
+    let fovy = (2.0*outer_cone_degree).clamp(1.0, 179.0);
+    graphic_math::perspective(fovy, 1.0, near, far)*graphic_math::look_at(position, position + direction, light_up(direction))
+}
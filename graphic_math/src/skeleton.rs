@@ -0,0 +1,242 @@
+use linear_transform::{vector::*,matrix::*,quaternion::*};
+
+use crate::graphic_math;
+use crate::scene_graph::Transform;
+use crate::animation::{Track,PlaybackMode};
+
+/*
+  スケルトンとスキニング. 関節の階層と逆バインド行列を持ち,関節ごとの移動,回転,拡大縮小の
+  アニメーションから姿勢(Pose)を求めて,頂点シェーダに渡す行列にする.
+  skin_position,skin_normalはシェーダと同じ計算をCPUでするもので,決まった姿勢での結果を確かめるのに使う
+*/
+
+/* 1頂点に影響する関節の数の上限 */
+pub const MAX_JOINT_INFLUENCES: usize = 4;
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /* 親は自分より前にある */
+    pub parent: Option<usize>,
+    /* 休止姿勢での親からの変換 */
+    pub rest: Transform,
+    /* モデル座標を関節の座標にする. 休止姿勢のワールド行列の逆 */
+    pub inverse_bind: Matrix4x4
+}
+
+/* 関節ごとの親からの変換. 並びはSkeletonの関節と同じ */
+#[derive(Debug,Clone,PartialEq)]
+pub struct Pose {
+    pub locals: Vec<Transform>
+}
+
+/* Transformの逆行列. 拡大縮小に0があってはいけない */
+fn inverse_transform_matrix(t:&Transform) -> Matrix4x4 {
+    graphic_math::scale(Vector3(1.0/t.scale.0, 1.0/t.scale.1, 1.0/t.scale.2))
+	*t.rotation.conjugate().to_matrix()
+	*graphic_math::translate(-t.translation)
+}
+
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct Skeleton {
+    joints: Vec<Joint>
+}
+
+impl Skeleton {
+    pub fn new() -> Self {
+	Skeleton { joints: Vec::new() }
+    }
+
+    /*
+      親より後に足す. 逆バインド行列は休止姿勢から求める.
+      glTFのように逆バインド行列が決まっているときはset_inverse_bindで置き換える
+    */
+    pub fn add_joint(&mut self, name:&str, parent:Option<usize>, rest:Transform) -> usize {
+	let parent_inverse = match parent {
+	    Some(index) => {
+		assert!(index < self.joints.len(), "parent joint {} must be added before {}", index, name);
+		self.joints[index].inverse_bind
+	    },
+	    None => Matrix4x4::identity()
+	};
+	self.joints.push(Joint {
+	    name: name.to_owned(),
+	    parent,
+	    rest,
+	    inverse_bind: inverse_transform_matrix(&rest)*parent_inverse
+	});
+	self.joints.len() - 1
+    }
+
+    pub fn set_inverse_bind(&mut self, index:usize, inverse_bind:Matrix4x4) {
+	self.joints[index].inverse_bind = inverse_bind;
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+	&self.joints
+    }
+
+    pub fn len(&self) -> usize {
+	self.joints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.joints.is_empty()
+    }
+
+    pub fn find(&self, name:&str) -> Option<usize> {
+	self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    pub fn rest_pose(&self) -> Pose {
+	Pose { locals: self.joints.iter().map(|joint| joint.rest).collect() }
+    }
+
+    /* 関節ごとのモデル座標での変換. 親が先にあるので前から順に掛けていける */
+    pub fn world_matrices(&self, pose:&Pose) -> Vec<Matrix4x4> {
+	let mut worlds:Vec<Matrix4x4> = Vec::with_capacity(self.joints.len());
+	for (joint, local) in self.joints.iter().zip(pose.locals.iter()) {
+	    let world = match joint.parent {
+		Some(parent) => worlds[parent]*local.matrix(),
+		None => local.matrix()
+	    };
+	    worlds.push(world);
+	}
+	worlds
+    }
+
+    /* 頂点シェーダのjointMatricesに渡す行列. 休止姿勢ではすべて単位行列になる */
+    pub fn skinning_matrices(&self, pose:&Pose) -> Vec<Matrix4x4> {
+	self.world_matrices(pose).iter().zip(self.joints.iter())
+	    .map(|(world, joint)| *world*joint.inverse_bind)
+	    .collect()
+    }
+}
+
+/* 1つの関節の移動,回転,拡大縮小のアニメーション. Noneの成分は休止姿勢のまま */
+#[derive(Debug,Clone,PartialEq,Default)]
+pub struct JointChannels {
+    pub translation: Option<Track<Vector3>>,
+    pub rotation: Option<Track<Quaternion>>,
+    pub scale: Option<Track<Vector3>>
+}
+
+#[derive(Debug,Clone,PartialEq)]
+pub struct SkeletalClip {
+    pub duration: f64,
+    pub mode: PlaybackMode,
+    /* 関節の番号とそのチャンネル */
+    channels: Vec<(usize, JointChannels)>
+}
+
+impl SkeletalClip {
+    pub fn new(duration:f64, mode:PlaybackMode) -> Self {
+	SkeletalClip { duration, mode, channels: Vec::new() }
+    }
+
+    /* 関節のチャンネル. なければ空のものを作る */
+    pub fn channels_mut(&mut self, joint:usize) -> &mut JointChannels {
+	let index = match self.channels.iter().position(|(j, _)| *j == joint) {
+	    Some(index) => index,
+	    None => {
+		self.channels.push((joint, JointChannels::default()));
+		self.channels.len() - 1
+	    }
+	};
+	&mut self.channels[index].1
+    }
+
+    pub fn set_translation(&mut self, joint:usize, track:Track<Vector3>) {
+	self.channels_mut(joint).translation = Some(track);
+    }
+
+    pub fn set_rotation(&mut self, joint:usize, track:Track<Quaternion>) {
+	self.channels_mut(joint).rotation = Some(track);
+    }
+
+    pub fn set_scale(&mut self, joint:usize, track:Track<Vector3>) {
+	self.channels_mut(joint).scale = Some(track);
+    }
+
+    /* timeは再生を始めてからの時刻. チャンネルのない関節は休止姿勢 */
+    pub fn sample(&self, skeleton:&Skeleton, time:f64) -> Pose {
+	let local_time = self.mode.local_time(self.duration, time);
+	let mut pose = skeleton.rest_pose();
+	for (joint, channels) in self.channels.iter() {
+	    if let Some(local) = pose.locals.get_mut(*joint) {
+		if let Some(translation) = channels.translation.as_ref().and_then(|track| track.sample(local_time)) {
+		    local.translation = translation;
+		}
+		if let Some(rotation) = channels.rotation.as_ref().and_then(|track| track.sample(local_time)) {
+		    local.rotation = rotation;
+		}
+		if let Some(scale) = channels.scale.as_ref().and_then(|track| track.sample(local_time)) {
+		    local.scale = scale;
+		}
+	    }
+	}
+	pose
+    }
+}
+
+/* 頂点に影響する関節の番号と重み. 使わない枠は重み0にする */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct SkinWeights {
+    pub joints: [u32;MAX_JOINT_INFLUENCES],
+    pub weights: [f32;MAX_JOINT_INFLUENCES]
+}
+
+impl SkinWeights {
+    /* 重みの合計が1になるようにする. 全部0なら最初の関節だけにする */
+    pub fn new(joints:[u32;MAX_JOINT_INFLUENCES], weights:[f32;MAX_JOINT_INFLUENCES]) -> Self {
+	let sum:f32 = weights.iter().sum();
+	let weights = if sum > 0.0 {
+	    let mut normalized = weights;
+	    normalized.iter_mut().for_each(|w| *w /= sum);
+	    normalized
+	}
+	else {
+	    [1.0, 0.0, 0.0, 0.0]
+	};
+	SkinWeights { joints, weights }
+    }
+
+    /* 1つの関節だけに従う */
+    pub fn single(joint:u32) -> Self {
+	SkinWeights { joints: [joint, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] }
+    }
+
+    /* 関節の行列を重みで足し合わせる. 頂点シェーダのskinMatrixと同じ */
+    pub fn blend(&self, matrices:&[Matrix4x4]) -> Matrix4x4 {
+	let mut m = Matrix4x4::zero();
+	for (joint, weight) in self.joints.iter().zip(self.weights.iter()) {
+	    if *weight == 0.0 {
+		continue;
+	    }
+	    let joint_matrix = &matrices[*joint as usize];
+	    for i in 0..4 {
+		for j in 0..4 {
+		    m.v[i][j] += (*weight as f64)*joint_matrix[i][j];
+		}
+	    }
+	}
+	m
+    }
+}
+
+/* 線形ブレンドスキニングで頂点を動かす */
+pub fn skin_position(position:Vector3, skin:&SkinWeights, matrices:&[Matrix4x4]) -> Vector3 {
+    let p = skin.blend(matrices)*Vector4(position.0, position.1, position.2, 1.0);
+    Vector3(p.0, p.1, p.2)
+}
+
+/* 法線は足し合わせた行列の逆転置で変換する. シェーダのnormalMatrixと同じ */
+pub fn skin_normal(normal:Vector3, skin:&SkinWeights, matrices:&[Matrix4x4]) -> Vector3 {
+    let m = skin.blend(matrices);
+    let column = |j:usize| Vector3(m[0][j], m[1][j], m[2][j]);
+    let (c0, c1, c2) = (column(0), column(1), column(2));
+    //3x3の余因子行列は行列式倍した逆転置. 向きを保つために行列式の符号を掛ける
+    let (x, y, z) = (Vector3::cross_product(c1, c2), Vector3::cross_product(c2, c0), Vector3::cross_product(c0, c1));
+    let sign = (c0*x).signum();
+    (sign*(normal.0*x + normal.1*y + normal.2*z)).normalize()
+}
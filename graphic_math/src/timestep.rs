@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+/*
+  固定時間刻みのメインループ. 経過時間を貯めて決まった刻みでupdateを呼び,
+  余りの割合(alpha)で前の状態と今の状態の間を補間して描く.
+  時間はDurationのまま足し引きするので,同じフレーム時間の並びからは必ず同じ回数のupdateになる
+*/
+
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct FixedTimestep {
+    step: Duration,
+    /* 1フレームで受け取る経過時間の上限. 止まっていたあとにupdateを何百回も呼ばないようにする */
+    max_frame_time: Duration,
+    accumulator: Duration,
+    /* 今までに進めた刻みの数 */
+    steps: u64
+}
+
+impl FixedTimestep {
+    pub fn new(step:Duration) -> Self {
+	assert!(step > Duration::new(0,0), "fixed timestep must be positive");
+	FixedTimestep { step, max_frame_time: Duration::from_millis(250), accumulator: Duration::new(0,0), steps: 0 }
+    }
+
+    /* 1秒にhz回 */
+    pub fn from_hz(hz:f64) -> Self {
+	FixedTimestep::new(Duration::from_secs_f64(1.0/hz))
+    }
+
+    pub fn with_max_frame_time(mut self, max_frame_time:Duration) -> Self {
+	self.max_frame_time = max_frame_time;
+	self
+    }
+
+    pub fn step(&self) -> Duration {
+	self.step
+    }
+
+    /* updateで進めた時間の合計 */
+    pub fn time(&self) -> Duration {
+	Duration::from_nanos((self.step.as_nanos()*(self.steps as u128)) as u64)
+    }
+
+    pub fn steps(&self) -> u64 {
+	self.steps
+    }
+
+    /* フレームの経過時間を足して,このフレームでupdateを呼ぶ回数を返す */
+    pub fn advance(&mut self, frame_time:Duration) -> u32 {
+	self.accumulator += frame_time.min(self.max_frame_time);
+	let mut count = 0;
+	while self.accumulator >= self.step {
+	    self.accumulator -= self.step;
+	    count += 1;
+	}
+	self.steps += count as u64;
+	count
+    }
+
+    /* 最後のupdateから次のupdateまでの進み具合(0-1). 描くときの補間に使う */
+    pub fn alpha(&self) -> f64 {
+	self.accumulator.as_secs_f64()/self.step.as_secs_f64()
+    }
+
+    pub fn reset(&mut self) {
+	self.accumulator = Duration::new(0,0);
+	self.steps = 0;
+    }
+}
+
+/*
+  垂直同期を使わないときにフレームレートを抑える. フレームの処理にかかった時間から待つ時間を決める.
+  Noneなら待たない
+*/
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct FramePacer {
+    frame_interval: Option<Duration>
+}
+
+impl FramePacer {
+    pub fn new(max_fps:Option<f64>) -> Self {
+	FramePacer { frame_interval: max_fps.filter(|fps| *fps > 0.0).map(|fps| Duration::from_secs_f64(1.0/fps)) }
+    }
+
+    pub fn frame_interval(&self) -> Option<Duration> {
+	self.frame_interval
+    }
+
+    /* busyはフレームの始めからの時間 */
+    pub fn wait_time(&self, busy:Duration) -> Duration {
+	self.frame_interval.map_or(Duration::new(0,0), |interval| interval.saturating_sub(busy))
+    }
+}
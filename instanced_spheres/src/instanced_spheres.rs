@@ -0,0 +1,259 @@
+use std::ffi::CStr;
+use std::{ptr, mem, time};
+use std::{f64::consts::PI};
+use gl::types::*;
+use linear_transform::{vector::*,matrix::*};
+use graphic_math::graphic_math;
+use ::graphic_math::camera::Camera;
+use glutils::renderer::{Renderer,DrawItem,RenderState,PolygonMode,VertexArrayObjectContext,FrameStats};
+use glutils::material::{MaterialPrograms,ShadingModel,PhongParameter};
+use glutils::light::{LightSet,Light};
+use glutils::instancing::{InstancedMesh,Instance};
+use glutils::hdr::{HdrTarget,ToneMapPass,ToneMapSettings};
+use glutils::shadow::ShadowSettings;
+use glutils::cubemap::environment_uniforms;
+use glutils::ibl::ibl_uniforms;
+use glutils::ssao::ssao_uniforms;
+
+/* 球の間隔と半径 */
+const SPACING: f64 = 0.25;
+const RADIUS: f64 = 0.08;
+
+/* 頂点属性の配置はglutilsのmaterial.vertに合わせる. position=0,normal=1,vertexColor=2 */
+fn create_sphere_mesh(slice:u32, stack:u32) -> (GLuint, [GLuint;3], VertexArrayObjectContext) {
+    let mut positions:Vec<GLfloat> = Vec::new();
+    let mut indices:Vec<GLuint> = Vec::new();
+    for j in 0..=stack {
+	let theta = PI*(j as f64)/(stack as f64);
+	for i in 0..=slice {
+	    let phi = 2.0*PI*(i as f64)/(slice as f64);
+	    positions.extend_from_slice(&[(theta.sin()*phi.cos()) as GLfloat, (theta.sin()*phi.sin()) as GLfloat, theta.cos() as GLfloat]);
+	}
+    }
+    for j in 0..stack {
+	let k1 = j*(slice + 1);
+	let k2 = k1 + slice + 1;
+	for i in 0..slice {
+	    indices.extend_from_slice(&[k1+i, k2+i, k1+1+i, k1+1+i, k2+i, k2+1+i]);
+	}
+    }
+    //色はインスタンスごとに掛けるので頂点は白にする
+    let colors:Vec<GLfloat> = vec![1.0; positions.len()/3*4];
+
+    let mut vao = 0;
+    let mut vbos : [GLuint;3] = [0,0,0];
+    unsafe {
+	gl::GenVertexArrays(1, &mut vao);
+	gl::BindVertexArray(vao);
+	gl::GenBuffers(3, &mut vbos[0]);
+	//単位球なので法線は位置と同じバッファを使う
+	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[0]);
+	gl::BufferData(gl::ARRAY_BUFFER, (positions.len()*mem::size_of::<GLfloat>()) as GLsizeiptr,
+		       positions.as_ptr() as *const _, gl::STATIC_DRAW);
+	for location in 0..2 {
+	    gl::EnableVertexAttribArray(location);
+	    gl::VertexAttribPointer(location, 3, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+	}
+	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[1]);
+	gl::BufferData(gl::ARRAY_BUFFER, (colors.len()*mem::size_of::<GLfloat>()) as GLsizeiptr,
+		       colors.as_ptr() as *const _, gl::STATIC_DRAW);
+	gl::EnableVertexAttribArray(2);
+	gl::VertexAttribPointer(2, 4, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vbos[2]);
+	gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, (indices.len()*mem::size_of::<GLuint>()) as GLsizeiptr,
+		       indices.as_ptr() as *const _, gl::STATIC_DRAW);
+	gl::BindVertexArray(0); //先にVAOを解く. でないと,ELEMENT_BUFFERがVAOから外される.
+	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+	gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    }
+    (vao, vbos, VertexArrayObjectContext::new(vao, gl::TRIANGLES, indices.len() as GLsizei))
+}
+
+/* 色相hue(0-1)の鮮やかな色 */
+fn hue_color(hue:f64) -> [GLfloat;4] {
+    let channel = |offset:f64| (0.5 + 0.5*(2.0*PI*(hue + offset)).cos()) as GLfloat;
+    [channel(0.0), channel(2.0/3.0), channel(1.0/3.0), 1.0]
+}
+
+/* 格子に並べた球が波打つ. 格子の一辺はside個 */
+fn sphere_instances(side:usize, seconds:f64) -> Vec<Instance> {
+    let offset = (side as f64 - 1.0)*SPACING*0.5;
+    let mut instances = Vec::with_capacity(side*side);
+    for j in 0..side {
+	for i in 0..side {
+	    let x = (i as f64)*SPACING - offset;
+	    let y = (j as f64)*SPACING - offset;
+	    let distance = (x*x + y*y).sqrt();
+	    let z = 0.3*(3.0*distance - 2.0*seconds).sin();
+	    let size = RADIUS*(0.75 + 0.25*(5.0*distance - seconds).cos());
+	    instances.push(Instance {
+		transform: graphic_math::translate(Vector3(x, y, z))*graphic_math::scale(Vector3(size, size, size)),
+		color: hue_color(distance/(offset.max(SPACING)*1.5) + 0.1*seconds)
+	    });
+	}
+    }
+    instances
+}
+
+pub struct InstancedSpheres {
+    elapsed_time: time::Duration,
+    vao: GLuint,
+    vbos: [GLuint;3],
+    sphere: VertexArrayObjectContext,
+    instanced_mesh: InstancedMesh,
+    side: usize,
+    /* falseなら比較のために1つずつDrawItemにして描く */
+    instancing: bool,
+    wireframe: bool,
+    renderer: Renderer,
+    materials: MaterialPrograms,
+    shading: ShadingModel,
+    lights: LightSet,
+    hdr_target: HdrTarget,
+    tone_map: ToneMapPass,
+    tone_map_settings: ToneMapSettings
+}
+
+impl InstancedSpheres {
+
+    /* count個以上になるように格子の一辺を決める */
+    pub fn create(count:usize) -> Self {
+	unsafe {
+	    println!("Open GL version:{}",
+		     CStr::from_ptr(gl::GetString(gl::VERSION) as *const i8).to_string_lossy().into_owned());
+	    gl::ClearDepth(1.0);
+	    gl::DepthFunc(gl::LESS);
+	}
+	let (vao, vbos, sphere) = create_sphere_mesh(16, 12);
+	let mut lights = LightSet::new();
+	lights.add(Light::directional((-Vector3(2.0,4.0,8.0).normalize()).serialize_f32(), [1.0,1.0,1.0], 2.0));
+	lights.add(Light::directional((-Vector3(-4.0,-2.0,1.0).normalize()).serialize_f32(), [0.3,0.4,0.6], 0.5));
+
+	InstancedSpheres {
+	    elapsed_time: time::Duration::new(0,0),
+	    vao,
+	    vbos,
+	    sphere,
+	    instanced_mesh: InstancedMesh::create(sphere),
+	    side: ((count.max(1) as f64).sqrt().ceil()) as usize,
+	    instancing: true,
+	    wireframe: false,
+	    renderer: Renderer::new(),
+	    materials: MaterialPrograms::create(),
+	    shading: ShadingModel::BlinnPhong(PhongParameter::default()),
+	    lights,
+	    //大きさは最初のrenderで合わせる
+	    hdr_target: HdrTarget::create(1, 1),
+	    tone_map: ToneMapPass::create(),
+	    tone_map_settings: ToneMapSettings::default()
+	}
+    }
+
+    pub fn count(&self) -> usize {
+	self.side*self.side
+    }
+
+    pub fn instancing(&self) -> bool {
+	self.instancing
+    }
+
+    pub fn set_instancing(&mut self, instancing:bool) {
+	self.instancing = instancing;
+    }
+
+    pub fn wireframe(&self) -> bool {
+	self.wireframe
+    }
+
+    pub fn set_wireframe(&mut self, wireframe:bool) {
+	self.wireframe = wireframe;
+    }
+
+    pub fn tone_map_settings_mut(&mut self) -> &mut ToneMapSettings {
+	&mut self.tone_map_settings
+    }
+
+    /* 直近のフレームの描画回数など */
+    pub fn stats(&self) -> FrameStats {
+	self.renderer.stats()
+    }
+
+    /* 球の動きとカメラを進める */
+    pub fn update(&mut self, interval:time::Duration) {
+	self.elapsed_time += interval;
+    }
+
+    pub fn render(&mut self, width:i32, height:i32) {
+	let seconds = self.elapsed_time.as_secs_f64();
+	let instances = sphere_instances(self.side, seconds);
+
+	//格子全体が入るように,斜め上からゆっくり回りながら見下ろす
+	let extent = (self.side as f64)*SPACING;
+	let angle = 0.05*seconds;
+	let mut camera = Camera::new(Vector3(extent*angle.sin(), -extent*angle.cos(), 0.6*extent),
+				     Vector3(0.0,0.0,0.0), Vector3(0.0,0.0,1.0));
+	camera.fovy = 45.0;
+	camera.near = 0.1;
+	camera.far = 4.0*extent;
+	camera.resize(width, height);
+
+	let material = self.materials.material(&self.shading);
+	let state = RenderState {
+	    polygon: if self.wireframe { PolygonMode::Line } else { PolygonMode::Fill },
+	    ..RenderState::default()
+	};
+	let mut items:Vec<DrawItem> = Vec::new();
+	if self.instancing {
+	    self.instanced_mesh.set_instances(&instances);
+	    items.extend(self.instanced_mesh.draw_item(material, Matrix4x4::identity(), state));
+	}
+	else {
+	    //インスタンスの色は頂点色に掛かるので,1つずつ描くときは色をuniformで渡せない. 白のまま描く
+	    items.extend(instances.iter().map(|instance| DrawItem {
+		mesh: self.sphere,
+		material: material.clone(),
+		transform: instance.transform,
+		state
+	    }));
+	}
+
+	self.hdr_target.resize(width, height);
+	self.hdr_target.bind();
+	unsafe {
+	    gl::ClearColor(0.02, 0.02, 0.03, 1.0);
+	    gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT);
+	}
+	self.renderer.begin_frame(camera.view(), camera.projection());
+	self.lights.apply(&mut self.renderer);
+	//影,環境マップ,IBL,SSAOは使わない. programは他と共有されるので毎フレーム無効にしておく
+	let uniforms = ShadowSettings::default().uniforms(None, &[]).into_iter()
+	    .chain(environment_uniforms(false, 0.0))
+	    .chain(ibl_uniforms(None, 0.0))
+	    .chain(ssao_uniforms(false));
+	for (name, value) in uniforms {
+	    self.renderer.set_frame_uniform(&name, value);
+	}
+	for item in items {
+	    self.renderer.submit(item);
+	}
+	self.renderer.end_frame();
+
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	    gl::Viewport(0, 0, width, height);
+	}
+	self.tone_map.render(self.hdr_target.color_texture(), &self.tone_map_settings);
+	unsafe {
+	    gl::Flush();
+	}
+    }
+}
+
+impl Drop for InstancedSpheres {
+    fn drop(&mut self) {
+	unsafe {
+	    gl::DeleteBuffers(3, &self.vbos[0]);
+	    gl::DeleteVertexArrays(1, &self.vao);
+	}
+    }
+}
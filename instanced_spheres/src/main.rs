@@ -0,0 +1,73 @@
+mod instanced_spheres;
+
+use std::time;
+use app_framework::{App,AppConfig,InitContext,FrameContext};
+use graphic_math::input::{Input,ActionMap,Button};
+use glutils::hdr::OutputEncoding;
+use instanced_spheres::*;
+
+/* --count N 描く球の数(格子に並べるので一辺に切り上げる) */
+fn count_option(default:usize) -> usize {
+    let args:Vec<String> = std::env::args().collect();
+    args.windows(2).find(|pair| pair[0] == "--count")
+	.and_then(|pair| pair[1].parse::<usize>().ok())
+	.unwrap_or(default)
+}
+
+struct InstancedSpheresApp {
+    spheres: InstancedSpheres,
+    report_time: time::Instant,
+    frames: u32
+}
+
+impl App for InstancedSpheresApp {
+    fn init(context:&InitContext) -> Self {
+	let mut spheres = InstancedSpheres::create(count_option(2500));
+	if !context.srgb_framebuffer {
+	    spheres.tone_map_settings_mut().output = OutputEncoding::ShaderSrgb;
+	}
+	println!("{} spheres. Space: toggle instancing, F: toggle wireframe", spheres.count());
+	InstancedSpheresApp { spheres, report_time: time::Instant::now(), frames: 0 }
+    }
+
+    fn action_map(&self) -> ActionMap {
+	ActionMap::new()
+	    .button("toggle_instancing", Button::key("Space"))
+	    .button("toggle_wireframe", Button::key("F"))
+    }
+
+    fn update(&mut self, dt:time::Duration, input:&Input) {
+	//インスタンス描画と1つずつの描画を切り替える
+	if input.action_pressed("toggle_instancing") {
+	    self.spheres.set_instancing(!self.spheres.instancing());
+	}
+	if input.action_pressed("toggle_wireframe") {
+	    self.spheres.set_wireframe(!self.spheres.wireframe());
+	}
+	self.spheres.update(dt);
+    }
+
+    fn render(&mut self, context:&FrameContext) {
+	self.spheres.render(context.width, context.height);
+
+	//1秒ごとにフレームレートと描画回数を出す
+	self.frames += 1;
+	if self.report_time.elapsed() >= time::Duration::from_secs(1) {
+	    let stats = self.spheres.stats();
+	    println!("{} fps, instancing: {}, draw calls: {}, triangles: {}",
+		     self.frames, self.spheres.instancing(), stats.draw_calls, stats.triangles);
+	    self.frames = 0;
+	    self.report_time = time::Instant::now();
+	}
+    }
+}
+
+fn main() {
+    app_framework::run::<InstancedSpheresApp>(AppConfig {
+	title: "Instanced Spheres".to_string(),
+	//描画回数の違いが分かるように垂直同期は待たない
+	vsync: false,
+	bindings_file: Some("instanced_spheres_bindings.ron".into()),
+	..AppConfig::default()
+    });
+}
@@ -2,9 +2,54 @@
 
 precision highp float;
 
-smooth in vec4 lightIntensity;
+#define MAX_LIGHTS 8
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+
+//same layout as glutils::light::Light
+struct Light {
+    int lightType;
+    vec3 position; //world coordinates
+    vec3 direction; //world coordinates. the direction light travels
+    vec3 radiance; //color*intensity
+    vec3 attenuation; //constant, linear, quadratic
+    float cosInner;
+    float cosOuter;
+};
+
+uniform mat4 view;
+uniform Light lights[MAX_LIGHTS];
+uniform int lightCount;
+
+smooth in vec3 viewPosition;
+smooth in vec3 viewNormal;
+smooth in vec4 fragmentColor;
 out vec4 outputColor;
 
 void main(void){
-    outputColor = lightIntensity;
+    vec3 Kd = fragmentColor.rgb;
+    vec3 tnorm = normalize(viewNormal);
+    vec3 color = vec3(0.0);
+
+    for (int i = 0; i < lightCount; i++) {
+        vec3 s;
+        float attenuation = 1.0;
+        if (lights[i].lightType == LIGHT_DIRECTIONAL) {
+            s = normalize(-(mat3(view)*lights[i].direction));
+        }
+        else {
+            vec3 toLight = (view*vec4(lights[i].position, 1.0)).xyz - viewPosition;
+            float d = length(toLight);
+            s = toLight/max(d, 1.0e-6);
+            attenuation = 1.0/max(dot(lights[i].attenuation, vec3(1.0, d, d*d)), 1.0e-6);
+            if (lights[i].lightType == LIGHT_SPOT) {
+                vec3 axis = normalize(mat3(view)*lights[i].direction);
+                attenuation *= smoothstep(lights[i].cosOuter, lights[i].cosInner, dot(-s, axis));
+            }
+        }
+        color += lights[i].radiance*attenuation*Kd*max(dot(s, tnorm), 0.0);
+    }
+
+    outputColor = vec4(color, fragmentColor.a);
 }
@@ -11,25 +11,18 @@ uniform mat4 view; //view transform matrix. to move camera position
 uniform mat4 projection; //projection transform matrix. to project to screen
 uniform mat3 normalMatrix;
 
-uniform vec4 lightPosition;
-uniform vec3 Ld; // strength of Light;
-
-out vec4 lightIntensity;
-
+//lighting is calculated per fragment in view coordinates. see lighting.frag
+smooth out vec3 viewPosition;
+smooth out vec3 viewNormal;
 smooth out vec4 fragmentColor;
 
 void main(void){
     mat4 modelview = view*model;
-    vec3 Kd = vec3(vertexColor.x, vertexColor.y, vertexColor.z);
-    vec3 tnorm = normalize(normalMatrix * normal);
-    vec4 eyeCoords = view*model*vec4(position, 1.0);
-    vec3 s = normalize(vec3(lightPosition-eyeCoords));
-
-    mat4 mvp = projection*modelview;
-
-    //calc reflection
-    lightIntensity = vec4(Ld*Kd*max(dot(-s,tnorm),0.0),vertexColor.w);
+    vec4 eyeCoords = modelview*vec4(position, 1.0);
 
-    gl_Position = mvp*vec4(position, 1.0);
+    viewPosition = eyeCoords.xyz;
+    viewNormal = normalize(normalMatrix * normal);
+    fragmentColor = vertexColor;
 
+    gl_Position = projection*eyeCoords;
 }
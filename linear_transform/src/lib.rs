@@ -1,12 +1,19 @@
 pub mod vector;
 pub mod matrix;
+pub mod quaternion;
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use vector::Vector2;
     use vector::Vector3;
+    use vector::Vector4;
     use matrix::Matrix4x4;
+    use quaternion::Quaternion;
+
+    fn vector3_approx_eq(a:Vector3, b:Vector3) -> bool {
+	(a-b).length() < 1.0e-9
+    }
 
     #[test]
     fn vector2_add(){
@@ -92,4 +99,50 @@ mod tests {
 	assert_eq!(m1*Matrix4x4::identity(), Matrix4x4::identity()*m1);
 	assert_eq!(m4*m5, m6);
     }
+
+    #[test]
+    fn matrix_vector_product(){
+	let m = Matrix4x4 {
+	    v: [ [1.0, 0.0, 0.0, 2.0],
+		 [0.0, 2.0, 0.0, 3.0],
+		 [0.0, 0.0, 3.0, 4.0],
+		 [0.0, 0.0, 0.0, 1.0] ]
+	};
+	assert_eq!(m*Vector4(1.0, 1.0, 1.0, 1.0), Vector4(3.0, 5.0, 7.0, 1.0));
+    }
+
+    #[test]
+    fn quaternion_rotate_vector(){
+	let q = Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 90.0);
+	assert!(vector3_approx_eq(q.rotate_vector(Vector3(1.0, 0.0, 0.0)), Vector3(0.0, 1.0, 0.0)));
+	let p = q.to_matrix()*Vector4(1.0, 0.0, 0.0, 1.0);
+	assert!(vector3_approx_eq(Vector3(p.0, p.1, p.2), Vector3(0.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn quaternion_composition(){
+	let a = Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 30.0);
+	let b = Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 60.0);
+	let c = Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 90.0);
+	assert!((a*b).dot(c) > 1.0-1.0e-9);
+	assert!(((a*a.inverse()).w - 1.0).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn quaternion_slerp_and_basis(){
+	let a = Quaternion::identity();
+	let b = Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 90.0);
+	let half = a.slerp(b, 0.5);
+	assert!(half.dot(Quaternion::from_axis_angle(Vector3(0.0, 0.0, 1.0), 45.0)) > 1.0-1.0e-9);
+	/* -bは同じ回転なので,近い方を通って同じ結果になる */
+	assert!(a.slerp(-b, 0.5).dot(half).abs() > 1.0-1.0e-9);
+	assert!(a.slerp(b, 1.0).dot(b) > 1.0-1.0e-9);
+
+	let q = Quaternion::from_axis_angle(Vector3(1.0, 2.0, 3.0), 130.0);
+	let (x, y, z) = (q.rotate_vector(Vector3(1.0, 0.0, 0.0)), q.rotate_vector(Vector3(0.0, 1.0, 0.0)), q.rotate_vector(Vector3(0.0, 0.0, 1.0)));
+	assert!(Quaternion::from_basis(x, y, z).dot(q).abs() > 1.0-1.0e-9);
+	let r = Quaternion::from_axis_angle(Vector3(0.0, 1.0, 0.0), 180.0);
+	let (x, y, z) = (r.rotate_vector(Vector3(1.0, 0.0, 0.0)), r.rotate_vector(Vector3(0.0, 1.0, 0.0)), r.rotate_vector(Vector3(0.0, 0.0, 1.0)));
+	assert!(Quaternion::from_basis(x, y, z).dot(r).abs() > 1.0-1.0e-9);
+    }
 }
@@ -1,7 +1,10 @@
 
 use std::{f64,ops,fmt};
 
+use crate::vector::Vector4;
+
 #[derive(Debug,Copy,Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Matrix4x4 {
     pub v : [[f64; 4]; 4]
 }
@@ -73,6 +76,16 @@ impl ops::Mul for Matrix4x4 {
     }
 }
 
+impl ops::Mul<Vector4> for Matrix4x4 {
+    type Output = Vector4;
+
+    fn mul(self, other: Vector4) -> Vector4 {
+	let o = other.serialize();
+	let row = |i:usize| self.v[i][0]*o[0] + self.v[i][1]*o[1] + self.v[i][2]*o[2] + self.v[i][3]*o[3];
+	Vector4(row(0), row(1), row(2), row(3))
+    }
+}
+
 /* OpenGL側に渡すときは転置する必要がある !! */
 impl Matrix4x4 {
 
@@ -135,6 +148,7 @@ impl Matrix4x4 {
 }
 
 #[derive(Debug,Copy,Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Matrix3x3 {
     pub v : [[f64; 3]; 3]
 }
@@ -0,0 +1,155 @@
+use std::{f64,ops,fmt};
+use std::{f64::consts::PI};
+
+use crate::vector::Vector3;
+use crate::matrix::Matrix4x4;
+
+/* 四元数 q = w + (x,y,z) */
+#[derive(Debug,Copy,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quaternion {
+    pub w: f64,
+    pub v: Vector3
+}
+
+#[allow(dead_code)]
+impl fmt::Display for Quaternion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+	write!(f,"Quaternion:[{},{},{},{}]", self.w, self.v.0, self.v.1, self.v.2)
+    }
+}
+
+impl ops::Add for Quaternion {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+	Quaternion { w: self.w+other.w, v: self.v+other.v }
+    }
+}
+
+impl ops::Sub for Quaternion {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+	Quaternion { w: self.w-other.w, v: self.v-other.v }
+    }
+}
+
+/* ハミルトン積 */
+impl ops::Mul for Quaternion {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+	Quaternion {
+	    w: self.w*other.w - self.v*other.v,
+	    v: self.w*other.v + other.w*self.v + Vector3::cross_product(self.v, other.v)
+	}
+    }
+}
+
+impl ops::Mul<Quaternion> for f64 {
+    type Output = Quaternion;
+    fn mul(self, other: Quaternion) -> Quaternion {
+	Quaternion { w: self*other.w, v: self*other.v }
+    }
+}
+
+impl ops::Neg for Quaternion {
+    type Output = Self;
+    fn neg(self) -> Self {
+	Quaternion { w: -self.w, v: -self.v }
+    }
+}
+
+impl Quaternion {
+
+    pub fn new(w:f64, x:f64, y:f64, z:f64) -> Self {
+	Quaternion { w, v: Vector3(x, y, z) }
+    }
+
+    /* 恒等回転 */
+    pub fn identity() -> Self {
+	Quaternion { w: 1.0, v: Vector3(0.0, 0.0, 0.0) }
+    }
+
+    /* 任意軸回りの回転. graphic_math::rotateと同じくangleはdegree */
+    pub fn from_axis_angle(axis:Vector3, angle_in_degree:f64) -> Self {
+	let half = PI*angle_in_degree/360.0;
+	Quaternion { w: half.cos(), v: half.sin()*axis.normalize() }
+    }
+
+    /* 内積 */
+    pub fn dot(self, other: Self) -> f64 {
+	self.w*other.w + self.v*other.v
+    }
+
+    pub fn norm(self) -> f64 {
+	self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+	(1.0/self.norm())*self
+    }
+
+    /* 共役 */
+    pub fn conjugate(self) -> Self {
+	Quaternion { w: self.w, v: -self.v }
+    }
+
+    /* 単位四元数ならconjugateと同じ */
+    pub fn inverse(self) -> Self {
+	(1.0/self.dot(self))*self.conjugate()
+    }
+
+    /*
+      x,y,z軸をそれぞれx,y,zに移す回転. x,y,zは右手系の正規直交基底であること.
+      回転行列の列がx,y,zになる
+    */
+    pub fn from_basis(x:Vector3, y:Vector3, z:Vector3) -> Self {
+	let trace = x.0 + y.1 + z.2;
+	if trace > 0.0 {
+	    let s = 2.0*(trace + 1.0).sqrt();
+	    Quaternion::new(0.25*s, (y.2 - z.1)/s, (z.0 - x.2)/s, (x.1 - y.0)/s)
+	}
+	else if x.0 > y.1 && x.0 > z.2 {
+	    let s = 2.0*(1.0 + x.0 - y.1 - z.2).sqrt();
+	    Quaternion::new((y.2 - z.1)/s, 0.25*s, (y.0 + x.1)/s, (z.0 + x.2)/s)
+	}
+	else if y.1 > z.2 {
+	    let s = 2.0*(1.0 + y.1 - x.0 - z.2).sqrt();
+	    Quaternion::new((z.0 - x.2)/s, (y.0 + x.1)/s, 0.25*s, (z.1 + y.2)/s)
+	}
+	else {
+	    let s = 2.0*(1.0 + z.2 - x.0 - y.1).sqrt();
+	    Quaternion::new((x.1 - y.0)/s, (z.0 + x.2)/s, (z.1 + y.2)/s, 0.25*s)
+	}
+    }
+
+    /* 球面線形補間. 単位四元数であること. 近い方の向きを通る */
+    pub fn slerp(self, other:Self, t:f64) -> Self {
+	let mut d = self.dot(other);
+	let other = if d < 0.0 { d = -d; -other } else { other };
+	//ほぼ同じ向きのときはsinが0に近くなるので線形補間で済ます
+	if d > 0.9995 {
+	    return (self + t*(other - self)).normalize();
+	}
+	let theta = d.acos();
+	(1.0/theta.sin())*(((1.0 - t)*theta).sin()*self + (t*theta).sin()*other)
+    }
+
+    /* q*p*q^-1 でベクトルを回転させる */
+    pub fn rotate_vector(self, p:Vector3) -> Vector3 {
+	(self*Quaternion { w: 0.0, v: p }*self.conjugate()).v
+    }
+
+    /* 回転行列. 単位四元数であること */
+    pub fn to_matrix(self) -> Matrix4x4 {
+	let (w, x, y, z) = (self.w, self.v.0, self.v.1, self.v.2);
+	Matrix4x4 {
+	    v:
+	    [
+		[ 1.0-2.0*(y*y+z*z),     2.0*(x*y-w*z),     2.0*(x*z+w*y), 0.0 ],
+		[     2.0*(x*y+w*z), 1.0-2.0*(x*x+z*z),     2.0*(y*z-w*x), 0.0 ],
+		[     2.0*(x*z-w*y),     2.0*(y*z+w*x), 1.0-2.0*(x*x+y*y), 0.0 ],
+		[               0.0,               0.0,               0.0, 1.0 ]
+	    ]
+	}
+    }
+}
@@ -2,6 +2,7 @@
 use std::{f64,ops,fmt};
 
 #[derive(Debug,Copy,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector2 (pub f64, pub f64);
 
 impl ops::Add for Vector2 {
@@ -48,6 +49,7 @@ impl Vector2 {
 }
 
 #[derive(Debug,Copy,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector3 (pub f64, pub f64, pub f64);
 
 #[allow(dead_code)]
@@ -161,6 +163,7 @@ impl ops::BitAnd for Vector3 {
 }
 
 #[derive(Debug,Copy,Clone,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector4 (pub f64, pub f64, pub f64, pub f64);
 
 impl Vector4 {
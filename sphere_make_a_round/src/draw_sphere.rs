@@ -4,30 +4,84 @@ use std::{ptr, str, mem, time};
 use std::{f64::consts::PI};
 use gl;
 use gl::types::*;
-use linear_transform::{vector::*,matrix::Matrix4x4};
-use graphic_math::graphic_math;
+use linear_transform::{vector::*,quaternion::Quaternion};
+use graphic_math::scene_graph::{SceneGraph,NodeId,Transform,Attachment};
+use graphic_math::camera::Camera;
+use graphic_math::animation::{AnimationClip,AnimationPlayer,PlaybackMode,Track,Easing};
+use glutils::renderer::{Renderer,DrawItem,Material,RenderState,VertexArrayObjectContext,FrameStats};
+use glutils::material::{MaterialPrograms,ShadingModel,PhongParameter};
+use glutils::light::{LightSet,Light,LightKind};
+use glutils::shadow::{ShadowMap,ShadowSettings,ViewFrustum,Cascade};
+use glutils::texture::{Texture2D,TextureData,TextureError,Sampler,SamplerDescription,ColorSpace};
+use glutils::cubemap::{CubeMap,CubeMapData,Skybox,ENVIRONMENT_TEXTURE_UNIT,environment_uniforms};
+use glutils::ibl::{IblMaps,IblSettings,SourceStamp,ibl_uniforms};
+use glutils::hdr::{HdrTarget,ToneMapSettings};
+use glutils::postprocess::{PostProcessStack,PostProcessSettings,ColorLut};
+use glutils::ssao::{SsaoPass,SsaoSettings,ssao_uniforms};
+use glutils::deferred::{DeferredRenderer,DeferredSettings,GBufferView,is_deferred};
 
 static VERTEX_SHADER_CODE: &'static str = include_str!("simple_viewport.vert");
 
-static LIGHTING_VERTEX_SHADER_CODE: &'static str = include_str!("lighting.vert");
-
 static FRAGMENT_SHADER_CODE: &'static str = include_str!("simple.frag");
 
-struct VertexArrayObjectContext {
-    vao: GLuint,
-    draw_mode: GLenum,
-    count_of_draw_index: GLsizei
-}
-
 struct RenderSet {
     shader: GLuint,
-    vao_context: VertexArrayObjectContext
+    vao_context: VertexArrayObjectContext,
+    state: RenderState
+}
+
+/* render_setsの添字. シーングラフのAttachment::Meshから参照する */
+const AXIS_MESH: usize = 0;
+const SPHERE_MESH: usize = 1;
+const GROUND_MESH: usize = 2;
+const PLANET_MESH: usize = 3;
+
+/* 背景と映り込み. reflectionが0なら映り込まない. iblはPBRのときだけ効く */
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct EnvironmentSettings {
+    pub skybox: bool,
+    pub reflection: f32,
+    pub ibl: bool
+}
+
+/*
+  20秒で1周する公転. 月はorbitの4倍の角度で回る.
+  値は角度(degree)なので,四元数と違って360度を超えても補間できる
+*/
+fn orbit_clip() -> AnimationClip {
+    AnimationClip::new(20.0, PlaybackMode::Loop)
+	.track("orbit", Track::new().key(0.0, 0.0, Easing::Linear).key(20.0, 360.0, Easing::Linear))
 }
 
 pub struct DrawSphere {
-    elapsed_time: time::Duration,
-    axis_set: RenderSet,
-    sphere_set: RenderSet
+    animation: AnimationPlayer,
+    render_sets: Vec<RenderSet>,
+    renderer: Renderer,
+    materials: MaterialPrograms,
+    shading: ShadingModel,
+    lights: LightSet,
+    shadow_map: ShadowMap,
+    shadow_settings: ShadowSettings,
+    planet_texture: Texture2D,
+    planet_sampler: Sampler,
+    textured: bool,
+    skybox: Skybox,
+    environment: CubeMap,
+    environment_settings: EnvironmentSettings,
+    ibl: IblMaps,
+    ssao: SsaoPass,
+    ssao_settings: SsaoSettings,
+    deferred: DeferredRenderer,
+    deferred_settings: DeferredSettings,
+    /* 遅延シェーディングのときだけ足す,床の上を回る小さな点光源の数 */
+    extra_point_lights: usize,
+    hdr_target: HdrTarget,
+    tone_map_settings: ToneMapSettings,
+    post_process: PostProcessStack,
+    post_process_settings: PostProcessSettings,
+    scene: SceneGraph,
+    planet_orbit: NodeId,
+    moon_orbit: NodeId
 }
 
 fn compile_shader(shader_code: &str, shader_type: GLenum) -> GLuint {
@@ -208,17 +262,17 @@ fn create_coordinate_axes_array(vao:GLuint) -> RenderSet {
 
     RenderSet {
 	shader: shader_program,
-	vao_context : VertexArrayObjectContext {
-	    vao: vao,
-	    draw_mode: gl::LINES,
-	    count_of_draw_index: (COORDINATE_AXES_INDEX_DATA.len() * 2) as GLsizei
-	}
+	vao_context : VertexArrayObjectContext::new(vao, gl::LINES, (COORDINATE_AXES_INDEX_DATA.len() * 2) as GLsizei),
+	//axesの線は隠面消去しない.
+	state: RenderState { depth_test: false, ..RenderState::default() }
     }
 }
 
-fn sphere_vertices(radius:f64, slice:u32, stack:u32) -> (Vec<[f64;3]>,Vec<[f64;3]>,Vec<[u32;3]>) {
+/* 位置,法線,テクスチャ座標,インデックス. テクスチャ座標は経度をu,北極(+z)をv=1にする正距円筒図法 */
+fn sphere_vertices(radius:f64, slice:u32, stack:u32) -> (Vec<[f64;3]>,Vec<[f64;3]>,Vec<[f64;2]>,Vec<[u32;3]>) {
     let mut ps:Vec<[f64;3]> = Vec::new();
     let mut ns:Vec<[f64;3]> = Vec::new();
+    let mut ts:Vec<[f64;2]> = Vec::new();
     let mut is:Vec<[u32;3]> = Vec::new();
 
     for j in 0 ..  stack+1 {
@@ -227,6 +281,7 @@ fn sphere_vertices(radius:f64, slice:u32, stack:u32) -> (Vec<[f64;3]>,Vec<[f64;3
 	    let phi:f64 = 2.0*PI*(i as f64)/(slice as f64);
 	    ps.push([radius*theta.sin()*phi.cos(), radius*theta.sin()*phi.sin(), radius*theta.cos()]);
 	    ns.push([radius*theta.sin()*phi.cos(), radius*theta.sin()*phi.sin(), radius*theta.cos()]);
+	    ts.push([(i as f64)/(slice as f64), 1.0-(j as f64)/(stack as f64)]);
 	}
     }
 
@@ -239,35 +294,32 @@ fn sphere_vertices(radius:f64, slice:u32, stack:u32) -> (Vec<[f64;3]>,Vec<[f64;3
 	}
     }
 
-    (ps,ns,is)
+    (ps,ns,ts,is)
 }
 
-fn create_sphere_array_object(vao:GLuint) -> RenderSet {
-    let mut vbos : [GLuint;4] = [0,0,0,0];
-    let mut shader_program = 0;
+/* シェーダはglutils::materialのものを使う. 頂点属性の配置はmaterial.vertに合わせる */
+fn create_sphere_array_object(vao:GLuint, shader_program:GLuint, color:[GLfloat;4]) -> RenderSet {
+    let mut vbos : [GLuint;5] = [0,0,0,0,0];
     enum LocationInShader {
 	Position = 0,
 	Normal   = 1,
-	Color    = 2
+	Color    = 2,
+	TexCoord = 3
     }
 
     enum VBOIndex {
 	Vertex = 0,
 	Normal = 1,
 	ElementIndex = 2,
-	Color = 3
+	Color = 3,
+	TexCoord = 4
     }
 
-    let (circle_vertices, circle_normals, circle_indices) = sphere_vertices(1.0, 24, 24);
+    let (circle_vertices, circle_normals, circle_texcoords, circle_indices) = sphere_vertices(1.0, 24, 24);
 
     unsafe {
-	let vertex_shader = compile_shader(LIGHTING_VERTEX_SHADER_CODE, gl::VERTEX_SHADER);
-	let fragment_shader = compile_shader(FRAGMENT_SHADER_CODE, gl::FRAGMENT_SHADER);
-	shader_program = link_program(vertex_shader, fragment_shader);
-	gl::DeleteShader(fragment_shader);
-	gl::DeleteShader(vertex_shader);
 	gl::BindVertexArray(vao);
-	gl::GenBuffers(4, &mut vbos[0]);
+	gl::GenBuffers(5, &mut vbos[0]);
 
 	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[VBOIndex::Vertex as usize]);
 	gl::BufferData(gl::ARRAY_BUFFER,
@@ -321,14 +373,21 @@ fn create_sphere_array_object(vao:GLuint) -> RenderSet {
 		let g: *mut GLfloat = mapped_buffer.offset((4*i+1) as isize);
 		let b: *mut GLfloat = mapped_buffer.offset((4*i+2) as isize);
 		let a: *mut GLfloat = mapped_buffer.offset((4*i+3) as isize);
-		*r = 1.0;
-		*g = 1.0;
-		*b = 0.0;
-		*a = 1.0;
+		*r = color[0];
+		*g = color[1];
+		*b = color[2];
+		*a = color[3];
 	    }
 	    gl::UnmapBuffer(gl::ARRAY_BUFFER);
 	}
 
+	let texcoords:Vec<GLfloat> = circle_texcoords.iter().flat_map(|t| vec![t[0] as GLfloat, t[1] as GLfloat]).collect();
+	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[VBOIndex::TexCoord as usize]);
+	gl::BufferData(gl::ARRAY_BUFFER,
+		       (texcoords.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+		       texcoords.as_ptr() as *const _,
+		       gl::STATIC_DRAW);
+
 	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vbos[VBOIndex::ElementIndex as usize]);
 	gl::BufferData(gl::ELEMENT_ARRAY_BUFFER,
                        (circle_indices.len() * 3 * mem::size_of::<GLuint>()) as GLsizeiptr,
@@ -347,27 +406,32 @@ fn create_sphere_array_object(vao:GLuint) -> RenderSet {
 	    gl::UnmapBuffer(gl::ELEMENT_ARRAY_BUFFER);
 	}
 
-	//lighting.vertの変数"position"と頂点バッファを結びつける.
+	//material.vertの変数"position"と頂点バッファを結びつける.
 	gl::EnableVertexAttribArray(LocationInShader::Position as GLuint);
 	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[VBOIndex::Vertex as usize]);
 	//shader側のメモリに配置したVertex情報のフォーマットを設定する.
 	//今回は3次元座標なので,size=3
 	gl::VertexAttribPointer(LocationInShader::Position as GLuint, 3, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
 
-	//lighting.vertの変数"normal"と法線バッファを結びつける.
+	//material.vertの変数"normal"と法線バッファを結びつける.
 	gl::EnableVertexAttribArray(LocationInShader::Normal as GLuint);
 	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[VBOIndex::Normal as usize]);
 	//shader側のメモリに配置したVertex情報のフォーマットを設定する.
 	//今回は3次元座標なので,size=3
 	gl::VertexAttribPointer(LocationInShader::Normal as GLuint, 3, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
 
-	//lighting.vertの変数"vertexColor"と頂点での色情報バッファを結びつける.
+	//material.vertの変数"vertexColor"と頂点での色情報バッファを結びつける.
 	gl::EnableVertexAttribArray(LocationInShader::Color as GLuint);
 	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[VBOIndex::Color as usize]);
 	//shader側のメモリに配置したVertex情報のフォーマットを設定する.
 	//今回はRGBAなので,size=4
 	gl::VertexAttribPointer(LocationInShader::Color as GLuint, 4, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
 
+	//material.vertの変数"texCoord"とテクスチャ座標バッファを結びつける.
+	gl::EnableVertexAttribArray(LocationInShader::TexCoord as GLuint);
+	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[VBOIndex::TexCoord as usize]);
+	gl::VertexAttribPointer(LocationInShader::TexCoord as GLuint, 2, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+
 	gl::BindVertexArray(0); //先にVAOを解く. でないと,ELEMENT_BUFFERとARRAY_BUFFERがVAOから外される.
 	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
 	gl::BindBuffer(gl::ARRAY_BUFFER, 0);
@@ -378,18 +442,90 @@ fn create_sphere_array_object(vao:GLuint) -> RenderSet {
 
     RenderSet {
 	shader: shader_program,
-	vao_context : VertexArrayObjectContext {
-	    vao: vao,
-	    draw_mode: gl::TRIANGLES,
-	    count_of_draw_index: (circle_indices.len()*3) as GLsizei
-	}
+	vao_context : VertexArrayObjectContext::new(vao, gl::TRIANGLES, (circle_indices.len()*3) as GLsizei),
+	state: RenderState::default()
+    }
+}
+
+/* 影を受けるための床. z=GROUND_HEIGHTに置いた一辺4の正方形 */
+const GROUND_HEIGHT: GLfloat = -0.5;
+
+static GROUND_VERTEX_DATA: [[GLfloat;3];4] = [
+    [ -2.0, -2.0, GROUND_HEIGHT ],
+    [  2.0, -2.0, GROUND_HEIGHT ],
+    [  2.0,  2.0, GROUND_HEIGHT ],
+    [ -2.0,  2.0, GROUND_HEIGHT ],
+];
+
+static GROUND_NORMAL_DATA: [[GLfloat;3];4] = [[ 0.0, 0.0, 1.0 ];4];
+
+static GROUND_COLOR_DATA: [[GLfloat;4];4] = [[ 0.8, 0.8, 0.8, 1.0 ];4];
+
+static GROUND_INDEX_DATA: [[GLuint;3];2] = [
+    [ 0, 1, 2 ],
+    [ 0, 2, 3 ],
+];
+
+fn create_ground_array_object(vao:GLuint, shader_program:GLuint) -> RenderSet {
+    let mut vbos : [GLuint;4] = [0,0,0,0];
+    unsafe {
+	gl::BindVertexArray(vao);
+	gl::GenBuffers(4, &mut vbos[0]);
+
+	//頂点属性の配置はmaterial.vertに合わせる. position=0,normal=1,vertexColor=2
+	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[0]);
+	gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(&GROUND_VERTEX_DATA) as GLsizeiptr,
+		       GROUND_VERTEX_DATA.as_ptr() as *const _, gl::STATIC_DRAW);
+	gl::EnableVertexAttribArray(0);
+	gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+
+	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[1]);
+	gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(&GROUND_NORMAL_DATA) as GLsizeiptr,
+		       GROUND_NORMAL_DATA.as_ptr() as *const _, gl::STATIC_DRAW);
+	gl::EnableVertexAttribArray(1);
+	gl::VertexAttribPointer(1, 3, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+
+	gl::BindBuffer(gl::ARRAY_BUFFER, vbos[2]);
+	gl::BufferData(gl::ARRAY_BUFFER, mem::size_of_val(&GROUND_COLOR_DATA) as GLsizeiptr,
+		       GROUND_COLOR_DATA.as_ptr() as *const _, gl::STATIC_DRAW);
+	gl::EnableVertexAttribArray(2);
+	gl::VertexAttribPointer(2, 4, gl::FLOAT, gl::FALSE as GLboolean, 0, ptr::null());
+
+	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, vbos[3]);
+	gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, mem::size_of_val(&GROUND_INDEX_DATA) as GLsizeiptr,
+		       GROUND_INDEX_DATA.as_ptr() as *const _, gl::STATIC_DRAW);
+
+	gl::BindVertexArray(0);
+	gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+	gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+    }
+
+    RenderSet {
+	shader: shader_program,
+	vao_context : VertexArrayObjectContext::new(vao, gl::TRIANGLES, (GROUND_INDEX_DATA.len()*3) as GLsizei),
+	state: RenderState::default()
     }
 }
 
+/* 床の少し上を回る色とりどりの点光源. 遅延シェーディングで光源が多い場合を試す */
+fn orbiting_point_lights(count:usize, degree:f64) -> Vec<Light> {
+    (0..count).map(|i| {
+	let theta = 2.0*PI*(i as f64)/(count as f64) + degree*PI/180.0;
+	let hue = 2.0*PI*(i as f64)/(count as f64);
+	let channel = |offset:f64| (0.5 + 0.5*(hue + offset).cos()) as f32;
+	let mut light = Light::point([(1.5*theta.cos()) as f32, (1.5*theta.sin()) as f32, GROUND_HEIGHT + 0.1],
+				     [channel(0.0), channel(-2.0*PI/3.0), channel(2.0*PI/3.0)], 0.5);
+	if let LightKind::Point { ref mut attenuation, .. } = light.kind {
+	    attenuation.quadratic = 20.0;
+	}
+	light
+    }).collect()
+}
+
 impl DrawSphere {
 
     pub fn create() -> Self {
-	let mut vao: [GLuint;2] = [0,0];
+	let mut vao: [GLuint;4] = [0,0,0,0];
 
 	unsafe {
 
@@ -401,77 +537,303 @@ impl DrawSphere {
 	    gl::ClearDepth(1.0);
 	    gl::DepthFunc(gl::LESS);
 
-	    gl::GenVertexArrays(2, &mut vao[0]);
+	    gl::GenVertexArrays(4, &mut vao[0]);
 	}
 
+	/*
+	  root ─┬─ axes
+	        ├─ ground
+	        └─ planet_orbit(z軸回転) ─ planet(平行移動) ─┬─ planet_body(縮小)
+	                                                    └─ moon_orbit(z軸回転) ─ moon(平行移動,縮小)
+	 */
+	let mut scene = SceneGraph::new();
+	let root = scene.root();
+	let axes = scene.add_node(root, "axes", Transform::identity());
+	scene.attach(axes, Attachment::Mesh(AXIS_MESH));
+	let ground = scene.add_node(root, "ground", Transform::identity());
+	scene.attach(ground, Attachment::Mesh(GROUND_MESH));
+	let planet_orbit = scene.add_node(root, "planet_orbit", Transform::identity());
+	let planet = scene.add_node(planet_orbit, "planet", Transform::from_translation(Vector3(1.0,1.0,0.0)));
+	let planet_body = scene.add_node(planet, "planet_body", Transform::from_scale(Vector3(0.1,0.1,0.1)));
+	scene.attach(planet_body, Attachment::Mesh(PLANET_MESH));
+	let moon_orbit = scene.add_node(planet, "moon_orbit", Transform::identity());
+	let moon = scene.add_node(moon_orbit, "moon", Transform {
+	    translation: Vector3(0.25,0.0,0.0),
+	    scale: Vector3(0.04,0.04,0.04),
+	    ..Transform::identity()
+	});
+	scene.attach(moon, Attachment::Mesh(SPHERE_MESH));
+
+	let materials = MaterialPrograms::create();
+	let shading = ShadingModel::BlinnPhong(PhongParameter::default());
+	let renderer = Renderer::new();
+	let mut lights = LightSet::new();
+	//以前のlighting.vertと同じ向きの平行光源
+	lights.add(Light::directional((-Vector3(8.0,4.0,2.0).normalize()).serialize_f32(), [1.0,1.0,1.0], 1.0));
+	//原点に置いた太陽の代わりの点光源と,真上から照らすスポットライト
+	lights.add(Light::point([0.0,0.0,0.0], [1.0,0.6,0.2], 2.0));
+	//スポットライトで惑星と月の影を床に落とす
+	let mut spot = Light::spot([0.0,0.0,2.0], [0.0,0.0,-1.0], 20.0, 30.0, [0.4,0.6,1.0], 2.0);
+	spot.cast_shadow = true;
+	lights.add(spot);
+	let shadow_settings = ShadowSettings::default();
+	//画像が指定されるまでは上下で色が変わるだけの空にしておく
+	let environment = CubeMap::from_data(&CubeMapData::gradient(64, [60,110,200,255], [200,210,220,255], [70,60,50,255]),
+					     ColorSpace::Srgb, true);
+	let ibl = IblMaps::bake(&environment, &IblSettings::default());
+	//LUTが指定されるまでは,暗部を青く明部を黄色くして少しコントラストを上げる表を使う
+	let mut post_process = PostProcessStack::create();
+	post_process.set_lut(&ColorLut::from_fn(16, |[r, g, b]| {
+	    let contrast = |x:f32| x*x*(3.0 - 2.0*x);
+	    let luma = 0.299*r + 0.587*g + 0.114*b;
+	    [contrast(r) + 0.05*(luma - 0.3), contrast(g), contrast(b) - 0.05*(luma - 0.3)]
+	}));
+
 	DrawSphere {
-	    elapsed_time: time::Duration::new(0,0),
-	    axis_set: create_coordinate_axes_array(vao[0]),
-	    sphere_set: create_sphere_array_object(vao[1])
+	    animation: AnimationPlayer::new(orbit_clip()),
+	    render_sets: vec![create_coordinate_axes_array(vao[0]),
+			      create_sphere_array_object(vao[1], materials.program(&shading), [1.0,1.0,0.0,1.0]),
+			      create_ground_array_object(vao[2], materials.program(&shading)),
+			      //テクスチャの色をそのまま出すために頂点色は白にする
+			      create_sphere_array_object(vao[3], materials.program(&shading), [1.0,1.0,1.0,1.0])],
+	    renderer,
+	    materials,
+	    shading,
+	    lights,
+	    shadow_map: ShadowMap::create(shadow_settings.resolution),
+	    shadow_settings,
+	    //画像が指定されるまでは市松模様を貼っておく
+	    planet_texture: Texture2D::from_data(&TextureData::checkerboard(256, 8, [40,90,200,255], [230,230,230,255]),
+						 ColorSpace::Srgb, true),
+	    planet_sampler: Sampler::create(SamplerDescription { max_anisotropy: 8.0, ..SamplerDescription::default() }),
+	    textured: true,
+	    skybox: Skybox::create(),
+	    environment,
+	    environment_settings: EnvironmentSettings { skybox: true, reflection: 0.5, ibl: true },
+	    ibl,
+	    ssao: SsaoPass::create(),
+	    ssao_settings: SsaoSettings::default(),
+	    deferred: DeferredRenderer::create(),
+	    deferred_settings: DeferredSettings::default(),
+	    extra_point_lights: 32,
+	    //大きさは最初のrenderで合わせる
+	    hdr_target: HdrTarget::create(1, 1),
+	    tone_map_settings: ToneMapSettings::default(),
+	    post_process,
+	    post_process_settings: PostProcessSettings::default(),
+	    scene,
+	    planet_orbit,
+	    moon_orbit
 	}
     }
 
-    pub fn render(&mut self, width:i32, height:i32, camera_param:((f64,f64,f64),(f64,f64,f64),(f64,f64,f64)), interval:time::Duration ) -> () {
+    pub fn shading(&self) -> ShadingModel {
+	self.shading
+    }
+
+    pub fn set_shading(&mut self, shading:ShadingModel) {
+	self.shading = shading;
+    }
+
+    pub fn lights_mut(&mut self) -> &mut LightSet {
+	&mut self.lights
+    }
+
+    /* 惑星に貼る画像(PNGかJPEG)を読み込む. 地図なら経度0が左端,北極が上端 */
+    pub fn load_planet_texture(&mut self, path:&str) -> Result<(), TextureError> {
+	self.planet_texture = Texture2D::load(path, ColorSpace::Srgb, true)?;
+	Ok(())
+    }
+
+    /*
+      背景に使うパノラマ画像(PNGかJPEG,+zが上)を読み込む.
+      IBLの前計算は時間がかかるので,画像の隣の<path>.iblディレクトリにキャッシュする
+    */
+    pub fn load_environment(&mut self, path:&str) -> Result<(), TextureError> {
+	self.environment = CubeMap::load_equirect(path, 512, ColorSpace::Srgb)?;
+	let source = SourceStamp::of_file(path).unwrap_or_default();
+	self.ibl = IblMaps::load_or_bake(format!("{}.ibl", path), &self.environment, &IblSettings::default(), source);
+	Ok(())
+    }
+
+    pub fn environment_settings_mut(&mut self) -> &mut EnvironmentSettings {
+	&mut self.environment_settings
+    }
+
+    pub fn ssao_settings_mut(&mut self) -> &mut SsaoSettings {
+	&mut self.ssao_settings
+    }
+
+    pub fn deferred_settings_mut(&mut self) -> &mut DeferredSettings {
+	&mut self.deferred_settings
+    }
+
+    pub fn extra_point_lights(&self) -> usize {
+	self.extra_point_lights
+    }
+
+    pub fn set_extra_point_lights(&mut self, count:usize) {
+	self.extra_point_lights = count;
+    }
+
+    pub fn tone_map_settings_mut(&mut self) -> &mut ToneMapSettings {
+	&mut self.tone_map_settings
+    }
+
+    pub fn post_process_settings_mut(&mut self) -> &mut PostProcessSettings {
+	&mut self.post_process_settings
+    }
+
+    /* color gradingの表(256x16のような横長の画像)を読み込む */
+    pub fn load_color_lut(&mut self, path:&str) -> Result<(), TextureError> {
+	self.post_process.set_lut(&ColorLut::load(path)?);
+	Ok(())
+    }
+
+    pub fn textured(&self) -> bool {
+	self.textured
+    }
+
+    /* 公転の速さや一時停止 */
+    pub fn animation_mut(&mut self) -> &mut AnimationPlayer {
+	&mut self.animation
+    }
+
+    pub fn set_textured(&mut self, textured:bool) {
+	self.textured = textured;
+    }
+
+    pub fn shadow_settings_mut(&mut self) -> &mut ShadowSettings {
+	&mut self.shadow_settings
+    }
+
+    /* 直前のフレームの描画統計 */
+    pub fn stats(&self) -> FrameStats {
+	self.renderer.stats()
+    }
+
+    /* アニメーションを進める. 描く回数とは別に決まった間隔で呼ぶ */
+    pub fn update(&mut self, interval:time::Duration) {
+	self.animation.update(interval);
+    }
+
+    /* cameraのaspectは呼ぶ側でresizeしておく */
+    pub fn render(&mut self, width:i32, height:i32, camera:&Camera) -> () {
 	//射影変換行列を計算する.
-	let r:f64      = 4.0;
-	let theta:f64  = PI*60.0/180.0;
-	let phi:f64    = PI*45.0/180.0;
-	//let eye    = Vector3(r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos());
-	//let center = Vector3(0.0,0.0,0.0);
-	//let up     = Vector3(0.0,0.0,1.0);
-	let eye    = Vector3::make_from_tuple(camera_param.0);
-	let center = Vector3::make_from_tuple(camera_param.1);
-	let up     = Vector3::make_from_tuple(camera_param.2);
-	let lookat = graphic_math::look_at(eye,center,up);
-	let aspect = (width as f64)/ (height as f64);
-	let pers   = graphic_math::perspective(30.0, aspect, 1.0, 11.0);
-	let mvp    = pers*lookat;
-	let degree = (((self.elapsed_time.as_millis())%(20000)) as f64)*(360.0/20000.0);
-
-	self.elapsed_time += interval;
-	unsafe {
+	let lookat = camera.view();
+	let pers   = camera.projection();
+	let frustum = ViewFrustum::from_camera(camera);
+	let degree = self.animation.scalar("orbit").unwrap_or(0.0);
+
+	//公転の角度だけ更新すれば,子のplanetとmoonのワールド行列はシーングラフが計算しなおす.
+	self.scene.set_rotation(self.planet_orbit, Quaternion::from_axis_angle(Vector3(0.0,0.0,1.0), degree));
+	self.scene.set_rotation(self.moon_orbit, Quaternion::from_axis_angle(Vector3(0.0,0.0,1.0), 4.0*degree));
+	self.scene.update_world_matrices();
+
+	let render_sets = &self.render_sets;
+	let mut items:Vec<DrawItem> = Vec::new();
+	let deferred = self.deferred_settings.enabled;
+	let lit_material = if deferred {
+	    self.deferred.material(&self.shading)
+	}
+	else {
+	    self.materials.material(&self.shading)
+	};
+	let planet_material = match (self.textured, deferred) {
+	    (true, true) => self.deferred.textured_material(&self.shading, &self.planet_texture, Some(&self.planet_sampler)),
+	    (true, false) => self.materials.textured_material(&self.shading, &self.planet_texture, Some(&self.planet_sampler)),
+	    (false, _) => lit_material.clone()
+	};
+	self.scene.traverse(|_, _, world, attachments| {
+	    for attachment in attachments {
+		if let Attachment::Mesh(index) = attachment {
+		    let render_set = &render_sets[*index];
+		    let material = match *index {
+			AXIS_MESH => Material::new(render_set.shader),
+			PLANET_MESH => planet_material.clone(),
+			_ => lit_material.clone()
+		    };
+		    items.push(DrawItem {
+			mesh: render_set.vao_context,
+			material,
+			transform: *world,
+			state: render_set.state
+		    });
+		}
+	    }
+	});
+
+	//影の解像度が変わったらシャドウマップを作りなおす.
+	if self.shadow_map.resolution() != self.shadow_settings.resolution {
+	    self.shadow_map = ShadowMap::create(self.shadow_settings.resolution);
+	}
+	//光源から見た深度を描く. 軸の線は影を落とさない.
+	let (shadow_light, cascades) = match self.lights.shadow_caster() {
+	    Some((index, light)) => (Some(index), self.shadow_settings.cascades(light, &frustum)),
+	    None => (None, Vec::<Cascade>::new())
+	};
+	if !cascades.is_empty() {
+	    let casters:Vec<DrawItem> = items.iter().filter(|item| item.mesh.draw_mode == gl::TRIANGLES).cloned().collect();
+	    self.shadow_map.render(&cascades, &casters);
+	}
+
+	//視点から見た法線と深度から遮蔽を求めておき,描画時に環境光へ掛ける.
+	if self.ssao_settings.enabled {
+	    self.ssao.render(&lookat, &pers, &items, width, height, &self.ssao_settings);
+	}
 
+	//シーンはリニアなままHDRのframebufferに描き,最後にトーンマッピングして画面に出す.
+	self.hdr_target.resize(width, height);
+	self.hdr_target.bind();
+	unsafe {
 	    gl::ClearColor(0.3, 0.3, 0.3, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT);
-	    gl::Viewport(0, 0, width, height);
-
-	    gl::UseProgram(self.axis_set.shader);
-	    {
-		let mvp_str = CString::new("mvp").unwrap_or_else(|_| panic!("failed to allocate string space"));
-		let mvp_location = gl::GetUniformLocation(self.axis_set.shader, mvp_str.as_ptr());
-		gl::ProgramUniformMatrix4fv(self.axis_set.shader, mvp_location, 1, gl::TRUE, mem::transmute(&mvp.serialize_f32()[0]));
-
-		gl::BindVertexArray(self.axis_set.vao_context.vao);
-		gl::DrawElements(self.axis_set.vao_context.draw_mode,
-				 self.axis_set.vao_context.count_of_draw_index,
-				 gl::UNSIGNED_INT, ptr::null());
-		gl::BindVertexArray(0);
-	    }
+	}
 
-	    gl::Enable(gl::DEPTH_TEST);
-	    gl::UseProgram(self.sphere_set.shader);
-	    {
-		let model_str = CString::new("model").unwrap_or_else(|_| panic!("failed to allocate string space"));
-		let view_str = CString::new("view").unwrap_or_else(|_| panic!("failed to allocate string space"));
-		let projection_str = CString::new("projection").unwrap_or_else(|_| panic!("failed to allocate string space"));
-		let model_location = gl::GetUniformLocation(self.sphere_set.shader, model_str.as_ptr());
-		let view_location = gl::GetUniformLocation(self.sphere_set.shader, view_str.as_ptr());
-		let projection_location = gl::GetUniformLocation(self.sphere_set.shader, projection_str.as_ptr());
-		let translate = graphic_math::rotate(Vector3(0.0,0.0,1.0),degree)*
-		    graphic_math::translate(Vector3(1.0,1.0,0.0))*
-		    graphic_math::scale(Vector3(0.1,0.1,0.1));
-
-		gl::ProgramUniformMatrix4fv(self.sphere_set.shader, model_location, 1, gl::TRUE, mem::transmute(&translate.serialize_f32()[0]));
-		gl::ProgramUniformMatrix4fv(self.sphere_set.shader, view_location, 1, gl::TRUE, mem::transmute(&lookat.serialize_f32()[0]));
-		gl::ProgramUniformMatrix4fv(self.sphere_set.shader, projection_location, 1, gl::TRUE, mem::transmute(&pers.serialize_f32()[0]));
-
-		gl::BindVertexArray(self.sphere_set.vao_context.vao);
-		gl::DrawElements(self.sphere_set.vao_context.draw_mode,
-				 self.sphere_set.vao_context.count_of_draw_index,
-				 gl::UNSIGNED_INT, ptr::null());
-		gl::BindVertexArray(0);
+	//uniformのmodel,view,projection(axesはmvp)はRendererが設定する.
+	self.renderer.begin_frame(lookat, pers);
+	self.lights.apply(&mut self.renderer);
+	let reflection = self.environment_settings.reflection;
+	let ibl = if self.environment_settings.ibl { Some(&self.ibl) } else { None };
+	let mut frame_uniforms = self.shadow_settings.uniforms(shadow_light, &cascades);
+	frame_uniforms.append(&mut environment_uniforms(reflection > 0.0, reflection));
+	frame_uniforms.append(&mut ibl_uniforms(ibl, 1.0));
+	frame_uniforms.append(&mut ssao_uniforms(self.ssao_settings.enabled));
+	for (name, value) in frame_uniforms {
+	    if deferred {
+		self.deferred.set_frame_uniform(&name, value.clone());
 	    }
-	    gl::Disable(gl::DEPTH_TEST);
+	    self.renderer.set_frame_uniform(&name, value);
+	}
+	self.shadow_map.bind();
+	self.environment.bind(ENVIRONMENT_TEXTURE_UNIT);
+	self.ibl.bind();
+	self.ssao.bind();
+
+	//遅延シェーディングでは不透明な三角形をGバッファから照らし,残りの軸の線だけを前方描画で重ねる.
+	if deferred {
+	    let mut lights:Vec<Light> = self.lights.iter().cloned().collect();
+	    lights.append(&mut orbiting_point_lights(self.extra_point_lights, degree));
+	    self.deferred.render(&lookat, &pers, &items, &lights, &self.deferred_settings);
+	    items.retain(|item| !is_deferred(item));
+	}
+	for item in items {
+	    self.renderer.submit(item);
+	}
+	self.renderer.end_frame();
+	//背景は物体の後に描いて,隠れた部分を塗らないようにする
+	if self.environment_settings.skybox && !(deferred && self.deferred_settings.debug_view != GBufferView::Lit) {
+	    self.skybox.render(&lookat, &pers, &self.environment, 1.0);
+	}
+
+	//bloomなどをかけてトーンマッピングし,画面に出す. eguiはこの後に描く
+	unsafe {
+	    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	}
+	self.post_process.render(self.hdr_target.color_texture(), width, height,
+				 &self.tone_map_settings, &self.post_process_settings);
+
+	unsafe {
 	    gl::Flush();
 	}
 
@@ -1,211 +1,138 @@
-
 mod draw_sphere;
+mod settings_ui;
 
-use std::{f64::consts::PI};
 use std::time;
 
-use glfw::{Context};
-use egui_glfw_gl::egui::{vec2, Color32, Image, Pos2, Rect};
+use app_framework::{App,AppConfig,InitContext,FrameContext,LoopControl};
+use app_framework::widgets::{CameraEntry,CameraCommand,camera_ui};
+use linear_transform::vector::*;
+use graphic_math::camera::{Camera,CameraController,OrbitController};
+use graphic_math::input::{Input,ActionMap,drive_camera};
 
 use draw_sphere::DrawSphere;
-
-macro_rules! real_fixed {
-    ($x:expr,$decimal:expr) => (($x * 10f64.powf($decimal as f64)).round()/(10f64.powf($decimal as f64)))
-}
-
-#[derive(Clone,Copy,Debug)]
-struct CameraParamInput {
-    position: (f64, f64, f64),
-    center: (f64, f64, f64),
-    up: (f64, f64, f64),
-}
+use glutils::material::ShadingModel;
+use glutils::hdr::OutputEncoding;
 
 struct AppControlContext {
-    camera_parameter : CameraParamInput
+    camera : Camera,
+    shading : ShadingModel
 }
 
-fn main() {
-    let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap_or_else(|e| panic!("failed to init GLFW. {}",e));
-
-    let (mut window, events) = glfw.create_window(800, 600, "Draw Frame Sphere", glfw::WindowMode::Windowed)
-        .unwrap_or_else(|| panic!("Failed to create GLFW window."));
-
-    window.set_all_polling(true);
-    window.set_mouse_button_polling(true);
-    window.make_current();
-    glfw.set_swap_interval(glfw::SwapInterval::Sync(1));
-
-    gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
-
-    let mut painter = egui_glfw_gl::Painter::new(&mut window);
-    let mut egui_ctx = egui::CtxRef::default();
-
-    let (width, height) = window.get_framebuffer_size();
-    let native_pixels_per_point = window.get_content_scale().0;
-    let mut egui_input_state = egui_glfw_gl::EguiInputState::new(egui::RawInput {
-        screen_rect: Some(Rect::from_min_size(
-            Pos2::new(0f32, 0f32),
-            vec2(width as f32, height as f32) / native_pixels_per_point,
-        )),
-        pixels_per_point: Some(native_pixels_per_point),
-        ..Default::default()
-    });
-    let start_time = time::Instant::now();
-    //let mut value_str: String = "0.0".to_owned();
-
-    let r:f64 = 5.0;
-    let theta:f64 = PI*75.0/180.0;
-    let phi:f64 = PI*30.0/180.0;
-    let init_camera_param : CameraParamInput = CameraParamInput {
-	position: (r*theta.sin()*phi.cos(), r*theta.sin()*phi.sin(), r*theta.cos()),
-	center: (0.0, 0.0, 0.0),
-	up: (0.0, 0.0, 1.0),
-    };
+struct SphereApp {
+    draw_sphere: DrawSphere,
+    control_context: AppControlContext,
+    /* ドラッグとスクロールで視点を回す. 入力欄で決めた視点もこれに渡す */
+    camera_controller: OrbitController,
+    init_camera: Camera,
+    camera_entry: CameraEntry
+}
 
-    let mut control_context = AppControlContext {
-	camera_parameter: init_camera_param
-    };
+impl App for SphereApp {
+    fn init(context:&InitContext) -> Self {
+	let init_camera = Camera::from_spherical(Vector3(0.0, 0.0, 0.0), 5.0, 75.0, 30.0);
 
-    let camera_position_fixed = (real_fixed!(control_context.camera_parameter.position.0, 3),
-				 real_fixed!(control_context.camera_parameter.position.1, 3),
-				 real_fixed!(control_context.camera_parameter.position.2, 3));
-    let camera_center_fixed = (real_fixed!(control_context.camera_parameter.center.0, 3),
-				 real_fixed!(control_context.camera_parameter.center.1, 3),
-			       real_fixed!(control_context.camera_parameter.center.2, 3));
-    let camera_up_fixed = (real_fixed!(control_context.camera_parameter.up.0, 3),
-			       real_fixed!(control_context.camera_parameter.up.1, 3),
-			       real_fixed!(control_context.camera_parameter.up.2, 3));
-    let mut camera_position = (camera_position_fixed.0.to_string().to_owned(),
-			       camera_position_fixed.1.to_string().to_owned(),
-			       camera_position_fixed.2.to_string().to_owned());
-    let mut camera_center   = (camera_center_fixed.0.to_string().to_owned(),
-			       camera_center_fixed.1.to_string().to_owned(),
-			       camera_center_fixed.2.to_string().to_owned());
-    let mut camera_up       = (camera_up_fixed.0.to_string().to_owned(),
-			       camera_up_fixed.1.to_string().to_owned(),
-			       camera_up_fixed.2.to_string().to_owned());
+	let mut draw_sphere = DrawSphere::create();
+	//sRGBのframebufferが取れなかったときは,シーンもシェーダで変換する
+	if !context.srgb_framebuffer {
+	    draw_sphere.tone_map_settings_mut().output = OutputEncoding::ShaderSrgb;
+	}
+	//--texture 惑星に貼る画像, --environment 背景のパノラマ画像, --lut color gradingの表
+	let args:Vec<String> = std::env::args().collect();
+	//前からの使い方どおり,最初の引数がオプションでなければ惑星に貼る画像にする
+	if let Some(path) = args.get(1).filter(|arg| !arg.starts_with("--")) {
+	    if let Err(e) = draw_sphere.load_planet_texture(path) {
+		println!("{}", e);
+	    }
+	}
+	for pair in args[1..].windows(2) {
+	    let result = match pair[0].as_str() {
+		"--texture" => draw_sphere.load_planet_texture(&pair[1]),
+		"--environment" => draw_sphere.load_environment(&pair[1]),
+		"--lut" => draw_sphere.load_color_lut(&pair[1]),
+		_ => Ok(())
+	    };
+	    if let Err(e) = result {
+		println!("{}", e);
+	    }
+	}
 
-    let mut draw_sphere = DrawSphere::create();
+	let control_context = AppControlContext {
+	    camera: init_camera,
+	    shading: draw_sphere.shading()
+	};
+	let mut camera_controller = OrbitController::new(init_camera.center, 5.0, 75.0, 30.0);
+	camera_controller.set_look_at(init_camera.eye, init_camera.center, init_camera.up);
+	SphereApp {
+	    draw_sphere,
+	    control_context,
+	    camera_controller,
+	    init_camera,
+	    camera_entry: CameraEntry::new(&init_camera)
+	}
+    }
 
-    println!("{:?}",camera_position);
-    let mut prev_instant = time::Instant::now();
-    while !window.should_close() {
-	let mut camera_update = false;
-	let mut camera_reset = false;
-	let (width,height) = window.get_size();
-	let native_pixels_per_point = window.get_content_scale().0;
+    fn action_map(&self) -> ActionMap {
+	ActionMap::camera_defaults()
+    }
 
-	egui_input_state.input.time = Some(start_time.elapsed().as_secs_f64());
-	egui_ctx.begin_frame(egui_input_state.input.take());
+    fn update(&mut self, dt:time::Duration, input:&Input) {
+	drive_camera(input, &mut self.camera_controller);
+	self.camera_controller.fovy = self.control_context.camera.fovy;
+	self.camera_controller.update(dt.as_secs_f64());
+	self.control_context.camera.follow(&self.camera_controller);
+	self.draw_sphere.update(dt);
+    }
 
-	egui_input_state.input.screen_rect = Some(Rect::from_min_size(
-            Pos2::new(0f32, 0f32),
-            vec2(width as f32, height as f32) / native_pixels_per_point,
-        ));
-	egui_input_state.input.pixels_per_point = Some(native_pixels_per_point);
+    fn render(&mut self, context:&FrameContext) {
+	self.camera_controller.set_viewport(context.width as f64, context.height as f64);
+	self.draw_sphere.set_shading(self.control_context.shading);
+	self.control_context.camera.resize(context.width, context.height);
+	self.draw_sphere.render(context.width, context.height, &self.control_context.camera);
+    }
 
-	egui::Window::new("Control").show(&egui_ctx, |ui| {
+    fn ui(&mut self, egui_ctx:&egui::CtxRef, _control:&mut LoopControl) {
+	let mut camera_command = None;
+	let camera_entry = &mut self.camera_entry;
+	let control_context = &mut self.control_context;
+	let draw_sphere = &mut self.draw_sphere;
+	egui::Window::new("Control").show(egui_ctx, |ui| {
 	    ui.set_max_width(280.0);
 	    ui.group(|ui| {
 		ui.label("camera");
-		ui.horizontal(|ui| {
-		    ui.set_max_width(250.0);
-		    ui.label("position");
-		    ui.label("x:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_position.0).desired_width(30.0));
-		    ui.label("y:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_position.1).desired_width(30.0));
-		    ui.label("z:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_position.2).desired_width(30.0));
-		});
-		ui.horizontal(|ui| {
-		    ui.set_max_width(250.0);
-		    ui.label("center");
-		    ui.label("x:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_center.0).desired_width(30.0));
-		    ui.label("y:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_center.1).desired_width(30.0));
-		    ui.label("z:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_center.2).desired_width(30.0));
-		});
-		ui.horizontal(|ui| {
-		    ui.set_max_width(250.0);
-		    ui.label("up");
-		    ui.label("x:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_up.0).desired_width(30.0));
-		    ui.label("y:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_up.1).desired_width(30.0));
-		    ui.label("z:");
-		    ui.add(egui::TextEdit::singleline(&mut camera_up.2).desired_width(30.0));
-		});
-		ui.horizontal(|ui| {
-		    if ui.button("update").clicked() {
-			camera_update = true;
-		    }
-		    if ui.button("reset").clicked() {
-			camera_reset = true;
-		    }
-		});
+		ui.label("drag: rotate/pan(right button), scroll: zoom");
+		ui.label("gamepad: right stick rotate, left stick pan, triggers: zoom");
+		camera_command = camera_entry.ui(ui);
+		//SSAOと遅延シェーディングは透視投影として深度から位置を戻すので,平行投影では正しく出ない
+		camera_ui(ui, &mut control_context.camera);
 	    });
+	    settings_ui::scene_ui(ui, draw_sphere, &mut control_context.shading);
 	});
 
-	let (egui_output, paint_cmds) = egui_ctx.end_frame();
-	if camera_reset {
-	    control_context.camera_parameter = init_camera_param;
-	}
-	else if camera_update {
-	    let position = (camera_position.0.parse::<f64>(),
-			    camera_position.1.parse::<f64>(),
-			    camera_position.2.parse::<f64>());
-	    let center = (camera_center.0.parse::<f64>(),
-			  camera_center.1.parse::<f64>(),
-			  camera_center.2.parse::<f64>());
-	    let up = (camera_up.0.parse::<f64>(),
-		      camera_up.1.parse::<f64>(),
-		      camera_up.2.parse::<f64>());
-	    match (position,center,up) {
-		((Ok(px),Ok(py),Ok(pz)),(Ok(cx),Ok(cy),Ok(cz)),(Ok(ux),Ok(uy),Ok(uz))) => {
-		    control_context.camera_parameter.position = (px,py,pz);
-		    control_context.camera_parameter.center = (cx,cy,cz);
-		    control_context.camera_parameter.up = (ux,uy,uz);
-		},
-		_ => {
-		    println!("invalid input");
-		    ()
-		}
-	    }
+	match camera_command {
+	    Some(CameraCommand::Reset) => {
+		self.control_context.camera = self.init_camera;
+		self.camera_controller.set_look_at(self.init_camera.eye, self.init_camera.center, self.init_camera.up);
+	    },
+	    Some(CameraCommand::LookAt(eye, center, up)) => {
+		self.camera_controller.set_look_at(eye, center, up);
+		self.control_context.camera.follow(&self.camera_controller);
+	    },
+	    None => {}
 	}
+    }
+}
 
-	let now_instant = time::Instant::now();
-	draw_sphere.render(width,height,
-			   (control_context.camera_parameter.position,
-			    control_context.camera_parameter.center,
-			    control_context.camera_parameter.up),
-			   now_instant-prev_instant);
-	prev_instant = now_instant;
-	//Handle cut, copy text from egui
-        if !egui_output.copied_text.is_empty() {
-            egui_glfw_gl::copy_to_clipboard(&mut egui_input_state, egui_output.copied_text);
-        }
-
-        let paint_jobs = egui_ctx.tessellate(paint_cmds);
-        painter.paint_jobs(
-            None,
-            paint_jobs,
-            &egui_ctx.texture(),
-	    width,
-	    height,
-            native_pixels_per_point,
-        );
-
-	for (_, event) in glfw::flush_messages(&events) {
-            match event {
-                glfw::WindowEvent::Close => window.set_should_close(true),
-                _ => { egui_glfw_gl::handle_event(event, &mut egui_input_state); }
-            }
-        }
+/* --record FILE 入力を記録する. app_framework::replayでウィンドウなしに流せる */
+fn record_option() -> Option<std::path::PathBuf> {
+    let args:Vec<String> = std::env::args().collect();
+    args.windows(2).find(|pair| pair[0] == "--record").map(|pair| pair[1].clone().into())
+}
 
-	window.swap_buffers();
-        glfw.poll_events();
-    }
+fn main() {
+    app_framework::run::<SphereApp>(AppConfig {
+	title: "Draw Frame Sphere".to_string(),
+	bindings_file: Some("sphere_make_a_round_bindings.ron".into()),
+	record_file: record_option(),
+	..AppConfig::default()
+    });
 }
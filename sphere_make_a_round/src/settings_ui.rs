@@ -0,0 +1,237 @@
+use glutils::material::ShadingModel;
+use glutils::light::{LightSet,LightKind};
+use glutils::shadow::{ShadowSettings,MAX_CASCADES};
+use glutils::hdr::{ToneMapSettings,TONE_MAPPERS};
+use glutils::postprocess::{PostProcessSettings,MAX_BLOOM_ITERATIONS};
+use glutils::ssao::{SsaoSettings,MAX_SSAO_KERNEL_SIZE};
+use glutils::deferred::{DeferredSettings,GBUFFER_VIEWS};
+
+use crate::draw_sphere::DrawSphere;
+
+/*
+  DrawSphereの設定を編集するeguiのパネル. カメラ以外はscene_uiでまとめて出す.
+*/
+
+fn color_edit_row(ui: &mut egui::Ui, label: &str, color: &mut [f32;3]) {
+    ui.horizontal(|ui| {
+	ui.label(label);
+	ui.color_edit_button_rgb(color);
+    });
+}
+
+fn vector_edit_row(ui: &mut egui::Ui, label: &str, v: &mut [f32;3]) {
+    ui.horizontal(|ui| {
+	ui.label(label);
+	for e in v.iter_mut() {
+	    ui.add(egui::DragValue::new(e).speed(0.05));
+	}
+    });
+}
+
+/* 光源ごとに有効/無効,色,強さ,種類ごとのパラメータを編集する */
+fn lights_ui(ui: &mut egui::Ui, lights: &mut LightSet) {
+    for (i, light) in lights.iter_mut().enumerate() {
+	ui.collapsing(format!("light {} ({})", i, light.type_name()), |ui| {
+	    ui.checkbox(&mut light.enabled, "enabled");
+	    if !matches!(light.kind, LightKind::Point { .. }) {
+		ui.checkbox(&mut light.cast_shadow, "cast shadow");
+	    }
+	    color_edit_row(ui, "color", &mut light.color);
+	    ui.add(egui::Slider::new(&mut light.intensity, 0.0..=10.0).text("intensity"));
+	    match &mut light.kind {
+		LightKind::Directional { direction } => {
+		    vector_edit_row(ui, "direction", direction);
+		},
+		LightKind::Point { position, attenuation } => {
+		    vector_edit_row(ui, "position", position);
+		    ui.add(egui::Slider::new(&mut attenuation.linear, 0.0..=1.0).text("linear"));
+		    ui.add(egui::Slider::new(&mut attenuation.quadratic, 0.0..=1.0).text("quadratic"));
+		},
+		LightKind::Spot { position, direction, attenuation, inner_cone_degree, outer_cone_degree } => {
+		    vector_edit_row(ui, "position", position);
+		    vector_edit_row(ui, "direction", direction);
+		    ui.add(egui::Slider::new(&mut attenuation.linear, 0.0..=1.0).text("linear"));
+		    ui.add(egui::Slider::new(&mut attenuation.quadratic, 0.0..=1.0).text("quadratic"));
+		    ui.add(egui::Slider::new(inner_cone_degree, 0.0..=90.0).text("inner cone"));
+		    ui.add(egui::Slider::new(outer_cone_degree, 0.0..=90.0).text("outer cone"));
+		    if *outer_cone_degree < *inner_cone_degree {
+			*outer_cone_degree = *inner_cone_degree;
+		    }
+		}
+	    }
+	});
+    }
+}
+
+/* 影を落とすのは最初に見つかったcast shadowの光源だけ. カスケードは平行光源のみ */
+fn shadow_ui(ui: &mut egui::Ui, settings: &mut ShadowSettings) {
+    egui::ComboBox::from_label("resolution")
+	.selected_text(settings.resolution.to_string())
+	.show_ui(ui, |ui| {
+	    for resolution in [512, 1024, 2048, 4096].iter() {
+		ui.selectable_value(&mut settings.resolution, *resolution, resolution.to_string());
+	    }
+	});
+    ui.add(egui::Slider::new(&mut settings.cascade_count, 1..=MAX_CASCADES).text("cascades"));
+    ui.add(egui::Slider::new(&mut settings.split_lambda, 0.0..=1.0).text("split lambda"));
+    ui.add(egui::Slider::new(&mut settings.bias, 0.0..=0.01).logarithmic(true).text("bias"));
+    ui.add(egui::Slider::new(&mut settings.slope_bias, 0.0..=0.05).logarithmic(true).text("slope bias"));
+    ui.add(egui::Slider::new(&mut settings.pcf_radius, 0..=3).text("pcf radius"));
+}
+
+/* 選択中のシェーディングモデルのパラメータを編集する */
+fn shading_parameter_ui(ui: &mut egui::Ui, shading: &mut ShadingModel) {
+    match shading {
+	ShadingModel::Unlit(p) => {
+	    ui.horizontal(|ui| {
+		ui.label("base color");
+		ui.color_edit_button_rgba_unmultiplied(&mut p.base_color);
+	    });
+	},
+	ShadingModel::Lambert(p) => {
+	    color_edit_row(ui, "ambient", &mut p.ambient);
+	    color_edit_row(ui, "diffuse", &mut p.diffuse);
+	},
+	ShadingModel::Phong(p) | ShadingModel::BlinnPhong(p) => {
+	    color_edit_row(ui, "ambient", &mut p.ambient);
+	    color_edit_row(ui, "diffuse", &mut p.diffuse);
+	    color_edit_row(ui, "specular", &mut p.specular);
+	    ui.add(egui::Slider::new(&mut p.shininess, 1.0..=256.0).logarithmic(true).text("shininess"));
+	},
+	ShadingModel::Pbr(p) => {
+	    color_edit_row(ui, "base color", &mut p.base_color);
+	    ui.add(egui::Slider::new(&mut p.metallic, 0.0..=1.0).text("metallic"));
+	    ui.add(egui::Slider::new(&mut p.roughness, 0.05..=1.0).text("roughness"));
+	    ui.add(egui::Slider::new(&mut p.ambient_occlusion, 0.0..=1.0).text("ao"));
+	}
+    }
+}
+
+fn tone_map_ui(ui: &mut egui::Ui, settings: &mut ToneMapSettings) {
+    egui::ComboBox::from_label("tone mapper")
+	.selected_text(settings.tone_mapper.name())
+	.show_ui(ui, |ui| {
+	    for mapper in TONE_MAPPERS.iter() {
+		ui.selectable_value(&mut settings.tone_mapper, *mapper, mapper.name());
+	    }
+	});
+    ui.add(egui::Slider::new(&mut settings.exposure, 0.05..=8.0).logarithmic(true).text("exposure"));
+}
+
+fn ssao_ui(ui: &mut egui::Ui, settings: &mut SsaoSettings) {
+    ui.checkbox(&mut settings.enabled, "enabled");
+    if settings.enabled {
+	ui.add(egui::Slider::new(&mut settings.radius, 0.05..=2.0).logarithmic(true).text("radius"));
+	ui.add(egui::Slider::new(&mut settings.bias, 0.0..=0.1).logarithmic(true).text("bias"));
+	ui.add(egui::Slider::new(&mut settings.strength, 0.1..=4.0).text("strength"));
+	ui.add(egui::Slider::new(&mut settings.kernel_size, 1..=MAX_SSAO_KERNEL_SIZE).text("kernel size"));
+	ui.checkbox(&mut settings.blur, "blur");
+    }
+}
+
+/* 前方描画との切り替えと,Gバッファの中身の表示 */
+fn deferred_ui(ui: &mut egui::Ui, settings: &mut DeferredSettings) {
+    ui.checkbox(&mut settings.enabled, "enabled");
+    if settings.enabled {
+	egui::ComboBox::from_label("view")
+	    .selected_text(settings.debug_view.name())
+	    .show_ui(ui, |ui| {
+		for view in GBUFFER_VIEWS.iter() {
+		    ui.selectable_value(&mut settings.debug_view, *view, view.name());
+		}
+	    });
+	ui.add(egui::Slider::new(&mut settings.light_cutoff, 0.001..=0.1).logarithmic(true).text("light cutoff"));
+    }
+}
+
+/* 効果ごとに有効/無効とパラメータ */
+fn post_process_ui(ui: &mut egui::Ui, settings: &mut PostProcessSettings) {
+    ui.checkbox(&mut settings.bloom.enabled, "bloom");
+    if settings.bloom.enabled {
+	ui.add(egui::Slider::new(&mut settings.bloom.threshold, 0.0..=4.0).text("threshold"));
+	ui.add(egui::Slider::new(&mut settings.bloom.knee, 0.0..=1.0).text("knee"));
+	ui.add(egui::Slider::new(&mut settings.bloom.intensity, 0.0..=2.0).text("intensity"));
+	ui.add(egui::Slider::new(&mut settings.bloom.iterations, 1..=MAX_BLOOM_ITERATIONS).text("iterations"));
+    }
+    ui.checkbox(&mut settings.color_grading.enabled, "color grading");
+    if settings.color_grading.enabled {
+	ui.add(egui::Slider::new(&mut settings.color_grading.strength, 0.0..=1.0).text("strength"));
+    }
+    ui.checkbox(&mut settings.vignette.enabled, "vignette");
+    if settings.vignette.enabled {
+	ui.add(egui::Slider::new(&mut settings.vignette.intensity, 0.0..=1.0).text("intensity"));
+	ui.add(egui::Slider::new(&mut settings.vignette.radius, 0.0..=1.0).text("radius"));
+	ui.add(egui::Slider::new(&mut settings.vignette.softness, 0.01..=1.0).text("softness"));
+    }
+    ui.checkbox(&mut settings.fxaa.enabled, "FXAA");
+}
+
+/* マテリアルから統計までのグループ. シェーディングモデルはrenderでDrawSphereに渡すので別に受け取る */
+pub fn scene_ui(ui: &mut egui::Ui, draw_sphere: &mut DrawSphere, shading: &mut ShadingModel) {
+    ui.group(|ui| {
+	ui.label("material");
+	egui::ComboBox::from_label("shading")
+	    .selected_text(shading.name())
+	    .show_ui(ui, |ui| {
+		for model in ShadingModel::defaults().iter() {
+		    let selected = shading.same_model(model);
+		    if ui.selectable_label(selected, model.name()).clicked() && !selected {
+			*shading = *model;
+		    }
+		}
+	    });
+	shading_parameter_ui(ui, shading);
+	let mut textured = draw_sphere.textured();
+	if ui.checkbox(&mut textured, "planet texture").changed() {
+	    draw_sphere.set_textured(textured);
+	}
+    });
+    ui.group(|ui| {
+	ui.label("lights");
+	lights_ui(ui, draw_sphere.lights_mut());
+    });
+    ui.group(|ui| {
+	let environment = draw_sphere.environment_settings_mut();
+	ui.label("environment");
+	ui.checkbox(&mut environment.skybox, "skybox");
+	ui.add(egui::Slider::new(&mut environment.reflection, 0.0..=1.0).text("reflection"));
+	ui.checkbox(&mut environment.ibl, "image based lighting (PBR)");
+    });
+    ui.group(|ui| {
+	ui.label("deferred shading");
+	deferred_ui(ui, draw_sphere.deferred_settings_mut());
+	let mut count = draw_sphere.extra_point_lights();
+	if ui.add(egui::Slider::new(&mut count, 0..=256).text("extra point lights")).changed() {
+	    draw_sphere.set_extra_point_lights(count);
+	}
+    });
+    ui.group(|ui| {
+	ui.label("ambient occlusion (SSAO)");
+	ssao_ui(ui, draw_sphere.ssao_settings_mut());
+    });
+    ui.group(|ui| {
+	ui.label("tone mapping");
+	tone_map_ui(ui, draw_sphere.tone_map_settings_mut());
+    });
+    ui.group(|ui| {
+	ui.label("post process");
+	post_process_ui(ui, draw_sphere.post_process_settings_mut());
+    });
+    ui.group(|ui| {
+	ui.label("shadow");
+	shadow_ui(ui, draw_sphere.shadow_settings_mut());
+    });
+    ui.group(|ui| {
+	let animation = draw_sphere.animation_mut();
+	ui.label("animation");
+	ui.checkbox(&mut animation.playing, "playing");
+	ui.add(egui::Slider::new(&mut animation.speed, -4.0..=4.0).text("speed"));
+    });
+    ui.group(|ui| {
+	let stats = draw_sphere.stats();
+	ui.label("renderer");
+	ui.label(format!("draw calls: {}", stats.draw_calls));
+	ui.label(format!("state changes: {}", stats.state_changes));
+	ui.label(format!("triangles: {}", stats.triangles));
+    });
+}